@@ -0,0 +1,312 @@
+//! Core-local interruptors, which provide timer and software interrupts.
+//!
+//! The devices of the ACLINT specification are provided separately by
+//! [Mtimer], [Mswi], and [Sswi], and [Clint] combines an [Mswi] and an
+//! [Mtimer] with the layout of the SiFive CLINT.
+
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
+
+use irv_traits::{BusError, Device, Interrupt, InterruptLines};
+
+/// The offset of the `mtime` register of an [Mtimer].
+const MTIME: u64 = 0x7FF8;
+
+/// The size of the address range of an [Mtimer].
+const MTIMER_SIZE: u64 = 0x8000;
+
+/// The size of the address range of an [Mswi] or an [Sswi].
+const SWI_SIZE: u64 = 0x4000;
+
+/// How the `mtime` counter of an [Mtimer] advances.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimeSource {
+    /// `mtime` only advances when [Mtimer::advance] is called, such as once
+    /// for each instruction executed, which keeps execution deterministic.
+    Counter,
+    /// `mtime` follows the wall clock of the host, advancing at the given
+    /// frequency in hertz.
+    WallClock { frequency: u64 },
+}
+
+/// An ACLINT machine-level timer device, which raises the machine timer
+/// interrupt of each hart while `mtime` is greater than or equal to its
+/// `mtimecmp`.
+///
+/// The `mtimecmp` register of hart `n` is at offset `8 * n`, and `mtime` is at
+/// offset `0x7FF8`. Both can be accessed as 32-bit or 64-bit values.
+///
+/// Interrupt lines are updated whenever a register is written or
+/// [Mtimer::advance] is called. With [TimeSource::WallClock], the host must
+/// also call [Mtimer::update] regularly, such as between instructions.
+#[derive(Debug)]
+pub struct Mtimer {
+    harts: Vec<Arc<InterruptLines>>,
+    mtimecmp: Vec<AtomicU64>,
+    source: TimeSource,
+    /// The value of `mtime`, or with [TimeSource::WallClock], the value
+    /// `mtime` had at `start`.
+    base: AtomicU64,
+    start: Instant,
+}
+
+impl Mtimer {
+    /// Creates a new timer connected to the interrupt lines of the given
+    /// harts, with `mtime` starting at zero.
+    ///
+    /// Each `mtimecmp` starts at its maximum value, so no interrupts are
+    /// raised until it is written.
+    pub fn new(harts: Vec<Arc<InterruptLines>>, source: TimeSource) -> Mtimer {
+        let mtimecmp = harts.iter().map(|_| AtomicU64::new(u64::MAX)).collect();
+
+        Mtimer {
+            harts,
+            mtimecmp,
+            source,
+            base: AtomicU64::new(0),
+            start: Instant::now(),
+        }
+    }
+
+    /// Gets the current value of `mtime`.
+    pub fn mtime(&self) -> u64 {
+        self.base
+            .load(Ordering::SeqCst)
+            .wrapping_add(self.elapsed())
+    }
+
+    /// Advances `mtime` by the given number of `ticks`, updating the
+    /// interrupt lines.
+    ///
+    /// With [TimeSource::WallClock], this skips ahead of the wall clock.
+    pub fn advance(&self, ticks: u64) {
+        self.base.fetch_add(ticks, Ordering::SeqCst);
+        self.update();
+    }
+
+    /// Updates the interrupt lines with the current value of `mtime`.
+    pub fn update(&self) {
+        let mtime = self.mtime();
+
+        for (lines, mtimecmp) in self.harts.iter().zip(&self.mtimecmp) {
+            lines.set(
+                Interrupt::MachineTimer,
+                mtime >= mtimecmp.load(Ordering::SeqCst),
+            );
+        }
+    }
+
+    /// Gets the number of ticks of the wall clock since the timer was created,
+    /// which is always zero with [TimeSource::Counter].
+    fn elapsed(&self) -> u64 {
+        match self.source {
+            TimeSource::Counter => 0,
+            TimeSource::WallClock { frequency } => {
+                let nanos = self.start.elapsed().as_nanos();
+
+                (nanos * frequency as u128 / 1_000_000_000) as u64
+            }
+        }
+    }
+
+    /// Reads the 64-bit register at the given 8-byte-aligned `offset`.
+    fn read_register(&self, offset: u64) -> Result<u64, BusError> {
+        match offset {
+            MTIME => Ok(self.mtime()),
+            _ => self
+                .mtimecmp
+                .get((offset / 8) as usize)
+                .map(|mtimecmp| mtimecmp.load(Ordering::SeqCst))
+                .ok_or(BusError::AccessFault),
+        }
+    }
+
+    /// Writes the 64-bit register at the given 8-byte-aligned `offset`,
+    /// updating the interrupt lines.
+    fn write_register(&self, offset: u64, value: u64) -> Result<(), BusError> {
+        match offset {
+            MTIME => self
+                .base
+                .store(value.wrapping_sub(self.elapsed()), Ordering::SeqCst),
+            _ => self
+                .mtimecmp
+                .get((offset / 8) as usize)
+                .ok_or(BusError::AccessFault)?
+                .store(value, Ordering::SeqCst),
+        }
+
+        self.update();
+
+        Ok(())
+    }
+}
+
+impl Device for Mtimer {
+    fn read(&self, offset: u64, size: u64) -> Result<u64, BusError> {
+        check(offset, size, &[4, 8], MTIMER_SIZE)?;
+
+        let shift = (offset & 0b111) * 8;
+        let mask = u64::MAX >> (64 - size * 8);
+
+        Ok(self.read_register(offset & !0b111)? >> shift & mask)
+    }
+
+    fn write(&self, offset: u64, size: u64, value: u64) -> Result<(), BusError> {
+        check(offset, size, &[4, 8], MTIMER_SIZE)?;
+
+        // Only the addressed half of the register is changed by 32-bit writes
+        let shift = (offset & 0b111) * 8;
+        let mask = u64::MAX >> (64 - size * 8) << shift;
+        let old = self.read_register(offset & !0b111)?;
+
+        self.write_register(offset & !0b111, old & !mask | value << shift & mask)
+    }
+}
+
+/// An ACLINT machine-level software interrupt device, with an `msip` register
+/// for each hart that raises its machine software interrupt.
+///
+/// The 32-bit `msip` register of hart `n` is at offset `4 * n`. Only its lowest
+/// bit is writable, and it directly controls the interrupt line.
+#[derive(Debug)]
+pub struct Mswi {
+    harts: Vec<Arc<InterruptLines>>,
+}
+
+impl Mswi {
+    /// Creates a new device connected to the interrupt lines of the given
+    /// harts.
+    pub fn new(harts: Vec<Arc<InterruptLines>>) -> Mswi {
+        Mswi { harts }
+    }
+}
+
+impl Device for Mswi {
+    fn read(&self, offset: u64, size: u64) -> Result<u64, BusError> {
+        check(offset, size, &[4], SWI_SIZE)?;
+
+        let lines = self.harts.get((offset / 4) as usize);
+
+        Ok(lines
+            .ok_or(BusError::AccessFault)?
+            .is_raised(Interrupt::MachineSoftware) as u64)
+    }
+
+    fn write(&self, offset: u64, size: u64, value: u64) -> Result<(), BusError> {
+        check(offset, size, &[4], SWI_SIZE)?;
+
+        let lines = self.harts.get((offset / 4) as usize);
+
+        lines
+            .ok_or(BusError::AccessFault)?
+            .set(Interrupt::MachineSoftware, value & 1 != 0);
+
+        Ok(())
+    }
+}
+
+/// An ACLINT supervisor-level software interrupt device, with a `setssip`
+/// register for each hart that triggers its supervisor software interrupt.
+///
+/// The 32-bit `setssip` register of hart `n` is at offset `4 * n`. Writing one
+/// to it sets `mip.SSIP`, which software then clears, and it always reads as
+/// zero.
+#[derive(Debug)]
+pub struct Sswi {
+    harts: Vec<Arc<InterruptLines>>,
+}
+
+impl Sswi {
+    /// Creates a new device connected to the interrupt lines of the given
+    /// harts.
+    pub fn new(harts: Vec<Arc<InterruptLines>>) -> Sswi {
+        Sswi { harts }
+    }
+}
+
+impl Device for Sswi {
+    fn read(&self, offset: u64, size: u64) -> Result<u64, BusError> {
+        check(offset, size, &[4], SWI_SIZE)?;
+
+        self.harts
+            .get((offset / 4) as usize)
+            .ok_or(BusError::AccessFault)?;
+
+        Ok(0)
+    }
+
+    fn write(&self, offset: u64, size: u64, value: u64) -> Result<(), BusError> {
+        check(offset, size, &[4], SWI_SIZE)?;
+
+        let lines = self
+            .harts
+            .get((offset / 4) as usize)
+            .ok_or(BusError::AccessFault)?;
+
+        if value & 1 != 0 {
+            lines.trigger(Interrupt::SupervisorSoftware);
+        }
+
+        Ok(())
+    }
+}
+
+/// A CLINT device, which is an [Mswi] at offset `0x0` followed by an [Mtimer]
+/// at offset `0x4000`, as in the SiFive CLINT.
+///
+/// This places `msip` at offset `0x0`, `mtimecmp` at offset `0x4000`, and
+/// `mtime` at offset `0xBFF8`.
+#[derive(Debug)]
+pub struct Clint {
+    pub mswi: Mswi,
+    pub mtimer: Mtimer,
+}
+
+impl Clint {
+    /// The size of the address range of a CLINT.
+    pub const SIZE: u64 = 0x10000;
+
+    /// Creates a new CLINT connected to the interrupt lines of the given harts.
+    pub fn new(harts: Vec<Arc<InterruptLines>>, source: TimeSource) -> Clint {
+        Clint {
+            mswi: Mswi::new(harts.clone()),
+            mtimer: Mtimer::new(harts, source),
+        }
+    }
+}
+
+impl Device for Clint {
+    fn read(&self, offset: u64, size: u64) -> Result<u64, BusError> {
+        match offset {
+            SWI_SIZE.. => self.mtimer.read(offset - SWI_SIZE, size),
+            _ => self.mswi.read(offset, size),
+        }
+    }
+
+    fn write(&self, offset: u64, size: u64, value: u64) -> Result<(), BusError> {
+        match offset {
+            SWI_SIZE.. => self.mtimer.write(offset - SWI_SIZE, size, value),
+            _ => self.mswi.write(offset, size, value),
+        }
+    }
+}
+
+/// Checks that an access of the given `size`, which must be one of the given
+/// power-of-two `sizes`, to the given `address` is aligned and within a device with the
+/// given address range size.
+fn check(address: u64, size: u64, sizes: &[u64], range: u64) -> Result<(), BusError> {
+    if !sizes.contains(&size) {
+        Err(BusError::AccessFault)
+    } else if address & (size - 1) != 0 {
+        Err(BusError::AddressMisaligned)
+    } else if address >= range {
+        Err(BusError::AccessFault)
+    } else {
+        Ok(())
+    }
+}
@@ -0,0 +1,19 @@
+//! Models of platform devices that can be connected to an `irv` bus.
+//!
+//! Each device implements [Device](irv_traits::Device), so it can be mapped
+//! into a bus at any base address and accessed at offsets relative to it, and
+//! raises interrupts through the
+//! [InterruptLines](irv_traits::InterruptLines) of the harts it is connected
+//! to.
+
+mod clint;
+mod plic;
+mod uart;
+
+pub use clint::{Clint, Mswi, Mtimer, Sswi, TimeSource};
+pub use plic::{Context, Plic};
+#[cfg(target_os = "linux")]
+pub use uart::Pty;
+#[cfg(unix)]
+pub use uart::UnixSocket;
+pub use uart::{Backend, Buffer, Stdio, Uart};
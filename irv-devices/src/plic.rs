@@ -0,0 +1,301 @@
+//! The platform-level interrupt controller, which routes external interrupts
+//! to harts.
+
+use std::sync::{Arc, Mutex};
+
+use irv_traits::{BusError, Device, Interrupt, InterruptLines};
+
+/// The highest priority that can be given to a source or a threshold.
+const MAX_PRIORITY: u32 = 7;
+
+/// The offset of the pending bits.
+const PENDING: u64 = 0x1000;
+/// The offset of the enable bits of the first context.
+const ENABLE: u64 = 0x2000;
+/// The distance between the enable bits of consecutive contexts.
+const ENABLE_STRIDE: u64 = 0x80;
+/// The offset of the threshold and claim/complete registers of the first
+/// context.
+const CONTEXT: u64 = 0x200000;
+/// The distance between the registers of consecutive contexts.
+const CONTEXT_STRIDE: u64 = 0x1000;
+
+/// A target of the interrupts routed by a [Plic], which is a hart at a
+/// particular privilege level.
+#[derive(Clone, Debug)]
+pub struct Context {
+    /// The interrupt lines of the hart.
+    pub lines: Arc<InterruptLines>,
+    /// The interrupt raised for the hart, which is usually
+    /// [Interrupt::MachineExternal] or [Interrupt::SupervisorExternal].
+    pub interrupt: Interrupt,
+}
+
+/// A platform-level interrupt controller, with the register layout of the
+/// RISC-V PLIC specification.
+///
+/// Each source has a 32-bit priority register at offset `4 * source`, and its
+/// pending bit is at offset `0x1000`. Each context has enable bits at offset
+/// `0x2000 + 0x80 * context`, a threshold register at offset
+/// `0x200000 + 0x1000 * context`, and a claim/complete register just after it.
+/// Priorities and thresholds range from zero to seven.
+///
+/// Device models assert the line of each source with [Plic::set]. Sources are
+/// level-triggered, so a source that is still asserted when its interrupt is
+/// completed becomes pending again.
+#[derive(Debug)]
+pub struct Plic {
+    sources: u32,
+    contexts: Vec<Context>,
+    state: Mutex<State>,
+}
+
+/// The state of the registers of a [Plic].
+#[derive(Debug)]
+struct State {
+    /// The priority of each source, including the nonexistent source zero.
+    priority: Vec<u32>,
+    /// Whether the line of each source is asserted.
+    asserted: Vec<bool>,
+    /// Whether each source is pending.
+    pending: Vec<bool>,
+    /// Whether each source has been claimed and not yet completed.
+    claimed: Vec<bool>,
+    /// The enable bits of each context, packed into 32-bit words.
+    enable: Vec<Vec<u32>>,
+    /// The priority threshold of each context.
+    threshold: Vec<u32>,
+}
+
+impl Plic {
+    /// The size of the address range of a PLIC.
+    pub const SIZE: u64 = 0x4000000;
+
+    /// Creates a new PLIC with sources 1 through `sources` that routes
+    /// interrupts to the given contexts.
+    ///
+    /// # Panics
+    /// Panics if there are more than 1023 sources or 15872 contexts.
+    pub fn new(sources: u32, contexts: Vec<Context>) -> Plic {
+        assert!(sources <= 1023, "at most 1023 sources are supported");
+        assert!(
+            contexts.len() <= 15872,
+            "at most 15872 contexts are supported"
+        );
+
+        let count = sources as usize + 1;
+        let words = count.div_ceil(32);
+
+        let state = State {
+            priority: vec![0; count],
+            asserted: vec![false; count],
+            pending: vec![false; count],
+            claimed: vec![false; count],
+            enable: vec![vec![0; words]; contexts.len()],
+            threshold: vec![0; contexts.len()],
+        };
+
+        Plic {
+            sources,
+            contexts,
+            state: Mutex::new(state),
+        }
+    }
+
+    /// Asserts the line of the given `source` if `asserted` is set, or
+    /// deasserts it otherwise.
+    ///
+    /// Nothing is done if the source does not exist.
+    pub fn set(&self, source: u32, asserted: bool) {
+        if source == 0 || source > self.sources {
+            return;
+        }
+
+        let mut state = self.state.lock().unwrap();
+        let source = source as usize;
+
+        state.asserted[source] = asserted;
+
+        // Claimed sources only become pending again once completed
+        if asserted && !state.claimed[source] {
+            state.pending[source] = true;
+        }
+
+        self.update(&state);
+    }
+
+    /// Gets the highest-priority source that is pending and enabled for the
+    /// given context with a priority above its threshold, preferring the
+    /// lowest-numbered source among equal priorities.
+    fn best(&self, state: &State, context: usize) -> Option<usize> {
+        let mut best = None;
+        let mut best_priority = state.threshold[context];
+
+        for source in 1..=self.sources as usize {
+            let enabled = state.enable[context][source / 32] >> (source % 32) & 1 != 0;
+
+            if enabled && state.pending[source] && state.priority[source] > best_priority {
+                best = Some(source);
+                best_priority = state.priority[source];
+            }
+        }
+
+        best
+    }
+
+    /// Raises or lowers the interrupt of each context depending on whether it
+    /// has an interrupt to claim.
+    fn update(&self, state: &State) {
+        for (index, context) in self.contexts.iter().enumerate() {
+            context
+                .lines
+                .set(context.interrupt, self.best(state, index).is_some());
+        }
+    }
+
+    /// Claims the best interrupt for the given context, returning its source
+    /// or zero if there is none.
+    fn claim(&self, state: &mut State, context: usize) -> u32 {
+        let Some(source) = self.best(state, context) else {
+            return 0;
+        };
+
+        state.pending[source] = false;
+        state.claimed[source] = true;
+
+        source as u32
+    }
+
+    /// Completes the interrupt from the given source for the given context.
+    ///
+    /// Completions of sources that are not enabled for the context are
+    /// ignored.
+    fn complete(&self, state: &mut State, context: usize, source: u32) {
+        if source == 0 || source > self.sources {
+            return;
+        }
+
+        let source = source as usize;
+
+        if state.enable[context][source / 32] >> (source % 32) & 1 == 0 {
+            return;
+        }
+
+        state.claimed[source] = false;
+
+        // The source is level-triggered
+        if state.asserted[source] {
+            state.pending[source] = true;
+        }
+    }
+}
+
+impl Device for Plic {
+    fn read(&self, address: u64, size: u64) -> Result<u64, BusError> {
+        if size != 4 {
+            return Err(BusError::AccessFault);
+        }
+
+        if address & 0b11 != 0 {
+            return Err(BusError::AddressMisaligned);
+        }
+
+        let mut state = self.state.lock().unwrap();
+        let words = state.priority.len().div_ceil(32) as u64;
+        let contexts = self.contexts.len() as u64;
+
+        let value = match address {
+            ..PENDING => *state
+                .priority
+                .get((address / 4) as usize)
+                .ok_or(BusError::AccessFault)?,
+            PENDING..ENABLE if (address - PENDING) / 4 < words => {
+                let word = ((address - PENDING) / 4) as usize;
+
+                (0..32).fold(0, |value, bit| {
+                    let pending = state.pending.get(word * 32 + bit).copied().unwrap_or(false);
+
+                    value | (pending as u32) << bit
+                })
+            }
+            ENABLE..CONTEXT
+                if (address - ENABLE) / ENABLE_STRIDE < contexts
+                    && (address - ENABLE) % ENABLE_STRIDE / 4 < words =>
+            {
+                let context = ((address - ENABLE) / ENABLE_STRIDE) as usize;
+
+                state.enable[context][((address - ENABLE) % ENABLE_STRIDE / 4) as usize]
+            }
+            CONTEXT.. if (address - CONTEXT) / CONTEXT_STRIDE < contexts => {
+                let context = ((address - CONTEXT) / CONTEXT_STRIDE) as usize;
+
+                let value = match (address - CONTEXT) % CONTEXT_STRIDE {
+                    0 => state.threshold[context],
+                    4 => self.claim(&mut state, context),
+                    _ => return Err(BusError::AccessFault),
+                };
+
+                self.update(&state);
+
+                value
+            }
+            _ => return Err(BusError::AccessFault),
+        };
+
+        Ok(value.into())
+    }
+
+    fn write(&self, address: u64, size: u64, value: u64) -> Result<(), BusError> {
+        if size != 4 {
+            return Err(BusError::AccessFault);
+        }
+
+        if address & 0b11 != 0 {
+            return Err(BusError::AddressMisaligned);
+        }
+
+        let mut state = self.state.lock().unwrap();
+        let words = state.priority.len().div_ceil(32) as u64;
+        let contexts = self.contexts.len() as u64;
+        let value = value as u32;
+
+        match address {
+            // Source zero does not exist, so its priority is always zero
+            ..PENDING if address / 4 <= self.sources as u64 => {
+                if address != 0 {
+                    state.priority[(address / 4) as usize] = value.min(MAX_PRIORITY);
+                }
+            }
+            // Pending bits are read-only
+            PENDING..ENABLE if (address - PENDING) / 4 < words => (),
+            ENABLE..CONTEXT
+                if (address - ENABLE) / ENABLE_STRIDE < contexts
+                    && (address - ENABLE) % ENABLE_STRIDE / 4 < words =>
+            {
+                let context = ((address - ENABLE) / ENABLE_STRIDE) as usize;
+                let word = ((address - ENABLE) % ENABLE_STRIDE / 4) as usize;
+
+                // Only existing sources other than source zero can be enabled
+                let mask = (0..32)
+                    .filter(|bit| (1..=self.sources as usize).contains(&(word * 32 + bit)))
+                    .fold(0, |mask, bit| mask | 1 << bit);
+
+                state.enable[context][word] = value & mask;
+            }
+            CONTEXT.. if (address - CONTEXT) / CONTEXT_STRIDE < contexts => {
+                let context = ((address - CONTEXT) / CONTEXT_STRIDE) as usize;
+
+                match (address - CONTEXT) % CONTEXT_STRIDE {
+                    0 => state.threshold[context] = value.min(MAX_PRIORITY),
+                    4 => self.complete(&mut state, context, value),
+                    _ => return Err(BusError::AccessFault),
+                }
+            }
+            _ => return Err(BusError::AccessFault),
+        }
+
+        self.update(&state);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,318 @@
+//! The NS16550A UART, which provides a serial port.
+
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+use irv_traits::{BusError, Device};
+
+use crate::Plic;
+
+mod backend;
+
+#[cfg(target_os = "linux")]
+pub use backend::Pty;
+#[cfg(unix)]
+pub use backend::UnixSocket;
+pub use backend::{Buffer, Stdio};
+
+/// The number of bytes that can be held by the receive FIFO.
+const FIFO_SIZE: usize = 16;
+
+// The bits of the interrupt enable register
+const IER_ERBFI: u8 = 1 << 0;
+const IER_ETBEI: u8 = 1 << 1;
+const IER_MASK: u8 = 0b1111;
+
+// The interrupt identifications of the interrupt identification register
+const IIR_NONE: u8 = 0b0001;
+const IIR_THR_EMPTY: u8 = 0b0010;
+const IIR_RX_DATA: u8 = 0b0100;
+const IIR_RX_TIMEOUT: u8 = 0b1100;
+const IIR_FIFO_ENABLED: u8 = 0b11 << 6;
+
+// The bits of the FIFO control register
+const FCR_ENABLE: u8 = 1 << 0;
+const FCR_CLEAR_RX: u8 = 1 << 1;
+
+// The bits of the line control register
+const LCR_DLAB: u8 = 1 << 7;
+
+// The bits of the modem control register
+const MCR_LOOPBACK: u8 = 1 << 4;
+const MCR_MASK: u8 = 0b11111;
+
+// The bits of the line status register
+const LSR_DR: u8 = 1 << 0;
+const LSR_THRE: u8 = 1 << 5;
+const LSR_TEMT: u8 = 1 << 6;
+
+// The bits of the modem status register
+const MSR_CTS: u8 = 1 << 4;
+const MSR_DSR: u8 = 1 << 5;
+const MSR_DCD: u8 = 1 << 7;
+
+/// A host-side connection of a [Uart], which transmitted bytes are written to
+/// and received bytes are read from.
+pub trait Backend: Send {
+    /// Writes a byte transmitted by the UART.
+    fn write(&mut self, byte: u8);
+
+    /// Reads a byte to be received by the UART without blocking, if one is
+    /// available.
+    fn read(&mut self) -> Option<u8>;
+}
+
+/// An NS16550A UART, which has 8-bit registers at offsets 0 through 7.
+///
+/// Transmitted bytes are written to the backend immediately, so the
+/// transmitter is always empty. Received bytes are read from the backend as
+/// there is space for them in the receiver, which holds a single byte, or 16
+/// bytes when the FIFOs are enabled. The baud rate and line settings have no
+/// effect, and no line errors occur.
+///
+/// If the UART is connected to a [Plic], it asserts its source while an
+/// interrupt is identified in `IIR`. Since the backend is only read when the
+/// UART is accessed, the host must call [Uart::update] regularly for received
+/// bytes to raise interrupts.
+pub struct Uart {
+    state: Mutex<State>,
+    interrupt: Option<(Arc<Plic>, u32)>,
+}
+
+/// The state of the registers of a [Uart].
+struct State {
+    backend: Box<dyn Backend>,
+    /// The received bytes that have not yet been read.
+    rx: VecDeque<u8>,
+    ier: u8,
+    fcr: u8,
+    lcr: u8,
+    mcr: u8,
+    scr: u8,
+    dll: u8,
+    dlm: u8,
+    /// Whether the transmitter empty interrupt is pending, which is cleared by
+    /// reading `IIR` or writing `THR`.
+    thr_empty: bool,
+}
+
+impl Uart {
+    /// The size of the address range of a UART.
+    pub const SIZE: u64 = 8;
+
+    /// Creates a new UART connected to the given backend, in its reset state.
+    pub fn new(backend: impl Backend + 'static) -> Uart {
+        let state = State {
+            backend: Box::new(backend),
+            rx: VecDeque::new(),
+            ier: 0,
+            fcr: 0,
+            lcr: 0,
+            mcr: 0,
+            scr: 0,
+            dll: 0,
+            dlm: 0,
+            thr_empty: false,
+        };
+
+        Uart {
+            state: Mutex::new(state),
+            interrupt: None,
+        }
+    }
+
+    /// Connects the interrupt of the UART to the given `source` of a PLIC.
+    pub fn connect(&mut self, plic: Arc<Plic>, source: u32) {
+        self.interrupt = Some((plic, source));
+        self.update();
+    }
+
+    /// Receives any bytes available from the backend and updates the
+    /// interrupt.
+    pub fn update(&self) {
+        let mut state = self.state.lock().unwrap();
+
+        state.receive();
+        self.update_interrupt(&state);
+    }
+
+    /// Asserts or deasserts the interrupt of the UART depending on whether an
+    /// interrupt is identified.
+    fn update_interrupt(&self, state: &State) {
+        if let Some((plic, source)) = &self.interrupt {
+            plic.set(*source, state.iir() & IIR_NONE == 0);
+        }
+    }
+}
+
+impl State {
+    /// Gets the number of bytes that the receiver can hold.
+    fn capacity(&self) -> usize {
+        if self.fcr & FCR_ENABLE != 0 {
+            FIFO_SIZE
+        } else {
+            1
+        }
+    }
+
+    /// Reads bytes from the backend while there is space for them, unless the
+    /// UART is in loopback mode.
+    fn receive(&mut self) {
+        while self.mcr & MCR_LOOPBACK == 0 && self.rx.len() < self.capacity() {
+            match self.backend.read() {
+                Some(byte) => self.rx.push_back(byte),
+                None => break,
+            }
+        }
+    }
+
+    /// Transmits a byte, which is received again in loopback mode.
+    fn transmit(&mut self, byte: u8) {
+        if self.mcr & MCR_LOOPBACK == 0 {
+            self.backend.write(byte);
+        } else if self.rx.len() < self.capacity() {
+            self.rx.push_back(byte);
+        }
+
+        self.thr_empty = true;
+    }
+
+    /// Gets the value of the interrupt identification register.
+    fn iir(&self) -> u8 {
+        let fifo = self.fcr & FCR_ENABLE != 0;
+
+        let id = if self.ier & IER_ERBFI != 0 && !self.rx.is_empty() {
+            // Data below the trigger level is reported as a timeout
+            if fifo && self.rx.len() < self.trigger_level() {
+                IIR_RX_TIMEOUT
+            } else {
+                IIR_RX_DATA
+            }
+        } else if self.ier & IER_ETBEI != 0 && self.thr_empty {
+            IIR_THR_EMPTY
+        } else {
+            IIR_NONE
+        };
+
+        if fifo {
+            id | IIR_FIFO_ENABLED
+        } else {
+            id
+        }
+    }
+
+    /// Gets the number of received bytes that raises an interrupt when the
+    /// FIFOs are enabled.
+    fn trigger_level(&self) -> usize {
+        [1, 4, 8, 14][(self.fcr >> 6) as usize]
+    }
+
+    /// Gets the value of the modem status register, which reflects the modem
+    /// control register in loopback mode.
+    fn msr(&self) -> u8 {
+        if self.mcr & MCR_LOOPBACK != 0 {
+            // DTR, RTS, OUT1, and OUT2 are looped back to DSR, CTS, RI, and
+            // DCD respectively
+            (self.mcr & 0b1) << 5 | (self.mcr & 0b10) << 3 | (self.mcr & 0b1100) << 4
+        } else {
+            MSR_CTS | MSR_DSR | MSR_DCD
+        }
+    }
+}
+
+impl Device for Uart {
+    fn read(&self, offset: u64, size: u64) -> Result<u64, BusError> {
+        if size != 1 {
+            return Err(BusError::AccessFault);
+        }
+
+        let mut state = self.state.lock().unwrap();
+        let dlab = state.lcr & LCR_DLAB != 0;
+
+        state.receive();
+
+        let value = match offset {
+            0 if dlab => state.dll,
+            0 => {
+                let byte = state.rx.pop_front().unwrap_or(0);
+
+                state.receive();
+                byte
+            }
+            1 if dlab => state.dlm,
+            1 => state.ier,
+            2 => {
+                let iir = state.iir();
+
+                // Identifying the transmitter empty interrupt clears it
+                if iir & 0b1111 == IIR_THR_EMPTY {
+                    state.thr_empty = false;
+                }
+
+                iir
+            }
+            3 => state.lcr,
+            4 => state.mcr,
+            5 if state.rx.is_empty() => LSR_THRE | LSR_TEMT,
+            5 => LSR_THRE | LSR_TEMT | LSR_DR,
+            6 => state.msr(),
+            7 => state.scr,
+            _ => return Err(BusError::AccessFault),
+        };
+
+        self.update_interrupt(&state);
+
+        Ok(value.into())
+    }
+
+    fn write(&self, offset: u64, size: u64, value: u64) -> Result<(), BusError> {
+        if size != 1 {
+            return Err(BusError::AccessFault);
+        }
+
+        let mut state = self.state.lock().unwrap();
+        let dlab = state.lcr & LCR_DLAB != 0;
+        let value = value as u8;
+
+        match offset {
+            0 if dlab => state.dll = value,
+            0 => state.transmit(value),
+            1 if dlab => state.dlm = value,
+            1 => {
+                // Enabling the transmitter empty interrupt raises it
+                // immediately, since the transmitter is always empty
+                if value & !state.ier & IER_ETBEI != 0 {
+                    state.thr_empty = true;
+                }
+
+                state.ier = value & IER_MASK;
+            }
+            2 => {
+                // Changing whether the FIFOs are enabled clears them
+                if (value ^ state.fcr) & FCR_ENABLE != 0 || value & FCR_CLEAR_RX != 0 {
+                    state.rx.clear();
+                }
+
+                // The trigger level can only be set while the FIFOs are enabled
+                state.fcr = if value & FCR_ENABLE != 0 {
+                    value & (0b11 << 6 | FCR_ENABLE)
+                } else {
+                    0
+                };
+            }
+            3 => state.lcr = value,
+            4 => state.mcr = value & MCR_MASK,
+            // The line and modem status registers are read-only
+            5 | 6 => (),
+            7 => state.scr = value,
+            _ => return Err(BusError::AccessFault),
+        }
+
+        state.receive();
+        self.update_interrupt(&state);
+
+        Ok(())
+    }
+}
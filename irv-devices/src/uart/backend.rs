@@ -0,0 +1,255 @@
+//! Backends connecting a UART to the host.
+
+use std::{
+    collections::VecDeque,
+    io::{self, Read, Write},
+    sync::{
+        mpsc::{self, Receiver},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+#[cfg(target_os = "linux")]
+use std::{
+    ffi::{CStr, OsStr},
+    fs::{File, OpenOptions},
+    os::unix::{ffi::OsStrExt, fs::OpenOptionsExt, io::AsRawFd},
+    path::{Path, PathBuf},
+};
+
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+
+use super::Backend;
+
+/// A backend that keeps transmitted bytes in memory and receives bytes
+/// supplied by the host, which is useful for testing.
+///
+/// Clones of a buffer share the same bytes, so one clone can be given to a
+/// UART while another is used to supply input and inspect output.
+#[derive(Clone, Debug, Default)]
+pub struct Buffer {
+    input: Arc<Mutex<VecDeque<u8>>>,
+    output: Arc<Mutex<Vec<u8>>>,
+}
+
+impl Buffer {
+    /// Creates a new buffer without any input or output.
+    pub fn new() -> Buffer {
+        Buffer::default()
+    }
+
+    /// Supplies the given `bytes` to be received by the UART.
+    pub fn push_input(&self, bytes: &[u8]) {
+        self.input.lock().unwrap().extend(bytes);
+    }
+
+    /// Takes all bytes transmitted by the UART so far.
+    pub fn take_output(&self) -> Vec<u8> {
+        core::mem::take(&mut *self.output.lock().unwrap())
+    }
+}
+
+impl Backend for Buffer {
+    fn write(&mut self, byte: u8) {
+        self.output.lock().unwrap().push(byte);
+    }
+
+    fn read(&mut self) -> Option<u8> {
+        self.input.lock().unwrap().pop_front()
+    }
+}
+
+/// A backend that writes transmitted bytes to standard output and receives
+/// bytes from standard input.
+///
+/// Standard input is read on a separate thread, so any input is as buffered
+/// by the terminal of the host.
+#[derive(Debug)]
+pub struct Stdio {
+    input: Receiver<u8>,
+}
+
+impl Stdio {
+    /// Creates a new backend, starting a thread that reads standard input.
+    pub fn new() -> Stdio {
+        let (sender, input) = mpsc::channel();
+
+        thread::spawn(move || {
+            for byte in io::stdin().lock().bytes() {
+                let Ok(byte) = byte else {
+                    break;
+                };
+
+                if sender.send(byte).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Stdio { input }
+    }
+}
+
+impl Default for Stdio {
+    fn default() -> Stdio {
+        Stdio::new()
+    }
+}
+
+impl Backend for Stdio {
+    fn write(&mut self, byte: u8) {
+        let mut stdout = io::stdout().lock();
+
+        let _ = stdout.write_all(&[byte]);
+        let _ = stdout.flush();
+    }
+
+    fn read(&mut self) -> Option<u8> {
+        self.input.try_recv().ok()
+    }
+}
+
+/// A backend connected to a Unix domain socket, such as one that a terminal
+/// program like `socat` is connected to.
+///
+/// Transmitted bytes are dropped if they cannot be written without blocking.
+#[cfg(unix)]
+#[derive(Debug)]
+pub struct UnixSocket {
+    stream: UnixStream,
+}
+
+#[cfg(unix)]
+impl UnixSocket {
+    /// Connects to the socket at the given `path`.
+    pub fn connect(path: impl AsRef<std::path::Path>) -> io::Result<UnixSocket> {
+        UnixSocket::from_stream(UnixStream::connect(path)?)
+    }
+
+    /// Creates a socket at the given `path` and waits for a single connection
+    /// to it.
+    pub fn accept(path: impl AsRef<std::path::Path>) -> io::Result<UnixSocket> {
+        let (stream, _) = UnixListener::bind(path)?.accept()?;
+
+        UnixSocket::from_stream(stream)
+    }
+
+    /// Creates a backend using the given connected `stream`.
+    pub fn from_stream(stream: UnixStream) -> io::Result<UnixSocket> {
+        stream.set_nonblocking(true)?;
+
+        Ok(UnixSocket { stream })
+    }
+}
+
+#[cfg(unix)]
+impl Backend for UnixSocket {
+    fn write(&mut self, byte: u8) {
+        let _ = self.stream.write_all(&[byte]);
+    }
+
+    fn read(&mut self) -> Option<u8> {
+        let mut byte = [0];
+
+        match self.stream.read(&mut byte) {
+            Ok(1) => Some(byte[0]),
+            _ => None,
+        }
+    }
+}
+
+/// A backend connected to a new pseudoterminal, which a terminal program like
+/// `screen` can open at [Pty::path].
+///
+/// The pseudoterminal is in raw mode, so bytes are passed through unchanged.
+/// Transmitted bytes are dropped if they cannot be written without blocking.
+#[cfg(target_os = "linux")]
+#[derive(Debug)]
+pub struct Pty {
+    master: File,
+    /// The terminal end, which is kept open so its settings are kept and
+    /// reads from `master` do not fail while nothing else has it open.
+    _slave: File,
+    path: PathBuf,
+}
+
+#[cfg(target_os = "linux")]
+impl Pty {
+    /// Creates a new pseudoterminal.
+    pub fn open() -> io::Result<Pty> {
+        let master = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NOCTTY | libc::O_NONBLOCK)
+            .open("/dev/ptmx")?;
+
+        let fd = master.as_raw_fd();
+        let mut name = [0; 64];
+
+        // SAFETY: fd is an open file descriptor, and name is a valid buffer
+        // of the given length
+        let failed = unsafe {
+            libc::grantpt(fd) != 0
+                || libc::unlockpt(fd) != 0
+                || libc::ptsname_r(fd, name.as_mut_ptr(), name.len()) != 0
+        };
+
+        if failed {
+            return Err(io::Error::last_os_error());
+        }
+
+        // SAFETY: ptsname_r succeeded, so name holds a nul-terminated string
+        let name = unsafe { CStr::from_ptr(name.as_ptr()) };
+        let path = PathBuf::from(OsStr::from_bytes(name.to_bytes()));
+
+        let slave = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NOCTTY)
+            .open(&path)?;
+
+        // SAFETY: termios is only used after tcgetattr initializes it
+        unsafe {
+            let mut termios = core::mem::zeroed();
+
+            if libc::tcgetattr(slave.as_raw_fd(), &mut termios) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            libc::cfmakeraw(&mut termios);
+
+            if libc::tcsetattr(slave.as_raw_fd(), libc::TCSANOW, &termios) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+
+        Ok(Pty {
+            master,
+            _slave: slave,
+            path,
+        })
+    }
+
+    /// Gets the path of the terminal end of the pseudoterminal.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Backend for Pty {
+    fn write(&mut self, byte: u8) {
+        let _ = self.master.write_all(&[byte]);
+    }
+
+    fn read(&mut self) -> Option<u8> {
+        let mut byte = [0];
+
+        match self.master.read(&mut byte) {
+            Ok(1) => Some(byte[0]),
+            _ => None,
+        }
+    }
+}
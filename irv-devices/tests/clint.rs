@@ -0,0 +1,114 @@
+//! Checks the registers of the CLINT and the ACLINT devices, and the
+//! interrupts they raise.
+
+use std::sync::Arc;
+
+use irv_devices::{Clint, Mswi, Mtimer, Sswi, TimeSource};
+use irv_traits::{BusError, Device, Interrupt, InterruptLines};
+
+/// The offset of `mtimecmp` for hart 0 in a CLINT.
+const MTIMECMP: u64 = 0x4000;
+/// The offset of `mtime` in a CLINT.
+const MTIME: u64 = 0xBFF8;
+
+fn harts(count: usize) -> Vec<Arc<InterruptLines>> {
+    (0..count)
+        .map(|_| Arc::new(InterruptLines::new()))
+        .collect()
+}
+
+#[test]
+fn test_mtimecmp() {
+    let harts = harts(2);
+    let clint = Clint::new(harts.clone(), TimeSource::Counter);
+    let timer_raised = |hart: usize| harts[hart].is_raised(Interrupt::MachineTimer);
+
+    // mtimecmp starts at its maximum value, so nothing is raised
+    assert_eq!(clint.read(MTIMECMP, 8).unwrap(), u64::MAX);
+    assert!(!timer_raised(0));
+
+    // The interrupt is raised once mtime reaches mtimecmp
+    clint.write(MTIMECMP, 8, 10).unwrap();
+    clint.mtimer.advance(9);
+    assert!(!timer_raised(0));
+
+    clint.mtimer.advance(1);
+    assert!(timer_raised(0));
+    assert!(!timer_raised(1));
+    assert_eq!(clint.read(MTIME, 8).unwrap(), 10);
+
+    // Writing mtimecmp past mtime lowers it again
+    clint.write(MTIMECMP, 8, 11).unwrap();
+    assert!(!timer_raised(0));
+
+    // Writing mtime updates the lines too
+    clint.write(MTIME, 8, 100).unwrap();
+    assert!(timer_raised(0));
+
+    clint.write(MTIMECMP + 8, 8, 100).unwrap();
+    assert!(timer_raised(1));
+
+    // Registers of harts that do not exist cannot be accessed
+    assert!(matches!(
+        clint.read(MTIMECMP + 16, 8),
+        Err(BusError::AccessFault)
+    ));
+}
+
+#[test]
+fn test_half_writes() {
+    let harts = harts(1);
+    let timer = Mtimer::new(harts.clone(), TimeSource::Counter);
+
+    // Each 32-bit write only changes its half of the register
+    timer.write(0, 8, 0x1111_1111_2222_2222).unwrap();
+    timer.write(4, 4, 0x3333_3333).unwrap();
+    assert_eq!(timer.read(0, 8).unwrap(), 0x3333_3333_2222_2222);
+
+    timer.write(0, 4, 0x4444_4444).unwrap();
+    assert_eq!(timer.read(0, 8).unwrap(), 0x3333_3333_4444_4444);
+    assert_eq!(timer.read(0, 4).unwrap(), 0x4444_4444);
+    assert_eq!(timer.read(4, 4).unwrap(), 0x3333_3333);
+
+    // Writing the high half first avoids a spurious interrupt while
+    // mtimecmp is lowered
+    timer.advance(0x1_0000_0000);
+    timer.write(4, 4, 0xFFFF_FFFF).unwrap();
+    timer.write(0, 4, 0).unwrap();
+    assert!(!harts[0].is_raised(Interrupt::MachineTimer));
+
+    timer.write(4, 4, 1).unwrap();
+    assert!(harts[0].is_raised(Interrupt::MachineTimer));
+
+    timer.write(0x7FFC, 4, 0).unwrap();
+    assert_eq!(timer.read(0x7FF8, 8).unwrap(), 0);
+
+    // Other sizes and misaligned accesses are rejected
+    assert!(matches!(timer.read(0, 2), Err(BusError::AccessFault)));
+    assert!(matches!(timer.read(4, 8), Err(BusError::AddressMisaligned)));
+}
+
+#[test]
+fn test_software_interrupts() {
+    let harts = harts(2);
+    let mswi = Mswi::new(harts.clone());
+    let sswi = Sswi::new(harts.clone());
+
+    mswi.write(4, 4, 1).unwrap();
+    assert!(harts[1].is_raised(Interrupt::MachineSoftware));
+    assert!(!harts[0].is_raised(Interrupt::MachineSoftware));
+    assert_eq!(mswi.read(4, 4).unwrap(), 1);
+
+    mswi.write(4, 4, 0).unwrap();
+    assert!(!harts[1].is_raised(Interrupt::MachineSoftware));
+
+    // setssip triggers the interrupt once, and always reads as zero
+    sswi.write(0, 4, 1).unwrap();
+    assert_eq!(
+        harts[0].take_triggered(),
+        Interrupt::SupervisorSoftware.bit()
+    );
+    assert_eq!(sswi.read(0, 4).unwrap(), 0);
+
+    assert!(matches!(mswi.read(8, 4), Err(BusError::AccessFault)));
+}
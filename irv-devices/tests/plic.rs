@@ -0,0 +1,154 @@
+//! Checks the routing of interrupts by the PLIC, and its claim/complete
+//! protocol.
+
+use std::sync::Arc;
+
+use irv_devices::{Context, Plic};
+use irv_traits::{BusError, Device, Interrupt, InterruptLines};
+
+const PENDING: u64 = 0x1000;
+const ENABLE: u64 = 0x2000;
+const THRESHOLD: u64 = 0x200000;
+const CLAIM: u64 = 0x200004;
+
+/// Creates a PLIC with 40 sources and a machine-level and supervisor-level
+/// context for one hart.
+fn plic() -> (Plic, Arc<InterruptLines>) {
+    let lines = Arc::new(InterruptLines::new());
+    let contexts = [Interrupt::MachineExternal, Interrupt::SupervisorExternal]
+        .map(|interrupt| Context {
+            lines: lines.clone(),
+            interrupt,
+        })
+        .to_vec();
+
+    (Plic::new(40, contexts), lines)
+}
+
+fn read(plic: &Plic, offset: u64) -> u64 {
+    plic.read(offset, 4).expect("Failed to read register")
+}
+
+fn write(plic: &Plic, offset: u64, value: u64) {
+    plic.write(offset, 4, value)
+        .expect("Failed to write register");
+}
+
+#[test]
+fn test_claim_complete() {
+    let (plic, lines) = plic();
+    let raised = || lines.is_raised(Interrupt::MachineExternal);
+
+    write(&plic, 4 * 3, 1);
+    write(&plic, 4 * 33, 2);
+    write(&plic, ENABLE, 1 << 3);
+    write(&plic, ENABLE + 4, 1 << 1);
+
+    // Pending sources are visible even before they are enabled
+    plic.set(3, true);
+    plic.set(33, true);
+    plic.set(3, false);
+    assert_eq!(read(&plic, PENDING), 1 << 3);
+    assert_eq!(read(&plic, PENDING + 4), 1 << 1);
+    assert!(raised());
+
+    // Sources are claimed in order of priority, clearing their pending bits
+    assert_eq!(read(&plic, CLAIM), 33);
+    assert_eq!(read(&plic, PENDING + 4), 0);
+    assert!(raised());
+
+    assert_eq!(read(&plic, CLAIM), 3);
+    assert!(!raised());
+    assert_eq!(read(&plic, CLAIM), 0);
+
+    // A claimed source does not become pending until it is completed
+    plic.set(3, true);
+    assert!(!raised());
+
+    write(&plic, CLAIM, 3);
+    assert!(raised());
+    assert_eq!(read(&plic, CLAIM), 3);
+
+    // The supervisor-level context has nothing enabled
+    assert!(!lines.is_raised(Interrupt::SupervisorExternal));
+    assert_eq!(read(&plic, CLAIM + 0x1000), 0);
+}
+
+#[test]
+fn test_level_triggered() {
+    let (plic, lines) = plic();
+
+    write(&plic, 4 * 5, 1);
+    write(&plic, ENABLE, 1 << 5);
+
+    plic.set(5, true);
+    assert_eq!(read(&plic, CLAIM), 5);
+
+    // A source that is still asserted is pending again after completion
+    write(&plic, CLAIM, 5);
+    assert_eq!(read(&plic, PENDING), 1 << 5);
+    assert!(lines.is_raised(Interrupt::MachineExternal));
+    assert_eq!(read(&plic, CLAIM), 5);
+
+    // One that has been deasserted is not
+    plic.set(5, false);
+    write(&plic, CLAIM, 5);
+    assert_eq!(read(&plic, PENDING), 0);
+    assert!(!lines.is_raised(Interrupt::MachineExternal));
+}
+
+#[test]
+fn test_threshold() {
+    let (plic, lines) = plic();
+    let raised = || lines.is_raised(Interrupt::MachineExternal);
+
+    write(&plic, 4 * 7, 3);
+    write(&plic, ENABLE, 1 << 7);
+    plic.set(7, true);
+    assert!(raised());
+
+    // Only priorities strictly above the threshold are delivered
+    write(&plic, THRESHOLD, 3);
+    assert!(!raised());
+    assert_eq!(read(&plic, CLAIM), 0);
+
+    write(&plic, THRESHOLD, 2);
+    assert!(raised());
+
+    // A priority of zero is never delivered, and priorities and thresholds
+    // are limited to seven
+    write(&plic, 4 * 7, 0);
+    assert!(!raised());
+
+    write(&plic, 4 * 7, 100);
+    write(&plic, THRESHOLD, 100);
+    assert_eq!(read(&plic, 4 * 7), 7);
+    assert_eq!(read(&plic, THRESHOLD), 7);
+    assert!(!raised());
+}
+
+#[test]
+fn test_registers() {
+    let (plic, _) = plic();
+
+    // Source zero and sources that do not exist cannot be enabled or given a
+    // priority
+    write(&plic, 0, 5);
+    write(&plic, ENABLE + 4, u32::MAX as u64);
+    assert_eq!(read(&plic, 0), 0);
+    assert_eq!(read(&plic, ENABLE + 4), 0x1FF);
+
+    write(&plic, ENABLE, u32::MAX as u64);
+    assert_eq!(read(&plic, ENABLE), 0xFFFF_FFFE);
+
+    assert!(matches!(
+        plic.write(4 * 41, 4, 1),
+        Err(BusError::AccessFault)
+    ));
+    assert!(matches!(
+        plic.read(CLAIM + 0x2000, 4),
+        Err(BusError::AccessFault)
+    ));
+    assert!(matches!(plic.read(4, 8), Err(BusError::AccessFault)));
+    assert!(matches!(plic.read(2, 4), Err(BusError::AddressMisaligned)));
+}
@@ -0,0 +1,163 @@
+//! Checks the registers of the UART with the [Buffer] backend.
+
+use std::sync::Arc;
+
+use irv_devices::{Buffer, Context, Plic, Uart};
+use irv_traits::{Device, Interrupt, InterruptLines};
+
+const RBR: u64 = 0;
+const THR: u64 = 0;
+const IER: u64 = 1;
+const IIR: u64 = 2;
+const FCR: u64 = 2;
+const LCR: u64 = 3;
+const MCR: u64 = 4;
+const LSR: u64 = 5;
+const MSR: u64 = 6;
+
+const LSR_IDLE: u64 = 0x60;
+const LSR_DATA: u64 = 0x61;
+
+fn read(uart: &Uart, offset: u64) -> u64 {
+    uart.read(offset, 1).expect("Failed to read register")
+}
+
+fn write(uart: &Uart, offset: u64, value: u64) {
+    uart.write(offset, 1, value)
+        .expect("Failed to write register");
+}
+
+#[test]
+fn test_transmit() {
+    let buffer = Buffer::new();
+    let uart = Uart::new(buffer.clone());
+
+    for &byte in b"hello" {
+        write(&uart, THR, byte.into());
+    }
+
+    assert_eq!(buffer.take_output(), b"hello");
+    assert_eq!(read(&uart, LSR), LSR_IDLE);
+
+    // With DLAB set, offset 0 is the divisor latch instead
+    write(&uart, LCR, 0x80);
+    write(&uart, THR, 0x12);
+    assert_eq!(read(&uart, 0), 0x12);
+    assert!(buffer.take_output().is_empty());
+}
+
+#[test]
+fn test_receive() {
+    let buffer = Buffer::new();
+    let uart = Uart::new(buffer.clone());
+
+    buffer.push_input(b"abc");
+
+    // Without the FIFOs, the receiver holds a single byte
+    assert_eq!(read(&uart, IIR), 0x01);
+    assert_eq!(read(&uart, LSR), LSR_DATA);
+    assert_eq!(read(&uart, RBR), b'a'.into());
+    assert_eq!(read(&uart, RBR), b'b'.into());
+
+    // Enabling the FIFOs clears them, and sets the top bits of IIR
+    write(&uart, FCR, 0x01);
+    assert_eq!(read(&uart, IIR), 0xC1);
+    assert_eq!(read(&uart, LSR), LSR_IDLE);
+
+    buffer.push_input(b"0123456789ABCDEFGHIJ");
+
+    // The FIFO holds 16 bytes, with the rest left in the backend
+    write(&uart, IER, 0x01);
+    let received: Vec<u8> = (0..16).map(|_| read(&uart, RBR) as u8).collect();
+    assert_eq!(received, b"0123456789ABCDEF");
+
+    // Reading makes space for the rest
+    let received: Vec<u8> = (0..4).map(|_| read(&uart, RBR) as u8).collect();
+    assert_eq!(received, b"GHIJ");
+    assert_eq!(read(&uart, LSR), LSR_IDLE);
+
+    // Data below the trigger level is reported as a timeout
+    write(&uart, FCR, 0x01 | 0b01 << 6);
+    buffer.push_input(b"xyz");
+    uart.update();
+    assert_eq!(read(&uart, IIR), 0xCC);
+
+    buffer.push_input(b"w");
+    uart.update();
+    assert_eq!(read(&uart, IIR), 0xC4);
+
+    // Clearing the receive FIFO empties it
+    write(&uart, FCR, 0x01 | 0b10);
+    assert_eq!(read(&uart, LSR), LSR_IDLE);
+    assert_eq!(read(&uart, IIR), 0xC1);
+}
+
+#[test]
+fn test_transmitter_empty_interrupt() {
+    let uart = Uart::new(Buffer::new());
+
+    // Enabling the interrupt raises it, and identifying it clears it
+    write(&uart, IER, 0x02);
+    assert_eq!(read(&uart, IIR), 0x02);
+    assert_eq!(read(&uart, IIR), 0x01);
+
+    write(&uart, THR, b'!'.into());
+    assert_eq!(read(&uart, IIR), 0x02);
+}
+
+#[test]
+fn test_loopback() {
+    let buffer = Buffer::new();
+    let uart = Uart::new(buffer.clone());
+
+    buffer.push_input(b"in");
+    write(&uart, MCR, 0x10 | 0b1011);
+
+    // Transmitted bytes are received again instead of reaching the backend
+    write(&uart, THR, b'x'.into());
+    assert!(buffer.take_output().is_empty());
+    assert_eq!(read(&uart, LSR), LSR_DATA);
+    assert_eq!(read(&uart, RBR), b'x'.into());
+    assert_eq!(read(&uart, LSR), LSR_IDLE);
+
+    // DTR, RTS, and OUT2 are reflected as DSR, CTS, and DCD
+    assert_eq!(read(&uart, MSR), 0xB0);
+
+    // The backend is read again once loopback is turned off
+    write(&uart, MCR, 0);
+    assert_eq!(read(&uart, RBR), b'i'.into());
+}
+
+#[test]
+fn test_interrupt() {
+    let lines = Arc::new(InterruptLines::new());
+    let context = Context {
+        lines: lines.clone(),
+        interrupt: Interrupt::MachineExternal,
+    };
+    let plic = Arc::new(Plic::new(10, vec![context]));
+
+    plic.write(4 * 10, 4, 1).unwrap();
+    plic.write(0x2000, 4, 1 << 10).unwrap();
+
+    let buffer = Buffer::new();
+    let mut uart = Uart::new(buffer.clone());
+    uart.connect(plic.clone(), 10);
+
+    write(&uart, IER, 0x01);
+    assert!(!lines.is_raised(Interrupt::MachineExternal));
+
+    // Received bytes are only noticed when the UART is updated
+    buffer.push_input(b"a");
+    assert!(!lines.is_raised(Interrupt::MachineExternal));
+
+    uart.update();
+    assert!(lines.is_raised(Interrupt::MachineExternal));
+    assert_eq!(plic.read(0x200004, 4).unwrap(), 10);
+
+    // Reading the byte deasserts the source, so completing does not make it
+    // pending again
+    read(&uart, RBR);
+    plic.write(0x200004, 4, 10).unwrap();
+    assert!(!lines.is_raised(Interrupt::MachineExternal));
+}
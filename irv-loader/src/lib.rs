@@ -33,15 +33,8 @@ where
         let data = &elf_data[phdr.file_range()];
         let vm_range = phdr.vm_range();
 
-        let mut vm_addr = phdr.vm_range().start as u64;
-        for byte in data {
-            if vm_addr == vm_range.end as u64 {
-                break;
-            }
-
+        for (vm_addr, byte) in (vm_range.start as u64..vm_range.end as u64).zip(data) {
             bus.store(vm_addr, *byte)?;
-
-            vm_addr += 1;
         }
     }
 
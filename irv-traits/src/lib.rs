@@ -1,5 +1,10 @@
 #![no_std]
 
+use core::{
+    mem::size_of,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
 /// An error that can be thrown on a memory access.
 #[derive(Debug)]
 pub enum BusError {
@@ -9,12 +14,356 @@ pub enum BusError {
     AddressMisaligned,
 }
 
+/// The kinds of memory access made by a hart.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AccessKind {
+    /// An instruction fetch.
+    Fetch,
+    /// A load, including a load-reserved.
+    Load,
+    /// A store, including a store-conditional.
+    Store,
+    /// An atomic memory operation, which reads and writes in a single access.
+    Amo,
+}
+
+/// How likely the data accessed by a load or store is to be accessed again
+/// soon, as hinted by an instruction of the Zihintntl extension.
+///
+/// Each non-temporal level hints that the data has no temporal locality within
+/// the given levels of the memory hierarchy, so caches there need not keep it.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum Locality {
+    /// No hint was given, so the data may be accessed again soon.
+    #[default]
+    Temporal,
+    /// `NTL.P1`: no temporal locality within the innermost private cache.
+    InnermostPrivate,
+    /// `NTL.PALL`: no temporal locality within any private cache.
+    AllPrivate,
+    /// `NTL.S1`: no temporal locality within the innermost shared cache.
+    InnermostShared,
+    /// `NTL.ALL`: no temporal locality within any level of the memory
+    /// hierarchy.
+    All,
+}
+
+/// The attributes of a memory access made by a hart, which a bus can use to
+/// treat accesses differently, such as to forbid instruction fetches from
+/// I/O devices.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct MemoryAccess {
+    /// The kind of the access.
+    pub kind: AccessKind,
+    /// The effective privilege level of the access, which for loads and
+    /// stores takes `mstatus.MPRV` into account.
+    pub privilege: Privilege,
+    /// Whether the access reads or updates a page table entry while
+    /// translating an address, rather than being made by an instruction.
+    pub walk: bool,
+    /// The temporal locality of the data accessed, as hinted by the
+    /// instruction before the one making the access.
+    pub locality: Locality,
+}
+
+impl MemoryAccess {
+    /// Creates the attributes of an access of the given kind made by an
+    /// instruction at the given `privilege` level.
+    pub const fn new(kind: AccessKind, privilege: Privilege) -> MemoryAccess {
+        MemoryAccess {
+            kind,
+            privilege,
+            walk: false,
+            locality: Locality::Temporal,
+        }
+    }
+
+    /// Creates the attributes of an access of the given kind made to a page
+    /// table entry, which is always made at supervisor level.
+    pub const fn walk(kind: AccessKind) -> MemoryAccess {
+        MemoryAccess {
+            kind,
+            privilege: Privilege::Supervisor,
+            walk: true,
+            locality: Locality::Temporal,
+        }
+    }
+}
+
 /// A bus facilitating accesses on the emulated physical bus.
+///
+/// Harts make every access with [Bus::load_with] and [Bus::store_with], which
+/// also give the attributes of the access. By default, these ignore the
+/// attributes, so a simple bus only needs to implement [Bus::load] and
+/// [Bus::store].
 pub trait Bus<A, V> {
     /// Loads the value located at the given `address`.
     fn load(&self, address: A) -> Result<V, BusError>;
     /// Stores the given `value` to the given `address`.
     fn store(&self, address: A, value: V) -> Result<(), BusError>;
+
+    /// Loads the value located at the given `address` for a fetch, load, or
+    /// AMO with the given attributes.
+    fn load_with(&self, address: A, _access: MemoryAccess) -> Result<V, BusError> {
+        self.load(address)
+    }
+
+    /// Stores the given `value` to the given `address` for a store or AMO with
+    /// the given attributes.
+    fn store_with(&self, address: A, value: V, _access: MemoryAccess) -> Result<(), BusError> {
+        self.store(address, value)
+    }
+}
+
+/// A bus that additionally supports the atomic memory operations required by
+/// the A extension.
+///
+/// Reservations are tracked by the bus on behalf of each hart, so that stores
+/// made through the bus (by any hart or device) can invalidate them.
+pub trait AtomicBus<A, V>: Bus<A, V> {
+    /// Loads the value located at the given `address` and registers a
+    /// reservation on the reservation set containing it for the given `hart`.
+    fn load_reserved(&self, hart: u64, address: A) -> Result<V, BusError>;
+    /// Stores the given `value` to the given `address` only if `hart` still
+    /// holds a valid reservation covering it.
+    ///
+    /// Any reservation held by `hart` is invalidated, regardless of whether the
+    /// store takes place. Returns whether the store took place.
+    fn store_conditional(&self, hart: u64, address: A, value: V) -> Result<bool, BusError>;
+    /// Atomically replaces the value located at the given `address` with the
+    /// result of calling `f` with its current value.
+    ///
+    /// Returns the original value on success.
+    fn fetch_update(&self, address: A, f: impl FnOnce(V) -> V) -> Result<V, BusError>;
+
+    /// Like [AtomicBus::load_reserved], for a load with the given attributes.
+    ///
+    /// As with [Bus::load_with], the attributes are ignored by default.
+    fn load_reserved_with(
+        &self,
+        hart: u64,
+        address: A,
+        _access: MemoryAccess,
+    ) -> Result<V, BusError> {
+        self.load_reserved(hart, address)
+    }
+
+    /// Like [AtomicBus::store_conditional], for a store with the given
+    /// attributes.
+    fn store_conditional_with(
+        &self,
+        hart: u64,
+        address: A,
+        value: V,
+        _access: MemoryAccess,
+    ) -> Result<bool, BusError> {
+        self.store_conditional(hart, address, value)
+    }
+
+    /// Like [AtomicBus::fetch_update], for an AMO with the given attributes.
+    fn fetch_update_with(
+        &self,
+        address: A,
+        f: impl FnOnce(V) -> V,
+        _access: MemoryAccess,
+    ) -> Result<V, BusError> {
+        self.fetch_update(address, f)
+    }
+}
+
+/// A device on the emulated physical bus, such as memory or a memory-mapped
+/// I/O device, which is accessed with offsets relative to its base address.
+///
+/// Unlike [Bus], the size of each access is given at runtime rather than by the
+/// value type, so a device covers every access width with a single
+/// implementation and can be used as a `dyn Device`. Every device is also a
+/// [Bus] for each access width, and a [Bus] can be used as a device with
+/// [BusDevice].
+///
+/// Devices take `&self`, so any state changed by an access, including the side
+/// effects of reads (such as popping a FIFO), uses interior mutability.
+pub trait Device {
+    /// Reads the value of the given `size` in bytes located at the given
+    /// `offset`, which is zero-extended.
+    ///
+    /// The size is always 1, 2, 4, or 8. Devices return
+    /// [BusError::AccessFault] for sizes they do not support.
+    fn read(&self, offset: u64, size: u64) -> Result<u64, BusError>;
+
+    /// Writes the lowest `size` bytes of the given `value` to the given
+    /// `offset`, with sizes as in [Device::read].
+    fn write(&self, offset: u64, size: u64, value: u64) -> Result<(), BusError>;
+
+    /// Reads the value located at the given `offset` to fetch an instruction,
+    /// with sizes as in [Device::read].
+    ///
+    /// By default, this is the same as [Device::read].
+    fn fetch(&self, offset: u64, size: u64) -> Result<u64, BusError> {
+        self.read(offset, size)
+    }
+}
+
+macro_rules! impl_device_bus {
+    ($($val:ident)*) => {
+        $(impl<D: Device + ?Sized> Bus<u64, $val> for D {
+            fn load(&self, address: u64) -> Result<$val, BusError> {
+                Ok(self.read(address, size_of::<$val>() as u64)? as $val)
+            }
+
+            fn store(&self, address: u64, value: $val) -> Result<(), BusError> {
+                self.write(address, size_of::<$val>() as u64, value as u64)
+            }
+
+            fn load_with(&self, address: u64, access: MemoryAccess) -> Result<$val, BusError> {
+                match access.kind {
+                    AccessKind::Fetch => Ok(self.fetch(address, size_of::<$val>() as u64)? as $val),
+                    _ => self.load(address),
+                }
+            }
+        })*
+    };
+}
+
+impl_device_bus! { u8 u16 u32 u64 }
+
+/// A device that makes each access through a [Bus] of the matching width,
+/// which allows a type implementing [Bus] for every access width, such as
+/// memory, to be used as a [Device].
+#[derive(Debug)]
+pub struct BusDevice<B>(pub B);
+
+impl<B> Device for BusDevice<B>
+where
+    B: Bus<u64, u8> + Bus<u64, u16> + Bus<u64, u32> + Bus<u64, u64>,
+{
+    fn read(&self, offset: u64, size: u64) -> Result<u64, BusError> {
+        match size {
+            1 => Bus::<u64, u8>::load(&self.0, offset).map(u64::from),
+            2 => Bus::<u64, u16>::load(&self.0, offset).map(u64::from),
+            4 => Bus::<u64, u32>::load(&self.0, offset).map(u64::from),
+            8 => Bus::<u64, u64>::load(&self.0, offset),
+            _ => Err(BusError::AccessFault),
+        }
+    }
+
+    fn write(&self, offset: u64, size: u64, value: u64) -> Result<(), BusError> {
+        match size {
+            1 => self.0.store(offset, value as u8),
+            2 => self.0.store(offset, value as u16),
+            4 => self.0.store(offset, value as u32),
+            8 => self.0.store(offset, value),
+            _ => Err(BusError::AccessFault),
+        }
+    }
+}
+
+/// A source of the interrupts raised for each hart, which is usually
+/// implemented by the bus that interrupting devices are connected to.
+pub trait Interrupts {
+    /// Gets the interrupt lines that are currently raised for the given
+    /// `hart`, with each [Interrupt] at the bit given by [Interrupt::bit].
+    fn pending(&self, hart: u64) -> u64;
+
+    /// Takes the interrupts that have been triggered for the given `hart`
+    /// since this was last called, in the same format as [Interrupts::pending].
+    ///
+    /// Unlike a raised line, a triggered interrupt only sets its bit of `mip`
+    /// once, and leaves software to clear it. By default, no interrupts are
+    /// ever triggered.
+    fn triggered(&self, _hart: u64) -> u64 {
+        0
+    }
+}
+
+/// An interrupt that can be raised for a hart, which is identified by its
+/// exception code.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Interrupt {
+    SupervisorSoftware = 1,
+    MachineSoftware = 3,
+    SupervisorTimer = 5,
+    MachineTimer = 7,
+    SupervisorExternal = 9,
+    MachineExternal = 11,
+}
+
+impl Interrupt {
+    /// All interrupts, from the highest priority to the lowest.
+    pub const PRIORITY: [Interrupt; 6] = [
+        Interrupt::MachineExternal,
+        Interrupt::MachineSoftware,
+        Interrupt::MachineTimer,
+        Interrupt::SupervisorExternal,
+        Interrupt::SupervisorSoftware,
+        Interrupt::SupervisorTimer,
+    ];
+
+    /// Gets the bit of `mip` and `mie` that corresponds to this interrupt.
+    pub const fn bit(self) -> u64 {
+        1 << self as u64
+    }
+}
+
+/// The interrupt lines of a single hart, which can be raised, lowered, and
+/// triggered by the host or by devices on any thread.
+///
+/// A bus can implement [Interrupts] by forwarding to the lines of each hart.
+#[derive(Debug, Default)]
+pub struct InterruptLines {
+    raised: AtomicU64,
+    triggered: AtomicU64,
+}
+
+impl InterruptLines {
+    /// Creates a new set of interrupt lines, which are all lowered.
+    pub const fn new() -> InterruptLines {
+        InterruptLines {
+            raised: AtomicU64::new(0),
+            triggered: AtomicU64::new(0),
+        }
+    }
+
+    /// Raises the line of the given `interrupt`.
+    pub fn raise(&self, interrupt: Interrupt) {
+        self.raised.fetch_or(interrupt.bit(), Ordering::SeqCst);
+    }
+
+    /// Lowers the line of the given `interrupt`.
+    pub fn lower(&self, interrupt: Interrupt) {
+        self.raised.fetch_and(!interrupt.bit(), Ordering::SeqCst);
+    }
+
+    /// Raises the line of the given `interrupt` if `raised` is set, or lowers
+    /// it otherwise.
+    pub fn set(&self, interrupt: Interrupt, raised: bool) {
+        if raised {
+            self.raise(interrupt);
+        } else {
+            self.lower(interrupt);
+        }
+    }
+
+    /// Checks whether the line of the given `interrupt` is raised.
+    pub fn is_raised(&self, interrupt: Interrupt) -> bool {
+        self.pending() & interrupt.bit() != 0
+    }
+
+    /// Triggers the given `interrupt` once, without changing its line (see
+    /// [Interrupts::triggered]).
+    pub fn trigger(&self, interrupt: Interrupt) {
+        self.triggered.fetch_or(interrupt.bit(), Ordering::SeqCst);
+    }
+
+    /// Gets the lines that are raised, as returned by [Interrupts::pending].
+    pub fn pending(&self) -> u64 {
+        self.raised.load(Ordering::SeqCst)
+    }
+
+    /// Takes the interrupts that have been triggered since this was last
+    /// called, as returned by [Interrupts::triggered].
+    pub fn take_triggered(&self) -> u64 {
+        self.triggered.swap(0, Ordering::SeqCst)
+    }
 }
 
 /// A bus facilitating accesses to the emulated CSRs.
@@ -32,6 +381,48 @@ pub trait Csr {
         address: CsrAddress,
         f: impl FnOnce(u64) -> u64,
     ) -> Result<u64, CsrIllegal>;
+
+    /// Checks whether an instruction executing at the given `privilege` level
+    /// is permitted to access the CSR at the given `address`, which it will
+    /// write to if `write` is set.
+    ///
+    /// This is called before each access made by an instruction, but not
+    /// before accesses made by the hart itself (such as when taking a trap).
+    /// By default, this only enforces the conventions of the CSR address
+    /// encoding, as checked by [CsrAddress::is_accessible].
+    fn check(
+        &self,
+        address: CsrAddress,
+        privilege: Privilege,
+        write: bool,
+    ) -> Result<(), CsrIllegal> {
+        if address.is_accessible(privilege, write) {
+            Ok(())
+        } else {
+            Err(CsrIllegal)
+        }
+    }
+}
+
+/// A privilege level at which a hart can execute.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Privilege {
+    User = 0,
+    Supervisor = 1,
+    Machine = 3,
+}
+
+impl Privilege {
+    /// Gets the privilege level encoded by the given bits, as found in fields
+    /// such as `mstatus.MPP`, if they encode a valid privilege level.
+    pub const fn from_bits(bits: u64) -> Option<Privilege> {
+        match bits {
+            0 => Some(Privilege::User),
+            1 => Some(Privilege::Supervisor),
+            3 => Some(Privilege::Machine),
+            _ => None,
+        }
+    }
 }
 
 /// Returned by [Csr::access] to indicate an illegal CSR access.
@@ -39,20 +430,125 @@ pub struct CsrIllegal;
 
 /// A wrapper for integers guaranteed to be less than 4096 that is used to represent
 /// CSR addresses.
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub struct CsrAddress(u16);
 
 impl Csr for () {
     fn access(
         &mut self,
-        address: CsrAddress,
-        f: impl FnOnce(u64) -> u64,
+        _address: CsrAddress,
+        _f: impl FnOnce(u64) -> u64,
     ) -> Result<u64, CsrIllegal> {
         Err(CsrIllegal)
     }
 }
 
 impl CsrAddress {
+    /// Floating-point accrued exceptions.
+    pub const FFLAGS: CsrAddress = CsrAddress(0x001);
+    /// Floating-point dynamic rounding mode.
+    pub const FRM: CsrAddress = CsrAddress(0x002);
+    /// Floating-point control and status register (`frm` and `fflags`).
+    pub const FCSR: CsrAddress = CsrAddress(0x003);
+    /// Vector start position.
+    pub const VSTART: CsrAddress = CsrAddress(0x008);
+    /// Fixed-point accrued saturation flag.
+    pub const VXSAT: CsrAddress = CsrAddress(0x009);
+    /// Fixed-point rounding mode.
+    pub const VXRM: CsrAddress = CsrAddress(0x00A);
+    /// Vector control and status register (`vxrm` and `vxsat`).
+    pub const VCSR: CsrAddress = CsrAddress(0x00F);
+    /// Seed for cryptographic random bit generators.
+    pub const SEED: CsrAddress = CsrAddress(0x015);
+    /// Supervisor status register.
+    pub const SSTATUS: CsrAddress = CsrAddress(0x100);
+    /// Supervisor interrupt-enable register.
+    pub const SIE: CsrAddress = CsrAddress(0x104);
+    /// Supervisor trap handler base address.
+    pub const STVEC: CsrAddress = CsrAddress(0x105);
+    /// Supervisor counter-enable register.
+    pub const SCOUNTEREN: CsrAddress = CsrAddress(0x106);
+    /// Scratch register for supervisor trap handlers.
+    pub const SSCRATCH: CsrAddress = CsrAddress(0x140);
+    /// Supervisor exception program counter.
+    pub const SEPC: CsrAddress = CsrAddress(0x141);
+    /// Supervisor trap cause.
+    pub const SCAUSE: CsrAddress = CsrAddress(0x142);
+    /// Supervisor bad address or instruction.
+    pub const STVAL: CsrAddress = CsrAddress(0x143);
+    /// Supervisor interrupt pending.
+    pub const SIP: CsrAddress = CsrAddress(0x144);
+    /// Supervisor address translation and protection.
+    pub const SATP: CsrAddress = CsrAddress(0x180);
+    /// Machine status register.
+    pub const MSTATUS: CsrAddress = CsrAddress(0x300);
+    /// ISA and extensions.
+    pub const MISA: CsrAddress = CsrAddress(0x301);
+    /// Machine exception delegation register.
+    pub const MEDELEG: CsrAddress = CsrAddress(0x302);
+    /// Machine interrupt delegation register.
+    pub const MIDELEG: CsrAddress = CsrAddress(0x303);
+    /// Machine interrupt-enable register.
+    pub const MIE: CsrAddress = CsrAddress(0x304);
+    /// Machine trap-handler base address.
+    pub const MTVEC: CsrAddress = CsrAddress(0x305);
+    /// Machine counter-enable register.
+    pub const MCOUNTEREN: CsrAddress = CsrAddress(0x306);
+    /// Upper 32 bits of `mstatus`, RV32 only.
+    pub const MSTATUSH: CsrAddress = CsrAddress(0x310);
+    /// Machine counter-inhibit register.
+    pub const MCOUNTINHIBIT: CsrAddress = CsrAddress(0x320);
+    /// Scratch register for machine trap handlers.
+    pub const MSCRATCH: CsrAddress = CsrAddress(0x340);
+    /// Machine exception program counter.
+    pub const MEPC: CsrAddress = CsrAddress(0x341);
+    /// Machine trap cause.
+    pub const MCAUSE: CsrAddress = CsrAddress(0x342);
+    /// Machine bad address or instruction.
+    pub const MTVAL: CsrAddress = CsrAddress(0x343);
+    /// Machine interrupt pending.
+    pub const MIP: CsrAddress = CsrAddress(0x344);
+    /// Machine security configuration register.
+    pub const MSECCFG: CsrAddress = CsrAddress(0x747);
+    /// Upper 32 bits of `mseccfg`, RV32 only.
+    pub const MSECCFGH: CsrAddress = CsrAddress(0x757);
+    /// Machine cycle counter.
+    pub const MCYCLE: CsrAddress = CsrAddress(0xB00);
+    /// Machine instructions-retired counter.
+    pub const MINSTRET: CsrAddress = CsrAddress(0xB02);
+    /// Upper 32 bits of `mcycle`, RV32 only.
+    pub const MCYCLEH: CsrAddress = CsrAddress(0xB80);
+    /// Upper 32 bits of `minstret`, RV32 only.
+    pub const MINSTRETH: CsrAddress = CsrAddress(0xB82);
+    /// Cycle counter, a read-only shadow of `mcycle`.
+    pub const CYCLE: CsrAddress = CsrAddress(0xC00);
+    /// Real-time counter.
+    pub const TIME: CsrAddress = CsrAddress(0xC01);
+    /// Instructions-retired counter, a read-only shadow of `minstret`.
+    pub const INSTRET: CsrAddress = CsrAddress(0xC02);
+    /// Vector length.
+    pub const VL: CsrAddress = CsrAddress(0xC20);
+    /// Vector data type register.
+    pub const VTYPE: CsrAddress = CsrAddress(0xC21);
+    /// The length of each vector register in bytes (VLEN / 8).
+    pub const VLENB: CsrAddress = CsrAddress(0xC22);
+    /// Upper 32 bits of `cycle`, RV32 only.
+    pub const CYCLEH: CsrAddress = CsrAddress(0xC80);
+    /// Upper 32 bits of `time`, RV32 only.
+    pub const TIMEH: CsrAddress = CsrAddress(0xC81);
+    /// Upper 32 bits of `instret`, RV32 only.
+    pub const INSTRETH: CsrAddress = CsrAddress(0xC82);
+    /// Vendor ID.
+    pub const MVENDORID: CsrAddress = CsrAddress(0xF11);
+    /// Architecture ID.
+    pub const MARCHID: CsrAddress = CsrAddress(0xF12);
+    /// Implementation ID.
+    pub const MIMPID: CsrAddress = CsrAddress(0xF13);
+    /// Hardware thread ID.
+    pub const MHARTID: CsrAddress = CsrAddress(0xF14);
+    /// Pointer to configuration data structure.
+    pub const MCONFIGPTR: CsrAddress = CsrAddress(0xF15);
+
     /// Creates a new CsrAddress if the given address is less than 4096.
     pub const fn new(address: u16) -> Option<CsrAddress> {
         if address < 4096 {
@@ -76,4 +572,14 @@ impl CsrAddress {
     pub const fn address(self) -> u16 {
         self.0
     }
+
+    /// Checks whether the conventions of the CSR address encoding permit an
+    /// access to this CSR at the given `privilege` level, which will write to
+    /// it if `write` is set.
+    ///
+    /// Bits 9:8 give the lowest privilege level that can access the CSR, and
+    /// CSRs with bits 11:10 set to `0b11` are read-only.
+    pub const fn is_accessible(self, privilege: Privilege, write: bool) -> bool {
+        (self.0 >> 8 & 0b11) as u8 <= privilege as u8 && !(write && self.0 >> 10 == 0b11)
+    }
 }
@@ -0,0 +1,237 @@
+//! The counters of the Zicntr and Zihpm extensions.
+
+use core::ops::{BitOr, BitOrAssign};
+
+use alloc::vec::Vec;
+
+use crate::{CsrAddress, CsrIllegal};
+
+/// The number of hardware performance-monitoring counters, `mhpmcounter3`
+/// through `mhpmcounter31`.
+const HPM_COUNTERS: usize = 29;
+
+/// The fields of `mcountinhibit` that can be written, which excludes `TM`
+/// since `time` cannot be inhibited.
+const MCOUNTINHIBIT_MASK: u64 = 0xFFFF_FFFD;
+
+/// A set of events that can happen while a hart executes, which a
+/// hardware performance-monitoring counter can be programmed to count.
+///
+/// Events other than [Events::CYCLES], [Events::EXCEPTIONS], and
+/// [Events::INTERRUPTS] are only counted for instructions that retire.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Events(u32);
+
+impl Events {
+    /// No events.
+    pub const NONE: Events = Events(0);
+    /// A cycle passed, including while the hart is waiting for an interrupt.
+    pub const CYCLES: Events = Events(1 << 0);
+    /// An instruction retired.
+    pub const INSTRUCTIONS: Events = Events(1 << 1);
+    /// An instruction loaded from memory, including `LR`, AMOs, and the
+    /// floating-point and vector loads.
+    pub const LOADS: Events = Events(1 << 2);
+    /// An instruction stored to memory, including `SC`, AMOs, and the
+    /// floating-point and vector stores.
+    pub const STORES: Events = Events(1 << 3);
+    /// A conditional branch was executed.
+    pub const BRANCHES: Events = Events(1 << 4);
+    /// A conditional branch was taken.
+    pub const BRANCHES_TAKEN: Events = Events(1 << 5);
+    /// An instruction raised an exception.
+    pub const EXCEPTIONS: Events = Events(1 << 6);
+    /// An interrupt was taken as a trap.
+    pub const INTERRUPTS: Events = Events(1 << 7);
+    /// An exception was raised or an interrupt was taken.
+    pub const TRAPS: Events = Events(Events::EXCEPTIONS.0 | Events::INTERRUPTS.0);
+
+    /// Checks whether any of the events in `other` are in this set.
+    pub const fn intersects(self, other: Events) -> bool {
+        self.0 & other.0 != 0
+    }
+}
+
+impl BitOr for Events {
+    type Output = Events;
+
+    fn bitor(self, other: Events) -> Events {
+        Events(self.0 | other.0)
+    }
+}
+
+impl BitOrAssign for Events {
+    fn bitor_assign(&mut self, other: Events) {
+        self.0 |= other.0;
+    }
+}
+
+/// The counters of a hart, which hold the state of `mcycle`, `minstret`,
+/// `mcountinhibit`, and the hardware performance-monitoring counters along
+/// with their `mhpmevent` selectors.
+///
+/// `cycle`, `time`, `instret`, and `hpmcounter3` through `hpmcounter31` are
+/// read-only shadows of these counters. Whether they can be read below machine
+/// mode is controlled by `mcounteren` and `scounteren`, which belong to the
+/// CSRs of the hart (see [MachineCsrs](crate::MachineCsrs)).
+///
+/// Each `mhpmevent` holds an event selector whose meaning is defined by the
+/// host with [Counters::define_event]. A counter whose selector is zero or has
+/// not been defined counts nothing.
+#[derive(Clone, Debug, Default)]
+pub struct Counters {
+    /// Machine cycle counter, which is incremented each time the hart is
+    /// stepped.
+    pub mcycle: u64,
+    /// Machine instructions-retired counter.
+    pub minstret: u64,
+    /// The real-time counter read through `time`, which the host is
+    /// responsible for keeping up to date with the platform's `mtime`.
+    pub time: u64,
+    /// Machine counter-inhibit register, where each set bit stops the
+    /// corresponding counter from being incremented.
+    pub mcountinhibit: u64,
+    /// The values of `mhpmcounter3` through `mhpmcounter31`.
+    hpm_counters: [u64; HPM_COUNTERS],
+    /// The values of `mhpmevent3` through `mhpmevent31`.
+    hpm_events: [u64; HPM_COUNTERS],
+    /// The events selected by each `mhpmevent`, as given by `definitions`.
+    selected: [Events; HPM_COUNTERS],
+    /// The events defined by the host for each event selector.
+    definitions: Vec<(u64, Events)>,
+    /// The counters written by the current instruction, in the same layout
+    /// as `mcountinhibit`.
+    written: u64,
+}
+
+impl Counters {
+    /// Creates a new set of counters that are all zero, with no events
+    /// defined.
+    pub const fn new() -> Counters {
+        Counters {
+            mcycle: 0,
+            minstret: 0,
+            time: 0,
+            mcountinhibit: 0,
+            hpm_counters: [0; HPM_COUNTERS],
+            hpm_events: [0; HPM_COUNTERS],
+            selected: [Events::NONE; HPM_COUNTERS],
+            definitions: Vec::new(),
+            written: 0,
+        }
+    }
+
+    /// Defines the events counted by a hardware performance-monitoring
+    /// counter whose `mhpmevent` holds `selector`, replacing any previous
+    /// definition.
+    ///
+    /// A counter is incremented once each time the hart is stepped if any of
+    /// the given events happen.
+    ///
+    /// # Panics
+    /// Panics if `selector` is zero, which always counts nothing.
+    pub fn define_event(&mut self, selector: u64, events: Events) {
+        assert!(selector != 0, "event selector zero cannot be defined");
+
+        match self.definitions.iter_mut().find(|(s, _)| *s == selector) {
+            Some(definition) => definition.1 = events,
+            None => self.definitions.push((selector, events)),
+        }
+
+        self.selected = self.hpm_events.map(|selector| self.lookup(selector));
+    }
+
+    /// Gets the value of `mhpmcounter<n>`.
+    ///
+    /// # Panics
+    /// Panics if `n` is not between 3 and 31.
+    pub fn hpm_counter(&self, n: usize) -> u64 {
+        assert!((3..=31).contains(&n), "only mhpmcounter3 through 31 exist");
+
+        self.hpm_counters[n - 3]
+    }
+
+    /// Gets the event selector held by `mhpmevent<n>`.
+    ///
+    /// # Panics
+    /// Panics if `n` is not between 3 and 31.
+    pub fn hpm_event(&self, n: usize) -> u64 {
+        assert!((3..=31).contains(&n), "only mhpmevent3 through 31 exist");
+
+        self.hpm_events[n - 3]
+    }
+
+    /// Increments each counter that is not inhibited and counts any of the
+    /// given `events`.
+    ///
+    /// Counters written by the instruction being counted are not incremented,
+    /// so that the next instruction reads the value that was written.
+    pub(crate) fn count(&mut self, events: Events) {
+        let inhibit = self.mcountinhibit | core::mem::take(&mut self.written);
+
+        if events.intersects(Events::CYCLES) && inhibit & 1 == 0 {
+            self.mcycle = self.mcycle.wrapping_add(1);
+        }
+
+        if events.intersects(Events::INSTRUCTIONS) && inhibit & 1 << 2 == 0 {
+            self.minstret = self.minstret.wrapping_add(1);
+        }
+
+        let counters = self.hpm_counters.iter_mut().zip(self.selected);
+
+        for (i, (counter, selected)) in counters.enumerate() {
+            if selected.intersects(events) && inhibit >> (i + 3) & 1 == 0 {
+                *counter = counter.wrapping_add(1);
+            }
+        }
+    }
+
+    /// Accesses `mcountinhibit`, one of the `mhpmevent` CSRs, or one of the
+    /// counters or their unprivileged shadows, which ignore writes.
+    ///
+    /// A counter is only written if `write` is set.
+    pub(crate) fn access_csr(
+        &mut self,
+        address: CsrAddress,
+        write: bool,
+        f: impl FnOnce(u64) -> u64,
+    ) -> Result<u64, CsrIllegal> {
+        let index = address.address() as usize & 0x1F;
+
+        let counter = match address.address() {
+            0x320 => {
+                let value = self.mcountinhibit;
+                self.mcountinhibit = f(value) & MCOUNTINHIBIT_MASK;
+                return Ok(value);
+            }
+            0x323..=0x33F => {
+                let value = self.hpm_events[index - 3];
+                self.hpm_events[index - 3] = f(value);
+                self.selected[index - 3] = self.lookup(self.hpm_events[index - 3]);
+                return Ok(value);
+            }
+            0xC01 => return Ok(self.time),
+            0xB00 | 0xC00 => &mut self.mcycle,
+            0xB02 | 0xC02 => &mut self.minstret,
+            0xB03..=0xB1F | 0xC03..=0xC1F => &mut self.hpm_counters[index - 3],
+            _ => return Err(CsrIllegal),
+        };
+
+        let value = *counter;
+
+        if write && address.address() < 0xC00 {
+            *counter = f(value);
+            self.written |= 1 << index;
+        }
+
+        Ok(value)
+    }
+
+    /// Gets the events defined for the given event selector.
+    fn lookup(&self, selector: u64) -> Events {
+        self.definitions
+            .iter()
+            .find(|&&(s, _)| s == selector)
+            .map_or(Events::NONE, |&(_, events)| events)
+    }
+}
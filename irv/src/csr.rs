@@ -0,0 +1,645 @@
+use crate::{
+    Csr, CsrAddress, CsrIllegal, Privilege, Xlen, MACHINE_INTERRUPTS, MSECCFG_SSEED, MSECCFG_USEED,
+    MSTATUS_FS, MSTATUS_MIE, MSTATUS_MPIE, MSTATUS_MPP, MSTATUS_MPRV, MSTATUS_MXR, MSTATUS_SD,
+    MSTATUS_SIE, MSTATUS_SPIE, MSTATUS_SPP, MSTATUS_SUM, MSTATUS_TSR, MSTATUS_TVM, MSTATUS_TW,
+    MSTATUS_UXL, MSTATUS_VS, SUPERVISOR_INTERRUPTS,
+};
+
+/// The supervisor software interrupt bit of `mip`, which is the only bit of
+/// `sip` that can be written.
+const SSIP: u64 = 1 << 1;
+
+/// The exceptions that can be delegated by `medeleg`, which excludes
+/// environment calls from machine mode.
+const DELEGABLE_EXCEPTIONS: u64 = 0xB3FF;
+
+/// The fields of `mcounteren` and `scounteren` that can be written, which
+/// enable each of the 32 unprivileged counters.
+const COUNTEREN_MASK: u64 = 0xFFFF_FFFF;
+
+/// The `SD` field of `mstatus` in RV32, which is its most significant bit.
+const MSTATUS_SD_32: u64 = 1 << 31;
+
+/// The fields of `mstatus` that are visible through `sstatus`.
+const SSTATUS_FIELDS: u64 = MSTATUS_SIE
+    | MSTATUS_SPIE
+    | MSTATUS_SPP
+    | MSTATUS_VS
+    | MSTATUS_FS
+    | MSTATUS_SUM
+    | MSTATUS_MXR
+    | MSTATUS_UXL
+    | MSTATUS_SD_32
+    | MSTATUS_SD;
+
+/// A standard implementation of the CSRs of a hart that implements machine
+/// mode and optionally supervisor and user modes.
+///
+/// Which CSRs exist and which of their fields can be written depends on the
+/// extensions advertised by `misa`. The supervisor-level CSRs only exist when
+/// the S extension is advertised, and user mode is only supported when the U
+/// extension is advertised.
+///
+/// Writes made through [Csr::access] only change the fields of each CSR that
+/// are writable, leaving the others with their legal values. The fields of
+/// this struct can also be modified directly (such as to set the values of
+/// read-only CSRs), in which case it is up to the caller to keep them legal.
+#[derive(Clone, Debug)]
+pub struct MachineCsrs {
+    /// The ISA and extensions advertised by the hart, which is read-only.
+    pub misa: u64,
+    /// The JEDEC manufacturer ID of the hart, which is read-only.
+    pub mvendorid: u64,
+    /// The microarchitecture ID of the hart, which is read-only.
+    pub marchid: u64,
+    /// The implementation version of the hart, which is read-only.
+    pub mimpid: u64,
+    /// The ID of the hart, which is read-only.
+    pub mhartid: u64,
+    /// The address of the configuration data structure, which is read-only.
+    pub mconfigptr: u64,
+    /// Machine status register, which `sstatus` is a view of.
+    pub mstatus: u64,
+    /// Machine exception delegation register.
+    pub medeleg: u64,
+    /// Machine interrupt delegation register.
+    pub mideleg: u64,
+    /// Machine interrupt-enable register, which `sie` is a view of.
+    pub mie: u64,
+    /// Machine interrupt-pending register, which `sip` is a view of.
+    pub mip: u64,
+    /// Machine trap-handler base address.
+    pub mtvec: u64,
+    /// Scratch register for machine trap handlers.
+    pub mscratch: u64,
+    /// Machine exception program counter.
+    pub mepc: u64,
+    /// Machine trap cause.
+    pub mcause: u64,
+    /// Machine bad address or instruction.
+    pub mtval: u64,
+    /// Machine counter-enable register, which controls whether the counters
+    /// can be read below machine mode.
+    pub mcounteren: u64,
+    /// Machine security configuration register.
+    pub mseccfg: u64,
+    /// Supervisor trap handler base address.
+    pub stvec: u64,
+    /// Supervisor counter-enable register, which controls whether the
+    /// counters can be read in user mode.
+    pub scounteren: u64,
+    /// Scratch register for supervisor trap handlers.
+    pub sscratch: u64,
+    /// Supervisor exception program counter.
+    pub sepc: u64,
+    /// Supervisor trap cause.
+    pub scause: u64,
+    /// Supervisor bad address or instruction.
+    pub stval: u64,
+    /// Supervisor address translation and protection.
+    pub satp: u64,
+}
+
+impl MachineCsrs {
+    /// Creates the CSRs of the hart with the given ID, in their reset state.
+    ///
+    /// `misa` advertises the extensions given by the letters in `extensions`,
+    /// such as `"imafdcs"`. The hart is RV64 unless the letters are preceded
+    /// by `"rv32"`, as in `"rv32imac"` (`"rv64"` is also accepted), and is
+    /// RV32E or RV64E if they include `e` rather than `i`.
+    ///
+    /// # Panics
+    /// Panics if `extensions` contains anything other than ASCII letters after
+    /// the optional prefix.
+    pub const fn new(mhartid: u64, extensions: &str) -> MachineCsrs {
+        let misa = misa(extensions);
+
+        let mut mstatus = MSTATUS_MPP;
+
+        // SXL = 2 indicates a 64-bit supervisor mode, and RV32 has no SXL
+        if misa & 1 << (b's' - b'a') != 0 && misa >> 62 == 2 {
+            mstatus |= 2 << 34;
+        }
+
+        // UXL = 2 indicates a 64-bit user mode, and RV32 has no UXL
+        if misa & 1 << (b'u' - b'a') != 0 && misa >> 62 == 2 {
+            mstatus |= 2 << 32;
+        }
+
+        MachineCsrs {
+            misa,
+            mvendorid: 0,
+            marchid: 0,
+            mimpid: 0,
+            mhartid,
+            mconfigptr: 0,
+            mstatus,
+            medeleg: 0,
+            mideleg: 0,
+            mie: 0,
+            mip: 0,
+            mtvec: 0,
+            mscratch: 0,
+            mepc: 0,
+            mcause: 0,
+            mtval: 0,
+            mcounteren: 0,
+            mseccfg: 0,
+            stvec: 0,
+            scounteren: 0,
+            sscratch: 0,
+            sepc: 0,
+            scause: 0,
+            stval: 0,
+            satp: 0,
+        }
+    }
+
+    /// Checks whether `misa` advertises the extension with the given
+    /// (ASCII) letter, which is never the case for other characters.
+    pub const fn has_extension(&self, letter: u8) -> bool {
+        match letter.to_ascii_lowercase() {
+            letter @ b'a'..=b'z' => self.misa & 1 << (letter - b'a') != 0,
+            _ => false,
+        }
+    }
+
+    /// Checks whether the hart can execute at the given privilege level.
+    pub const fn has_privilege(&self, privilege: Privilege) -> bool {
+        match privilege {
+            Privilege::User => self.has_extension(b'u'),
+            Privilege::Supervisor => self.has_extension(b's'),
+            Privilege::Machine => true,
+        }
+    }
+
+    /// Gets the XLEN given by `misa`.
+    pub const fn xlen(&self) -> Xlen {
+        Xlen::from_misa(self.misa)
+    }
+
+    /// Gets the mask of the writable fields of `mstatus`.
+    const fn mstatus_mask(&self) -> u64 {
+        let mut mask = MSTATUS_MIE | MSTATUS_MPIE;
+
+        if self.has_extension(b'f') || self.has_extension(b'd') {
+            mask |= MSTATUS_FS;
+        }
+
+        if self.has_extension(b'v') {
+            mask |= MSTATUS_VS;
+        }
+
+        if self.has_extension(b's') {
+            mask |= MSTATUS_SIE
+                | MSTATUS_SPIE
+                | MSTATUS_SPP
+                | MSTATUS_MPP
+                | MSTATUS_SUM
+                | MSTATUS_MXR
+                | MSTATUS_TVM
+                | MSTATUS_TW
+                | MSTATUS_TSR;
+        }
+
+        if self.has_extension(b'u') {
+            mask |= MSTATUS_MPP | MSTATUS_MPRV | MSTATUS_TW;
+        }
+
+        mask
+    }
+
+    /// Gets the mask of the writable fields of `mseccfg`, which are the fields
+    /// that grant access to `seed` to each supported privilege level below
+    /// machine mode.
+    const fn mseccfg_mask(&self) -> u64 {
+        let mut mask = 0;
+
+        if self.has_extension(b's') {
+            mask |= MSECCFG_SSEED;
+        }
+
+        if self.has_extension(b'u') {
+            mask |= MSECCFG_USEED;
+        }
+
+        mask
+    }
+
+    /// Replaces any illegal values of the fields of `mstatus` in `new` with
+    /// legal ones, given its `old` value.
+    fn legalize_mstatus(&self, old: u64, new: u64) -> u64 {
+        // Unsupported privilege levels are replaced by the next supported
+        // level above them, and reserved encodings leave MPP unchanged
+        let mpp = match Privilege::from_bits(new >> 11 & 0b11) {
+            Some(Privilege::User) if self.has_privilege(Privilege::User) => Privilege::User,
+            Some(Privilege::User | Privilege::Supervisor)
+                if self.has_privilege(Privilege::Supervisor) =>
+            {
+                Privilege::Supervisor
+            }
+            Some(_) => Privilege::Machine,
+            None => return self.legalize_mstatus(old, new & !MSTATUS_MPP | old & MSTATUS_MPP),
+        };
+
+        let mut mstatus = new & !(MSTATUS_MPP | MSTATUS_SD_32 | MSTATUS_SD) | (mpp as u64) << 11;
+
+        // SPP can only hold user mode if it is supported
+        if self.has_extension(b's') && !self.has_privilege(Privilege::User) {
+            mstatus |= MSTATUS_SPP;
+        }
+
+        // SD summarizes whether any extension state is dirty
+        if mstatus & MSTATUS_FS == MSTATUS_FS || mstatus & MSTATUS_VS == MSTATUS_VS {
+            mstatus |= match self.xlen() {
+                Xlen::Rv32 => MSTATUS_SD_32,
+                Xlen::Rv64 => MSTATUS_SD,
+            };
+        }
+
+        mstatus
+    }
+}
+
+impl Csr for MachineCsrs {
+    fn access(
+        &mut self,
+        address: CsrAddress,
+        f: impl FnOnce(u64) -> u64,
+    ) -> Result<u64, CsrIllegal> {
+        let mstatus_mask = self.mstatus_mask();
+        let mseccfg_mask = self.mseccfg_mask();
+        let supervisor = self.has_extension(b's');
+        let user = self.has_extension(b'u');
+        let xlen = self.xlen();
+
+        // Interrupts that are not delegated are invisible to supervisor mode
+        let mideleg = self.mideleg;
+
+        // The CSR being accessed, the mask of its fields that can be read
+        // through this address, and the mask of its fields that can be written
+        let (csr, visible, mask) = match address {
+            CsrAddress::MSTATUS => (&mut self.mstatus, !0, mstatus_mask),
+            CsrAddress::MEDELEG if supervisor => (&mut self.medeleg, !0, DELEGABLE_EXCEPTIONS),
+            CsrAddress::MIDELEG if supervisor => (&mut self.mideleg, !0, SUPERVISOR_INTERRUPTS),
+            CsrAddress::MIE if supervisor => (
+                &mut self.mie,
+                !0,
+                MACHINE_INTERRUPTS | SUPERVISOR_INTERRUPTS,
+            ),
+            CsrAddress::MIE => (&mut self.mie, !0, MACHINE_INTERRUPTS),
+            // The hart prevents software from changing the machine-level
+            // interrupt-pending bits, which follow the interrupt lines
+            CsrAddress::MIP if supervisor => (
+                &mut self.mip,
+                !0,
+                MACHINE_INTERRUPTS | SUPERVISOR_INTERRUPTS,
+            ),
+            CsrAddress::MIP => (&mut self.mip, !0, MACHINE_INTERRUPTS),
+            CsrAddress::MTVEC => (&mut self.mtvec, !0, !0),
+            CsrAddress::MSCRATCH => (&mut self.mscratch, !0, !0),
+            CsrAddress::MEPC => (&mut self.mepc, !0, !0b1),
+            CsrAddress::MCAUSE => (&mut self.mcause, !0, !0),
+            CsrAddress::MTVAL => (&mut self.mtval, !0, !0),
+            CsrAddress::MCOUNTEREN if user => (&mut self.mcounteren, !0, COUNTEREN_MASK),
+            CsrAddress::MSECCFG => (&mut self.mseccfg, !0, mseccfg_mask),
+            // Writes to misa are ignored, so the set of extensions is fixed
+            CsrAddress::MISA => (&mut self.misa, !0, 0),
+            CsrAddress::MVENDORID => (&mut self.mvendorid, !0, 0),
+            CsrAddress::MARCHID => (&mut self.marchid, !0, 0),
+            CsrAddress::MIMPID => (&mut self.mimpid, !0, 0),
+            CsrAddress::MHARTID => (&mut self.mhartid, !0, 0),
+            CsrAddress::MCONFIGPTR => (&mut self.mconfigptr, !0, 0),
+            CsrAddress::SSTATUS if supervisor => (
+                &mut self.mstatus,
+                SSTATUS_FIELDS,
+                mstatus_mask & SSTATUS_FIELDS,
+            ),
+            CsrAddress::SIE if supervisor => (&mut self.mie, mideleg, mideleg),
+            CsrAddress::SIP if supervisor => (&mut self.mip, mideleg, mideleg & SSIP),
+            CsrAddress::STVEC if supervisor => (&mut self.stvec, !0, !0),
+            CsrAddress::SCOUNTEREN if supervisor => (&mut self.scounteren, !0, COUNTEREN_MASK),
+            CsrAddress::SSCRATCH if supervisor => (&mut self.sscratch, !0, !0),
+            CsrAddress::SEPC if supervisor => (&mut self.sepc, !0, !0b1),
+            CsrAddress::SCAUSE if supervisor => (&mut self.scause, !0, !0),
+            CsrAddress::STVAL if supervisor => (&mut self.stval, !0, !0),
+            CsrAddress::SATP if supervisor => (&mut self.satp, !0, !0),
+            _ => return Err(CsrIllegal),
+        };
+
+        let value = *csr;
+        let new_value = value & !mask | f(value & visible) & mask;
+
+        match address {
+            // Only the direct and vectored modes are supported, so writes of
+            // other modes are ignored
+            CsrAddress::MTVEC | CsrAddress::STVEC if new_value & 0b11 >= 2 => (),
+            // Only Bare, Sv39, Sv48, and Sv57 translation are supported in
+            // RV64, so writes of other modes are ignored (RV32 supports both
+            // of its modes, Bare and Sv32)
+            CsrAddress::SATP
+                if xlen == Xlen::Rv64 && !matches!(new_value >> 60, 0 | 8 | 9 | 10) => {}
+            CsrAddress::MSTATUS | CsrAddress::SSTATUS => {
+                self.mstatus = self.legalize_mstatus(value, new_value)
+            }
+            _ => *csr = new_value,
+        }
+
+        Ok(value & visible)
+    }
+
+    fn check(
+        &self,
+        address: CsrAddress,
+        privilege: Privilege,
+        write: bool,
+    ) -> Result<(), CsrIllegal> {
+        // Supervisor mode cannot access satp while TVM is set
+        let mut trapped = address == CsrAddress::SATP
+            && privilege == Privilege::Supervisor
+            && self.mstatus & MSTATUS_TVM != 0;
+
+        // The unprivileged counters can only be read below machine mode when
+        // they are enabled by mcounteren, and in user mode also by scounteren
+        // if supervisor mode exists
+        if let 0xC00..=0xC1F | 0xC80..=0xC9F = address.address() {
+            let bit = 1 << (address.address() & 0x1F);
+
+            trapped |= match privilege {
+                Privilege::Machine => false,
+                Privilege::Supervisor => self.mcounteren & bit == 0,
+                Privilege::User => {
+                    self.mcounteren & bit == 0
+                        || self.has_extension(b's') && self.scounteren & bit == 0
+                }
+            };
+        }
+
+        if address.is_accessible(privilege, write) && !trapped {
+            Ok(())
+        } else {
+            Err(CsrIllegal)
+        }
+    }
+}
+
+/// Computes the value of `misa` for a hart advertising the extensions given
+/// by the letters in `extensions`, which may be preceded by `"rv32"` or
+/// `"rv64"`.
+///
+/// # Panics
+/// Panics if `extensions` contains anything other than ASCII letters after
+/// the prefix.
+const fn misa(extensions: &str) -> u64 {
+    let extensions = extensions.as_bytes();
+
+    let rv32 = matches!(extensions, [b'r' | b'R', b'v' | b'V', b'3', b'2', ..]);
+    let rv64 = matches!(extensions, [b'r' | b'R', b'v' | b'V', b'6', b'4', ..]);
+
+    // MXL = 1 indicates a 32-bit hart and MXL = 2 a 64-bit hart, which are
+    // held in the two most significant bits
+    let mut misa = if rv32 { 1 << 30 } else { 2 << 62 };
+    let mut i = if rv32 || rv64 { 4 } else { 0 };
+
+    while i < extensions.len() {
+        assert!(
+            extensions[i].is_ascii_alphabetic(),
+            "extensions must be given as letters"
+        );
+
+        misa |= 1 << (extensions[i].to_ascii_lowercase() - b'a');
+        i += 1;
+    }
+
+    misa
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_has_extension() {
+        let csrs = MachineCsrs::new(0, "imacsu");
+
+        assert!(csrs.has_extension(b'm'));
+        assert!(csrs.has_extension(b'S'));
+        assert!(!csrs.has_extension(b'f'));
+
+        // Characters that are not letters would shift by an invalid amount
+        for letter in [0, b'0', b'@', b'[', b'{', 0xFF] {
+            assert!(!csrs.has_extension(letter));
+        }
+    }
+
+    fn write(csrs: &mut MachineCsrs, address: CsrAddress, value: u64) -> u64 {
+        assert!(csrs.access(address, |_| value).is_ok());
+        csrs.access(address, |value| value).ok().unwrap()
+    }
+
+    #[test]
+    fn test_mstatus() {
+        let mut csrs = MachineCsrs::new(0, "imafdcsu");
+
+        // Unsupported privilege levels are replaced by machine mode, and
+        // reserved ones leave MPP unchanged
+        let mstatus = write(&mut csrs, CsrAddress::MSTATUS, 0);
+        assert_eq!(mstatus & MSTATUS_MPP, 0);
+        let mstatus = write(&mut csrs, CsrAddress::MSTATUS, 2 << 11);
+        assert_eq!(mstatus & MSTATUS_MPP, 0);
+
+        // SD is read-only, and set when any extension state is dirty
+        let mstatus = write(&mut csrs, CsrAddress::MSTATUS, MSTATUS_SD);
+        assert_eq!(mstatus & MSTATUS_SD, 0);
+        let mstatus = write(&mut csrs, CsrAddress::MSTATUS, MSTATUS_FS);
+        assert_eq!(mstatus & MSTATUS_SD, MSTATUS_SD);
+
+        // sstatus only shows the supervisor-level fields
+        assert!(csrs.access(CsrAddress::MSTATUS, |_| !0).is_ok());
+        let sstatus = csrs.access(CsrAddress::SSTATUS, |value| value).ok();
+        assert_eq!(sstatus.map(|sstatus| sstatus & MSTATUS_MPP), Some(0));
+
+        // Without supervisor and user modes, only MIE and MPIE are writable
+        let mut csrs = MachineCsrs::new(0, "imac");
+
+        let mstatus = write(&mut csrs, CsrAddress::MSTATUS, !0);
+        assert_eq!(mstatus, MSTATUS_MIE | MSTATUS_MPIE | MSTATUS_MPP);
+        let mstatus = write(&mut csrs, CsrAddress::MSTATUS, 0);
+        assert_eq!(mstatus, MSTATUS_MPP);
+
+        let mut csrs = MachineCsrs::new(0, "imacu");
+
+        let mstatus = write(&mut csrs, CsrAddress::MSTATUS, 1 << 11);
+        assert_eq!(mstatus & MSTATUS_MPP, MSTATUS_MPP);
+        let mstatus = write(&mut csrs, CsrAddress::MSTATUS, 0);
+        assert_eq!(mstatus & MSTATUS_MPP, 0);
+    }
+
+    #[test]
+    fn test_tvec() {
+        let mut csrs = MachineCsrs::new(0, "imacsu");
+
+        // Only the direct and vectored modes are supported
+        assert_eq!(
+            write(&mut csrs, CsrAddress::MTVEC, 0x8000_0001),
+            0x8000_0001
+        );
+        assert_eq!(
+            write(&mut csrs, CsrAddress::MTVEC, 0x9000_0002),
+            0x8000_0001
+        );
+        assert_eq!(
+            write(&mut csrs, CsrAddress::MTVEC, 0x9000_0003),
+            0x8000_0001
+        );
+        assert_eq!(
+            write(&mut csrs, CsrAddress::STVEC, 0x8000_0000),
+            0x8000_0000
+        );
+        assert_eq!(
+            write(&mut csrs, CsrAddress::STVEC, 0x9000_0002),
+            0x8000_0000
+        );
+
+        // Exception program counters are always aligned to two bytes
+        assert_eq!(write(&mut csrs, CsrAddress::MEPC, 0x8000_0003), 0x8000_0002);
+        assert_eq!(write(&mut csrs, CsrAddress::SEPC, 0x8000_0003), 0x8000_0002);
+    }
+
+    #[test]
+    fn test_satp() {
+        let mut csrs = MachineCsrs::new(0, "imacsu");
+
+        // Sv39, Sv48, and Sv57 are supported in RV64, but not Sv64 or the
+        // reserved modes
+        for mode in [8, 9, 10, 0] {
+            assert_eq!(
+                write(&mut csrs, CsrAddress::SATP, mode << 60 | 5),
+                mode << 60 | 5
+            );
+        }
+
+        for mode in [1, 7, 11, 15] {
+            assert_eq!(write(&mut csrs, CsrAddress::SATP, mode << 60 | 6), 5);
+        }
+
+        // RV32 supports both of its modes
+        let mut csrs = MachineCsrs::new(0, "rv32imacsu");
+
+        assert_eq!(write(&mut csrs, CsrAddress::SATP, 1 << 31 | 5), 1 << 31 | 5);
+        assert_eq!(write(&mut csrs, CsrAddress::SATP, 6), 6);
+
+        // satp only exists with supervisor mode
+        let mut csrs = MachineCsrs::new(0, "imacu");
+
+        assert!(csrs.access(CsrAddress::SATP, |satp| satp).is_err());
+    }
+
+    #[test]
+    fn test_read_only() {
+        let mut csrs = MachineCsrs::new(7, "imacsu");
+
+        // CSRs with 0b11 in bits 11:10 cannot be written by instructions
+        for address in [
+            CsrAddress::MHARTID,
+            CsrAddress::MVENDORID,
+            CsrAddress::CYCLE,
+        ] {
+            assert!(csrs.check(address, Privilege::Machine, false).is_ok());
+            assert!(csrs.check(address, Privilege::Machine, true).is_err());
+        }
+
+        assert!(csrs
+            .check(CsrAddress::MSCRATCH, Privilege::Machine, true)
+            .is_ok());
+
+        // Writes through access are ignored
+        let misa = csrs.misa;
+        assert_eq!(write(&mut csrs, CsrAddress::MISA, 0), misa);
+        assert_eq!(write(&mut csrs, CsrAddress::MHARTID, 0), 7);
+    }
+
+    #[test]
+    fn test_privilege() {
+        let csrs = MachineCsrs::new(0, "imacsu");
+
+        // Bits 9:8 give the lowest privilege level that can access a CSR
+        for write in [false, true] {
+            let check = |address, privilege| csrs.check(address, privilege, write).is_ok();
+
+            assert!(check(CsrAddress::MSTATUS, Privilege::Machine));
+            assert!(!check(CsrAddress::MSTATUS, Privilege::Supervisor));
+            assert!(!check(CsrAddress::MSTATUS, Privilege::User));
+            assert!(check(CsrAddress::SSTATUS, Privilege::Machine));
+            assert!(check(CsrAddress::SSTATUS, Privilege::Supervisor));
+            assert!(!check(CsrAddress::SSTATUS, Privilege::User));
+        }
+    }
+
+    #[test]
+    fn test_tvm() {
+        let mut csrs = MachineCsrs::new(0, "imacsu");
+
+        assert!(csrs
+            .check(CsrAddress::SATP, Privilege::Supervisor, true)
+            .is_ok());
+
+        // TVM traps accesses to satp in supervisor mode only
+        csrs.mstatus |= MSTATUS_TVM;
+
+        assert!(csrs
+            .check(CsrAddress::SATP, Privilege::Supervisor, false)
+            .is_err());
+        assert!(csrs
+            .check(CsrAddress::SATP, Privilege::Supervisor, true)
+            .is_err());
+        assert!(csrs
+            .check(CsrAddress::SATP, Privilege::Machine, true)
+            .is_ok());
+        assert!(csrs
+            .check(CsrAddress::STVEC, Privilege::Supervisor, true)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_counteren() {
+        let mut csrs = MachineCsrs::new(0, "rv32imacsu");
+
+        let readable =
+            |csrs: &MachineCsrs, address, privilege| csrs.check(address, privilege, false).is_ok();
+
+        for address in [CsrAddress::CYCLE, CsrAddress::CYCLEH] {
+            assert!(readable(&csrs, address, Privilege::Machine));
+            assert!(!readable(&csrs, address, Privilege::Supervisor));
+            assert!(!readable(&csrs, address, Privilege::User));
+        }
+
+        // mcounteren enables the counters below machine mode, and scounteren
+        // also has to enable them for user mode
+        csrs.mcounteren = 1 << 0;
+
+        for address in [CsrAddress::CYCLE, CsrAddress::CYCLEH] {
+            assert!(readable(&csrs, address, Privilege::Supervisor));
+            assert!(!readable(&csrs, address, Privilege::User));
+        }
+
+        csrs.scounteren = 1 << 0 | 1 << 2;
+
+        assert!(readable(&csrs, CsrAddress::CYCLE, Privilege::User));
+        assert!(!readable(&csrs, CsrAddress::INSTRET, Privilege::User));
+
+        csrs.mcounteren = 0;
+
+        assert!(!readable(&csrs, CsrAddress::CYCLE, Privilege::User));
+
+        // Without supervisor mode, only mcounteren applies
+        let mut csrs = MachineCsrs::new(0, "imacu");
+        csrs.mcounteren = 1 << 1;
+
+        assert!(readable(&csrs, CsrAddress::TIME, Privilege::User));
+        assert!(!readable(&csrs, CsrAddress::CYCLE, Privilege::User));
+
+        // Writes to the counters are illegal even when they are enabled
+        assert!(csrs.check(CsrAddress::TIME, Privilege::User, true).is_err());
+    }
+}
@@ -0,0 +1,40 @@
+//! The entropy source of the Zkr extension.
+
+/// The entropy source that the hart reads through the `seed` CSR.
+///
+/// The source is a deterministic generator, so a hart whose source is created
+/// with the same seed reads the same sequence of values from `seed`. This
+/// makes programs that use it reproducible, but it is not a true source of
+/// randomness, so it must not be used to protect anything.
+#[derive(Clone, Debug)]
+pub struct Entropy {
+    /// The state of the generator, which is advanced by each poll.
+    state: u64,
+}
+
+impl Entropy {
+    /// Creates a new entropy source whose output is determined by `seed`.
+    pub const fn new(seed: u64) -> Entropy {
+        Entropy { state: seed }
+    }
+
+    /// Gets the next 16 bits of entropy.
+    pub fn poll(&mut self) -> u16 {
+        // SplitMix64, keeping the high bits of each output
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+
+        let mut bits = self.state;
+        bits = (bits ^ bits >> 30).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        bits = (bits ^ bits >> 27).wrapping_mul(0x94D0_49BB_1331_11EB);
+        bits ^= bits >> 31;
+
+        (bits >> 48) as u16
+    }
+}
+
+impl Default for Entropy {
+    /// Creates an entropy source with a seed of zero.
+    fn default() -> Entropy {
+        Entropy::new(0)
+    }
+}
@@ -1,14 +1,25 @@
 //! Implementation of each base instruction.
 
 use core::{
+    mem::size_of,
     num::{NonZeroU32, NonZeroU64},
     ops::{Index, IndexMut},
 };
 
-use crate::*;
+use crate::{misaligned::Word, *};
+
+pub mod bitmanip;
+pub mod compressed;
+pub mod crypto;
+pub mod float;
+pub mod vector;
 
 /// A register index that is guaranteed to index a valid register (i.e., it is
 /// less than 32).
+///
+/// In RV32E and RV64E, instructions referring to x16 through x31 are rejected
+/// before they are executed (see [uses_upper_registers]), so those registers
+/// are never accessed.
 #[derive(Clone, Copy, PartialEq)]
 struct RegisterIndex(usize);
 
@@ -22,9 +33,9 @@ pub fn auipc<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
 
 pub fn jal<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
     hart.gpr[rd(raw)] = hart.next;
-    let target = hart.pc.wrapping_add(j_imm(raw) as u64);
+    let target = hart.xlen.truncate(hart.pc.wrapping_add(j_imm(raw) as u64));
 
-    if target & 0b11 == 0 {
+    if target & hart.instruction_alignment_mask() == 0 {
         hart.next = target
     } else {
         hart.raise(Exception::InstructionAddressMisaligned {
@@ -34,10 +45,13 @@ pub fn jal<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
 }
 
 pub fn jalr<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
-    let target = hart.gpr[rs1(raw)].wrapping_add(i_imm(raw) as u64) & !0 << 1;
+    let target = hart
+        .xlen
+        .truncate(hart.gpr[rs1(raw)].wrapping_add(i_imm(raw) as u64))
+        & !0 << 1;
     hart.gpr[rd(raw)] = hart.next;
 
-    if target & 0b11 == 0 {
+    if target & hart.instruction_alignment_mask() == 0 {
         hart.next = target
     } else {
         hart.raise(Exception::InstructionAddressMisaligned {
@@ -80,10 +94,14 @@ pub fn bgeu<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
 
 #[inline(always)]
 fn branch<B, C>(hart: &mut BaseHart<B, C>, raw: u32, condition: bool) {
-    let target = hart.pc.wrapping_add(b_imm(raw) as u64);
+    let target = hart.xlen.truncate(hart.pc.wrapping_add(b_imm(raw) as u64));
+
+    hart.events |= Events::BRANCHES;
 
     if condition {
-        if target & 0b11 == 0 {
+        hart.events |= Events::BRANCHES_TAKEN;
+
+        if target & hart.instruction_alignment_mask() == 0 {
             hart.next = target;
         } else {
             hart.raise(Exception::InstructionAddressMisaligned {
@@ -93,79 +111,164 @@ fn branch<B, C>(hart: &mut BaseHart<B, C>, raw: u32, condition: bool) {
     }
 }
 
+/// Loads a `T` from the given virtual `address`, which is truncated to XLEN
+/// bits, raising the appropriate exception if the load fails.
 #[inline]
-fn l<T, B: Bus<u64, T>, C>(hart: &mut BaseHart<B, C>, raw: u32, convert: impl FnOnce(T) -> u64) {
-    let address = hart.gpr[rs1(raw)].wrapping_add(i_imm(raw) as u64);
+fn load<T: Word, B: Bus<u64, T> + AtomicBus<u64, u64>, C: Csr>(
+    hart: &mut BaseHart<B, C>,
+    address: u64,
+) -> Option<T> {
+    let address = hart.xlen.truncate(address);
+
+    hart.events |= Events::LOADS;
+
+    let (physical, privilege) = match hart.translate(address, size_of::<T>() as u64, Access::Load) {
+        Ok(translation) => translation,
+        // Misaligned loads that cannot be made as a single access are split
+        Err(Exception::LoadAddressMisaligned { .. })
+            if hart.misaligned_mode == MisalignedMode::Emulate =>
+        {
+            return match hart.load_misaligned(address) {
+                Ok(value) => Some(value),
+                Err(exception) => {
+                    hart.raise(exception);
+                    None
+                }
+            };
+        }
+        Err(exception) => {
+            hart.raise(exception);
+            return None;
+        }
+    };
 
-    match hart.bus.load(address) {
-        Ok(value) => hart.gpr[rd(raw)] = convert(value),
-        Err(BusError::AccessFault) => hart.raise(Exception::LoadAccessFault {
+    let access = hart.data_access(AccessKind::Load, privilege);
+
+    match hart.bus.load_with(physical, access) {
+        Ok(value) => Some(value),
+        Err(BusError::AccessFault) => {
+            hart.raise(Exception::LoadAccessFault {
+                address: NonZeroU64::new(address),
+            });
+            None
+        }
+        Err(BusError::AddressMisaligned) => {
+            hart.raise(Exception::LoadAddressMisaligned {
+                address: NonZeroU64::new(address),
+            });
+            None
+        }
+    }
+}
+
+/// Stores `value` to the given virtual `address`, which is truncated to XLEN
+/// bits, raising the appropriate exception if the store fails.
+#[inline]
+fn store<T: Word, B: Bus<u64, T> + AtomicBus<u64, u64>, C: Csr>(
+    hart: &mut BaseHart<B, C>,
+    address: u64,
+    value: T,
+) {
+    let address = hart.xlen.truncate(address);
+
+    hart.events |= Events::STORES;
+
+    let (physical, privilege) = match hart.translate(address, size_of::<T>() as u64, Access::Store)
+    {
+        Ok(translation) => translation,
+        // Misaligned stores that cannot be made as a single access are split
+        Err(Exception::StoreAmoAddressMisaligned { .. })
+            if hart.misaligned_mode == MisalignedMode::Emulate =>
+        {
+            if let Err(exception) = hart.store_misaligned(address, value) {
+                hart.raise(exception);
+            }
+
+            return;
+        }
+        Err(exception) => return hart.raise(exception),
+    };
+
+    let access = hart.data_access(AccessKind::Store, privilege);
+
+    match hart.bus.store_with(physical, value, access) {
+        Ok(()) => (),
+        Err(BusError::AccessFault) => hart.raise(Exception::StoreAmoAccessFault {
             address: NonZeroU64::new(address),
         }),
-        Err(BusError::AddressMisaligned) => hart.raise(Exception::LoadAddressMisaligned {
+        Err(BusError::AddressMisaligned) => hart.raise(Exception::StoreAmoAddressMisaligned {
             address: NonZeroU64::new(address),
         }),
     }
 }
 
-pub fn lb<B: Bus<u64, u8>, C>(hart: &mut BaseHart<B, C>, raw: u32) {
-    l(hart, raw, |x| x as i8 as u64)
+#[inline]
+fn l<T: Word, B: Bus<u64, T> + AtomicBus<u64, u64>, C: Csr>(
+    hart: &mut BaseHart<B, C>,
+    raw: u32,
+    convert: impl FnOnce(T) -> u64,
+) {
+    let address = hart.gpr[rs1(raw)].wrapping_add(i_imm(raw) as u64);
+
+    if let Some(value) = load(hart, address) {
+        hart.gpr[rd(raw)] = convert(value);
+    }
+}
+
+pub fn lb<B: Bus<u64, u8> + AtomicBus<u64, u64>, C: Csr>(hart: &mut BaseHart<B, C>, raw: u32) {
+    l(hart, raw, |x: u8| x as i8 as u64)
 }
 
-pub fn lh<B: Bus<u64, u16>, C>(hart: &mut BaseHart<B, C>, raw: u32) {
-    l(hart, raw, |x| x as i16 as u64)
+pub fn lh<B: Bus<u64, u16> + AtomicBus<u64, u64>, C: Csr>(hart: &mut BaseHart<B, C>, raw: u32) {
+    l(hart, raw, |x: u16| x as i16 as u64)
 }
 
-pub fn lw<B: Bus<u64, u32>, C>(hart: &mut BaseHart<B, C>, raw: u32) {
-    l(hart, raw, |x| x as i32 as u64)
+pub fn lw<B: Bus<u64, u32> + AtomicBus<u64, u64>, C: Csr>(hart: &mut BaseHart<B, C>, raw: u32) {
+    l(hart, raw, |x: u32| x as i32 as u64)
 }
 
-pub fn ld<B: Bus<u64, u64>, C>(hart: &mut BaseHart<B, C>, raw: u32) {
-    l(hart, raw, |x| x)
+pub fn ld<B: AtomicBus<u64, u64>, C: Csr>(hart: &mut BaseHart<B, C>, raw: u32) {
+    l(hart, raw, |x: u64| x)
 }
 
-pub fn lbu<B: Bus<u64, u8>, C>(hart: &mut BaseHart<B, C>, raw: u32) {
-    l(hart, raw, |x| x as u64)
+pub fn lbu<B: Bus<u64, u8> + AtomicBus<u64, u64>, C: Csr>(hart: &mut BaseHart<B, C>, raw: u32) {
+    l(hart, raw, |x: u8| x as u64)
 }
 
-pub fn lhu<B: Bus<u64, u16>, C>(hart: &mut BaseHart<B, C>, raw: u32) {
-    l(hart, raw, |x| x as u64)
+pub fn lhu<B: Bus<u64, u16> + AtomicBus<u64, u64>, C: Csr>(hart: &mut BaseHart<B, C>, raw: u32) {
+    l(hart, raw, |x: u16| x as u64)
 }
 
-pub fn lwu<B: Bus<u64, u32>, C>(hart: &mut BaseHart<B, C>, raw: u32) {
-    l(hart, raw, |x| x as u64)
+pub fn lwu<B: Bus<u64, u32> + AtomicBus<u64, u64>, C: Csr>(hart: &mut BaseHart<B, C>, raw: u32) {
+    l(hart, raw, |x: u32| x as u64)
 }
 
 #[inline]
-fn s<T, B: Bus<u64, T>, C>(hart: &mut BaseHart<B, C>, raw: u32, convert: impl FnOnce(u64) -> T) {
+fn s<T: Word, B: Bus<u64, T> + AtomicBus<u64, u64>, C: Csr>(
+    hart: &mut BaseHart<B, C>,
+    raw: u32,
+    convert: impl FnOnce(u64) -> T,
+) {
     let address = hart.gpr[rs1(raw)].wrapping_add(s_imm(raw) as u64);
     let value = convert(hart.gpr[rs2(raw)]);
 
-    match hart.bus.store(address, value) {
-        Ok(()) => (),
-        Err(BusError::AccessFault) => hart.raise(Exception::StoreAmoAccessFault {
-            address: NonZeroU64::new(address),
-        }),
-        Err(BusError::AddressMisaligned) => hart.raise(Exception::StoreAmoAccessFault {
-            address: NonZeroU64::new(address),
-        }),
-    }
+    store(hart, address, value);
 }
 
-pub fn sb<B: Bus<u64, u8>, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+pub fn sb<B: Bus<u64, u8> + AtomicBus<u64, u64>, C: Csr>(hart: &mut BaseHart<B, C>, raw: u32) {
     s(hart, raw, |r| r as u8)
 }
 
-pub fn sh<B: Bus<u64, u16>, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+pub fn sh<B: Bus<u64, u16> + AtomicBus<u64, u64>, C: Csr>(hart: &mut BaseHart<B, C>, raw: u32) {
     s(hart, raw, |r| r as u16)
 }
 
-pub fn sw<B: Bus<u64, u32>, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+pub fn sw<B: Bus<u64, u32> + AtomicBus<u64, u64>, C: Csr>(hart: &mut BaseHart<B, C>, raw: u32) {
     s(hart, raw, |r| r as u32)
 }
 
-pub fn sd<B: Bus<u64, u64>, C>(hart: &mut BaseHart<B, C>, raw: u32) {
-    s(hart, raw, |r| r as u64)
+pub fn sd<B: AtomicBus<u64, u64>, C: Csr>(hart: &mut BaseHart<B, C>, raw: u32) {
+    s(hart, raw, |r| r)
 }
 
 pub fn addi<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
@@ -281,6 +384,22 @@ pub fn and<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
     hart.gpr[rd(raw)] = hart.gpr[rs1(raw)] & hart.gpr[rs2(raw)];
 }
 
+pub fn czero_eqz<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    hart.gpr[rd(raw)] = if hart.gpr[rs2(raw)] == 0 {
+        0
+    } else {
+        hart.gpr[rs1(raw)]
+    };
+}
+
+pub fn czero_nez<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    hart.gpr[rd(raw)] = if hart.gpr[rs2(raw)] != 0 {
+        0
+    } else {
+        hart.gpr[rs1(raw)]
+    };
+}
+
 pub fn sllw<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
     hart.gpr[rd(raw)] =
         (hart.gpr[rs1(raw)] as u32).wrapping_shl(hart.gpr[rs2(raw)] as u32) as i32 as u64;
@@ -298,16 +417,463 @@ pub fn srxw<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
     }
 }
 
+pub fn mul<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    hart.gpr[rd(raw)] = hart.gpr[rs1(raw)].wrapping_mul(hart.gpr[rs2(raw)]);
+}
+
+// The upper halves of products are taken from XLEN-bit operands, where
+// unsigned RV32 operands must be zero-extended
+
+pub fn mulh<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    let product = hart.gpr[rs1(raw)] as i64 as i128 * hart.gpr[rs2(raw)] as i64 as i128;
+
+    hart.gpr[rd(raw)] = (product >> hart.xlen.bits()) as u64;
+}
+
+pub fn mulhsu<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    let src2 = hart.xlen.truncate(hart.gpr[rs2(raw)]);
+    let product = hart.gpr[rs1(raw)] as i64 as i128 * src2 as i128;
+
+    hart.gpr[rd(raw)] = (product >> hart.xlen.bits()) as u64;
+}
+
+pub fn mulhu<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    let src1 = hart.xlen.truncate(hart.gpr[rs1(raw)]);
+    let src2 = hart.xlen.truncate(hart.gpr[rs2(raw)]);
+    let product = src1 as u128 * src2 as u128;
+
+    hart.gpr[rd(raw)] = (product >> hart.xlen.bits()) as u64;
+}
+
+pub fn div<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    let (dividend, divisor) = (hart.gpr[rs1(raw)] as i64, hart.gpr[rs2(raw)] as i64);
+
+    hart.gpr[rd(raw)] = if divisor == 0 {
+        // Division by zero results in all bits being set
+        u64::MAX
+    } else {
+        // Overflow (i64::MIN / -1) wraps around to the dividend
+        dividend.wrapping_div(divisor) as u64
+    };
+}
+
+pub fn divu<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    let (dividend, divisor) = (hart.gpr[rs1(raw)], hart.gpr[rs2(raw)]);
+
+    hart.gpr[rd(raw)] = dividend.checked_div(divisor).unwrap_or(u64::MAX);
+}
+
+pub fn rem<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    let (dividend, divisor) = (hart.gpr[rs1(raw)] as i64, hart.gpr[rs2(raw)] as i64);
+
+    hart.gpr[rd(raw)] = if divisor == 0 {
+        // The remainder of division by zero is the dividend
+        dividend as u64
+    } else {
+        // The remainder of an overflowing division (i64::MIN % -1) is zero
+        dividend.wrapping_rem(divisor) as u64
+    };
+}
+
+pub fn remu<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    let (dividend, divisor) = (hart.gpr[rs1(raw)], hart.gpr[rs2(raw)]);
+
+    hart.gpr[rd(raw)] = dividend.checked_rem(divisor).unwrap_or(dividend);
+}
+
+pub fn mulw<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    hart.gpr[rd(raw)] =
+        (hart.gpr[rs1(raw)] as u32).wrapping_mul(hart.gpr[rs2(raw)] as u32) as i32 as u64;
+}
+
+pub fn divw<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    let (dividend, divisor) = (hart.gpr[rs1(raw)] as i32, hart.gpr[rs2(raw)] as i32);
+
+    hart.gpr[rd(raw)] = if divisor == 0 {
+        u64::MAX
+    } else {
+        dividend.wrapping_div(divisor) as u64
+    };
+}
+
+pub fn divuw<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    let (dividend, divisor) = (hart.gpr[rs1(raw)] as u32, hart.gpr[rs2(raw)] as u32);
+
+    hart.gpr[rd(raw)] = dividend.checked_div(divisor).unwrap_or(u32::MAX) as i32 as u64;
+}
+
+pub fn remw<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    let (dividend, divisor) = (hart.gpr[rs1(raw)] as i32, hart.gpr[rs2(raw)] as i32);
+
+    hart.gpr[rd(raw)] = if divisor == 0 {
+        dividend as u64
+    } else {
+        dividend.wrapping_rem(divisor) as u64
+    };
+}
+
+pub fn remuw<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    let (dividend, divisor) = (hart.gpr[rs1(raw)] as u32, hart.gpr[rs2(raw)] as u32);
+
+    hart.gpr[rd(raw)] = dividend.checked_rem(divisor).unwrap_or(dividend) as i32 as u64;
+}
+
+pub fn amo_w<B: AtomicBus<u64, u32> + AtomicBus<u64, u64>, C: Csr>(
+    hart: &mut BaseHart<B, C>,
+    raw: u32,
+) {
+    let operation: fn(u32, u32) -> u32 = match raw >> 27 {
+        0b00010 => return lr(hart, raw, |x: u32| x as i32 as u64),
+        0b00011 => return sc(hart, raw, |r| r as u32),
+        0b00001 => |_, src| src,
+        0b00000 => u32::wrapping_add,
+        0b00100 => |value, src| value ^ src,
+        0b01100 => |value, src| value & src,
+        0b01000 => |value, src| value | src,
+        0b10000 => |value, src| (value as i32).min(src as i32) as u32,
+        0b10100 => |value, src| (value as i32).max(src as i32) as u32,
+        0b11000 => u32::min,
+        0b11100 => u32::max,
+        _ => {
+            return hart.raise(Exception::IllegalInstruction {
+                instruction: NonZeroU32::new(raw),
+            })
+        }
+    };
+
+    amo(hart, raw, operation, |x| x as i32 as u64, |r| r as u32)
+}
+
+pub fn amo_d<B: AtomicBus<u64, u64>, C: Csr>(hart: &mut BaseHart<B, C>, raw: u32) {
+    let operation: fn(u64, u64) -> u64 = match raw >> 27 {
+        0b00010 => return lr(hart, raw, |x: u64| x),
+        0b00011 => return sc(hart, raw, |r| r),
+        0b00001 => |_, src| src,
+        0b00000 => u64::wrapping_add,
+        0b00100 => |value, src| value ^ src,
+        0b01100 => |value, src| value & src,
+        0b01000 => |value, src| value | src,
+        0b10000 => |value, src| (value as i64).min(src as i64) as u64,
+        0b10100 => |value, src| (value as i64).max(src as i64) as u64,
+        0b11000 => u64::min,
+        0b11100 => u64::max,
+        _ => {
+            return hart.raise(Exception::IllegalInstruction {
+                instruction: NonZeroU32::new(raw),
+            })
+        }
+    };
+
+    amo(hart, raw, operation, |x| x, |r| r)
+}
+
+#[inline]
+fn lr<T, B: AtomicBus<u64, T> + AtomicBus<u64, u64>, C: Csr>(
+    hart: &mut BaseHart<B, C>,
+    raw: u32,
+    convert: impl FnOnce(T) -> u64,
+) {
+    if rs2(raw) != RegisterIndex(0) {
+        return hart.raise(Exception::IllegalInstruction {
+            instruction: NonZeroU32::new(raw),
+        });
+    }
+
+    let address = hart.xlen.truncate(hart.gpr[rs1(raw)]);
+
+    hart.events |= Events::LOADS;
+
+    if address & (size_of::<T>() as u64 - 1) != 0 {
+        return hart.raise(Exception::LoadAddressMisaligned {
+            address: NonZeroU64::new(address),
+        });
+    }
+
+    let (physical, privilege) = match hart.translate(address, size_of::<T>() as u64, Access::Load) {
+        Ok(translation) => translation,
+        Err(exception) => return hart.raise(exception),
+    };
+
+    if !hart.pma_supports(physical, size_of::<T>() as u64, |attributes| {
+        attributes.reservable
+    }) {
+        return hart.raise(Exception::LoadAccessFault {
+            address: NonZeroU64::new(address),
+        });
+    }
+
+    let access = hart.data_access(AccessKind::Load, privilege);
+
+    match hart.bus.load_reserved_with(hart.id, physical, access) {
+        Ok(value) => hart.gpr[rd(raw)] = convert(value),
+        Err(BusError::AccessFault) => hart.raise(Exception::LoadAccessFault {
+            address: NonZeroU64::new(address),
+        }),
+        Err(BusError::AddressMisaligned) => hart.raise(Exception::LoadAddressMisaligned {
+            address: NonZeroU64::new(address),
+        }),
+    }
+}
+
+#[inline]
+fn sc<T, B: AtomicBus<u64, T> + AtomicBus<u64, u64>, C: Csr>(
+    hart: &mut BaseHart<B, C>,
+    raw: u32,
+    convert: impl FnOnce(u64) -> T,
+) {
+    let address = hart.xlen.truncate(hart.gpr[rs1(raw)]);
+    let value = convert(hart.gpr[rs2(raw)]);
+
+    hart.events |= Events::STORES;
+
+    if address & (size_of::<T>() as u64 - 1) != 0 {
+        return hart.raise(Exception::StoreAmoAddressMisaligned {
+            address: NonZeroU64::new(address),
+        });
+    }
+
+    let (physical, privilege) = match hart.translate(address, size_of::<T>() as u64, Access::Store)
+    {
+        Ok(translation) => translation,
+        Err(exception) => return hart.raise(exception),
+    };
+
+    if !hart.pma_supports(physical, size_of::<T>() as u64, |attributes| {
+        attributes.reservable
+    }) {
+        return hart.raise(Exception::StoreAmoAccessFault {
+            address: NonZeroU64::new(address),
+        });
+    }
+
+    let access = hart.data_access(AccessKind::Store, privilege);
+
+    match hart
+        .bus
+        .store_conditional_with(hart.id, physical, value, access)
+    {
+        Ok(stored) => hart.gpr[rd(raw)] = !stored as u64,
+        Err(BusError::AccessFault) => hart.raise(Exception::StoreAmoAccessFault {
+            address: NonZeroU64::new(address),
+        }),
+        Err(BusError::AddressMisaligned) => hart.raise(Exception::StoreAmoAddressMisaligned {
+            address: NonZeroU64::new(address),
+        }),
+    }
+}
+
+#[inline]
+fn amo<T: Copy, B: AtomicBus<u64, T> + AtomicBus<u64, u64>, C: Csr>(
+    hart: &mut BaseHart<B, C>,
+    raw: u32,
+    operation: fn(T, T) -> T,
+    extend: impl FnOnce(T) -> u64,
+    truncate: impl FnOnce(u64) -> T,
+) {
+    let address = hart.xlen.truncate(hart.gpr[rs1(raw)]);
+    let src = truncate(hart.gpr[rs2(raw)]);
+
+    hart.events |= Events::LOADS | Events::STORES;
+
+    if address & (size_of::<T>() as u64 - 1) != 0 {
+        return hart.raise(Exception::StoreAmoAddressMisaligned {
+            address: NonZeroU64::new(address),
+        });
+    }
+
+    let (physical, privilege) = match hart.translate(address, size_of::<T>() as u64, Access::Store)
+    {
+        Ok(translation) => translation,
+        Err(exception) => return hart.raise(exception),
+    };
+
+    let class = match raw >> 27 {
+        0b00001 => AmoClass::Swap,
+        0b00100 | 0b01100 | 0b01000 => AmoClass::Logical,
+        _ => AmoClass::Arithmetic,
+    };
+
+    if !hart.pma_supports(physical, size_of::<T>() as u64, |attributes| {
+        attributes.amo >= class
+    }) {
+        return hart.raise(Exception::StoreAmoAccessFault {
+            address: NonZeroU64::new(address),
+        });
+    }
+
+    let access = hart.data_access(AccessKind::Amo, privilege);
+
+    match hart
+        .bus
+        .fetch_update_with(physical, |value| operation(value, src), access)
+    {
+        Ok(value) => hart.gpr[rd(raw)] = extend(value),
+        Err(BusError::AccessFault) => hart.raise(Exception::StoreAmoAccessFault {
+            address: NonZeroU64::new(address),
+        }),
+        Err(BusError::AddressMisaligned) => hart.raise(Exception::StoreAmoAddressMisaligned {
+            address: NonZeroU64::new(address),
+        }),
+    }
+}
+
 pub fn fence<B, C>(_hart: &mut BaseHart<B, C>, _raw: u32) {}
 
 pub fn fence_i<B, C>(_hart: &mut BaseHart<B, C>, _raw: u32) {}
 
+pub fn pause<B, C>(hart: &mut BaseHart<B, C>, _raw: u32) {
+    hart.paused = true;
+}
+
+/// Executes one of the `NTL` hints, which are `ADD x0, x0, rs2` where rs2 is
+/// x2 through x5 and selects the locality of the next instruction's accesses.
+pub fn ntl<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    hart.hinted_locality = match raw >> 20 {
+        2 => Locality::InnermostPrivate,
+        3 => Locality::AllPrivate,
+        4 => Locality::InnermostShared,
+        _ => Locality::All,
+    };
+}
+
+/// Executes one of the `MOP.R.n` or `MOP.RR.n` may-be-operations, which are
+/// not redefined by any implemented extension, so they only write zero to rd.
+pub fn mop<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    hart.gpr[rd(raw)] = 0;
+}
+
 pub fn ecall_ebreak<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
     if raw & 1 << 20 == 0 {
-        hart.raise(Exception::EnvironmentCall)
+        hart.raise(Exception::EnvironmentCall {
+            privilege: hart.privilege,
+        })
+    } else {
+        hart.raise(Exception::Breakpoint {
+            address: NonZeroU64::new(hart.pc),
+        })
+    }
+}
+
+pub fn mret<B, C: Csr>(hart: &mut BaseHart<B, C>, raw: u32) {
+    if hart.privilege != Privilege::Machine {
+        return illegal(hart, raw);
+    }
+
+    let Ok(mepc) = hart.csr.access(CsrAddress::MEPC, |mepc| mepc) else {
+        return illegal(hart, raw);
+    };
+
+    // Restore the interrupt-enable bit and privilege level saved when the
+    // trap was taken, leaving MPP with the least-privileged supported level
+    let result = hart.csr.access(CsrAddress::MSTATUS, |mstatus| {
+        let mpie = mstatus >> 7 & 1;
+        let restored = mstatus & !(MSTATUS_MIE | MSTATUS_MPP) | mpie << 3 | MSTATUS_MPIE;
+
+        // MPRV is cleared when returning to a lower privilege level
+        if mstatus & MSTATUS_MPP == MSTATUS_MPP {
+            restored
+        } else {
+            restored & !MSTATUS_MPRV
+        }
+    });
+
+    match result {
+        Ok(mstatus) => {
+            hart.privilege =
+                Privilege::from_bits(mstatus >> 11 & 0b11).unwrap_or(Privilege::Machine);
+            hart.next = mepc & !hart.instruction_alignment_mask();
+        }
+        Err(CsrIllegal) => illegal(hart, raw),
+    }
+}
+
+pub fn sret<B, C: Csr>(hart: &mut BaseHart<B, C>, raw: u32) {
+    let Ok(mstatus) = hart.csr.access(CsrAddress::MSTATUS, |mstatus| mstatus) else {
+        return illegal(hart, raw);
+    };
+
+    // SRET can be trapped by TSR
+    let allowed = match hart.privilege {
+        Privilege::Machine => true,
+        Privilege::Supervisor => mstatus & MSTATUS_TSR == 0,
+        Privilege::User => false,
+    };
+
+    if !allowed {
+        return illegal(hart, raw);
+    }
+
+    let Ok(sepc) = hart.csr.access(CsrAddress::SEPC, |sepc| sepc) else {
+        return illegal(hart, raw);
+    };
+
+    // Restore the interrupt-enable bit and privilege level saved when the
+    // trap was taken, leaving SPP with user mode
+    let _ = hart.csr.access(CsrAddress::MSTATUS, |mstatus| {
+        let spie = mstatus >> 5 & 1;
+
+        mstatus & !(MSTATUS_SIE | MSTATUS_SPP | MSTATUS_MPRV) | spie << 1 | MSTATUS_SPIE
+    });
+
+    hart.privilege = if mstatus & MSTATUS_SPP != 0 {
+        Privilege::Supervisor
     } else {
-        hart.raise(Exception::Breakpoint { address: None })
+        Privilege::User
+    };
+    hart.next = sepc & !hart.instruction_alignment_mask();
+}
+
+pub fn wfi<B, C: Csr>(hart: &mut BaseHart<B, C>, raw: u32) {
+    // WFI can be trapped by TW below machine mode
+    let tw = matches!(
+        hart.csr.access(CsrAddress::MSTATUS, |mstatus| mstatus),
+        Ok(mstatus) if mstatus & MSTATUS_TW != 0
+    );
+
+    // WFI is always trapped in user mode when there is a supervisor mode,
+    // since the hart may wait indefinitely
+    let supervisor = matches!(
+        hart.csr.access(CsrAddress::MISA, |misa| misa),
+        Ok(misa) if misa & 1 << (b's' - b'a') != 0
+    );
+
+    let trapped = match hart.privilege {
+        Privilege::Machine => false,
+        Privilege::Supervisor => tw,
+        Privilege::User => tw || supervisor,
+    };
+
+    if trapped {
+        return illegal(hart, raw);
     }
+
+    hart.waiting = true;
+}
+
+pub fn sfence_vma<B, C: Csr>(hart: &mut BaseHart<B, C>, raw: u32) {
+    // SFENCE.VMA can be trapped by TVM
+    let allowed = match hart.privilege {
+        Privilege::Machine => true,
+        Privilege::Supervisor => matches!(
+            hart.csr.access(CsrAddress::MSTATUS, |mstatus| mstatus),
+            Ok(mstatus) if mstatus & MSTATUS_TVM == 0
+        ),
+        Privilege::User => false,
+    };
+
+    if !allowed {
+        return illegal(hart, raw);
+    }
+
+    // Address spaces are not distinguished, so translations are flushed for
+    // all of them
+    let address = if rs1(raw) == RegisterIndex(0) {
+        None
+    } else {
+        Some(hart.xlen.truncate(hart.gpr[rs1(raw)]))
+    };
+
+    hart.tlb.flush(address);
 }
 
 pub fn csrrw<B, C: Csr>(hart: &mut BaseHart<B, C>, raw: u32) {
@@ -316,15 +882,13 @@ pub fn csrrw<B, C: Csr>(hart: &mut BaseHart<B, C>, raw: u32) {
     // Save value of rs1 in case it is an alias for rd
     let initial_rs1 = hart.gpr[rs1(raw)];
 
-    let result = hart.csr.access(address, |_| initial_rs1);
+    let result = hart.access_csr(address, true, |_| initial_rs1);
 
     match result {
         Ok(value) => hart.gpr[rd(raw)] = value,
-        Err(CsrIllegal) => {
-            return hart.raise(Exception::IllegalInstruction {
-                instruction: NonZeroU32::new(raw),
-            })
-        }
+        Err(CsrIllegal) => hart.raise(Exception::IllegalInstruction {
+            instruction: NonZeroU32::new(raw),
+        }),
     }
 }
 
@@ -334,15 +898,15 @@ pub fn csrrs<B, C: Csr>(hart: &mut BaseHart<B, C>, raw: u32) {
     // Save value of rs1 in case it is an alias for rd
     let initial_rs1 = hart.gpr[rs1(raw)];
 
-    let result = hart.csr.access(address, |value| value | initial_rs1);
+    let result = hart.access_csr(address, rs1(raw) != RegisterIndex(0), |value| {
+        value | initial_rs1
+    });
 
     match result {
         Ok(value) => hart.gpr[rd(raw)] = value,
-        Err(CsrIllegal) => {
-            return hart.raise(Exception::IllegalInstruction {
-                instruction: NonZeroU32::new(raw),
-            })
-        }
+        Err(CsrIllegal) => hart.raise(Exception::IllegalInstruction {
+            instruction: NonZeroU32::new(raw),
+        }),
     }
 }
 
@@ -352,65 +916,121 @@ pub fn csrrc<B, C: Csr>(hart: &mut BaseHart<B, C>, raw: u32) {
     // Save value of rs1 in case it is an alias for rd
     let initial_rs1 = hart.gpr[rs1(raw)];
 
-    let result = hart.csr.access(address, |value| value & !initial_rs1);
+    let result = hart.access_csr(address, rs1(raw) != RegisterIndex(0), |value| {
+        value & !initial_rs1
+    });
 
     match result {
         Ok(value) => hart.gpr[rd(raw)] = value,
-        Err(CsrIllegal) => {
-            return hart.raise(Exception::IllegalInstruction {
-                instruction: NonZeroU32::new(raw),
-            })
-        }
+        Err(CsrIllegal) => hart.raise(Exception::IllegalInstruction {
+            instruction: NonZeroU32::new(raw),
+        }),
     }
 }
 
 pub fn csrrwi<B, C: Csr>(hart: &mut BaseHart<B, C>, raw: u32) {
     let address = csr(raw);
 
-    let result = hart.csr.access(address, |_| uimm(raw) as u64);
+    let result = hart.access_csr(address, true, |_| uimm(raw) as u64);
 
     match result {
         Ok(value) => hart.gpr[rd(raw)] = value,
-        Err(CsrIllegal) => {
-            return hart.raise(Exception::IllegalInstruction {
-                instruction: NonZeroU32::new(raw),
-            })
-        }
+        Err(CsrIllegal) => hart.raise(Exception::IllegalInstruction {
+            instruction: NonZeroU32::new(raw),
+        }),
     }
 }
 
 pub fn csrrsi<B, C: Csr>(hart: &mut BaseHart<B, C>, raw: u32) {
     let address = csr(raw);
 
-    let result = hart.csr.access(address, |value| value | uimm(raw) as u64);
+    let result = hart.access_csr(address, uimm(raw) != 0, |value| value | uimm(raw) as u64);
 
     match result {
         Ok(value) => hart.gpr[rd(raw)] = value,
-        Err(CsrIllegal) => {
-            return hart.raise(Exception::IllegalInstruction {
-                instruction: NonZeroU32::new(raw),
-            })
-        }
+        Err(CsrIllegal) => hart.raise(Exception::IllegalInstruction {
+            instruction: NonZeroU32::new(raw),
+        }),
     }
 }
 
 pub fn csrrci<B, C: Csr>(hart: &mut BaseHart<B, C>, raw: u32) {
     let address = csr(raw);
 
-    let result = hart
-        .csr
-        .access(address, |value| value & !(uimm(raw) as u64));
+    let result = hart.access_csr(address, uimm(raw) != 0, |value| value & !(uimm(raw) as u64));
 
     match result {
         Ok(value) => hart.gpr[rd(raw)] = value,
-        Err(CsrIllegal) => {
-            return hart.raise(Exception::IllegalInstruction {
-                instruction: NonZeroU32::new(raw),
-            })
-        }
+        Err(CsrIllegal) => hart.raise(Exception::IllegalInstruction {
+            instruction: NonZeroU32::new(raw),
+        }),
     }
 }
 
+/// Checks whether any of the fields of the given instruction that refer to
+/// `x` registers refers to x16 through x31, which do not exist in RV32E and
+/// RV64E.
+pub(crate) const fn uses_upper_registers(raw: u32) -> bool {
+    // The most significant bit of each register field
+    let rd = raw & 1 << 11 != 0;
+    let rs1 = raw & 1 << 19 != 0;
+    let rs2 = raw & 1 << 24 != 0;
+
+    match raw & 0b1111111 {
+        // LUI, AUIPC, JAL
+        0b0110111 | 0b0010111 | 0b1101111 => rd,
+        // JALR, LOAD, OP-IMM, OP-IMM-32
+        0b1100111 | 0b0000011 | 0b0010011 | 0b0011011 => rd || rs1,
+        // BRANCH, STORE
+        0b1100011 | 0b0100011 => rs1 || rs2,
+        // OP, OP-32, AMO
+        0b0110011 | 0b0111011 | 0b0101111 => rd || rs1 || rs2,
+        // LOAD-FP, STORE-FP, where strided vector accesses also use rs2
+        0b0000111 | 0b0100111 => match (raw >> 12 & 0b111, raw >> 26 & 0b11) {
+            (0b000 | 0b101..=0b111, 0b10) => rs1 || rs2,
+            _ => rs1,
+        },
+        // OP-FP, where only comparisons, classifications, conversions, and
+        // moves between register files use `x` registers
+        0b1010011 => match raw >> 27 {
+            0b10100 | 0b11000 | 0b11100 => rd,
+            0b11010 | 0b11110 => rs1,
+            _ => false,
+        },
+        // OP-V, where the rs1 field of VSETIVLI and OPIVI instructions holds
+        // an immediate, and only VMV.X.S, VCPOP.M, and VFIRST.M write to an `x`
+        // register
+        0b1010111 => match raw >> 12 & 0b111 {
+            0b111 => match raw >> 30 {
+                0b11 => rd,
+                0b10 => rd || rs1 || rs2,
+                _ => rd || rs1,
+            },
+            0b010 => raw >> 26 == 0b010000 && rd,
+            0b100 | 0b110 => rs1,
+            _ => false,
+        },
+        // SYSTEM, where the rs1 field of immediate CSR instructions holds an
+        // immediate, rs1 and rs2 are only used by SFENCE.VMA otherwise, and
+        // rs2 is also used by MOP.RR.n
+        0b1110011 => match raw >> 12 & 0b111 {
+            0b000 => rs1 || rs2,
+            0b100 if raw & 1 << 25 != 0 => rd || rs1 || rs2,
+            0b101..=0b111 => rd,
+            _ => rd || rs1,
+        },
+        _ => false,
+    }
+}
+
+/// Raises an illegal instruction exception for the given instruction.
+#[inline(always)]
+pub fn illegal<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    hart.raise(Exception::IllegalInstruction {
+        instruction: NonZeroU32::new(raw),
+    })
+}
+
 /// Gets the `rd` field of R-, I-, U-, and J-type instructions.
 #[inline(always)]
 const fn rd(raw: u32) -> RegisterIndex {
@@ -435,6 +1055,14 @@ const fn rs2(raw: u32) -> RegisterIndex {
     unsafe { RegisterIndex::new_unchecked(raw as usize >> 20 & 0b11111) }
 }
 
+/// Gets the `rs3` field of R4-type instructions.
+#[inline(always)]
+const fn rs3(raw: u32) -> RegisterIndex {
+    // SAFETY: Since the value is masked with 0b11111, it will always be
+    // less than 32.
+    unsafe { RegisterIndex::new_unchecked(raw as usize >> 27 & 0b11111) }
+}
+
 /// Gets the `imm` field of I-type instructions.
 #[inline(always)]
 const fn i_imm(raw: u32) -> i32 {
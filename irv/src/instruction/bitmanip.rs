@@ -0,0 +1,247 @@
+//! Implementation of each instruction of the Zba, Zbb, Zbc, and Zbs
+//! extensions.
+//!
+//! Instructions that depend on XLEN act on the low 32 bits of their operands
+//! in RV32, where results are sign-extended after the instruction.
+
+use super::*;
+
+// Zba
+
+pub fn shadd<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    // SH1ADD, SH2ADD, and SH3ADD are given by funct3 = 2, 4, and 6
+    let shift = raw >> 13 & 0b11;
+
+    hart.gpr[rd(raw)] = (hart.gpr[rs1(raw)] << shift).wrapping_add(hart.gpr[rs2(raw)]);
+}
+
+pub fn add_uw<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    hart.gpr[rd(raw)] = (hart.gpr[rs1(raw)] as u32 as u64).wrapping_add(hart.gpr[rs2(raw)]);
+}
+
+pub fn shadd_uw<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    // SH1ADD.UW, SH2ADD.UW, and SH3ADD.UW are given by funct3 = 2, 4, and 6
+    let shift = raw >> 13 & 0b11;
+
+    hart.gpr[rd(raw)] = (hart.gpr[rs1(raw)] as u32 as u64)
+        .wrapping_shl(shift)
+        .wrapping_add(hart.gpr[rs2(raw)]);
+}
+
+pub fn slli_uw<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    hart.gpr[rd(raw)] = (hart.gpr[rs1(raw)] as u32 as u64).wrapping_shl(shamt(raw));
+}
+
+// Zbb
+
+pub fn andn<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    hart.gpr[rd(raw)] = hart.gpr[rs1(raw)] & !hart.gpr[rs2(raw)];
+}
+
+pub fn orn<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    hart.gpr[rd(raw)] = hart.gpr[rs1(raw)] | !hart.gpr[rs2(raw)];
+}
+
+pub fn xnor<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    hart.gpr[rd(raw)] = !(hart.gpr[rs1(raw)] ^ hart.gpr[rs2(raw)]);
+}
+
+pub fn clz<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    let value = hart.xlen.truncate(hart.gpr[rs1(raw)]);
+
+    hart.gpr[rd(raw)] = (value.leading_zeros() - (64 - hart.xlen.bits())) as u64;
+}
+
+pub fn ctz<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    let value = hart.xlen.truncate(hart.gpr[rs1(raw)]);
+
+    hart.gpr[rd(raw)] = value.trailing_zeros().min(hart.xlen.bits()) as u64;
+}
+
+pub fn cpop<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    hart.gpr[rd(raw)] = hart.xlen.truncate(hart.gpr[rs1(raw)]).count_ones() as u64;
+}
+
+pub fn clzw<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    hart.gpr[rd(raw)] = (hart.gpr[rs1(raw)] as u32).leading_zeros() as u64;
+}
+
+pub fn ctzw<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    hart.gpr[rd(raw)] = (hart.gpr[rs1(raw)] as u32).trailing_zeros() as u64;
+}
+
+pub fn cpopw<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    hart.gpr[rd(raw)] = (hart.gpr[rs1(raw)] as u32).count_ones() as u64;
+}
+
+pub fn max<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    hart.gpr[rd(raw)] = (hart.gpr[rs1(raw)] as i64).max(hart.gpr[rs2(raw)] as i64) as u64;
+}
+
+pub fn maxu<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    hart.gpr[rd(raw)] = hart.gpr[rs1(raw)].max(hart.gpr[rs2(raw)]);
+}
+
+pub fn min<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    hart.gpr[rd(raw)] = (hart.gpr[rs1(raw)] as i64).min(hart.gpr[rs2(raw)] as i64) as u64;
+}
+
+pub fn minu<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    hart.gpr[rd(raw)] = hart.gpr[rs1(raw)].min(hart.gpr[rs2(raw)]);
+}
+
+pub fn sext_b<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    hart.gpr[rd(raw)] = hart.gpr[rs1(raw)] as i8 as u64;
+}
+
+pub fn sext_h<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    hart.gpr[rd(raw)] = hart.gpr[rs1(raw)] as i16 as u64;
+}
+
+pub fn zext_h<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    hart.gpr[rd(raw)] = hart.gpr[rs1(raw)] as u16 as u64;
+}
+
+pub fn rol<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    let amount = hart.gpr[rs2(raw)] as u32;
+
+    hart.gpr[rd(raw)] = match hart.xlen {
+        Xlen::Rv32 => (hart.gpr[rs1(raw)] as u32).rotate_left(amount & 31) as u64,
+        Xlen::Rv64 => hart.gpr[rs1(raw)].rotate_left(amount & 63),
+    };
+}
+
+pub fn ror<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    rotate_right(hart, raw, hart.gpr[rs2(raw)] as u32)
+}
+
+pub fn rori<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    rotate_right(hart, raw, shamt(raw))
+}
+
+#[inline(always)]
+fn rotate_right<B, C>(hart: &mut BaseHart<B, C>, raw: u32, amount: u32) {
+    hart.gpr[rd(raw)] = match hart.xlen {
+        Xlen::Rv32 => (hart.gpr[rs1(raw)] as u32).rotate_right(amount & 31) as u64,
+        Xlen::Rv64 => hart.gpr[rs1(raw)].rotate_right(amount & 63),
+    };
+}
+
+pub fn rolw<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    let amount = hart.gpr[rs2(raw)] as u32 & 31;
+
+    hart.gpr[rd(raw)] = (hart.gpr[rs1(raw)] as u32).rotate_left(amount) as i32 as u64;
+}
+
+pub fn rorw<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    let amount = hart.gpr[rs2(raw)] as u32 & 31;
+
+    hart.gpr[rd(raw)] = (hart.gpr[rs1(raw)] as u32).rotate_right(amount) as i32 as u64;
+}
+
+pub fn roriw<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    let amount = shamt(raw) & 31;
+
+    hart.gpr[rd(raw)] = (hart.gpr[rs1(raw)] as u32).rotate_right(amount) as i32 as u64;
+}
+
+pub fn orc_b<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    let value = hart.gpr[rs1(raw)];
+
+    hart.gpr[rd(raw)] = (0..8).fold(0, |result, byte| {
+        if value >> (byte * 8) & 0xFF != 0 {
+            result | 0xFF << (byte * 8)
+        } else {
+            result
+        }
+    });
+}
+
+pub fn rev8<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    hart.gpr[rd(raw)] = match hart.xlen {
+        Xlen::Rv32 => (hart.gpr[rs1(raw)] as u32).swap_bytes() as u64,
+        Xlen::Rv64 => hart.gpr[rs1(raw)].swap_bytes(),
+    };
+}
+
+// Zbc
+
+pub fn clmul<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    hart.gpr[rd(raw)] = carryless_product(hart, raw) as u64;
+}
+
+pub fn clmulh<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    hart.gpr[rd(raw)] = (carryless_product(hart, raw) >> hart.xlen.bits()) as u64;
+}
+
+pub fn clmulr<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    hart.gpr[rd(raw)] = (carryless_product(hart, raw) >> (hart.xlen.bits() - 1)) as u64;
+}
+
+/// Computes the full carry-less product of the XLEN-bit values of `rs1` and
+/// `rs2`.
+#[inline(always)]
+fn carryless_product<B, C>(hart: &BaseHart<B, C>, raw: u32) -> u128 {
+    let a = hart.xlen.truncate(hart.gpr[rs1(raw)]) as u128;
+    let b = hart.xlen.truncate(hart.gpr[rs2(raw)]);
+
+    (0..64)
+        .filter(|bit| b >> bit & 1 != 0)
+        .fold(0, |product, bit| product ^ a << bit)
+}
+
+// Zbs
+
+pub fn bclr<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    let bit = bit_index(hart, hart.gpr[rs2(raw)] as u32);
+
+    hart.gpr[rd(raw)] = hart.gpr[rs1(raw)] & !(1 << bit);
+}
+
+pub fn bclri<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    let bit = bit_index(hart, shamt(raw));
+
+    hart.gpr[rd(raw)] = hart.gpr[rs1(raw)] & !(1 << bit);
+}
+
+pub fn bext<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    let bit = bit_index(hart, hart.gpr[rs2(raw)] as u32);
+
+    hart.gpr[rd(raw)] = hart.gpr[rs1(raw)] >> bit & 1;
+}
+
+pub fn bexti<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    let bit = bit_index(hart, shamt(raw));
+
+    hart.gpr[rd(raw)] = hart.gpr[rs1(raw)] >> bit & 1;
+}
+
+pub fn binv<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    let bit = bit_index(hart, hart.gpr[rs2(raw)] as u32);
+
+    hart.gpr[rd(raw)] = hart.gpr[rs1(raw)] ^ 1 << bit;
+}
+
+pub fn binvi<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    let bit = bit_index(hart, shamt(raw));
+
+    hart.gpr[rd(raw)] = hart.gpr[rs1(raw)] ^ 1 << bit;
+}
+
+pub fn bset<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    let bit = bit_index(hart, hart.gpr[rs2(raw)] as u32);
+
+    hart.gpr[rd(raw)] = hart.gpr[rs1(raw)] | 1 << bit;
+}
+
+pub fn bseti<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    let bit = bit_index(hart, shamt(raw));
+
+    hart.gpr[rd(raw)] = hart.gpr[rs1(raw)] | 1 << bit;
+}
+
+/// Gets the index of the bit selected by `index` modulo XLEN.
+#[inline(always)]
+fn bit_index<B, C>(hart: &BaseHart<B, C>, index: u32) -> u32 {
+    index & (hart.xlen.bits() - 1)
+}
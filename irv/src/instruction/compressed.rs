@@ -0,0 +1,456 @@
+//! Expansion of instructions of the C extension into their 32-bit equivalents.
+
+use crate::Xlen;
+
+/// Expands a 16-bit compressed instruction into the 32-bit instruction it is
+/// equivalent to, for a hart with the given XLEN.
+///
+/// Returns `None` if the encoding is reserved or otherwise illegal in RV32C
+/// or RV64C respectively.
+pub fn expand(parcel: u16, xlen: Xlen) -> Option<u32> {
+    let c = parcel as u32;
+    let rv32 = xlen == Xlen::Rv32;
+
+    let instruction = match (c & 0b11, c >> 13) {
+        // C.ADDI4SPN
+        (0b00, 0b000) => {
+            let imm = bits(c, 12, 11, 4) | bits(c, 10, 7, 6) | bits(c, 6, 6, 2) | bits(c, 5, 5, 3);
+
+            if imm == 0 {
+                return None;
+            }
+
+            i_type(imm, 2, 0b000, rd_prime(c), OP_IMM)
+        }
+        // C.FLD
+        (0b00, 0b001) => i_type(uimm_d(c), rs1_prime(c), 0b011, rd_prime(c), LOAD_FP),
+        // C.LW
+        (0b00, 0b010) => i_type(uimm_w(c), rs1_prime(c), 0b010, rd_prime(c), LOAD),
+        // C.FLW
+        (0b00, 0b011) if rv32 => i_type(uimm_w(c), rs1_prime(c), 0b010, rd_prime(c), LOAD_FP),
+        // C.LD
+        (0b00, 0b011) => i_type(uimm_d(c), rs1_prime(c), 0b011, rd_prime(c), LOAD),
+        // C.FSD
+        (0b00, 0b101) => s_type(uimm_d(c), rd_prime(c), rs1_prime(c), 0b011, STORE_FP),
+        // C.SW
+        (0b00, 0b110) => s_type(uimm_w(c), rd_prime(c), rs1_prime(c), 0b010, STORE),
+        // C.FSW
+        (0b00, 0b111) if rv32 => s_type(uimm_w(c), rd_prime(c), rs1_prime(c), 0b010, STORE_FP),
+        // C.SD
+        (0b00, 0b111) => s_type(uimm_d(c), rd_prime(c), rs1_prime(c), 0b011, STORE),
+        // C.ADDI (and C.NOP)
+        (0b01, 0b000) => i_type(imm6(c), rd_full(c), 0b000, rd_full(c), OP_IMM),
+        // C.JAL
+        (0b01, 0b001) if rv32 => j_type(j_offset(c), 1),
+        // C.ADDIW
+        (0b01, 0b001) => {
+            if rd_full(c) == 0 {
+                return None;
+            }
+
+            i_type(imm6(c), rd_full(c), 0b000, rd_full(c), OP_IMM_32)
+        }
+        // C.LI
+        (0b01, 0b010) => i_type(imm6(c), 0, 0b000, rd_full(c), OP_IMM),
+        // C.ADDI16SP
+        (0b01, 0b011) if rd_full(c) == 2 => {
+            let imm = sign_extend(
+                bits(c, 12, 12, 9)
+                    | bits(c, 6, 6, 4)
+                    | bits(c, 5, 5, 6)
+                    | bits(c, 4, 3, 7)
+                    | bits(c, 2, 2, 5),
+                10,
+            );
+
+            if imm == 0 {
+                return None;
+            }
+
+            i_type(imm, 2, 0b000, 2, OP_IMM)
+        }
+        // C.LUI
+        (0b01, 0b011) => {
+            let imm = sign_extend(bits(c, 12, 12, 17) | bits(c, 6, 2, 12), 18);
+
+            if imm == 0 {
+                return None;
+            }
+
+            imm & !0xFFF | rd_full(c) << 7 | LUI
+        }
+        (0b01, 0b100) => match (c >> 10 & 0b11, c >> 12 & 1, c >> 5 & 0b11) {
+            // Shift amounts of 32 or more are reserved in RV32C, which also
+            // has no C.SUBW or C.ADDW
+            (0b00 | 0b01 | 0b11, 1, _) if rv32 => return None,
+            // C.SRLI
+            (0b00, _, _) => i_type(shamt(c), rs1_prime(c), 0b101, rs1_prime(c), OP_IMM),
+            // C.SRAI
+            (0b01, _, _) => i_type(0x400 | shamt(c), rs1_prime(c), 0b101, rs1_prime(c), OP_IMM),
+            // C.ANDI
+            (0b10, _, _) => i_type(imm6(c), rs1_prime(c), 0b111, rs1_prime(c), OP_IMM),
+            // C.SUB, C.XOR, C.OR, C.AND
+            (0b11, 0, funct2) => {
+                let (funct7, funct3) = match funct2 {
+                    0b00 => (0b0100000, 0b000),
+                    0b01 => (0b0000000, 0b100),
+                    0b10 => (0b0000000, 0b110),
+                    _ => (0b0000000, 0b111),
+                };
+
+                r_type(funct7, rd_prime(c), rs1_prime(c), funct3, rs1_prime(c), OP)
+            }
+            // C.SUBW
+            (0b11, 1, 0b00) => r_type(
+                0b0100000,
+                rd_prime(c),
+                rs1_prime(c),
+                0b000,
+                rs1_prime(c),
+                OP_32,
+            ),
+            // C.ADDW
+            (0b11, 1, 0b01) => r_type(
+                0b0000000,
+                rd_prime(c),
+                rs1_prime(c),
+                0b000,
+                rs1_prime(c),
+                OP_32,
+            ),
+            _ => return None,
+        },
+        // C.J
+        (0b01, 0b101) => j_type(j_offset(c), 0),
+        // C.BEQZ
+        (0b01, 0b110) => b_type(b_offset(c), 0, rs1_prime(c), 0b000),
+        // C.BNEZ
+        (0b01, 0b111) => b_type(b_offset(c), 0, rs1_prime(c), 0b001),
+        // C.SLLI
+        (0b10, 0b000) if rv32 && c >> 12 & 1 != 0 => return None,
+        (0b10, 0b000) => i_type(shamt(c), rd_full(c), 0b001, rd_full(c), OP_IMM),
+        // C.FLDSP
+        (0b10, 0b001) => i_type(uimm_dsp(c), 2, 0b011, rd_full(c), LOAD_FP),
+        // C.LWSP
+        (0b10, 0b010) => {
+            if rd_full(c) == 0 {
+                return None;
+            }
+
+            i_type(uimm_wsp(c), 2, 0b010, rd_full(c), LOAD)
+        }
+        // C.FLWSP
+        (0b10, 0b011) if rv32 => i_type(uimm_wsp(c), 2, 0b010, rd_full(c), LOAD_FP),
+        // C.LDSP
+        (0b10, 0b011) => {
+            if rd_full(c) == 0 {
+                return None;
+            }
+
+            i_type(uimm_dsp(c), 2, 0b011, rd_full(c), LOAD)
+        }
+        (0b10, 0b100) => match (c >> 12 & 1, rd_full(c), rs2_full(c)) {
+            // Reserved
+            (0, 0, 0) => return None,
+            // C.JR
+            (0, rs1, 0) => i_type(0, rs1, 0b000, 0, JALR),
+            // C.MV
+            (0, rd, rs2) => r_type(0, rs2, 0, 0b000, rd, OP),
+            // C.EBREAK
+            (1, 0, 0) => 0x00100073,
+            // C.JALR
+            (1, rs1, 0) => i_type(0, rs1, 0b000, 1, JALR),
+            // C.ADD
+            (_, rd, rs2) => r_type(0, rs2, rd, 0b000, rd, OP),
+        },
+        // C.FSDSP
+        (0b10, 0b101) => s_type(uimm_sdsp(c), rs2_full(c), 2, 0b011, STORE_FP),
+        // C.SWSP
+        (0b10, 0b110) => s_type(uimm_swsp(c), rs2_full(c), 2, 0b010, STORE),
+        // C.FSWSP
+        (0b10, 0b111) if rv32 => s_type(uimm_swsp(c), rs2_full(c), 2, 0b010, STORE_FP),
+        // C.SDSP
+        (0b10, 0b111) => s_type(uimm_sdsp(c), rs2_full(c), 2, 0b011, STORE),
+        _ => return None,
+    };
+
+    Some(instruction)
+}
+
+const LOAD: u32 = 0b0000011;
+const LOAD_FP: u32 = 0b0000111;
+const OP_IMM: u32 = 0b0010011;
+const OP_IMM_32: u32 = 0b0011011;
+const STORE: u32 = 0b0100011;
+const STORE_FP: u32 = 0b0100111;
+const OP: u32 = 0b0110011;
+const LUI: u32 = 0b0110111;
+const OP_32: u32 = 0b0111011;
+const BRANCH: u32 = 0b1100011;
+const JALR: u32 = 0b1100111;
+const JAL: u32 = 0b1101111;
+
+/// Extracts bits `high..=low` of `c` and places them starting at bit `to`.
+#[inline(always)]
+const fn bits(c: u32, high: u32, low: u32, to: u32) -> u32 {
+    (c >> low & ((1 << (high - low + 1)) - 1)) << to
+}
+
+/// Sign-extends the lowest `width` bits of `value`.
+#[inline(always)]
+const fn sign_extend(value: u32, width: u32) -> u32 {
+    ((value << (32 - width)) as i32 >> (32 - width)) as u32
+}
+
+/// Gets the full 5-bit `rd`/`rs1` field in bits 11:7.
+#[inline(always)]
+const fn rd_full(c: u32) -> u32 {
+    c >> 7 & 0b11111
+}
+
+/// Gets the full 5-bit `rs2` field in bits 6:2.
+#[inline(always)]
+const fn rs2_full(c: u32) -> u32 {
+    c >> 2 & 0b11111
+}
+
+/// Gets the 3-bit `rs1'` field in bits 9:7, which refers to x8 through x15.
+#[inline(always)]
+const fn rs1_prime(c: u32) -> u32 {
+    8 + (c >> 7 & 0b111)
+}
+
+/// Gets the 3-bit `rd'`/`rs2'` field in bits 4:2, which refers to x8 through
+/// x15.
+#[inline(always)]
+const fn rd_prime(c: u32) -> u32 {
+    8 + (c >> 2 & 0b111)
+}
+
+/// Gets the sign-extended 6-bit immediate in bits 12 and 6:2.
+#[inline(always)]
+const fn imm6(c: u32) -> u32 {
+    sign_extend(bits(c, 12, 12, 5) | bits(c, 6, 2, 0), 6)
+}
+
+/// Gets the 6-bit shift amount in bits 12 and 6:2.
+#[inline(always)]
+const fn shamt(c: u32) -> u32 {
+    bits(c, 12, 12, 5) | bits(c, 6, 2, 0)
+}
+
+/// Gets the word-scaled offset of C.LW, C.SW, C.FLW, and C.FSW.
+#[inline(always)]
+const fn uimm_w(c: u32) -> u32 {
+    bits(c, 12, 10, 3) | bits(c, 6, 6, 2) | bits(c, 5, 5, 6)
+}
+
+/// Gets the doubleword-scaled offset of C.LD, C.SD, C.FLD and C.FSD.
+#[inline(always)]
+const fn uimm_d(c: u32) -> u32 {
+    bits(c, 12, 10, 3) | bits(c, 6, 5, 6)
+}
+
+/// Gets the word-scaled offset of C.LWSP and C.FLWSP.
+#[inline(always)]
+const fn uimm_wsp(c: u32) -> u32 {
+    bits(c, 12, 12, 5) | bits(c, 6, 4, 2) | bits(c, 3, 2, 6)
+}
+
+/// Gets the word-scaled offset of C.SWSP and C.FSWSP.
+#[inline(always)]
+const fn uimm_swsp(c: u32) -> u32 {
+    bits(c, 12, 9, 2) | bits(c, 8, 7, 6)
+}
+
+/// Gets the doubleword-scaled offset of C.LDSP and C.FLDSP.
+#[inline(always)]
+const fn uimm_dsp(c: u32) -> u32 {
+    bits(c, 12, 12, 5) | bits(c, 6, 5, 3) | bits(c, 4, 2, 6)
+}
+
+/// Gets the doubleword-scaled offset of C.SDSP and C.FSDSP.
+#[inline(always)]
+const fn uimm_sdsp(c: u32) -> u32 {
+    bits(c, 12, 10, 3) | bits(c, 9, 7, 6)
+}
+
+/// Gets the offset of C.J.
+#[inline(always)]
+const fn j_offset(c: u32) -> u32 {
+    sign_extend(
+        bits(c, 12, 12, 11)
+            | bits(c, 11, 11, 4)
+            | bits(c, 10, 9, 8)
+            | bits(c, 8, 8, 10)
+            | bits(c, 7, 7, 6)
+            | bits(c, 6, 6, 7)
+            | bits(c, 5, 3, 1)
+            | bits(c, 2, 2, 5),
+        12,
+    )
+}
+
+/// Gets the offset of C.BEQZ and C.BNEZ.
+#[inline(always)]
+const fn b_offset(c: u32) -> u32 {
+    sign_extend(
+        bits(c, 12, 12, 8)
+            | bits(c, 11, 10, 3)
+            | bits(c, 6, 5, 6)
+            | bits(c, 4, 3, 1)
+            | bits(c, 2, 2, 5),
+        9,
+    )
+}
+
+#[inline(always)]
+const fn r_type(funct7: u32, rs2: u32, rs1: u32, funct3: u32, rd: u32, opcode: u32) -> u32 {
+    funct7 << 25 | rs2 << 20 | rs1 << 15 | funct3 << 12 | rd << 7 | opcode
+}
+
+#[inline(always)]
+const fn i_type(imm: u32, rs1: u32, funct3: u32, rd: u32, opcode: u32) -> u32 {
+    (imm & 0xFFF) << 20 | rs1 << 15 | funct3 << 12 | rd << 7 | opcode
+}
+
+#[inline(always)]
+const fn s_type(imm: u32, rs2: u32, rs1: u32, funct3: u32, opcode: u32) -> u32 {
+    (imm >> 5 & 0x7F) << 25 | rs2 << 20 | rs1 << 15 | funct3 << 12 | (imm & 0x1F) << 7 | opcode
+}
+
+#[inline(always)]
+const fn b_type(imm: u32, rs2: u32, rs1: u32, funct3: u32) -> u32 {
+    bits(imm, 12, 12, 31)
+        | bits(imm, 10, 5, 25)
+        | rs2 << 20
+        | rs1 << 15
+        | funct3 << 12
+        | bits(imm, 4, 1, 8)
+        | bits(imm, 11, 11, 7)
+        | BRANCH
+}
+
+#[inline(always)]
+const fn j_type(imm: u32, rd: u32) -> u32 {
+    bits(imm, 20, 20, 31)
+        | bits(imm, 10, 1, 21)
+        | bits(imm, 11, 11, 20)
+        | bits(imm, 19, 12, 12)
+        | rd << 7
+        | JAL
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks that each compressed instruction expands to the instruction
+    /// with the same immediate, using the largest and most negative
+    /// immediates to check that every bit is placed correctly.
+    fn check(xlen: Xlen, expansions: &[(u16, u32)]) {
+        for &(parcel, expected) in expansions {
+            assert_eq!(
+                expand(parcel, xlen),
+                Some(expected),
+                "{parcel:#06x} in {xlen:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_immediates() {
+        check(
+            Xlen::Rv64,
+            &[
+                // addi x8, x2, 1020
+                (0x1FE0, 0x3FC10413),
+                // lw x9, 124(x15)
+                (0x5FE4, 0x07C7A483),
+                // ld x9, 248(x15)
+                (0x7FE4, 0x0F87B483),
+                // sw x9, 124(x15)
+                (0xDFE4, 0x0697AE23),
+                // sd x9, 248(x15)
+                (0xFFE4, 0x0E97BC23),
+                // addi x10, x10, -32
+                (0x1501, 0xFE050513),
+                // addiw x10, x10, 31
+                (0x257D, 0x01F5051B),
+                // addi x11, x0, -1
+                (0x55FD, 0xFFF00593),
+                // addi x2, x2, -512
+                (0x7101, 0xE0010113),
+                // addi x2, x2, 496
+                (0x617D, 0x1F010113),
+                // lui x12, 0xFFFE0
+                (0x7601, 0xFFFE0637),
+                // lui x12, 0x1F
+                (0x667D, 0x0001F637),
+                // srli x8, x8, 63
+                (0x907D, 0x03F45413),
+                // srai x9, x9, 1
+                (0x8485, 0x4014D493),
+                // andi x10, x10, -32
+                (0x9901, 0xFE057513),
+                // jal x0, -2048
+                (0xB001, 0x801FF06F),
+                // jal x0, 2046
+                (0xAFFD, 0x7FE0006F),
+                // beq x8, x0, -256
+                (0xD001, 0xF00400E3),
+                // bne x9, x0, 254
+                (0xECFD, 0x0E049F63),
+                // slli x13, x13, 63
+                (0x16FE, 0x03F69693),
+                // lw x14, 252(x2)
+                (0x577E, 0x0FC12703),
+                // ld x14, 504(x2)
+                (0x777E, 0x1F813703),
+                // sw x14, 252(x2)
+                (0xDFBA, 0x0EE12E23),
+                // sd x14, 504(x2)
+                (0xFFBA, 0x1EE13C23),
+                // fld f1, 504(x2)
+                (0x30FE, 0x1F813087),
+                // fsd f1, 504(x2)
+                (0xBF86, 0x1E113C27),
+            ],
+        );
+
+        // These encodings are C.ADDIW, C.LD, C.SD, C.LDSP, and C.SDSP in RV64
+        check(
+            Xlen::Rv32,
+            &[
+                // jal x1, -2048
+                (0x3001, 0x801FF0EF),
+                // jal x1, 2046
+                (0x2FFD, 0x7FE000EF),
+                // flw f9, 124(x15)
+                (0x7FE4, 0x07C7A487),
+                // fsw f9, 124(x15)
+                (0xFFE4, 0x0697AE27),
+                // flw f14, 252(x2)
+                (0x777E, 0x0FC12707),
+                // fsw f14, 252(x2)
+                (0xFFBA, 0x0EE12E27),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_reserved() {
+        for xlen in [Xlen::Rv32, Xlen::Rv64] {
+            // C.ADDI4SPN, C.ADDI16SP, and C.LUI with an immediate of zero
+            assert_eq!(expand(0x0000, xlen), None);
+            assert_eq!(expand(0x6101, xlen), None);
+            assert_eq!(expand(0x6501, xlen), None);
+            // C.LWSP to x0
+            assert_eq!(expand(0x4002, xlen), None);
+        }
+
+        // Shift amounts of 32 or more in RV32
+        assert_eq!(expand(0x16FE, Xlen::Rv32), None);
+        assert_eq!(expand(0x907D, Xlen::Rv32), None);
+    }
+}
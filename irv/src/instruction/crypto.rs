@@ -0,0 +1,457 @@
+//! Implementation of each instruction of the scalar cryptography extensions
+//! Zbkb, Zbkc, Zbkx, Zknd, Zkne, Zknh, Zksed, and Zksh.
+//!
+//! The instructions that Zbkb and Zbkc share with Zbb and Zbc are implemented
+//! in [bitmanip](super::bitmanip).
+
+use super::*;
+
+// Zbkb
+
+pub fn pack<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    let half = hart.xlen.bits() / 2;
+    let low = hart.gpr[rs1(raw)] & ((1 << half) - 1);
+
+    hart.gpr[rd(raw)] = hart.gpr[rs2(raw)] << half | low;
+}
+
+pub fn packh<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    hart.gpr[rd(raw)] = (hart.gpr[rs2(raw)] & 0xFF) << 8 | hart.gpr[rs1(raw)] & 0xFF;
+}
+
+pub fn packw<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    let value = (hart.gpr[rs2(raw)] as u32) << 16 | hart.gpr[rs1(raw)] as u16 as u32;
+
+    hart.gpr[rd(raw)] = value as i32 as u64;
+}
+
+pub fn brev8<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    // Reversing the bytes and then all of the bits reverses the bits of each
+    // byte in place
+    hart.gpr[rd(raw)] = hart.gpr[rs1(raw)].swap_bytes().reverse_bits();
+}
+
+pub fn zip<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    let value = hart.gpr[rs1(raw)] as u32;
+
+    hart.gpr[rd(raw)] = (0..16).fold(0, |result, bit| {
+        result | (value >> bit & 1) << (2 * bit) | (value >> (bit + 16) & 1) << (2 * bit + 1)
+    }) as u64;
+}
+
+pub fn unzip<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    let value = hart.gpr[rs1(raw)] as u32;
+
+    hart.gpr[rd(raw)] = (0..16).fold(0, |result, bit| {
+        result | (value >> (2 * bit) & 1) << bit | (value >> (2 * bit + 1) & 1) << (bit + 16)
+    }) as u64;
+}
+
+// Zbkx
+
+pub fn xperm4<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    crossbar_permute(hart, raw, 4)
+}
+
+pub fn xperm8<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    crossbar_permute(hart, raw, 8)
+}
+
+/// Replaces each `width`-bit element of `rs2` with the element of `rs1` that
+/// it indexes, or zero if the index is out of range.
+#[inline(always)]
+fn crossbar_permute<B, C>(hart: &mut BaseHart<B, C>, raw: u32, width: u32) {
+    let elements = hart.xlen.bits() / width;
+    let mask = (1 << width) - 1;
+
+    let table = hart.gpr[rs1(raw)];
+    let indices = hart.gpr[rs2(raw)];
+
+    hart.gpr[rd(raw)] = (0..elements).fold(0, |result, element| {
+        let index = (indices >> (element * width) & mask) as u32;
+
+        if index < elements {
+            result | (table >> (index * width) & mask) << (element * width)
+        } else {
+            result
+        }
+    });
+}
+
+// Zknd and Zkne
+
+pub fn aes32esi<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    aes32(hart, raw, |byte| AES_SBOX[byte as usize] as u32)
+}
+
+pub fn aes32esmi<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    aes32(hart, raw, |byte| {
+        let byte = AES_SBOX[byte as usize];
+
+        u32::from_le_bytes([gf_mul(byte, 2), byte, byte, gf_mul(byte, 3)])
+    })
+}
+
+pub fn aes32dsi<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    aes32(hart, raw, |byte| AES_INVERSE_SBOX[byte as usize] as u32)
+}
+
+pub fn aes32dsmi<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    aes32(hart, raw, |byte| {
+        let byte = AES_INVERSE_SBOX[byte as usize];
+
+        u32::from_le_bytes([
+            gf_mul(byte, 0x0E),
+            gf_mul(byte, 0x09),
+            gf_mul(byte, 0x0D),
+            gf_mul(byte, 0x0B),
+        ])
+    })
+}
+
+/// Writes the sign-extended result of [byte_select] to `rd`.
+#[inline(always)]
+fn aes32<B, C>(hart: &mut BaseHart<B, C>, raw: u32, f: impl FnOnce(u8) -> u32) {
+    hart.gpr[rd(raw)] = byte_select(hart, raw, f) as i32 as u64;
+}
+
+pub fn aes64es<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    let state = aes64_shift_rows(hart, raw, false).map(|byte| AES_SBOX[byte as usize]);
+
+    hart.gpr[rd(raw)] = u64::from_le_bytes(state);
+}
+
+pub fn aes64esm<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    let state = aes64_shift_rows(hart, raw, false).map(|byte| AES_SBOX[byte as usize]);
+
+    hart.gpr[rd(raw)] = map_words(u64::from_le_bytes(state), mix_column);
+}
+
+pub fn aes64ds<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    let state = aes64_shift_rows(hart, raw, true).map(|byte| AES_INVERSE_SBOX[byte as usize]);
+
+    hart.gpr[rd(raw)] = u64::from_le_bytes(state);
+}
+
+pub fn aes64dsm<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    let state = aes64_shift_rows(hart, raw, true).map(|byte| AES_INVERSE_SBOX[byte as usize]);
+
+    hart.gpr[rd(raw)] = map_words(u64::from_le_bytes(state), inverse_mix_column);
+}
+
+pub fn aes64im<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    hart.gpr[rd(raw)] = map_words(hart.gpr[rs1(raw)], inverse_mix_column);
+}
+
+pub fn aes64ks1i<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    let rnum = raw >> 20 & 0xF;
+
+    // Round numbers above 10 are reserved
+    if rnum > 0xA {
+        return illegal(hart, raw);
+    }
+
+    let word = (hart.gpr[rs1(raw)] >> 32) as u32;
+
+    // Round number 10 is used by AES-256 to substitute the word without
+    // rotating it or adding a round constant
+    let (word, round_constant) = match rnum {
+        0xA => (word, 0),
+        _ => (
+            word.rotate_right(8),
+            AES_ROUND_CONSTANTS[rnum as usize] as u32,
+        ),
+    };
+
+    let word = u32::from_le_bytes(word.to_le_bytes().map(|byte| AES_SBOX[byte as usize]));
+    let word = (word ^ round_constant) as u64;
+
+    hart.gpr[rd(raw)] = word << 32 | word;
+}
+
+pub fn aes64ks2<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    let high = (hart.gpr[rs1(raw)] >> 32) as u32;
+    let rs2 = hart.gpr[rs2(raw)];
+
+    let low = high ^ rs2 as u32;
+    let high = low ^ (rs2 >> 32) as u32;
+
+    hart.gpr[rd(raw)] = (high as u64) << 32 | low as u64;
+}
+
+/// Applies ShiftRows (or InvShiftRows if `inverse`) to the AES state held in
+/// `rs1` (columns 0 and 1) and `rs2` (columns 2 and 3), returning columns 0
+/// and 1 of the result.
+#[inline(always)]
+fn aes64_shift_rows<B, C>(hart: &BaseHart<B, C>, raw: u32, inverse: bool) -> [u8; 8] {
+    let low = hart.gpr[rs1(raw)].to_le_bytes();
+    let high = hart.gpr[rs2(raw)].to_le_bytes();
+
+    core::array::from_fn(|index| {
+        let (column, row) = (index / 4, index % 4);
+
+        // Row n is rotated left by n columns, or right if inverse
+        let column = if inverse {
+            (column + 4 - row) % 4
+        } else {
+            (column + row) % 4
+        };
+
+        let index = column * 4 + row;
+
+        if index < 8 {
+            low[index]
+        } else {
+            high[index - 8]
+        }
+    })
+}
+
+/// Applies MixColumns to a single column of the AES state.
+fn mix_column(column: u32) -> u32 {
+    let [a, b, c, d] = column.to_le_bytes();
+
+    u32::from_le_bytes([
+        gf_mul(a, 2) ^ gf_mul(b, 3) ^ c ^ d,
+        a ^ gf_mul(b, 2) ^ gf_mul(c, 3) ^ d,
+        a ^ b ^ gf_mul(c, 2) ^ gf_mul(d, 3),
+        gf_mul(a, 3) ^ b ^ c ^ gf_mul(d, 2),
+    ])
+}
+
+/// Applies InvMixColumns to a single column of the AES state.
+fn inverse_mix_column(column: u32) -> u32 {
+    let [a, b, c, d] = column.to_le_bytes();
+
+    u32::from_le_bytes([
+        gf_mul(a, 0x0E) ^ gf_mul(b, 0x0B) ^ gf_mul(c, 0x0D) ^ gf_mul(d, 0x09),
+        gf_mul(a, 0x09) ^ gf_mul(b, 0x0E) ^ gf_mul(c, 0x0B) ^ gf_mul(d, 0x0D),
+        gf_mul(a, 0x0D) ^ gf_mul(b, 0x09) ^ gf_mul(c, 0x0E) ^ gf_mul(d, 0x0B),
+        gf_mul(a, 0x0B) ^ gf_mul(b, 0x0D) ^ gf_mul(c, 0x09) ^ gf_mul(d, 0x0E),
+    ])
+}
+
+/// Multiplies two elements of GF(2^8) with the AES reduction polynomial
+/// x^8 + x^4 + x^3 + x + 1.
+const fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0;
+
+    while b != 0 {
+        if b & 1 != 0 {
+            product ^= a;
+        }
+
+        a = a << 1 ^ if a & 0x80 != 0 { 0x1B } else { 0 };
+        b >>= 1;
+    }
+
+    product
+}
+
+/// Applies `f` to both 32-bit halves of `value`.
+#[inline(always)]
+fn map_words(value: u64, f: fn(u32) -> u32) -> u64 {
+    (f((value >> 32) as u32) as u64) << 32 | f(value as u32) as u64
+}
+
+/// Transforms the byte of `rs2` selected by `bs` (bits 31:30) with `f`, and
+/// returns the result rotated to the position of the byte, XORed with `rs1`.
+#[inline(always)]
+fn byte_select<B, C>(hart: &BaseHart<B, C>, raw: u32, f: impl FnOnce(u8) -> u32) -> u32 {
+    let shift = (raw >> 30) * 8;
+    let byte = (hart.gpr[rs2(raw)] >> shift) as u8;
+
+    hart.gpr[rs1(raw)] as u32 ^ f(byte).rotate_left(shift)
+}
+
+// Zknh
+
+pub fn sha256sig0<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    sha256(hart, raw, |x| {
+        x.rotate_right(7) ^ x.rotate_right(18) ^ x >> 3
+    })
+}
+
+pub fn sha256sig1<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    sha256(hart, raw, |x| {
+        x.rotate_right(17) ^ x.rotate_right(19) ^ x >> 10
+    })
+}
+
+pub fn sha256sum0<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    sha256(hart, raw, |x| {
+        x.rotate_right(2) ^ x.rotate_right(13) ^ x.rotate_right(22)
+    })
+}
+
+pub fn sha256sum1<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    sha256(hart, raw, |x| {
+        x.rotate_right(6) ^ x.rotate_right(11) ^ x.rotate_right(25)
+    })
+}
+
+#[inline(always)]
+fn sha256<B, C>(hart: &mut BaseHart<B, C>, raw: u32, f: impl FnOnce(u32) -> u32) {
+    hart.gpr[rd(raw)] = f(hart.gpr[rs1(raw)] as u32) as i32 as u64;
+}
+
+pub fn sha512sig0<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    let x = hart.gpr[rs1(raw)];
+
+    hart.gpr[rd(raw)] = x.rotate_right(1) ^ x.rotate_right(8) ^ x >> 7;
+}
+
+pub fn sha512sig1<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    let x = hart.gpr[rs1(raw)];
+
+    hart.gpr[rd(raw)] = x.rotate_right(19) ^ x.rotate_right(61) ^ x >> 6;
+}
+
+pub fn sha512sum0<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    let x = hart.gpr[rs1(raw)];
+
+    hart.gpr[rd(raw)] = x.rotate_right(28) ^ x.rotate_right(34) ^ x.rotate_right(39);
+}
+
+pub fn sha512sum1<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    let x = hart.gpr[rs1(raw)];
+
+    hart.gpr[rd(raw)] = x.rotate_right(14) ^ x.rotate_right(18) ^ x.rotate_right(41);
+}
+
+// The RV32 SHA-512 instructions each compute one half of a 64-bit function,
+// given that half of its input in rs1 and the other half in rs2
+
+pub fn sha512sig0h<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    sha512_half(hart, raw, |a, b| {
+        a >> 1 ^ a >> 7 ^ a >> 8 ^ b << 31 ^ b << 24
+    })
+}
+
+pub fn sha512sig0l<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    sha512_half(hart, raw, |a, b| {
+        a >> 1 ^ a >> 7 ^ a >> 8 ^ b << 31 ^ b << 25 ^ b << 24
+    })
+}
+
+pub fn sha512sig1h<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    sha512_half(hart, raw, |a, b| {
+        a << 3 ^ a >> 6 ^ a >> 19 ^ b >> 29 ^ b << 13
+    })
+}
+
+pub fn sha512sig1l<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    sha512_half(hart, raw, |a, b| {
+        a << 3 ^ a >> 6 ^ a >> 19 ^ b >> 29 ^ b << 26 ^ b << 13
+    })
+}
+
+pub fn sha512sum0r<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    sha512_half(hart, raw, |a, b| {
+        a << 25 ^ a << 30 ^ a >> 28 ^ b >> 7 ^ b >> 2 ^ b << 4
+    })
+}
+
+pub fn sha512sum1r<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    sha512_half(hart, raw, |a, b| {
+        a << 23 ^ a >> 14 ^ a >> 18 ^ b >> 9 ^ b << 18 ^ b << 14
+    })
+}
+
+#[inline(always)]
+fn sha512_half<B, C>(hart: &mut BaseHart<B, C>, raw: u32, f: impl FnOnce(u32, u32) -> u32) {
+    hart.gpr[rd(raw)] = f(hart.gpr[rs1(raw)] as u32, hart.gpr[rs2(raw)] as u32) as i32 as u64;
+}
+
+// Zksed
+
+pub fn sm4ed<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    hart.gpr[rd(raw)] = byte_select(hart, raw, |byte| {
+        let x = SM4_SBOX[byte as usize] as u32;
+
+        // The linear transformation L, whose rotations of a single byte are
+        // only shifts
+        x ^ x << 2 ^ x << 10 ^ x << 18 ^ x << 24
+    }) as i32 as u64;
+}
+
+pub fn sm4ks<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    hart.gpr[rd(raw)] = byte_select(hart, raw, |byte| {
+        let x = SM4_SBOX[byte as usize] as u32;
+
+        // The linear transformation L' of the key schedule
+        x ^ x << 13 ^ x << 23
+    }) as i32 as u64;
+}
+
+// Zksh
+
+pub fn sm3p0<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    let x = hart.gpr[rs1(raw)] as u32;
+
+    hart.gpr[rd(raw)] = (x ^ x.rotate_left(9) ^ x.rotate_left(17)) as i32 as u64;
+}
+
+pub fn sm3p1<B, C>(hart: &mut BaseHart<B, C>, raw: u32) {
+    let x = hart.gpr[rs1(raw)] as u32;
+
+    hart.gpr[rd(raw)] = (x ^ x.rotate_left(15) ^ x.rotate_left(23)) as i32 as u64;
+}
+
+/// The round constants of the AES key schedule, indexed by round number.
+const AES_ROUND_CONSTANTS: [u8; 10] = [0x01, 0x02, 0x04, 0x08, 0x10, 0x20, 0x40, 0x80, 0x1B, 0x36];
+
+/// The AES substitution box.
+#[rustfmt::skip]
+const AES_SBOX: [u8; 256] = [
+    0x63, 0x7C, 0x77, 0x7B, 0xF2, 0x6B, 0x6F, 0xC5, 0x30, 0x01, 0x67, 0x2B, 0xFE, 0xD7, 0xAB, 0x76,
+    0xCA, 0x82, 0xC9, 0x7D, 0xFA, 0x59, 0x47, 0xF0, 0xAD, 0xD4, 0xA2, 0xAF, 0x9C, 0xA4, 0x72, 0xC0,
+    0xB7, 0xFD, 0x93, 0x26, 0x36, 0x3F, 0xF7, 0xCC, 0x34, 0xA5, 0xE5, 0xF1, 0x71, 0xD8, 0x31, 0x15,
+    0x04, 0xC7, 0x23, 0xC3, 0x18, 0x96, 0x05, 0x9A, 0x07, 0x12, 0x80, 0xE2, 0xEB, 0x27, 0xB2, 0x75,
+    0x09, 0x83, 0x2C, 0x1A, 0x1B, 0x6E, 0x5A, 0xA0, 0x52, 0x3B, 0xD6, 0xB3, 0x29, 0xE3, 0x2F, 0x84,
+    0x53, 0xD1, 0x00, 0xED, 0x20, 0xFC, 0xB1, 0x5B, 0x6A, 0xCB, 0xBE, 0x39, 0x4A, 0x4C, 0x58, 0xCF,
+    0xD0, 0xEF, 0xAA, 0xFB, 0x43, 0x4D, 0x33, 0x85, 0x45, 0xF9, 0x02, 0x7F, 0x50, 0x3C, 0x9F, 0xA8,
+    0x51, 0xA3, 0x40, 0x8F, 0x92, 0x9D, 0x38, 0xF5, 0xBC, 0xB6, 0xDA, 0x21, 0x10, 0xFF, 0xF3, 0xD2,
+    0xCD, 0x0C, 0x13, 0xEC, 0x5F, 0x97, 0x44, 0x17, 0xC4, 0xA7, 0x7E, 0x3D, 0x64, 0x5D, 0x19, 0x73,
+    0x60, 0x81, 0x4F, 0xDC, 0x22, 0x2A, 0x90, 0x88, 0x46, 0xEE, 0xB8, 0x14, 0xDE, 0x5E, 0x0B, 0xDB,
+    0xE0, 0x32, 0x3A, 0x0A, 0x49, 0x06, 0x24, 0x5C, 0xC2, 0xD3, 0xAC, 0x62, 0x91, 0x95, 0xE4, 0x79,
+    0xE7, 0xC8, 0x37, 0x6D, 0x8D, 0xD5, 0x4E, 0xA9, 0x6C, 0x56, 0xF4, 0xEA, 0x65, 0x7A, 0xAE, 0x08,
+    0xBA, 0x78, 0x25, 0x2E, 0x1C, 0xA6, 0xB4, 0xC6, 0xE8, 0xDD, 0x74, 0x1F, 0x4B, 0xBD, 0x8B, 0x8A,
+    0x70, 0x3E, 0xB5, 0x66, 0x48, 0x03, 0xF6, 0x0E, 0x61, 0x35, 0x57, 0xB9, 0x86, 0xC1, 0x1D, 0x9E,
+    0xE1, 0xF8, 0x98, 0x11, 0x69, 0xD9, 0x8E, 0x94, 0x9B, 0x1E, 0x87, 0xE9, 0xCE, 0x55, 0x28, 0xDF,
+    0x8C, 0xA1, 0x89, 0x0D, 0xBF, 0xE6, 0x42, 0x68, 0x41, 0x99, 0x2D, 0x0F, 0xB0, 0x54, 0xBB, 0x16,
+];
+
+/// The inverse of the AES substitution box.
+const AES_INVERSE_SBOX: [u8; 256] = {
+    let mut inverse = [0; 256];
+    let mut byte = 0;
+
+    while byte < 256 {
+        inverse[AES_SBOX[byte] as usize] = byte as u8;
+        byte += 1;
+    }
+
+    inverse
+};
+
+/// The SM4 substitution box.
+#[rustfmt::skip]
+const SM4_SBOX: [u8; 256] = [
+    0xD6, 0x90, 0xE9, 0xFE, 0xCC, 0xE1, 0x3D, 0xB7, 0x16, 0xB6, 0x14, 0xC2, 0x28, 0xFB, 0x2C, 0x05,
+    0x2B, 0x67, 0x9A, 0x76, 0x2A, 0xBE, 0x04, 0xC3, 0xAA, 0x44, 0x13, 0x26, 0x49, 0x86, 0x06, 0x99,
+    0x9C, 0x42, 0x50, 0xF4, 0x91, 0xEF, 0x98, 0x7A, 0x33, 0x54, 0x0B, 0x43, 0xED, 0xCF, 0xAC, 0x62,
+    0xE4, 0xB3, 0x1C, 0xA9, 0xC9, 0x08, 0xE8, 0x95, 0x80, 0xDF, 0x94, 0xFA, 0x75, 0x8F, 0x3F, 0xA6,
+    0x47, 0x07, 0xA7, 0xFC, 0xF3, 0x73, 0x17, 0xBA, 0x83, 0x59, 0x3C, 0x19, 0xE6, 0x85, 0x4F, 0xA8,
+    0x68, 0x6B, 0x81, 0xB2, 0x71, 0x64, 0xDA, 0x8B, 0xF8, 0xEB, 0x0F, 0x4B, 0x70, 0x56, 0x9D, 0x35,
+    0x1E, 0x24, 0x0E, 0x5E, 0x63, 0x58, 0xD1, 0xA2, 0x25, 0x22, 0x7C, 0x3B, 0x01, 0x21, 0x78, 0x87,
+    0xD4, 0x00, 0x46, 0x57, 0x9F, 0xD3, 0x27, 0x52, 0x4C, 0x36, 0x02, 0xE7, 0xA0, 0xC4, 0xC8, 0x9E,
+    0xEA, 0xBF, 0x8A, 0xD2, 0x40, 0xC7, 0x38, 0xB5, 0xA3, 0xF7, 0xF2, 0xCE, 0xF9, 0x61, 0x15, 0xA1,
+    0xE0, 0xAE, 0x5D, 0xA4, 0x9B, 0x34, 0x1A, 0x55, 0xAD, 0x93, 0x32, 0x30, 0xF5, 0x8C, 0xB1, 0xE3,
+    0x1D, 0xF6, 0xE2, 0x2E, 0x82, 0x66, 0xCA, 0x60, 0xC0, 0x29, 0x23, 0xAB, 0x0D, 0x53, 0x4E, 0x6F,
+    0xD5, 0xDB, 0x37, 0x45, 0xDE, 0xFD, 0x8E, 0x2F, 0x03, 0xFF, 0x6A, 0x72, 0x6D, 0x6C, 0x5B, 0x51,
+    0x8D, 0x1B, 0xAF, 0x92, 0xBB, 0xDD, 0xBC, 0x7F, 0x11, 0xD9, 0x5C, 0x41, 0x1F, 0x10, 0x5A, 0xD8,
+    0x0A, 0xC1, 0x31, 0x88, 0xA5, 0xCD, 0x7B, 0xBD, 0x2D, 0x74, 0xD0, 0x12, 0xB8, 0xE5, 0xB4, 0xB0,
+    0x89, 0x69, 0x97, 0x4A, 0x0C, 0x96, 0x77, 0x7E, 0x65, 0xB9, 0xF1, 0x09, 0xC5, 0x6E, 0xC6, 0x84,
+    0x18, 0xF0, 0x7D, 0xEC, 0x3A, 0xDC, 0x4D, 0x20, 0x79, 0xEE, 0x5F, 0x3E, 0xD7, 0xCB, 0x39, 0x48,
+];
@@ -0,0 +1,340 @@
+//! Implementation of each instruction of the F and D extensions.
+
+use crate::softfloat::{self, Format, RoundingMode, F32, F64};
+
+use super::*;
+
+/// A floating-point format whose values can be held in the `f` registers.
+pub(super) trait Register: Format {
+    /// Extracts a value of this format from an `f` register.
+    ///
+    /// Values that are narrower than the register but are not properly
+    /// NaN-boxed are treated as the canonical NaN.
+    fn unbox(value: u64) -> u64;
+
+    /// Converts a value of this format to be held in an `f` register.
+    fn nan_box(value: u64) -> u64;
+
+    /// Converts the raw contents of an `f` register to the value moved into an
+    /// `x` register by `FMV.X.W`/`FMV.X.D`.
+    fn move_to_int(value: u64) -> u64;
+}
+
+impl Register for F32 {
+    fn unbox(value: u64) -> u64 {
+        if value >> 32 == 0xFFFF_FFFF {
+            value & 0xFFFF_FFFF
+        } else {
+            F32::CANONICAL_NAN
+        }
+    }
+
+    fn nan_box(value: u64) -> u64 {
+        value | 0xFFFF_FFFF_0000_0000
+    }
+
+    fn move_to_int(value: u64) -> u64 {
+        value as i32 as u64
+    }
+}
+
+impl Register for F64 {
+    fn unbox(value: u64) -> u64 {
+        value
+    }
+
+    fn nan_box(value: u64) -> u64 {
+        value
+    }
+
+    fn move_to_int(value: u64) -> u64 {
+        value
+    }
+}
+
+pub fn flw<B: Bus<u64, u32> + AtomicBus<u64, u64>, C: Csr>(hart: &mut BaseHart<B, C>, raw: u32) {
+    fl::<F32, u32, B, C>(hart, raw)
+}
+
+pub fn fld<B: AtomicBus<u64, u64>, C: Csr>(hart: &mut BaseHart<B, C>, raw: u32) {
+    fl::<F64, u64, B, C>(hart, raw)
+}
+
+#[inline]
+fn fl<F: Register, T: Word + Into<u64>, B: Bus<u64, T> + AtomicBus<u64, u64>, C: Csr>(
+    hart: &mut BaseHart<B, C>,
+    raw: u32,
+) {
+    if !hart.fp_enabled() {
+        return illegal(hart, raw);
+    }
+
+    let address = hart.gpr[rs1(raw)].wrapping_add(i_imm(raw) as u64);
+
+    if let Some(value) = load::<T, B, C>(hart, address) {
+        write::<F, B, C>(hart, rd(raw), value.into());
+    }
+}
+
+pub fn fsw<B: Bus<u64, u32> + AtomicBus<u64, u64>, C: Csr>(hart: &mut BaseHart<B, C>, raw: u32) {
+    fs(hart, raw, |r| r as u32)
+}
+
+pub fn fsd<B: AtomicBus<u64, u64>, C: Csr>(hart: &mut BaseHart<B, C>, raw: u32) {
+    fs(hart, raw, |r| r)
+}
+
+#[inline]
+fn fs<T: Word, B: Bus<u64, T> + AtomicBus<u64, u64>, C: Csr>(
+    hart: &mut BaseHart<B, C>,
+    raw: u32,
+    convert: impl FnOnce(u64) -> T,
+) {
+    if !hart.fp_enabled() {
+        return illegal(hart, raw);
+    }
+
+    let address = hart.gpr[rs1(raw)].wrapping_add(s_imm(raw) as u64);
+
+    // Stores write the raw register contents, even if they are not NaN-boxed
+    store(hart, address, convert(hart.fpr[rs2(raw)]));
+}
+
+pub fn fmadd<B, C: Csr>(hart: &mut BaseHart<B, C>, raw: u32) {
+    fused(hart, raw, false, false)
+}
+
+pub fn fmsub<B, C: Csr>(hart: &mut BaseHart<B, C>, raw: u32) {
+    fused(hart, raw, false, true)
+}
+
+pub fn fnmsub<B, C: Csr>(hart: &mut BaseHart<B, C>, raw: u32) {
+    fused(hart, raw, true, false)
+}
+
+pub fn fnmadd<B, C: Csr>(hart: &mut BaseHart<B, C>, raw: u32) {
+    fused(hart, raw, true, true)
+}
+
+#[inline]
+fn fused<B, C: Csr>(
+    hart: &mut BaseHart<B, C>,
+    raw: u32,
+    negate_product: bool,
+    negate_addend: bool,
+) {
+    match raw >> 25 & 0b11 {
+        0b00 => fused_format::<F32, B, C>(hart, raw, negate_product, negate_addend),
+        0b01 => fused_format::<F64, B, C>(hart, raw, negate_product, negate_addend),
+        _ => illegal(hart, raw),
+    }
+}
+
+#[inline]
+fn fused_format<F: Register, B, C: Csr>(
+    hart: &mut BaseHart<B, C>,
+    raw: u32,
+    negate_product: bool,
+    negate_addend: bool,
+) {
+    if !hart.fp_enabled() {
+        return illegal(hart, raw);
+    }
+
+    let Some(rm) = rounding_mode(hart, raw) else {
+        return illegal(hart, raw);
+    };
+
+    // Negating an operand never affects the result of a NaN, since NaN results
+    // are always canonical
+    let a = read::<F, B, C>(hart, rs1(raw)) ^ if negate_product { F::SIGN } else { 0 };
+    let b = read::<F, B, C>(hart, rs2(raw));
+    let c = read::<F, B, C>(hart, rs3(raw)) ^ if negate_addend { F::SIGN } else { 0 };
+
+    let mut flags = 0;
+    let result = softfloat::fma::<F>(a, b, c, rm, &mut flags);
+
+    write::<F, B, C>(hart, rd(raw), result);
+    accrue(hart, flags);
+}
+
+pub fn op_fp<B, C: Csr>(hart: &mut BaseHart<B, C>, raw: u32) {
+    match raw >> 25 {
+        // FCVT.S.D
+        0b0100000 if rs2(raw) == RegisterIndex(1) => {
+            convert::<F64, F32, B, C>(hart, raw);
+        }
+        // FCVT.D.S
+        0b0100001 if rs2(raw) == RegisterIndex(0) => {
+            convert::<F32, F64, B, C>(hart, raw);
+        }
+        funct7 if funct7 & 0b11 == 0b00 => op_fp_format::<F32, B, C>(hart, raw),
+        funct7 if funct7 & 0b11 == 0b01 => op_fp_format::<F64, B, C>(hart, raw),
+        _ => illegal(hart, raw),
+    }
+}
+
+#[inline]
+fn op_fp_format<F: Register, B, C: Csr>(hart: &mut BaseHart<B, C>, raw: u32) {
+    if !hart.fp_enabled() {
+        return illegal(hart, raw);
+    }
+
+    let funct3 = raw >> 12 & 0b111;
+    let a = read::<F, B, C>(hart, rs1(raw));
+    let b = read::<F, B, C>(hart, rs2(raw));
+    let mut flags = 0;
+    let rv64 = hart.xlen == Xlen::Rv64;
+
+    match (raw >> 27, funct3) {
+        // FADD, FSUB, FMUL, FDIV, FSQRT
+        (0b00000..=0b00011, _) | (0b01011, _) => {
+            let Some(rm) = rounding_mode(hart, raw) else {
+                return illegal(hart, raw);
+            };
+
+            let result = match raw >> 27 {
+                0b00000 => softfloat::add::<F>(a, b, rm, &mut flags),
+                0b00001 => softfloat::sub::<F>(a, b, rm, &mut flags),
+                0b00010 => softfloat::mul::<F>(a, b, rm, &mut flags),
+                0b00011 => softfloat::div::<F>(a, b, rm, &mut flags),
+                _ if rs2(raw) == RegisterIndex(0) => softfloat::sqrt::<F>(a, rm, &mut flags),
+                _ => return illegal(hart, raw),
+            };
+
+            write::<F, B, C>(hart, rd(raw), result);
+        }
+        // FSGNJ, FSGNJN, FSGNJX
+        (0b00100, 0b000..=0b010) => {
+            let sign = match funct3 {
+                0b000 => b & F::SIGN,
+                0b001 => !b & F::SIGN,
+                _ => (a ^ b) & F::SIGN,
+            };
+
+            write::<F, B, C>(hart, rd(raw), a & !F::SIGN | sign);
+        }
+        // FMIN, FMAX
+        (0b00101, 0b000 | 0b001) => {
+            let result = softfloat::min_max::<F>(a, b, funct3 == 0b001, &mut flags);
+
+            write::<F, B, C>(hart, rd(raw), result);
+        }
+        // FLE, FLT, FEQ
+        (0b10100, 0b000..=0b010) => {
+            hart.gpr[rd(raw)] = match funct3 {
+                0b000 => softfloat::le::<F>(a, b, &mut flags),
+                0b001 => softfloat::lt::<F>(a, b, &mut flags),
+                _ => softfloat::eq::<F>(a, b, &mut flags),
+            } as u64;
+        }
+        // FCVT.W, FCVT.WU, FCVT.L, FCVT.LU
+        (0b11000, _) => {
+            let Some(rm) = rounding_mode(hart, raw) else {
+                return illegal(hart, raw);
+            };
+
+            // RV32 has no conversions to or from 64-bit integers
+            let (width, signed) = match rs2(raw).0 {
+                0 => (32, true),
+                1 => (32, false),
+                2 if rv64 => (64, true),
+                3 if rv64 => (64, false),
+                _ => return illegal(hart, raw),
+            };
+
+            hart.gpr[rd(raw)] = softfloat::to_int::<F>(a, width, signed, rm, &mut flags);
+        }
+        // FCVT from W, WU, L, LU
+        (0b11010, _) => {
+            let Some(rm) = rounding_mode(hart, raw) else {
+                return illegal(hart, raw);
+            };
+
+            // RV32 has no conversions to or from 64-bit integers
+            let (width, signed) = match rs2(raw).0 {
+                0 => (32, true),
+                1 => (32, false),
+                2 if rv64 => (64, true),
+                3 if rv64 => (64, false),
+                _ => return illegal(hart, raw),
+            };
+
+            let value = hart.gpr[rs1(raw)];
+            let result = softfloat::from_int::<F>(value, width, signed, rm, &mut flags);
+
+            write::<F, B, C>(hart, rd(raw), result);
+        }
+        // FMV.X.W, FMV.X.D (which RV32 does not have)
+        (0b11100, 0b000) if rs2(raw) == RegisterIndex(0) && (F::BITS == 32 || rv64) => {
+            hart.gpr[rd(raw)] = F::move_to_int(hart.fpr[rs1(raw)]);
+        }
+        // FCLASS
+        (0b11100, 0b001) if rs2(raw) == RegisterIndex(0) => {
+            hart.gpr[rd(raw)] = softfloat::classify::<F>(a);
+        }
+        // FMV.W.X, FMV.D.X (which RV32 does not have)
+        (0b11110, 0b000) if rs2(raw) == RegisterIndex(0) && (F::BITS == 32 || rv64) => {
+            let value = hart.gpr[rs1(raw)] & u64::MAX >> (64 - F::BITS);
+
+            write::<F, B, C>(hart, rd(raw), value);
+        }
+        _ => return illegal(hart, raw),
+    }
+
+    accrue(hart, flags);
+}
+
+#[inline]
+fn convert<F: Register, T: Register, B, C: Csr>(hart: &mut BaseHart<B, C>, raw: u32) {
+    if !hart.fp_enabled() {
+        return illegal(hart, raw);
+    }
+
+    let Some(rm) = rounding_mode(hart, raw) else {
+        return illegal(hart, raw);
+    };
+
+    let mut flags = 0;
+    let result = softfloat::convert::<F, T>(read::<F, B, C>(hart, rs1(raw)), rm, &mut flags);
+
+    write::<T, B, C>(hart, rd(raw), result);
+    accrue(hart, flags);
+}
+
+/// Reads a value of the format `F` from the given `f` register.
+#[inline(always)]
+pub(super) fn read<F: Register, B, C>(hart: &BaseHart<B, C>, index: RegisterIndex) -> u64 {
+    F::unbox(hart.fpr[index])
+}
+
+/// Writes a value of the format `F` to the given `f` register.
+#[inline(always)]
+pub(super) fn write<F: Register, B, C: Csr>(
+    hart: &mut BaseHart<B, C>,
+    index: RegisterIndex,
+    value: u64,
+) {
+    hart.fpr[index] = F::nan_box(value);
+    hart.fp_dirty();
+}
+
+/// Accrues the given exception flags into `fflags`.
+#[inline(always)]
+pub(super) fn accrue<B, C: Csr>(hart: &mut BaseHart<B, C>, flags: u8) {
+    if flags != 0 {
+        hart.fcsr |= flags as u32;
+        hart.fp_dirty();
+    }
+}
+
+/// Gets the rounding mode selected by the `rm` field of an instruction,
+/// returning `None` if it is reserved.
+#[inline(always)]
+fn rounding_mode<B, C>(hart: &BaseHart<B, C>, raw: u32) -> Option<RoundingMode> {
+    match raw >> 12 & 0b111 {
+        // Dynamic rounding mode
+        0b111 => RoundingMode::from_bits(hart.fcsr >> 5 & 0b111),
+        rm => RoundingMode::from_bits(rm),
+    }
+}
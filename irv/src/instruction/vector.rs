@@ -0,0 +1,621 @@
+//! Implementation of each instruction of the V extension.
+//!
+//! Instructions first check that the current `vtype` supports them and that
+//! their register groups are legal, raising an illegal instruction exception
+//! without changing any state otherwise. The results of all body elements are
+//! computed before any of them are written, so a destination group that
+//! overlaps a source group never affects the values read from it.
+
+use alloc::{vec, vec::Vec};
+
+use crate::{vector::VectorRegisters, AgnosticMode};
+
+use super::*;
+
+pub mod float;
+pub mod integer;
+pub mod mask;
+pub mod memory;
+pub mod permute;
+
+/// The widest element supported, in bits.
+const ELEN: u32 = 64;
+
+// The operand categories given by the funct3 field of OP-V instructions
+const OPIVV: u32 = 0b000;
+const OPFVV: u32 = 0b001;
+const OPMVV: u32 = 0b010;
+const OPIVI: u32 = 0b011;
+const OPIVX: u32 = 0b100;
+const OPFVF: u32 = 0b101;
+const OPMVX: u32 = 0b110;
+
+/// The configuration that an instruction executes with, given by `vtype`,
+/// `vl`, and `vstart`.
+#[derive(Clone, Copy)]
+struct Config {
+    /// The selected element width in bits.
+    sew: u32,
+    /// The register group multiplier in eighths of a register, so fractional
+    /// multipliers are less than 8.
+    lmul: u32,
+    /// The vector length.
+    vl: usize,
+    /// The index of the first body element.
+    vstart: usize,
+    /// The number of elements in a register group.
+    vlmax: usize,
+    /// Whether tail elements are agnostic rather than undisturbed.
+    tail_agnostic: bool,
+    /// Whether masked-off elements are agnostic rather than undisturbed.
+    mask_agnostic: bool,
+}
+
+impl Config {
+    /// Gets the configuration given by the vector CSRs, which must not have
+    /// `vill` set.
+    fn new(vector: &VectorRegisters) -> Config {
+        let (sew, lmul) = decode_vtype(vector.vtype).unwrap_or((8, 8));
+
+        Config {
+            sew,
+            lmul,
+            vl: vector.vl as usize,
+            vstart: vector.vstart as usize,
+            vlmax: vlmax(vector.vlen(), sew, lmul),
+            tail_agnostic: vector.vtype & VTYPE_VTA != 0,
+            mask_agnostic: vector.vtype & VTYPE_VMA != 0,
+        }
+    }
+
+    /// Gets the multiplier of a register group holding `vlmax` elements of
+    /// `eew` bits, in eighths of a register, returning `None` if it is not
+    /// between 1/8 and 8.
+    fn emul(&self, eew: u32) -> Option<u32> {
+        let emul = eew * self.lmul / self.sew;
+
+        (eew <= ELEN && (1..=64).contains(&emul)).then_some(emul)
+    }
+}
+
+/// The `vta` field of `vtype`.
+const VTYPE_VTA: u64 = 1 << 6;
+/// The `vma` field of `vtype`.
+const VTYPE_VMA: u64 = 1 << 7;
+
+/// Decodes the SEW (in bits) and LMUL (in eighths of a register) given by
+/// `vtype`, returning `None` if the configuration is not supported.
+fn decode_vtype(vtype: u64) -> Option<(u32, u32)> {
+    let sew = match vtype >> 3 & 0b111 {
+        vsew @ 0..=3 => 8 << vsew,
+        _ => return None,
+    };
+
+    let lmul = match vtype & 0b111 {
+        vlmul @ 0..=3 => 8 << vlmul,
+        vlmul @ 5..=7 => 1 << (vlmul - 5),
+        _ => return None,
+    };
+
+    // Fractional groups must hold at least one element of ELEN bits or less,
+    // and the bits above vma are reserved
+    (vtype >> 8 == 0 && sew * 8 <= lmul * ELEN).then_some((sew, lmul))
+}
+
+/// Gets the number of elements of `sew` bits in a register group with the
+/// multiplier `lmul`.
+const fn vlmax(vlen: usize, sew: u32, lmul: u32) -> usize {
+    vlen * lmul as usize / 8 / sew as usize
+}
+
+/// Gets the number of registers in a group with the multiplier `emul`, which
+/// is one for fractional groups and otherwise a power of two.
+const fn registers(emul: u32) -> usize {
+    (emul as usize).div_ceil(8)
+}
+
+/// Executes `VSETVLI`, `VSETIVLI`, or `VSETVL`, which set `vtype` and choose
+/// `vl` from the application vector length.
+pub fn vset<B, C: Csr>(hart: &mut BaseHart<B, C>, raw: u32) {
+    let vtype = match raw >> 30 {
+        // VSETVLI
+        0b00 | 0b01 => (raw >> 20 & 0x7FF) as u64,
+        // VSETIVLI
+        0b11 => (raw >> 20 & 0x3FF) as u64,
+        // VSETVL
+        0b10 if raw >> 25 == 0b1000000 => hart.xlen.truncate(hart.gpr[rs2(raw)]),
+        _ => return illegal(hart, raw),
+    };
+
+    if !hart.vector_enabled() {
+        return illegal(hart, raw);
+    }
+
+    let avl = if raw >> 30 == 0b11 {
+        uimm(raw) as u64
+    } else if rs1(raw) != RegisterIndex(0) {
+        hart.xlen.truncate(hart.gpr[rs1(raw)])
+    } else if rd(raw) != RegisterIndex(0) {
+        u64::MAX
+    } else {
+        // Only change vtype, keeping vl if the new VLMAX allows it
+        hart.vector.vl
+    };
+
+    let vector = &mut hart.vector;
+
+    match decode_vtype(vtype) {
+        Some((sew, lmul)) => {
+            vector.vtype = vtype;
+            vector.vill = false;
+            vector.vl = avl.min(vlmax(vector.vlen(), sew, lmul) as u64);
+        }
+        None => {
+            vector.vtype = 0;
+            vector.vill = true;
+            vector.vl = 0;
+        }
+    }
+
+    vector.vstart = 0;
+    hart.gpr[rd(raw)] = hart.vector.vl;
+    hart.vector_dirty();
+}
+
+/// Executes an instruction of the OP-V major opcode other than the
+/// configuration-setting instructions.
+pub fn op_v<B, C: Csr>(hart: &mut BaseHart<B, C>, raw: u32) {
+    let funct6 = raw >> 26;
+
+    match raw >> 12 & 0b111 {
+        // Whole register moves do not depend on vtype
+        OPIVI if funct6 == 0b100111 => execute_unconfigured(hart, raw, permute::move_whole),
+        OPIVV | OPIVX | OPIVI => execute(hart, raw, integer::opi),
+        OPMVV | OPMVX => execute(hart, raw, integer::opm),
+        OPFVV | OPFVF => execute(hart, raw, float::opf),
+        _ => illegal(hart, raw),
+    }
+}
+
+/// Executes a vector instruction that depends on `vtype`, raising an illegal
+/// instruction exception if vector instructions are disabled, `vtype.vill`
+/// is set, or `f` returns `None`.
+#[inline]
+fn execute<B, C: Csr>(
+    hart: &mut BaseHart<B, C>,
+    raw: u32,
+    f: impl FnOnce(&mut BaseHart<B, C>, u32, Config) -> Option<()>,
+) {
+    if hart.vector.vill {
+        return illegal(hart, raw);
+    }
+
+    let config = Config::new(&hart.vector);
+
+    execute_unconfigured(hart, raw, |hart, raw| f(hart, raw, config))
+}
+
+/// Executes a vector instruction that does not depend on `vtype`, raising
+/// an illegal instruction exception if vector instructions are disabled or
+/// `f` returns `None`.
+///
+/// `vstart` is reset to zero after the instruction, unless it raises an
+/// exception.
+#[inline]
+fn execute_unconfigured<B, C: Csr>(
+    hart: &mut BaseHart<B, C>,
+    raw: u32,
+    f: impl FnOnce(&mut BaseHart<B, C>, u32) -> Option<()>,
+) {
+    if !hart.vector_enabled() || f(hart, raw).is_none() {
+        return illegal(hart, raw);
+    }
+
+    if hart.result.is_ok() {
+        hart.vector.vstart = 0;
+    }
+
+    hart.vector_dirty();
+}
+
+/// The second source operand of an arithmetic instruction.
+#[derive(Clone, Copy)]
+enum Source {
+    /// The register group starting at vs1.
+    Vector(usize),
+    /// A scalar register or immediate, which is used for every element.
+    Scalar(u64),
+}
+
+impl Source {
+    /// Gets the operand for element `index`, with `eew` bits.
+    fn get(self, vector: &VectorRegisters, index: usize, eew: u32) -> u64 {
+        match self {
+            Source::Vector(register) => vector.element(register, index, eew as usize / 8),
+            Source::Scalar(value) => value & low_bits(eew),
+        }
+    }
+
+    /// Checks that a vector operand is a legal register group for the
+    /// multiplier `emul`, and that the destination group starting at `vd`
+    /// may overlap it.
+    fn check(self, emul: u32, eew: u32, vd: usize, vd_eew: u32, vd_emul: u32) -> Option<()> {
+        match self {
+            Source::Vector(register) => {
+                check_group(register, emul)?;
+                check_overlap(vd, vd_eew, vd_emul, register, eew, emul)
+            }
+            Source::Scalar(_) => Some(()),
+        }
+    }
+}
+
+/// Gets the second source operand of an integer instruction, which is vs1,
+/// `x[rs1]`, or a 5-bit immediate that is sign-extended if `signed` is set.
+fn source<B, C>(hart: &BaseHart<B, C>, raw: u32, signed: bool) -> Source {
+    match raw >> 12 & 0b111 {
+        OPIVV | OPMVV | OPFVV => Source::Vector(vs1(raw)),
+        OPIVI if signed => Source::Scalar(simm5(raw) as u64),
+        OPIVI => Source::Scalar(uimm(raw) as u64),
+        _ => Source::Scalar(hart.gpr[rs1(raw)]),
+    }
+}
+
+/// Checks that the register group starting at `register` with the multiplier
+/// `emul` is legal, which requires it to be aligned to its size.
+fn check_group(register: usize, emul: u32) -> Option<()> {
+    ((1..=64).contains(&emul) && register & (registers(emul) - 1) == 0).then_some(())
+}
+
+/// Checks that a destination group may overlap a source group.
+///
+/// Overlapping groups must have the same element width, or the destination
+/// must be the lowest-numbered part of a source with wider elements, or the
+/// source must be the highest-numbered part of a destination with wider
+/// elements and occupy at least one whole register. Mask registers have
+/// 1-bit elements.
+fn check_overlap(
+    vd: usize,
+    vd_eew: u32,
+    vd_emul: u32,
+    vs: usize,
+    vs_eew: u32,
+    vs_emul: u32,
+) -> Option<()> {
+    let vd_end = vd + registers(vd_emul);
+    let vs_end = vs + registers(vs_emul);
+
+    let legal = vd_end <= vs
+        || vs_end <= vd
+        || vd_eew == vs_eew
+        || vd_eew < vs_eew && vd == vs
+        || vd_eew > vs_eew && vs_emul >= 8 && vd_end == vs_end;
+
+    legal.then_some(())
+}
+
+/// Checks that two register groups do not overlap at all.
+fn check_disjoint(a: usize, a_emul: u32, b: usize, b_emul: u32) -> Option<()> {
+    (a + registers(a_emul) <= b || b + registers(b_emul) <= a).then_some(())
+}
+
+/// Checks that a masked instruction does not write to v0, which holds the
+/// mask, unless its destination is also a mask.
+fn check_mask_overlap(raw: u32, vd: usize) -> Option<()> {
+    (!masked(raw) || vd != 0).then_some(())
+}
+
+/// Computes `f` for each active body element, giving `None` for those that
+/// are masked off.
+fn body<T>(
+    vector: &VectorRegisters,
+    config: Config,
+    masked: bool,
+    mut f: impl FnMut(usize) -> T,
+) -> Vec<Option<T>> {
+    (config.vstart..config.vl)
+        .map(|i| (!masked || vector.mask(0, i)).then(|| f(i)))
+        .collect()
+}
+
+/// Writes `results` to the body elements of the register group starting at
+/// `vd`, which has `eew`-bit elements and the multiplier `emul`, then updates
+/// the tail elements that follow them up to the end of the group.
+///
+/// Nothing is written if `vstart` is not less than `vl`.
+fn write_elements<B, C>(
+    hart: &mut BaseHart<B, C>,
+    config: Config,
+    vd: usize,
+    eew: u32,
+    emul: u32,
+    results: Vec<Option<u64>>,
+) {
+    if config.vstart >= config.vl {
+        return;
+    }
+
+    let end = config.vstart + results.len();
+
+    write_body(hart, config, vd, eew, results);
+    write_tail(hart, config, vd, eew, emul, end);
+}
+
+/// Writes `results` to the body elements starting at `vstart` of the
+/// register group starting at `vd`, where `None` marks an element that is
+/// masked off.
+fn write_body<B, C>(
+    hart: &mut BaseHart<B, C>,
+    config: Config,
+    vd: usize,
+    eew: u32,
+    results: Vec<Option<u64>>,
+) {
+    let width = eew as usize / 8;
+    let ones = config.mask_agnostic && hart.agnostic_mode == AgnosticMode::Ones;
+
+    for (i, result) in (config.vstart..).zip(results) {
+        match result {
+            Some(value) => hart.vector.set_element(vd, i, width, value),
+            None if ones => hart.vector.set_element(vd, i, width, !0),
+            None => (),
+        }
+    }
+}
+
+/// Updates the tail elements of the register group starting at `vd` from
+/// element `start` to the end of the group.
+fn write_tail<B, C>(
+    hart: &mut BaseHart<B, C>,
+    config: Config,
+    vd: usize,
+    eew: u32,
+    emul: u32,
+    start: usize,
+) {
+    if config.tail_agnostic && hart.agnostic_mode == AgnosticMode::Ones {
+        let width = eew as usize / 8;
+        let end = registers(emul) * hart.vector.vlenb() / width;
+
+        for i in start..end {
+            hart.vector.set_element(vd, i, width, !0);
+        }
+    }
+}
+
+/// Writes `results` to the body elements of the mask register `vd`, whose
+/// tail is always agnostic.
+fn write_mask<B, C>(
+    hart: &mut BaseHart<B, C>,
+    config: Config,
+    vd: usize,
+    results: Vec<Option<bool>>,
+) {
+    if config.vstart >= config.vl {
+        return;
+    }
+
+    let ones = hart.agnostic_mode == AgnosticMode::Ones;
+
+    for (i, result) in (config.vstart..).zip(results) {
+        match result {
+            Some(value) => hart.vector.set_mask(vd, i, value),
+            None if ones && config.mask_agnostic => hart.vector.set_mask(vd, i, true),
+            None => (),
+        }
+    }
+
+    if ones {
+        for i in config.vl..hart.vector.vlen() {
+            hart.vector.set_mask(vd, i, true);
+        }
+    }
+}
+
+/// Executes a single-width instruction, computing each element of vd from
+/// the elements of vs2, the second operand, and vd, in that order.
+fn arithmetic<B, C>(
+    hart: &mut BaseHart<B, C>,
+    raw: u32,
+    config: Config,
+    source: Source,
+    mut f: impl FnMut(u64, u64, u64) -> u64,
+) -> Option<()> {
+    let Config { sew, lmul, .. } = config;
+    let (vd, vs2) = (vd(raw), vs2(raw));
+
+    check_group(vd, lmul)?;
+    check_group(vs2, lmul)?;
+    source.check(lmul, sew, vd, sew, lmul)?;
+    check_mask_overlap(raw, vd)?;
+
+    let width = sew as usize / 8;
+    let vector = &hart.vector;
+
+    let results = body(vector, config, masked(raw), |i| {
+        f(
+            vector.element(vs2, i, width),
+            source.get(vector, i, sew),
+            vector.element(vd, i, width),
+        )
+    });
+
+    write_elements(hart, config, vd, sew, lmul, results);
+    Some(())
+}
+
+/// Executes a widening instruction, computing each element of vd, which has
+/// 2 * SEW bits, from the elements of vs2 (which has 2 * SEW bits if `wide` is
+/// set, or SEW bits otherwise), the second operand, and vd.
+fn widening<B, C>(
+    hart: &mut BaseHart<B, C>,
+    raw: u32,
+    config: Config,
+    source: Source,
+    wide: bool,
+    mut f: impl FnMut(u64, u64, u64) -> u64,
+) -> Option<()> {
+    let Config { sew, lmul, .. } = config;
+    let (vd, vs2) = (vd(raw), vs2(raw));
+    let wide_emul = config.emul(sew * 2)?;
+    let (vs2_eew, vs2_emul) = if wide {
+        (sew * 2, wide_emul)
+    } else {
+        (sew, lmul)
+    };
+
+    check_group(vd, wide_emul)?;
+    check_group(vs2, vs2_emul)?;
+    check_overlap(vd, sew * 2, wide_emul, vs2, vs2_eew, vs2_emul)?;
+    source.check(lmul, sew, vd, sew * 2, wide_emul)?;
+    check_mask_overlap(raw, vd)?;
+
+    let vector = &hart.vector;
+
+    let results = body(vector, config, masked(raw), |i| {
+        f(
+            vector.element(vs2, i, vs2_eew as usize / 8),
+            source.get(vector, i, sew),
+            vector.element(vd, i, sew as usize / 4),
+        )
+    });
+
+    write_elements(hart, config, vd, sew * 2, wide_emul, results);
+    Some(())
+}
+
+/// Executes a narrowing instruction, computing each element of vd from the
+/// elements of vs2, which have 2 * SEW bits, and the second operand.
+fn narrowing<B, C>(
+    hart: &mut BaseHart<B, C>,
+    raw: u32,
+    config: Config,
+    source: Source,
+    mut f: impl FnMut(u64, u64) -> u64,
+) -> Option<()> {
+    let Config { sew, lmul, .. } = config;
+    let (vd, vs2) = (vd(raw), vs2(raw));
+    let wide_emul = config.emul(sew * 2)?;
+
+    check_group(vd, lmul)?;
+    check_group(vs2, wide_emul)?;
+    check_overlap(vd, sew, lmul, vs2, sew * 2, wide_emul)?;
+    source.check(lmul, sew, vd, sew, lmul)?;
+    check_mask_overlap(raw, vd)?;
+
+    let vector = &hart.vector;
+
+    let results = body(vector, config, masked(raw), |i| {
+        f(
+            vector.element(vs2, i, sew as usize / 4),
+            source.get(vector, i, sew),
+        )
+    });
+
+    write_elements(hart, config, vd, sew, lmul, results);
+    Some(())
+}
+
+/// Executes a comparison, setting each bit of the mask register vd from the
+/// elements of vs2 and the second operand.
+fn compare<B, C>(
+    hart: &mut BaseHart<B, C>,
+    raw: u32,
+    config: Config,
+    source: Source,
+    mut f: impl FnMut(u64, u64) -> bool,
+) -> Option<()> {
+    let Config { sew, lmul, .. } = config;
+    let (vd, vs2) = (vd(raw), vs2(raw));
+
+    check_group(vs2, lmul)?;
+    check_overlap(vd, 1, 8, vs2, sew, lmul)?;
+    source.check(lmul, sew, vd, 1, 8)?;
+
+    let width = sew as usize / 8;
+    let vector = &hart.vector;
+
+    let results = body(vector, config, masked(raw), |i| {
+        f(vector.element(vs2, i, width), source.get(vector, i, sew))
+    });
+
+    write_mask(hart, config, vd, results);
+    Some(())
+}
+
+/// Executes a reduction, folding the active elements of vs2 into element 0
+/// of vs1 with `f`, and writing the result to element 0 of vd, which has
+/// `eew` bits like vs1.
+///
+/// Nothing is written if `vl` is zero.
+fn reduction<B, C>(
+    hart: &mut BaseHart<B, C>,
+    raw: u32,
+    config: Config,
+    eew: u32,
+    f: impl FnMut(u64, u64) -> u64,
+) -> Option<()> {
+    let Config { sew, lmul, .. } = config;
+    let vs2 = vs2(raw);
+
+    // Reductions are never interrupted, so vstart is always zero
+    check_group(vs2, lmul)?;
+    (eew <= ELEN && config.vstart == 0).then_some(())?;
+
+    let width = sew as usize / 8;
+    let vector = &hart.vector;
+    let initial = vector.element(vs1(raw), 0, eew as usize / 8);
+
+    let result = body(vector, config, masked(raw), |i| {
+        vector.element(vs2, i, width)
+    })
+    .into_iter()
+    .flatten()
+    .fold(initial, f);
+
+    write_elements(hart, config, vd(raw), eew, 8, vec![Some(result)]);
+    Some(())
+}
+
+/// Gets the `vd` field of vector instructions.
+#[inline(always)]
+const fn vd(raw: u32) -> usize {
+    rd(raw).0
+}
+
+/// Gets the `vs1` field of vector instructions.
+#[inline(always)]
+const fn vs1(raw: u32) -> usize {
+    rs1(raw).0
+}
+
+/// Gets the `vs2` field of vector instructions.
+#[inline(always)]
+const fn vs2(raw: u32) -> usize {
+    rs2(raw).0
+}
+
+/// Checks whether the `vm` field of a vector instruction is clear, so it is
+/// masked by v0.
+#[inline(always)]
+const fn masked(raw: u32) -> bool {
+    raw & 1 << 25 == 0
+}
+
+/// Gets the `simm5` field of vector instructions, sign-extended.
+#[inline(always)]
+const fn simm5(raw: u32) -> i64 {
+    ((raw as i32) << 12 >> 27) as i64
+}
+
+/// Gets a mask of the low `bits` bits.
+#[inline(always)]
+const fn low_bits(bits: u32) -> u64 {
+    u64::MAX >> (64 - bits)
+}
+
+/// Sign-extends the low `bits` bits of `value`.
+#[inline(always)]
+const fn signed(value: u64, bits: u32) -> i64 {
+    (value << (64 - bits)) as i64 >> (64 - bits)
+}
@@ -0,0 +1,364 @@
+//! Implementation of the floating-point instructions of the V extension,
+//! which are encoded as OPFVV and OPFVF.
+//!
+//! Arithmetic is supported on elements of 32 and 64 bits, and conversions
+//! also accept integers of 16 bits.
+
+use crate::{
+    instruction::float::{accrue, read, write, Register},
+    softfloat::{self, Format, RoundingMode, F32, F64},
+};
+
+use super::*;
+
+/// Executes an OPFVV or OPFVF instruction.
+pub(super) fn opf<B, C: Csr>(hart: &mut BaseHart<B, C>, raw: u32, config: Config) -> Option<()> {
+    hart.fp_enabled().then_some(())?;
+
+    // Instructions that round are reserved while frm holds a reserved
+    // rounding mode
+    let rm = match RoundingMode::from_bits(hart.fcsr >> 5 & 0b111) {
+        Some(rm) => rm,
+        None if !rounds(raw) => RoundingMode::NearestEven,
+        None => return None,
+    };
+    let mut flags = 0;
+
+    let result = match (raw >> 26, config.sew) {
+        (0b010010, _) if raw >> 12 & 0b111 == OPFVV => convert(hart, raw, config, rm, &mut flags),
+        (0b110000.., 32) => widening_format(hart, raw, config, rm, &mut flags),
+        (0b110000.., _) => None,
+        (_, 32) => single_format::<F32, B, C>(hart, raw, config, rm, &mut flags),
+        (_, 64) => single_format::<F64, B, C>(hart, raw, config, rm, &mut flags),
+        _ => None,
+    };
+
+    accrue(hart, flags);
+    result
+}
+
+/// Executes a single-width instruction on elements of the format `F`.
+fn single_format<F: Register, B, C: Csr>(
+    hart: &mut BaseHart<B, C>,
+    raw: u32,
+    config: Config,
+    rm: RoundingMode,
+    flags: &mut u8,
+) -> Option<()> {
+    let vv = raw >> 12 & 0b111 == OPFVV;
+    let scalar = read::<F, B, C>(hart, rs1(raw));
+    let source = if vv {
+        Source::Vector(vs1(raw))
+    } else {
+        Source::Scalar(scalar)
+    };
+
+    match raw >> 26 {
+        // VFADD, VFSUB, VFRSUB
+        0b000000 => arithmetic(hart, raw, config, source, |a, b, _| {
+            softfloat::add::<F>(a, b, rm, flags)
+        }),
+        0b000010 => arithmetic(hart, raw, config, source, |a, b, _| {
+            softfloat::sub::<F>(a, b, rm, flags)
+        }),
+        0b100111 if !vv => arithmetic(hart, raw, config, source, |a, b, _| {
+            softfloat::sub::<F>(b, a, rm, flags)
+        }),
+        // VFREDUSUM, VFREDOSUM, which are both computed in order
+        0b000001 | 0b000011 if vv => reduction(hart, raw, config, F::BITS, |sum, a| {
+            softfloat::add::<F>(sum, a, rm, flags)
+        }),
+        // VFMIN, VFREDMIN, VFMAX, VFREDMAX
+        0b000100 => arithmetic(hart, raw, config, source, |a, b, _| {
+            softfloat::min_max::<F>(a, b, false, flags)
+        }),
+        0b000101 if vv => reduction(hart, raw, config, F::BITS, |min, a| {
+            softfloat::min_max::<F>(min, a, false, flags)
+        }),
+        0b000110 => arithmetic(hart, raw, config, source, |a, b, _| {
+            softfloat::min_max::<F>(a, b, true, flags)
+        }),
+        0b000111 if vv => reduction(hart, raw, config, F::BITS, |max, a| {
+            softfloat::min_max::<F>(max, a, true, flags)
+        }),
+        // VFSGNJ, VFSGNJN, VFSGNJX
+        0b001000 => arithmetic(hart, raw, config, source, |a, b, _| {
+            a & !F::SIGN | b & F::SIGN
+        }),
+        0b001001 => arithmetic(hart, raw, config, source, |a, b, _| {
+            a & !F::SIGN | !b & F::SIGN
+        }),
+        0b001010 => arithmetic(hart, raw, config, source, |a, b, _| a ^ b & F::SIGN),
+        // VFSLIDE1UP, VFSLIDE1DOWN
+        0b001110 if !vv => permute::slide1_up(hart, raw, config, scalar),
+        0b001111 if !vv => permute::slide1_down(hart, raw, config, scalar),
+        // VFMV.F.S, which runs regardless of vl
+        0b010000 if vv && vs1(raw) == 0 && !masked(raw) => {
+            let value = hart.vector.element(vs2(raw), 0, F::BITS as usize / 8);
+
+            write::<F, B, C>(hart, rd(raw), value);
+            Some(())
+        }
+        // VFMV.S.F
+        0b010000 if !vv && vs2(raw) == 0 => permute::move_from_scalar(hart, raw, config, scalar),
+        // VFSQRT, VFRSQRT7, VFREC7, VFCLASS
+        0b010011 if vv => match vs1(raw) {
+            0b00000 => unary(hart, raw, config, F::BITS, F::BITS, |a| {
+                softfloat::sqrt::<F>(a, rm, flags)
+            }),
+            0b00100 => unary(hart, raw, config, F::BITS, F::BITS, |a| {
+                softfloat::reciprocal_sqrt_estimate::<F>(a, flags)
+            }),
+            0b00101 => unary(hart, raw, config, F::BITS, F::BITS, |a| {
+                softfloat::reciprocal_estimate::<F>(a, rm, flags)
+            }),
+            0b10000 => unary(
+                hart,
+                raw,
+                config,
+                F::BITS,
+                F::BITS,
+                softfloat::classify::<F>,
+            ),
+            _ => None,
+        },
+        // VFMERGE
+        0b010111 if !vv && masked(raw) => {
+            integer::carry(hart, raw, config, source, |a, b, c| match c {
+                0 => a,
+                _ => b,
+            })
+        }
+        // VFMV.V.F
+        0b010111 if !vv && vs2(raw) == 0 => arithmetic(hart, raw, config, source, |_, b, _| b),
+        // VMFEQ, VMFLE, VMFLT, VMFNE, VMFGT, VMFGE
+        0b011000 => compare(hart, raw, config, source, |a, b| {
+            softfloat::eq::<F>(a, b, flags)
+        }),
+        0b011001 => compare(hart, raw, config, source, |a, b| {
+            softfloat::le::<F>(a, b, flags)
+        }),
+        0b011011 => compare(hart, raw, config, source, |a, b| {
+            softfloat::lt::<F>(a, b, flags)
+        }),
+        0b011100 => compare(hart, raw, config, source, |a, b| {
+            !softfloat::eq::<F>(a, b, flags)
+        }),
+        0b011101 if !vv => compare(hart, raw, config, source, |a, b| {
+            softfloat::lt::<F>(b, a, flags)
+        }),
+        0b011111 if !vv => compare(hart, raw, config, source, |a, b| {
+            softfloat::le::<F>(b, a, flags)
+        }),
+        // VFDIV, VFRDIV, VFMUL
+        0b100000 => arithmetic(hart, raw, config, source, |a, b, _| {
+            softfloat::div::<F>(a, b, rm, flags)
+        }),
+        0b100001 if !vv => arithmetic(hart, raw, config, source, |a, b, _| {
+            softfloat::div::<F>(b, a, rm, flags)
+        }),
+        0b100100 => arithmetic(hart, raw, config, source, |a, b, _| {
+            softfloat::mul::<F>(a, b, rm, flags)
+        }),
+        // VFMADD, VFNMADD, VFMSUB, VFNMSUB, which overwrite a multiplicand
+        0b101000 => arithmetic(hart, raw, config, source, |a, b, d| {
+            softfloat::fma::<F>(b, d, a, rm, flags)
+        }),
+        0b101001 => arithmetic(hart, raw, config, source, |a, b, d| {
+            softfloat::fma::<F>(b ^ F::SIGN, d, a ^ F::SIGN, rm, flags)
+        }),
+        0b101010 => arithmetic(hart, raw, config, source, |a, b, d| {
+            softfloat::fma::<F>(b, d, a ^ F::SIGN, rm, flags)
+        }),
+        0b101011 => arithmetic(hart, raw, config, source, |a, b, d| {
+            softfloat::fma::<F>(b ^ F::SIGN, d, a, rm, flags)
+        }),
+        // VFMACC, VFNMACC, VFMSAC, VFNMSAC, which overwrite the addend
+        0b101100 => arithmetic(hart, raw, config, source, |a, b, d| {
+            softfloat::fma::<F>(b, a, d, rm, flags)
+        }),
+        0b101101 => arithmetic(hart, raw, config, source, |a, b, d| {
+            softfloat::fma::<F>(b ^ F::SIGN, a, d ^ F::SIGN, rm, flags)
+        }),
+        0b101110 => arithmetic(hart, raw, config, source, |a, b, d| {
+            softfloat::fma::<F>(b, a, d ^ F::SIGN, rm, flags)
+        }),
+        0b101111 => arithmetic(hart, raw, config, source, |a, b, d| {
+            softfloat::fma::<F>(b ^ F::SIGN, a, d, rm, flags)
+        }),
+        _ => None,
+    }
+}
+
+/// Executes a widening instruction on elements of 32 bits, whose results
+/// have 64 bits.
+fn widening_format<B, C: Csr>(
+    hart: &mut BaseHart<B, C>,
+    raw: u32,
+    config: Config,
+    rm: RoundingMode,
+    flags: &mut u8,
+) -> Option<()> {
+    let vv = raw >> 12 & 0b111 == OPFVV;
+    let source = if vv {
+        Source::Vector(vs1(raw))
+    } else {
+        Source::Scalar(read::<F32, B, C>(hart, rs1(raw)))
+    };
+
+    match raw >> 26 {
+        // VFWADD, VFWSUB
+        0b110000 => widening(hart, raw, config, source, false, |a, b, _| {
+            softfloat::add::<F64>(widen(a, flags), widen(b, flags), rm, flags)
+        }),
+        0b110010 => widening(hart, raw, config, source, false, |a, b, _| {
+            softfloat::sub::<F64>(widen(a, flags), widen(b, flags), rm, flags)
+        }),
+        // VFWREDUSUM, VFWREDOSUM, which are both computed in order
+        0b110001 | 0b110011 if vv => reduction(hart, raw, config, 64, |sum, a| {
+            softfloat::add::<F64>(sum, widen(a, flags), rm, flags)
+        }),
+        // VFWADD.W, VFWSUB.W
+        0b110100 => widening(hart, raw, config, source, true, |a, b, _| {
+            softfloat::add::<F64>(a, widen(b, flags), rm, flags)
+        }),
+        0b110110 => widening(hart, raw, config, source, true, |a, b, _| {
+            softfloat::sub::<F64>(a, widen(b, flags), rm, flags)
+        }),
+        // VFWMUL
+        0b111000 => widening(hart, raw, config, source, false, |a, b, _| {
+            softfloat::mul::<F64>(widen(a, flags), widen(b, flags), rm, flags)
+        }),
+        // VFWMACC, VFWNMACC, VFWMSAC, VFWNMSAC
+        0b111100 => widening(hart, raw, config, source, false, |a, b, d| {
+            softfloat::fma::<F64>(widen(b, flags), widen(a, flags), d, rm, flags)
+        }),
+        0b111101 => widening(hart, raw, config, source, false, |a, b, d| {
+            let product = widen(b, flags) ^ F64::SIGN;
+
+            softfloat::fma::<F64>(product, widen(a, flags), d ^ F64::SIGN, rm, flags)
+        }),
+        0b111110 => widening(hart, raw, config, source, false, |a, b, d| {
+            softfloat::fma::<F64>(widen(b, flags), widen(a, flags), d ^ F64::SIGN, rm, flags)
+        }),
+        0b111111 => widening(hart, raw, config, source, false, |a, b, d| {
+            softfloat::fma::<F64>(widen(b, flags) ^ F64::SIGN, widen(a, flags), d, rm, flags)
+        }),
+        _ => None,
+    }
+}
+
+/// Executes one of the conversions encoded as VFUNARY0, whose operands can
+/// have different widths to SEW.
+fn convert<B, C: Csr>(
+    hart: &mut BaseHart<B, C>,
+    raw: u32,
+    config: Config,
+    rm: RoundingMode,
+    flags: &mut u8,
+) -> Option<()> {
+    // Bit 0 of vs1 selects signed integers (or rounding to odd), and the
+    // conversions to integers with bits 2:1 set always truncate
+    let signed = vs1(raw) & 1 != 0;
+    let rm = match vs1(raw) & 0b110 {
+        0b110 => RoundingMode::TowardZero,
+        _ => rm,
+    };
+
+    match (vs1(raw) >> 1, config.sew) {
+        // VFCVT.XU.F, VFCVT.X.F, VFCVT.RTZ.XU.F, VFCVT.RTZ.X.F
+        (0b0000 | 0b0011, 32) => unary(hart, raw, config, 32, 32, |a| {
+            softfloat::to_int::<F32>(a, 32, signed, rm, flags)
+        }),
+        (0b0000 | 0b0011, 64) => unary(hart, raw, config, 64, 64, |a| {
+            softfloat::to_int::<F64>(a, 64, signed, rm, flags)
+        }),
+        // VFCVT.F.XU, VFCVT.F.X
+        (0b0001, 32) => unary(hart, raw, config, 32, 32, |a| {
+            softfloat::from_int::<F32>(a, 32, signed, rm, flags)
+        }),
+        (0b0001, 64) => unary(hart, raw, config, 64, 64, |a| {
+            softfloat::from_int::<F64>(a, 64, signed, rm, flags)
+        }),
+        // VFWCVT.XU.F, VFWCVT.X.F, VFWCVT.RTZ.XU.F, VFWCVT.RTZ.X.F
+        (0b0100 | 0b0111, 32) => unary(hart, raw, config, 32, 64, |a| {
+            softfloat::to_int::<F32>(a, 64, signed, rm, flags)
+        }),
+        // VFWCVT.F.XU, VFWCVT.F.X
+        (0b0101, 16) => unary(hart, raw, config, 16, 32, |a| {
+            softfloat::from_int::<F32>(a, 16, signed, rm, flags)
+        }),
+        (0b0101, 32) => unary(hart, raw, config, 32, 64, |a| {
+            softfloat::from_int::<F64>(a, 32, signed, rm, flags)
+        }),
+        // VFWCVT.F.F
+        (0b0110, 32) if !signed => unary(hart, raw, config, 32, 64, |a| widen(a, flags)),
+        // VFNCVT.XU.F, VFNCVT.X.F, VFNCVT.RTZ.XU.F, VFNCVT.RTZ.X.F
+        (0b1000 | 0b1011, 16) => unary(hart, raw, config, 32, 16, |a| {
+            softfloat::to_int::<F32>(a, 16, signed, rm, flags)
+        }),
+        (0b1000 | 0b1011, 32) => unary(hart, raw, config, 64, 32, |a| {
+            softfloat::to_int::<F64>(a, 32, signed, rm, flags)
+        }),
+        // VFNCVT.F.XU, VFNCVT.F.X
+        (0b1001, 32) => unary(hart, raw, config, 64, 32, |a| {
+            softfloat::from_int::<F32>(a, 64, signed, rm, flags)
+        }),
+        // VFNCVT.F.F, VFNCVT.ROD.F.F
+        (0b1010, 32) if !signed => unary(hart, raw, config, 64, 32, |a| {
+            softfloat::convert::<F64, F32>(a, rm, flags)
+        }),
+        (0b1010, 32) => unary(hart, raw, config, 64, 32, |a| {
+            softfloat::convert_round_to_odd::<F64, F32>(a, flags)
+        }),
+        _ => None,
+    }
+}
+
+/// Executes an instruction that computes each element of vd, which has
+/// `vd_eew` bits, from the element of vs2, which has `vs2_eew` bits.
+fn unary<B, C>(
+    hart: &mut BaseHart<B, C>,
+    raw: u32,
+    config: Config,
+    vs2_eew: u32,
+    vd_eew: u32,
+    mut f: impl FnMut(u64) -> u64,
+) -> Option<()> {
+    let (vd, vs2) = (vd(raw), vs2(raw));
+    let (vd_emul, vs2_emul) = (config.emul(vd_eew)?, config.emul(vs2_eew)?);
+
+    check_group(vd, vd_emul)?;
+    check_group(vs2, vs2_emul)?;
+    check_overlap(vd, vd_eew, vd_emul, vs2, vs2_eew, vs2_emul)?;
+    check_mask_overlap(raw, vd)?;
+
+    let vector = &hart.vector;
+
+    let results = body(vector, config, masked(raw), |i| {
+        f(vector.element(vs2, i, vs2_eew as usize / 8))
+    });
+
+    write_elements(hart, config, vd, vd_eew, vd_emul, results);
+    Some(())
+}
+
+/// Converts a single-precision value to double precision, which is exact.
+fn widen(a: u64, flags: &mut u8) -> u64 {
+    softfloat::convert::<F32, F64>(a, RoundingMode::NearestEven, flags)
+}
+
+/// Checks whether an instruction depends on the dynamic rounding mode.
+fn rounds(raw: u32) -> bool {
+    match raw >> 26 {
+        // Minimums, maximums, sign injection, slides, moves, merges, and
+        // comparisons
+        0b000100..=0b000111 | 0b001000..=0b001111 | 0b010000 | 0b010111 => false,
+        0b011000..=0b011111 => false,
+        // Conversions other than those that truncate or round to odd
+        0b010010 => vs1(raw) & 0b110 != 0b110 && vs1(raw) != 0b10101,
+        // VFSQRT and VFREC7, but not VFRSQRT7 or VFCLASS
+        0b010011 => matches!(vs1(raw), 0b00000 | 0b00101),
+        _ => true,
+    }
+}
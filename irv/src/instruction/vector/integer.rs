@@ -0,0 +1,538 @@
+//! Implementation of the integer and fixed-point instructions of the V
+//! extension, which are encoded as OPIVV, OPIVX, OPIVI, OPMVV, and OPMVX.
+
+use super::*;
+
+/// Executes an OPIVV, OPIVX, or OPIVI instruction.
+pub(super) fn opi<B, C: Csr>(hart: &mut BaseHart<B, C>, raw: u32, config: Config) -> Option<()> {
+    let funct3 = raw >> 12 & 0b111;
+    let (vv, vi) = (funct3 == OPIVV, funct3 == OPIVI);
+    let sew = config.sew;
+    let vxrm = hart.vector.vxrm;
+
+    // Shifts, slides, and gathers take unsigned immediates
+    let source = source(
+        hart,
+        raw,
+        !matches!(raw >> 26, 0b001100..=0b001111 | 0b100101..=0b101111),
+    );
+    // Offsets and indices are taken from all XLEN bits of x[rs1]
+    let offset = match source {
+        Source::Scalar(_) if vi => uimm(raw) as u64,
+        _ => hart.xlen.truncate(hart.gpr[rs1(raw)]),
+    };
+
+    match raw >> 26 {
+        // VADD
+        0b000000 => arithmetic(hart, raw, config, source, |a, b, _| a.wrapping_add(b)),
+        // VSUB
+        0b000010 if !vi => arithmetic(hart, raw, config, source, |a, b, _| a.wrapping_sub(b)),
+        // VRSUB
+        0b000011 if !vv => arithmetic(hart, raw, config, source, |a, b, _| b.wrapping_sub(a)),
+        // VMINU
+        0b000100 if !vi => arithmetic(hart, raw, config, source, |a, b, _| a.min(b)),
+        // VMIN
+        0b000101 if !vi => arithmetic(hart, raw, config, source, |a, b, _| {
+            if signed(a, sew) < signed(b, sew) {
+                a
+            } else {
+                b
+            }
+        }),
+        // VMAXU
+        0b000110 if !vi => arithmetic(hart, raw, config, source, |a, b, _| a.max(b)),
+        // VMAX
+        0b000111 if !vi => arithmetic(hart, raw, config, source, |a, b, _| {
+            if signed(a, sew) > signed(b, sew) {
+                a
+            } else {
+                b
+            }
+        }),
+        // VAND, VOR, VXOR
+        0b001001 => arithmetic(hart, raw, config, source, |a, b, _| a & b),
+        0b001010 => arithmetic(hart, raw, config, source, |a, b, _| a | b),
+        0b001011 => arithmetic(hart, raw, config, source, |a, b, _| a ^ b),
+        // VRGATHER
+        0b001100 if vv => permute::gather(hart, raw, config, Source::Vector(vs1(raw)), sew),
+        0b001100 => permute::gather(hart, raw, config, Source::Scalar(offset), sew),
+        // VRGATHEREI16
+        0b001110 if vv => permute::gather(hart, raw, config, Source::Vector(vs1(raw)), 16),
+        // VSLIDEUP, VSLIDEDOWN
+        0b001110 => permute::slide_up(hart, raw, config, offset),
+        0b001111 if !vv => permute::slide_down(hart, raw, config, offset),
+        // VADC
+        0b010000 if masked(raw) => carry(hart, raw, config, source, |a, b, c| {
+            a.wrapping_add(b).wrapping_add(c)
+        }),
+        // VMADC
+        0b010001 => carry_out(hart, raw, config, source, |a, b, c| a + b + c),
+        // VSBC
+        0b010010 if masked(raw) && !vi => carry(hart, raw, config, source, |a, b, c| {
+            a.wrapping_sub(b).wrapping_sub(c)
+        }),
+        // VMSBC
+        0b010011 if !vi => carry_out(hart, raw, config, source, |a, b, c| a.wrapping_sub(b + c)),
+        // VMERGE
+        0b010111 if masked(raw) => carry(hart, raw, config, source, |a, b, c| match c {
+            0 => a,
+            _ => b,
+        }),
+        // VMV.V
+        0b010111 if vs2(raw) == 0 => arithmetic(hart, raw, config, source, |_, b, _| b),
+        // VMSEQ, VMSNE, VMSLTU, VMSLT, VMSLEU, VMSLE, VMSGTU, VMSGT
+        0b011000 => compare(hart, raw, config, source, |a, b| a == b),
+        0b011001 => compare(hart, raw, config, source, |a, b| a != b),
+        0b011010 if !vi => compare(hart, raw, config, source, |a, b| a < b),
+        0b011011 if !vi => compare(hart, raw, config, source, |a, b| {
+            signed(a, sew) < signed(b, sew)
+        }),
+        0b011100 => compare(hart, raw, config, source, |a, b| a <= b),
+        0b011101 => compare(hart, raw, config, source, |a, b| {
+            signed(a, sew) <= signed(b, sew)
+        }),
+        0b011110 if !vv => compare(hart, raw, config, source, |a, b| a > b),
+        0b011111 if !vv => compare(hart, raw, config, source, |a, b| {
+            signed(a, sew) > signed(b, sew)
+        }),
+        // VSADDU, VSADD, VSSUBU, VSSUB
+        0b100000 => saturating(hart, raw, config, source, |a, b| {
+            clamp_unsigned(a as i128 + b as i128, sew)
+        }),
+        0b100001 => saturating(hart, raw, config, source, |a, b| {
+            clamp_signed(signed(a, sew) as i128 + signed(b, sew) as i128, sew)
+        }),
+        0b100010 if !vi => saturating(hart, raw, config, source, |a, b| {
+            clamp_unsigned(a as i128 - b as i128, sew)
+        }),
+        0b100011 if !vi => saturating(hart, raw, config, source, |a, b| {
+            clamp_signed(signed(a, sew) as i128 - signed(b, sew) as i128, sew)
+        }),
+        // VSLL
+        0b100101 => arithmetic(hart, raw, config, source, |a, b, _| {
+            a << (b & (sew as u64 - 1))
+        }),
+        // VSMUL
+        0b100111 if !vi => saturating(hart, raw, config, source, |a, b| {
+            let product = signed(a, sew) as i128 * signed(b, sew) as i128;
+
+            clamp_signed(shift_round(product, sew - 1, vxrm), sew)
+        }),
+        // VSRL, VSRA
+        0b101000 => arithmetic(hart, raw, config, source, |a, b, _| {
+            a >> (b & (sew as u64 - 1))
+        }),
+        0b101001 => arithmetic(hart, raw, config, source, |a, b, _| {
+            (signed(a, sew) >> (b & (sew as u64 - 1))) as u64
+        }),
+        // VSSRL, VSSRA
+        0b101010 => arithmetic(hart, raw, config, source, |a, b, _| {
+            shift_round(a as i128, (b & (sew as u64 - 1)) as u32, vxrm) as u64
+        }),
+        0b101011 => arithmetic(hart, raw, config, source, |a, b, _| {
+            shift_round(signed(a, sew) as i128, (b & (sew as u64 - 1)) as u32, vxrm) as u64
+        }),
+        // VNSRL, VNSRA
+        0b101100 => narrowing(hart, raw, config, source, |a, b| {
+            a >> (b & (sew as u64 * 2 - 1))
+        }),
+        0b101101 => narrowing(hart, raw, config, source, |a, b| {
+            (signed(a, sew * 2) >> (b & (sew as u64 * 2 - 1))) as u64
+        }),
+        // VNCLIPU, VNCLIP
+        0b101110 => narrowing_clip(hart, raw, config, source, |a, b| {
+            let shift = (b & (sew as u64 * 2 - 1)) as u32;
+
+            clamp_unsigned(shift_round(a as i128, shift, vxrm), sew)
+        }),
+        0b101111 => narrowing_clip(hart, raw, config, source, |a, b| {
+            let shift = (b & (sew as u64 * 2 - 1)) as u32;
+
+            clamp_signed(shift_round(signed(a, sew * 2) as i128, shift, vxrm), sew)
+        }),
+        // VWREDSUMU, VWREDSUM
+        0b110000 if vv => reduction(hart, raw, config, sew * 2, |sum, a| sum.wrapping_add(a)),
+        0b110001 if vv => reduction(hart, raw, config, sew * 2, |sum, a| {
+            sum.wrapping_add(signed(a, sew) as u64)
+        }),
+        _ => None,
+    }
+}
+
+/// Executes an OPMVV or OPMVX instruction.
+pub(super) fn opm<B, C: Csr>(hart: &mut BaseHart<B, C>, raw: u32, config: Config) -> Option<()> {
+    let vv = raw >> 12 & 0b111 == OPMVV;
+    let sew = config.sew;
+    let vxrm = hart.vector.vxrm;
+    let source = source(hart, raw, true);
+    let scalar = hart.gpr[rs1(raw)];
+
+    // The sign-extended values of both operands
+    let s = |a: u64| signed(a, sew) as i128;
+
+    match raw >> 26 {
+        // VREDSUM, VREDAND, VREDOR, VREDXOR
+        0b000000 if vv => reduction(hart, raw, config, sew, |sum, a| sum.wrapping_add(a)),
+        0b000001 if vv => reduction(hart, raw, config, sew, |result, a| result & a),
+        0b000010 if vv => reduction(hart, raw, config, sew, |result, a| result | a),
+        0b000011 if vv => reduction(hart, raw, config, sew, |result, a| result ^ a),
+        // VREDMINU, VREDMIN, VREDMAXU, VREDMAX
+        0b000100 if vv => reduction(hart, raw, config, sew, |min, a| min.min(a)),
+        0b000101 if vv => reduction(
+            hart,
+            raw,
+            config,
+            sew,
+            |min, a| {
+                if s(a) < s(min) {
+                    a
+                } else {
+                    min
+                }
+            },
+        ),
+        0b000110 if vv => reduction(hart, raw, config, sew, |max, a| max.max(a)),
+        0b000111 if vv => reduction(
+            hart,
+            raw,
+            config,
+            sew,
+            |max, a| {
+                if s(a) > s(max) {
+                    a
+                } else {
+                    max
+                }
+            },
+        ),
+        // VAADDU, VAADD, VASUBU, VASUB
+        0b001000 => arithmetic(hart, raw, config, source, |a, b, _| {
+            shift_round(a as i128 + b as i128, 1, vxrm) as u64
+        }),
+        0b001001 => arithmetic(hart, raw, config, source, |a, b, _| {
+            shift_round(s(a) + s(b), 1, vxrm) as u64
+        }),
+        0b001010 => arithmetic(hart, raw, config, source, |a, b, _| {
+            shift_round(a as i128 - b as i128, 1, vxrm) as u64
+        }),
+        0b001011 => arithmetic(hart, raw, config, source, |a, b, _| {
+            shift_round(s(a) - s(b), 1, vxrm) as u64
+        }),
+        // VSLIDE1UP, VSLIDE1DOWN
+        0b001110 if !vv => permute::slide1_up(hart, raw, config, scalar),
+        0b001111 if !vv => permute::slide1_down(hart, raw, config, scalar),
+        // VMV.X.S, VCPOP.M, VFIRST.M
+        0b010000 if vv && vs1(raw) == 0b00000 => permute::move_to_scalar(hart, raw, config),
+        0b010000 if vv && vs1(raw) == 0b10000 => mask::count(hart, raw, config),
+        0b010000 if vv && vs1(raw) == 0b10001 => mask::find_first(hart, raw, config),
+        // VMV.S.X
+        0b010000 if !vv && vs2(raw) == 0 => permute::move_from_scalar(hart, raw, config, scalar),
+        // VZEXT, VSEXT
+        0b010010 if vv => match vs1(raw) {
+            0b00010 => extend(hart, raw, config, 8, false),
+            0b00011 => extend(hart, raw, config, 8, true),
+            0b00100 => extend(hart, raw, config, 4, false),
+            0b00101 => extend(hart, raw, config, 4, true),
+            0b00110 => extend(hart, raw, config, 2, false),
+            0b00111 => extend(hart, raw, config, 2, true),
+            _ => None,
+        },
+        // VMSBF, VMSOF, VMSIF, VIOTA, VID
+        0b010100 if vv => match vs1(raw) {
+            0b00001 => mask::set_first(hart, raw, config, |before, first| before && !first),
+            0b00010 => mask::set_first(hart, raw, config, |_, first| first),
+            0b00011 => mask::set_first(hart, raw, config, |before, _| before),
+            0b10000 => mask::iota(hart, raw, config),
+            0b10001 if vs2(raw) == 0 => mask::index(hart, raw, config),
+            _ => None,
+        },
+        // VCOMPRESS
+        0b010111 if vv && !masked(raw) => permute::compress(hart, raw, config),
+        // VMANDN, VMAND, VMOR, VMXOR, VMORN, VMNAND, VMNOR, VMXNOR
+        0b011000 if vv => mask::logical(hart, raw, config, |a, b| a & !b),
+        0b011001 if vv => mask::logical(hart, raw, config, |a, b| a & b),
+        0b011010 if vv => mask::logical(hart, raw, config, |a, b| a | b),
+        0b011011 if vv => mask::logical(hart, raw, config, |a, b| a ^ b),
+        0b011100 if vv => mask::logical(hart, raw, config, |a, b| a | !b),
+        0b011101 if vv => mask::logical(hart, raw, config, |a, b| !(a & b)),
+        0b011110 if vv => mask::logical(hart, raw, config, |a, b| !(a | b)),
+        0b011111 if vv => mask::logical(hart, raw, config, |a, b| !(a ^ b)),
+        // VDIVU, VDIV, VREMU, VREM
+        0b100000 => arithmetic(hart, raw, config, source, |a, b, _| {
+            a.checked_div(b).unwrap_or(u64::MAX)
+        }),
+        0b100001 => arithmetic(hart, raw, config, source, |a, b, _| match b {
+            0 => u64::MAX,
+            // The overflowing division of the most negative value by -1 gives
+            // the most negative value once truncated to SEW bits
+            _ => signed(a, sew).wrapping_div(signed(b, sew)) as u64,
+        }),
+        0b100010 => arithmetic(hart, raw, config, source, |a, b, _| {
+            a.checked_rem(b).unwrap_or(a)
+        }),
+        0b100011 => arithmetic(hart, raw, config, source, |a, b, _| match b {
+            0 => a,
+            _ => signed(a, sew).wrapping_rem(signed(b, sew)) as u64,
+        }),
+        // VMULHU, VMUL, VMULHSU, VMULH
+        0b100100 => arithmetic(hart, raw, config, source, |a, b, _| {
+            ((a as u128 * b as u128) >> sew) as u64
+        }),
+        0b100101 => arithmetic(hart, raw, config, source, |a, b, _| a.wrapping_mul(b)),
+        0b100110 => arithmetic(hart, raw, config, source, |a, b, _| {
+            ((s(a) * b as i128) >> sew) as u64
+        }),
+        0b100111 => arithmetic(hart, raw, config, source, |a, b, _| {
+            ((s(a) * s(b)) >> sew) as u64
+        }),
+        // VMADD, VNMSUB, VMACC, VNMSAC
+        0b101001 => arithmetic(hart, raw, config, source, |a, b, d| {
+            b.wrapping_mul(d).wrapping_add(a)
+        }),
+        0b101011 => arithmetic(hart, raw, config, source, |a, b, d| {
+            a.wrapping_sub(b.wrapping_mul(d))
+        }),
+        0b101101 => arithmetic(hart, raw, config, source, |a, b, d| {
+            b.wrapping_mul(a).wrapping_add(d)
+        }),
+        0b101111 => arithmetic(hart, raw, config, source, |a, b, d| {
+            d.wrapping_sub(b.wrapping_mul(a))
+        }),
+        // VWADDU, VWADD, VWSUBU, VWSUB
+        0b110000 => widening(hart, raw, config, source, false, |a, b, _| {
+            a.wrapping_add(b)
+        }),
+        0b110001 => widening(hart, raw, config, source, false, |a, b, _| {
+            (s(a) + s(b)) as u64
+        }),
+        0b110010 => widening(hart, raw, config, source, false, |a, b, _| {
+            a.wrapping_sub(b)
+        }),
+        0b110011 => widening(hart, raw, config, source, false, |a, b, _| {
+            (s(a) - s(b)) as u64
+        }),
+        // VWADDU.W, VWADD.W, VWSUBU.W, VWSUB.W
+        0b110100 => widening(hart, raw, config, source, true, |a, b, _| a.wrapping_add(b)),
+        0b110101 => widening(hart, raw, config, source, true, |a, b, _| {
+            a.wrapping_add(s(b) as u64)
+        }),
+        0b110110 => widening(hart, raw, config, source, true, |a, b, _| a.wrapping_sub(b)),
+        0b110111 => widening(hart, raw, config, source, true, |a, b, _| {
+            a.wrapping_sub(s(b) as u64)
+        }),
+        // VWMULU, VWMULSU, VWMUL
+        0b111000 => widening(hart, raw, config, source, false, |a, b, _| {
+            a.wrapping_mul(b)
+        }),
+        0b111010 => widening(hart, raw, config, source, false, |a, b, _| {
+            (s(a) * b as i128) as u64
+        }),
+        0b111011 => widening(hart, raw, config, source, false, |a, b, _| {
+            (s(a) * s(b)) as u64
+        }),
+        // VWMACCU, VWMACC, VWMACCUS, VWMACCSU
+        0b111100 => widening(hart, raw, config, source, false, |a, b, d| {
+            d.wrapping_add(b.wrapping_mul(a))
+        }),
+        0b111101 => widening(hart, raw, config, source, false, |a, b, d| {
+            d.wrapping_add((s(b) * s(a)) as u64)
+        }),
+        0b111110 if !vv => widening(hart, raw, config, source, false, |a, b, d| {
+            d.wrapping_add((b as i128 * s(a)) as u64)
+        }),
+        0b111111 => widening(hart, raw, config, source, false, |a, b, d| {
+            d.wrapping_add((s(b) * a as i128) as u64)
+        }),
+        _ => None,
+    }
+}
+
+/// Executes an instruction that computes each element of vd from the
+/// elements of vs2, the second operand, and bit `i` of v0, which holds a
+/// carry, a borrow, or a selector rather than a mask.
+pub(super) fn carry<B, C>(
+    hart: &mut BaseHart<B, C>,
+    raw: u32,
+    config: Config,
+    source: Source,
+    mut f: impl FnMut(u64, u64, u64) -> u64,
+) -> Option<()> {
+    let Config { sew, lmul, .. } = config;
+    let (vd, vs2) = (vd(raw), vs2(raw));
+
+    // The destination cannot overlap v0, which is always read
+    check_group(vd, lmul)?;
+    check_group(vs2, lmul)?;
+    source.check(lmul, sew, vd, sew, lmul)?;
+    (vd != 0).then_some(())?;
+
+    let width = sew as usize / 8;
+    let vector = &hart.vector;
+
+    let results = body(vector, config, false, |i| {
+        let carry = vector.mask(0, i) as u64;
+
+        f(
+            vector.element(vs2, i, width),
+            source.get(vector, i, sew),
+            carry,
+        )
+    });
+
+    write_elements(hart, config, vd, sew, lmul, results);
+    Some(())
+}
+
+/// Executes `VMADC` or `VMSBC`, setting each bit of the mask register vd to
+/// the carry or borrow out of `f`, which is given the elements of vs2, the
+/// second operand, and a carry or borrow in from v0 if the instruction is
+/// "masked".
+fn carry_out<B, C>(
+    hart: &mut BaseHart<B, C>,
+    raw: u32,
+    config: Config,
+    source: Source,
+    mut f: impl FnMut(u128, u128, u128) -> u128,
+) -> Option<()> {
+    let Config { sew, lmul, .. } = config;
+    let (vd, vs2) = (vd(raw), vs2(raw));
+
+    check_group(vs2, lmul)?;
+    check_overlap(vd, 1, 8, vs2, sew, lmul)?;
+    source.check(lmul, sew, vd, 1, 8)?;
+
+    let width = sew as usize / 8;
+    let vector = &hart.vector;
+    let with_carry = masked(raw);
+
+    let results = body(vector, config, false, |i| {
+        let a = vector.element(vs2, i, width) as u128;
+        let b = source.get(vector, i, sew) as u128;
+        let carry = (with_carry && vector.mask(0, i)) as u128;
+
+        // Borrows wrap around to set the bits above SEW too
+        f(a, b, carry) >> sew & 1 != 0
+    });
+
+    write_mask(hart, config, vd, results);
+    Some(())
+}
+
+/// Executes a single-width fixed-point instruction whose result `f` can
+/// saturate, setting `vxsat` if any element does.
+fn saturating<B, C>(
+    hart: &mut BaseHart<B, C>,
+    raw: u32,
+    config: Config,
+    source: Source,
+    mut f: impl FnMut(u64, u64) -> (u64, bool),
+) -> Option<()> {
+    let mut saturated = false;
+
+    arithmetic(hart, raw, config, source, |a, b, _| {
+        let (result, saturation) = f(a, b);
+        saturated |= saturation;
+        result
+    })?;
+
+    hart.vector.vxsat |= saturated;
+    Some(())
+}
+
+/// Executes `VNCLIPU` or `VNCLIP`, setting `vxsat` if any element saturates.
+fn narrowing_clip<B, C>(
+    hart: &mut BaseHart<B, C>,
+    raw: u32,
+    config: Config,
+    source: Source,
+    mut f: impl FnMut(u64, u64) -> (u64, bool),
+) -> Option<()> {
+    let mut saturated = false;
+
+    narrowing(hart, raw, config, source, |a, b| {
+        let (result, saturation) = f(a, b);
+        saturated |= saturation;
+        result
+    })?;
+
+    hart.vector.vxsat |= saturated;
+    Some(())
+}
+
+/// Executes `VZEXT` or `VSEXT`, extending each element of vs2, which has SEW /
+/// `factor` bits, to SEW bits.
+fn extend<B, C>(
+    hart: &mut BaseHart<B, C>,
+    raw: u32,
+    config: Config,
+    factor: u32,
+    sign: bool,
+) -> Option<()> {
+    let Config { sew, lmul, .. } = config;
+    let (vd, vs2) = (vd(raw), vs2(raw));
+    let eew = sew / factor;
+    let emul = (eew >= 8).then(|| config.emul(eew))??;
+
+    check_group(vd, lmul)?;
+    check_group(vs2, emul)?;
+    check_overlap(vd, sew, lmul, vs2, eew, emul)?;
+    check_mask_overlap(raw, vd)?;
+
+    let vector = &hart.vector;
+
+    let results = body(vector, config, masked(raw), |i| {
+        let value = vector.element(vs2, i, eew as usize / 8);
+
+        if sign {
+            signed(value, eew) as u64
+        } else {
+            value
+        }
+    });
+
+    write_elements(hart, config, vd, sew, lmul, results);
+    Some(())
+}
+
+/// Shifts `value` right by `shift` bits, rounding the result according to
+/// the fixed-point rounding mode `vxrm`.
+fn shift_round(value: i128, shift: u32, vxrm: u64) -> i128 {
+    if shift == 0 {
+        return value;
+    }
+
+    let kept = value >> shift;
+    // The most significant bit shifted out, and whether any others were set
+    let half = value >> (shift - 1) & 1 != 0;
+    let sticky = value & ((1 << (shift - 1)) - 1) != 0;
+
+    let increment = match vxrm {
+        // Round to nearest, ties up
+        0b00 => half,
+        // Round to nearest, ties to even
+        0b01 => half && (sticky || kept & 1 != 0),
+        // Round down
+        0b10 => false,
+        // Round to odd
+        _ => kept & 1 == 0 && (half || sticky),
+    };
+
+    kept + increment as i128
+}
+
+/// Saturates `value` to an unsigned integer of `bits` bits, also returning
+/// whether it saturated.
+fn clamp_unsigned(value: i128, bits: u32) -> (u64, bool) {
+    let max = low_bits(bits) as i128;
+
+    (value.clamp(0, max) as u64, !(0..=max).contains(&value))
+}
+
+/// Saturates `value` to a signed integer of `bits` bits, also returning
+/// whether it saturated.
+fn clamp_signed(value: i128, bits: u32) -> (u64, bool) {
+    let max = (low_bits(bits) >> 1) as i128;
+    let min = -max - 1;
+
+    (value.clamp(min, max) as u64, !(min..=max).contains(&value))
+}
@@ -0,0 +1,119 @@
+//! Implementation of the mask instructions of the V extension.
+
+use super::*;
+
+/// Executes one of the mask-register logical instructions, which are never
+/// masked, setting each bit of vd to `f` of the bits of vs2 and vs1.
+pub(super) fn logical<B, C>(
+    hart: &mut BaseHart<B, C>,
+    raw: u32,
+    config: Config,
+    f: impl Fn(bool, bool) -> bool,
+) -> Option<()> {
+    let (vs1, vs2) = (vs1(raw), vs2(raw));
+
+    (!masked(raw)).then_some(())?;
+
+    let vector = &hart.vector;
+    let results = body(vector, config, false, |i| {
+        f(vector.mask(vs2, i), vector.mask(vs1, i))
+    });
+
+    write_mask(hart, config, vd(raw), results);
+    Some(())
+}
+
+/// Executes `VCPOP.M`, counting the active set bits of vs2 into `x[rd]`.
+pub(super) fn count<B, C>(hart: &mut BaseHart<B, C>, raw: u32, config: Config) -> Option<()> {
+    (config.vstart == 0).then_some(())?;
+
+    let vs2 = vs2(raw);
+    let vector = &hart.vector;
+    let bits = body(vector, config, masked(raw), |i| vector.mask(vs2, i));
+
+    hart.gpr[rd(raw)] = bits.into_iter().filter(|&bit| bit == Some(true)).count() as u64;
+    Some(())
+}
+
+/// Executes `VFIRST.M`, writing the index of the first active set bit of vs2
+/// to `x[rd]`, or -1 if there is none.
+pub(super) fn find_first<B, C>(hart: &mut BaseHart<B, C>, raw: u32, config: Config) -> Option<()> {
+    (config.vstart == 0).then_some(())?;
+
+    let vs2 = vs2(raw);
+    let vector = &hart.vector;
+    let bits = body(vector, config, masked(raw), |i| vector.mask(vs2, i));
+
+    hart.gpr[rd(raw)] = bits
+        .into_iter()
+        .position(|bit| bit == Some(true))
+        .map_or(u64::MAX, |index| index as u64);
+    Some(())
+}
+
+/// Executes `VMSBF.M`, `VMSIF.M`, or `VMSOF.M`, setting each active bit of vd
+/// to `f` of whether it comes before the first active set bit of vs2 (or is
+/// it), and whether it is that bit.
+pub(super) fn set_first<B, C>(
+    hart: &mut BaseHart<B, C>,
+    raw: u32,
+    config: Config,
+    f: impl Fn(bool, bool) -> bool,
+) -> Option<()> {
+    let (vd, vs2) = (vd(raw), vs2(raw));
+
+    (config.vstart == 0 && vd != vs2).then_some(())?;
+    check_mask_overlap(raw, vd)?;
+
+    let vector = &hart.vector;
+    let mut found = false;
+
+    let results = body(vector, config, masked(raw), |i| {
+        let first = !found && vector.mask(vs2, i);
+        let before = !found;
+        found |= first;
+
+        f(before, first)
+    });
+
+    write_mask(hart, config, vd, results);
+    Some(())
+}
+
+/// Executes `VIOTA.M`, setting each active element of vd to the number of
+/// active set bits of vs2 before it.
+pub(super) fn iota<B, C>(hart: &mut BaseHart<B, C>, raw: u32, config: Config) -> Option<()> {
+    let Config { sew, lmul, .. } = config;
+    let (vd, vs2) = (vd(raw), vs2(raw));
+
+    (config.vstart == 0).then_some(())?;
+    check_group(vd, lmul)?;
+    check_disjoint(vd, lmul, vs2, 8)?;
+    check_mask_overlap(raw, vd)?;
+
+    let vector = &hart.vector;
+    let mut count = 0;
+
+    let results = body(vector, config, masked(raw), |i| {
+        let value = count;
+        count += vector.mask(vs2, i) as u64;
+        value
+    });
+
+    write_elements(hart, config, vd, sew, lmul, results);
+    Some(())
+}
+
+/// Executes `VID.V`, setting each active element of vd to its index.
+pub(super) fn index<B, C>(hart: &mut BaseHart<B, C>, raw: u32, config: Config) -> Option<()> {
+    let Config { sew, lmul, .. } = config;
+    let vd = vd(raw);
+
+    check_group(vd, lmul)?;
+    check_mask_overlap(raw, vd)?;
+
+    let results = body(&hart.vector, config, masked(raw), |i| i as u64);
+
+    write_elements(hart, config, vd, sew, lmul, results);
+    Some(())
+}
@@ -0,0 +1,407 @@
+//! Implementation of the load and store instructions of the V extension,
+//! which are encoded in the LOAD-FP and STORE-FP major opcodes.
+//!
+//! Segment instructions access `nf` fields per element, where field `f` of
+//! each element is held in the `f`th register group after vd (or vs3). When an
+//! element faults, `vstart` is set to its index so the instruction can be
+//! resumed, and the elements before it keep the values already loaded or
+//! stored.
+
+use super::*;
+
+/// How the address of each segment of a load or store is found.
+#[derive(Clone, Copy)]
+enum Addressing {
+    /// Segments are packed together in memory, starting at `x[rs1]`.
+    UnitStride,
+    /// Segment `i` is at `x[rs1]` plus `i` times the given stride.
+    Strided(u64),
+    /// Segment `i` is at `x[rs1]` plus element `i` of the register group
+    /// starting at `register`, which has `eew`-bit elements and the
+    /// multiplier `emul`.
+    Indexed {
+        register: usize,
+        eew: u32,
+        emul: u32,
+    },
+}
+
+impl Addressing {
+    /// Gets the addressing of an indexed instruction, whose indices have
+    /// `eew` bits, returning `None` if their register group is not legal.
+    fn indexed(raw: u32, config: Config, eew: u32) -> Option<Addressing> {
+        let emul = config.emul(eew)?;
+
+        check_group(vs2(raw), emul)?;
+        Some(Addressing::Indexed {
+            register: vs2(raw),
+            eew,
+            emul,
+        })
+    }
+
+    /// Gets the address of field `field` of segment `index`, where each
+    /// segment has `fields` fields of `width` bytes.
+    fn address(
+        self,
+        vector: &VectorRegisters,
+        base: u64,
+        index: usize,
+        fields: usize,
+        field: usize,
+        width: usize,
+    ) -> u64 {
+        let offset = match self {
+            Addressing::UnitStride => (index * fields * width) as u64,
+            Addressing::Strided(stride) => (index as u64).wrapping_mul(stride),
+            Addressing::Indexed { register, eew, .. } => {
+                vector.element(register, index, eew as usize / 8)
+            }
+        };
+
+        base.wrapping_add(offset)
+            .wrapping_add((field * width) as u64)
+    }
+}
+
+/// Executes a vector load.
+pub fn load<B, C: Csr>(hart: &mut BaseHart<B, C>, raw: u32)
+where
+    B: Bus<u64, u64> + Bus<u64, u32> + Bus<u64, u16> + Bus<u64, u8>,
+    B: AtomicBus<u64, u64>,
+{
+    let Some(eew) = width(raw) else {
+        return illegal(hart, raw);
+    };
+
+    match (raw >> 26 & 0b11, rs2(raw).0) {
+        // Unit-stride
+        (0b00, 0b00000) => execute(hart, raw, |hart, raw, config| {
+            load_elements(
+                hart,
+                raw,
+                config,
+                Addressing::UnitStride,
+                eew,
+                fields(raw),
+                false,
+            )
+        }),
+        // Whole register
+        (0b00, 0b01000) => execute_unconfigured(hart, raw, |hart, raw| {
+            let config = whole_config(hart, raw, eew)?;
+
+            load_elements(hart, raw, config, Addressing::UnitStride, eew, 1, false)
+        }),
+        // VLM.V
+        (0b00, 0b01011) if eew == 8 => execute(hart, raw, |hart, raw, config| {
+            let config = mask_config(raw, config)?;
+
+            load_elements(hart, raw, config, Addressing::UnitStride, 8, 1, false)
+        }),
+        // Unit-stride fault-only-first
+        (0b00, 0b10000) => execute(hart, raw, |hart, raw, config| {
+            load_elements(
+                hart,
+                raw,
+                config,
+                Addressing::UnitStride,
+                eew,
+                fields(raw),
+                true,
+            )
+        }),
+        // Strided
+        (0b10, _) => execute(hart, raw, |hart, raw, config| {
+            let addressing = Addressing::Strided(hart.gpr[rs2(raw)]);
+
+            load_elements(hart, raw, config, addressing, eew, fields(raw), false)
+        }),
+        // Indexed, unordered and ordered, where the data elements have SEW bits
+        (0b01 | 0b11, _) => execute(hart, raw, |hart, raw, config| {
+            let addressing = Addressing::indexed(raw, config, eew)?;
+
+            load_elements(
+                hart,
+                raw,
+                config,
+                addressing,
+                config.sew,
+                fields(raw),
+                false,
+            )
+        }),
+        _ => illegal(hart, raw),
+    }
+}
+
+/// Executes a vector store.
+pub fn store<B, C: Csr>(hart: &mut BaseHart<B, C>, raw: u32)
+where
+    B: Bus<u64, u64> + Bus<u64, u32> + Bus<u64, u16> + Bus<u64, u8>,
+    B: AtomicBus<u64, u64>,
+{
+    let Some(eew) = width(raw) else {
+        return illegal(hart, raw);
+    };
+
+    match (raw >> 26 & 0b11, rs2(raw).0) {
+        // Unit-stride
+        (0b00, 0b00000) => execute(hart, raw, |hart, raw, config| {
+            store_elements(hart, raw, config, Addressing::UnitStride, eew, fields(raw))
+        }),
+        // Whole register, which is always encoded with 8-bit elements
+        (0b00, 0b01000) if eew == 8 => execute_unconfigured(hart, raw, |hart, raw| {
+            let config = whole_config(hart, raw, eew)?;
+
+            store_elements(hart, raw, config, Addressing::UnitStride, eew, 1)
+        }),
+        // VSM.V
+        (0b00, 0b01011) if eew == 8 => execute(hart, raw, |hart, raw, config| {
+            let config = mask_config(raw, config)?;
+
+            store_elements(hart, raw, config, Addressing::UnitStride, 8, 1)
+        }),
+        // Strided
+        (0b10, _) => execute(hart, raw, |hart, raw, config| {
+            let addressing = Addressing::Strided(hart.gpr[rs2(raw)]);
+
+            store_elements(hart, raw, config, addressing, eew, fields(raw))
+        }),
+        // Indexed, unordered and ordered, where the data elements have SEW bits
+        (0b01 | 0b11, _) => execute(hart, raw, |hart, raw, config| {
+            let addressing = Addressing::indexed(raw, config, eew)?;
+
+            store_elements(hart, raw, config, addressing, config.sew, fields(raw))
+        }),
+        _ => illegal(hart, raw),
+    }
+}
+
+/// Loads segments of `fields` fields with `eew`-bit elements into the
+/// register groups starting at vd.
+///
+/// If `fault_only_first` is set, an element other than element 0 that faults
+/// instead reduces `vl` to its index, without raising an exception.
+fn load_elements<B, C: Csr>(
+    hart: &mut BaseHart<B, C>,
+    raw: u32,
+    config: Config,
+    addressing: Addressing,
+    eew: u32,
+    fields: usize,
+    fault_only_first: bool,
+) -> Option<()>
+where
+    B: Bus<u64, u64> + Bus<u64, u32> + Bus<u64, u16> + Bus<u64, u8>,
+    B: AtomicBus<u64, u64>,
+{
+    let vd = vd(raw);
+    let emul = config.emul(eew)?;
+    let size = registers(emul);
+
+    check_segment(vd, emul, fields)?;
+    check_mask_overlap(raw, vd)?;
+
+    // The destination can only overlap the indices like the source of any
+    // other instruction, and segments cannot overlap them at all
+    if let Addressing::Indexed {
+        register,
+        eew: index_eew,
+        emul: index_emul,
+    } = addressing
+    {
+        match fields {
+            1 => check_overlap(vd, eew, emul, register, index_eew, index_emul)?,
+            _ => check_disjoint(vd, (fields * size) as u32 * 8, register, index_emul)?,
+        }
+    }
+
+    let base = hart.gpr[rs1(raw)];
+    let width = eew as usize / 8;
+    let mut results = vec![Vec::new(); fields];
+    let mut end = config.vl;
+    let mut fault = false;
+
+    'elements: for i in config.vstart..config.vl {
+        if masked(raw) && !hart.vector.mask(0, i) {
+            results.iter_mut().for_each(|field| field.push(None));
+            continue;
+        }
+
+        for (field, field_results) in results.iter_mut().enumerate() {
+            let address = addressing.address(&hart.vector, base, i, fields, field, width);
+
+            match load_element(hart, address, eew) {
+                Some(value) => field_results.push(Some(value)),
+                None => {
+                    end = i;
+                    fault = !fault_only_first || i == 0;
+                    break 'elements;
+                }
+            }
+        }
+    }
+
+    // Discard the fields of the segment that faulted
+    for field_results in &mut results {
+        field_results.truncate(end - config.vstart);
+    }
+
+    if fault {
+        for (field, field_results) in results.into_iter().enumerate() {
+            write_body(hart, config, vd + field * size, eew, field_results);
+        }
+
+        hart.vector.vstart = end as u64;
+    } else {
+        if end < config.vl {
+            hart.result = Ok(());
+            hart.vector.vl = end as u64;
+        }
+
+        let config = Config { vl: end, ..config };
+
+        for (field, field_results) in results.into_iter().enumerate() {
+            write_elements(hart, config, vd + field * size, eew, emul, field_results);
+        }
+    }
+
+    Some(())
+}
+
+/// Stores segments of `fields` fields with `eew`-bit elements from the
+/// register groups starting at vs3.
+fn store_elements<B, C: Csr>(
+    hart: &mut BaseHart<B, C>,
+    raw: u32,
+    config: Config,
+    addressing: Addressing,
+    eew: u32,
+    fields: usize,
+) -> Option<()>
+where
+    B: Bus<u64, u64> + Bus<u64, u32> + Bus<u64, u16> + Bus<u64, u8>,
+    B: AtomicBus<u64, u64>,
+{
+    // The register holding the data is encoded in the vd field
+    let vs3 = vd(raw);
+    let emul = config.emul(eew)?;
+    let size = registers(emul);
+
+    check_segment(vs3, emul, fields)?;
+
+    let base = hart.gpr[rs1(raw)];
+    let width = eew as usize / 8;
+
+    for i in config.vstart..config.vl {
+        if masked(raw) && !hart.vector.mask(0, i) {
+            continue;
+        }
+
+        for field in 0..fields {
+            let address = addressing.address(&hart.vector, base, i, fields, field, width);
+            let value = hart.vector.element(vs3 + field * size, i, width);
+
+            store_element(hart, address, eew, value);
+
+            if hart.result.is_err() {
+                hart.vector.vstart = i as u64;
+                return Some(());
+            }
+        }
+    }
+
+    Some(())
+}
+
+/// Gets the configuration that a whole register load or store of `nf`
+/// registers executes with, treating them as one unmasked register group
+/// with `eew`-bit elements.
+fn whole_config<B, C>(hart: &BaseHart<B, C>, raw: u32, eew: u32) -> Option<Config> {
+    let count = fields(raw);
+
+    (count.is_power_of_two() && !masked(raw)).then_some(())?;
+
+    let lmul = count as u32 * 8;
+    let vl = vlmax(hart.vector.vlen(), eew, lmul);
+
+    Some(Config {
+        sew: eew,
+        lmul,
+        vl,
+        vlmax: vl,
+        tail_agnostic: false,
+        mask_agnostic: false,
+        ..Config::new(&hart.vector)
+    })
+}
+
+/// Gets the configuration that `VLM.V` or `VSM.V` executes with, treating
+/// the mask as an unmasked register of ceil(`vl` / 8) bytes, whose tail is
+/// always agnostic.
+fn mask_config(raw: u32, config: Config) -> Option<Config> {
+    (fields(raw) == 1 && !masked(raw)).then_some(())?;
+
+    Some(Config {
+        sew: 8,
+        lmul: 8,
+        vl: config.vl.div_ceil(8),
+        tail_agnostic: true,
+        ..config
+    })
+}
+
+/// Checks that the `fields` register groups starting at `register`, each
+/// with the multiplier `emul`, are legal and fit within the register file.
+fn check_segment(register: usize, emul: u32, fields: usize) -> Option<()> {
+    let count = fields * registers(emul);
+
+    check_group(register, emul)?;
+    (count <= 8 && register + count <= 32).then_some(())
+}
+
+/// Loads an element of `eew` bits from the given virtual `address`.
+fn load_element<B, C: Csr>(hart: &mut BaseHart<B, C>, address: u64, eew: u32) -> Option<u64>
+where
+    B: Bus<u64, u64> + Bus<u64, u32> + Bus<u64, u16> + Bus<u64, u8>,
+    B: AtomicBus<u64, u64>,
+{
+    match eew {
+        8 => crate::instruction::load::<u8, B, C>(hart, address).map(u64::from),
+        16 => crate::instruction::load::<u16, B, C>(hart, address).map(u64::from),
+        32 => crate::instruction::load::<u32, B, C>(hart, address).map(u64::from),
+        _ => crate::instruction::load::<u64, B, C>(hart, address),
+    }
+}
+
+/// Stores the low `eew` bits of `value` to the given virtual `address`.
+fn store_element<B, C: Csr>(hart: &mut BaseHart<B, C>, address: u64, eew: u32, value: u64)
+where
+    B: Bus<u64, u64> + Bus<u64, u32> + Bus<u64, u16> + Bus<u64, u8>,
+    B: AtomicBus<u64, u64>,
+{
+    match eew {
+        8 => crate::instruction::store::<u8, B, C>(hart, address, value as u8),
+        16 => crate::instruction::store::<u16, B, C>(hart, address, value as u16),
+        32 => crate::instruction::store::<u32, B, C>(hart, address, value as u32),
+        _ => crate::instruction::store::<u64, B, C>(hart, address, value),
+    }
+}
+
+/// Gets the element width in bits selected by the `width` and `mew` fields,
+/// returning `None` if they do not encode a vector access.
+fn width(raw: u32) -> Option<u32> {
+    match raw >> 12 & 0b111 | raw >> 25 & 0b1000 {
+        0b0000 => Some(8),
+        0b0101 => Some(16),
+        0b0110 => Some(32),
+        0b0111 => Some(64),
+        _ => None,
+    }
+}
+
+/// Gets the number of fields in each segment, given by the `nf` field.
+fn fields(raw: u32) -> usize {
+    (raw >> 29) as usize + 1
+}
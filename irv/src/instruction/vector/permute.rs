@@ -0,0 +1,264 @@
+//! Implementation of the permutation instructions of the V extension.
+
+use super::*;
+
+/// Executes `VMV.X.S`, copying element 0 of vs2 to `x[rd]`, sign-extended,
+/// regardless of `vl`.
+pub(super) fn move_to_scalar<B, C>(
+    hart: &mut BaseHart<B, C>,
+    raw: u32,
+    config: Config,
+) -> Option<()> {
+    (!masked(raw)).then_some(())?;
+
+    let value = hart.vector.element(vs2(raw), 0, config.sew as usize / 8);
+
+    hart.gpr[rd(raw)] = signed(value, config.sew) as u64;
+    Some(())
+}
+
+/// Executes `VMV.S.X` or `VFMV.S.F`, copying `value` to element 0 of vd if
+/// `vl` is not zero.
+pub(super) fn move_from_scalar<B, C>(
+    hart: &mut BaseHart<B, C>,
+    raw: u32,
+    config: Config,
+    value: u64,
+) -> Option<()> {
+    (!masked(raw)).then_some(())?;
+
+    // Elements before vstart are not body elements, so element 0 is only
+    // written when vstart is zero
+    let results = if config.vstart == 0 {
+        vec![Some(value)]
+    } else {
+        Vec::new()
+    };
+
+    write_elements(hart, config, vd(raw), config.sew, 8, results);
+    Some(())
+}
+
+/// Executes `VSLIDEUP`, which moves each element of vs2 up by `offset`
+/// elements, leaving the elements of vd below `offset` unchanged.
+pub(super) fn slide_up<B, C>(
+    hart: &mut BaseHart<B, C>,
+    raw: u32,
+    config: Config,
+    offset: u64,
+) -> Option<()> {
+    let Config { sew, lmul, .. } = config;
+    let (vd, vs2) = (vd(raw), vs2(raw));
+
+    check_slide(raw, config, true)?;
+
+    let width = sew as usize / 8;
+    let vector = &hart.vector;
+
+    let results = body(vector, config, masked(raw), |i| {
+        match (i as u64).checked_sub(offset) {
+            Some(from) => vector.element(vs2, from as usize, width),
+            None => vector.element(vd, i, width),
+        }
+    });
+
+    write_elements(hart, config, vd, sew, lmul, results);
+    Some(())
+}
+
+/// Executes `VSLIDEDOWN`, which moves each element of vs2 down by `offset`
+/// elements, reading zero past the end of the register group.
+pub(super) fn slide_down<B, C>(
+    hart: &mut BaseHart<B, C>,
+    raw: u32,
+    config: Config,
+    offset: u64,
+) -> Option<()> {
+    let Config { sew, lmul, .. } = config;
+    let (vd, vs2) = (vd(raw), vs2(raw));
+
+    check_slide(raw, config, false)?;
+
+    let width = sew as usize / 8;
+    let vector = &hart.vector;
+
+    let results = body(vector, config, masked(raw), |i| {
+        match offset.checked_add(i as u64) {
+            Some(from) if from < config.vlmax as u64 => vector.element(vs2, from as usize, width),
+            _ => 0,
+        }
+    });
+
+    write_elements(hart, config, vd, sew, lmul, results);
+    Some(())
+}
+
+/// Executes `VSLIDE1UP` or `VFSLIDE1UP`, which move each element of vs2 up by
+/// one element, inserting `value` at element 0.
+pub(super) fn slide1_up<B, C>(
+    hart: &mut BaseHart<B, C>,
+    raw: u32,
+    config: Config,
+    value: u64,
+) -> Option<()> {
+    let Config { sew, lmul, .. } = config;
+    let (vd, vs2) = (vd(raw), vs2(raw));
+
+    check_slide(raw, config, true)?;
+
+    let width = sew as usize / 8;
+    let vector = &hart.vector;
+
+    let results = body(vector, config, masked(raw), |i| match i {
+        0 => value,
+        _ => vector.element(vs2, i - 1, width),
+    });
+
+    write_elements(hart, config, vd, sew, lmul, results);
+    Some(())
+}
+
+/// Executes `VSLIDE1DOWN` or `VFSLIDE1DOWN`, which move each element of vs2
+/// down by one element, inserting `value` at element `vl - 1`.
+pub(super) fn slide1_down<B, C>(
+    hart: &mut BaseHart<B, C>,
+    raw: u32,
+    config: Config,
+    value: u64,
+) -> Option<()> {
+    let Config { sew, lmul, .. } = config;
+    let (vd, vs2) = (vd(raw), vs2(raw));
+
+    check_slide(raw, config, false)?;
+
+    let width = sew as usize / 8;
+    let vector = &hart.vector;
+
+    let results = body(vector, config, masked(raw), |i| {
+        if i + 1 < config.vl {
+            vector.element(vs2, i + 1, width)
+        } else {
+            value
+        }
+    });
+
+    write_elements(hart, config, vd, sew, lmul, results);
+    Some(())
+}
+
+/// Checks the register groups of a slide, where the destination of a slide
+/// up cannot overlap its source.
+fn check_slide(raw: u32, config: Config, up: bool) -> Option<()> {
+    let lmul = config.lmul;
+    let (vd, vs2) = (vd(raw), vs2(raw));
+
+    check_group(vd, lmul)?;
+    check_group(vs2, lmul)?;
+    check_mask_overlap(raw, vd)?;
+
+    if up {
+        check_disjoint(vd, lmul, vs2, lmul)?;
+    }
+
+    Some(())
+}
+
+/// Executes `VRGATHER` or `VRGATHEREI16`, setting each element of vd to the
+/// element of vs2 given by the indices in `source`, which have `eew` bits if
+/// they are held in a vector.
+///
+/// Indices past the end of the register group select zero.
+pub(super) fn gather<B, C>(
+    hart: &mut BaseHart<B, C>,
+    raw: u32,
+    config: Config,
+    source: Source,
+    eew: u32,
+) -> Option<()> {
+    let Config { sew, lmul, .. } = config;
+    let (vd, vs2) = (vd(raw), vs2(raw));
+    let emul = config.emul(eew)?;
+
+    check_group(vd, lmul)?;
+    check_group(vs2, lmul)?;
+    check_disjoint(vd, lmul, vs2, lmul)?;
+    check_mask_overlap(raw, vd)?;
+
+    if let Source::Vector(vs1) = source {
+        check_group(vs1, emul)?;
+        check_disjoint(vd, lmul, vs1, emul)?;
+    }
+
+    let width = sew as usize / 8;
+    let vector = &hart.vector;
+
+    let results = body(vector, config, masked(raw), |i| {
+        let index = match source {
+            Source::Vector(vs1) => vector.element(vs1, i, eew as usize / 8),
+            // Scalar indices are not truncated to SEW
+            Source::Scalar(index) => index,
+        };
+
+        if index < config.vlmax as u64 {
+            vector.element(vs2, index as usize, width)
+        } else {
+            0
+        }
+    });
+
+    write_elements(hart, config, vd, sew, lmul, results);
+    Some(())
+}
+
+/// Executes `VCOMPRESS.VM`, packing the elements of vs2 selected by the mask
+/// in vs1 into the lowest elements of vd.
+///
+/// The elements of vd after those that are packed are tail elements.
+pub(super) fn compress<B, C>(hart: &mut BaseHart<B, C>, raw: u32, config: Config) -> Option<()> {
+    let Config { sew, lmul, .. } = config;
+    let (vd, vs1, vs2) = (vd(raw), vs1(raw), vs2(raw));
+
+    (config.vstart == 0).then_some(())?;
+    check_group(vd, lmul)?;
+    check_group(vs2, lmul)?;
+    check_disjoint(vd, lmul, vs2, lmul)?;
+    check_disjoint(vd, lmul, vs1, 8)?;
+
+    let width = sew as usize / 8;
+    let vector = &hart.vector;
+
+    let results = (0..config.vl)
+        .filter(|&i| vector.mask(vs1, i))
+        .map(|i| Some(vector.element(vs2, i, width)))
+        .collect();
+
+    write_elements(hart, config, vd, sew, lmul, results);
+    Some(())
+}
+
+/// Executes `VMV<NR>R.V`, copying the group of 1, 2, 4, or 8 registers
+/// starting at vs2 to vd.
+///
+/// The copy is made in elements of SEW bits, so `vstart` is measured in
+/// elements of SEW bits, or in bytes if `vtype.vill` is set.
+pub(super) fn move_whole<B, C>(hart: &mut BaseHart<B, C>, raw: u32) -> Option<()> {
+    let count = uimm(raw) as usize + 1;
+    let (vd, vs2) = (vd(raw), vs2(raw));
+
+    (count.is_power_of_two() && count <= 8).then_some(())?;
+    (vd & (count - 1) == 0 && vs2 & (count - 1) == 0).then_some(())?;
+
+    let width = match hart.vector.vill {
+        true => 1,
+        false => Config::new(&hart.vector).sew as usize / 8,
+    };
+    let end = count * hart.vector.vlenb() / width;
+
+    for i in hart.vector.vstart as usize..end {
+        let value = hart.vector.element(vs2, i, width);
+
+        hart.vector.set_element(vd, i, width, value);
+    }
+
+    Some(())
+}
@@ -1,13 +1,37 @@
 #![no_std]
 
-use core::num::{NonZeroU32, NonZeroU64};
+extern crate alloc;
+
+use core::{
+    mem::size_of,
+    num::{NonZeroU32, NonZeroU64},
+};
 
 pub use irv_traits::*;
 
+mod counters;
+mod csr;
+mod entropy;
 mod instruction;
 mod memory;
+mod misaligned;
+mod mmu;
+mod pma;
+mod pmp;
+mod softfloat;
+mod system_bus;
+mod vector;
 
+pub use counters::{Counters, Events};
+pub use csr::MachineCsrs;
+pub use entropy::Entropy;
 pub use memory::Memory;
+pub use pma::{AmoClass, Attributes, Pma};
+pub use pmp::Pmp;
+pub use system_bus::{MapError, ReadOnly, SystemBus};
+pub use vector::VectorRegisters;
+
+use mmu::{Access, Tlb};
 
 /// Exceptions that can be encountered during the execution of an instruction
 /// by a [BaseHart].
@@ -24,21 +48,317 @@ pub enum Exception {
     LoadAccessFault { address: Option<NonZeroU64> },
     StoreAmoAddressMisaligned { address: Option<NonZeroU64> },
     StoreAmoAccessFault { address: Option<NonZeroU64> },
-    EnvironmentCall,
+    EnvironmentCall { privilege: Privilege },
+    InstructionPageFault { address: Option<NonZeroU64> },
+    LoadPageFault { address: Option<NonZeroU64> },
+    StoreAmoPageFault { address: Option<NonZeroU64> },
+}
+
+impl Exception {
+    /// Gets the exception code written to `mcause` when this exception is
+    /// taken as a trap.
+    pub const fn code(&self) -> u64 {
+        match self {
+            Exception::InstructionAddressMisaligned { .. } => 0,
+            Exception::InstructionAccessFault { .. } => 1,
+            Exception::IllegalInstruction { .. } => 2,
+            Exception::Breakpoint { .. } => 3,
+            Exception::LoadAddressMisaligned { .. } => 4,
+            Exception::LoadAccessFault { .. } => 5,
+            Exception::StoreAmoAddressMisaligned { .. } => 6,
+            Exception::StoreAmoAccessFault { .. } => 7,
+            Exception::EnvironmentCall { privilege } => 8 + *privilege as u64,
+            Exception::InstructionPageFault { .. } => 12,
+            Exception::LoadPageFault { .. } => 13,
+            Exception::StoreAmoPageFault { .. } => 15,
+        }
+    }
+
+    /// Gets the exception-specific information written to `mtval` when this
+    /// exception is taken as a trap, which is the faulting address or
+    /// instruction, or zero if there is none.
+    pub fn value(&self) -> u64 {
+        match self {
+            Exception::InstructionAddressMisaligned { address }
+            | Exception::InstructionAccessFault { address }
+            | Exception::Breakpoint { address }
+            | Exception::LoadAddressMisaligned { address }
+            | Exception::LoadAccessFault { address }
+            | Exception::StoreAmoAddressMisaligned { address }
+            | Exception::StoreAmoAccessFault { address }
+            | Exception::InstructionPageFault { address }
+            | Exception::LoadPageFault { address }
+            | Exception::StoreAmoPageFault { address } => address.map_or(0, NonZeroU64::get),
+            Exception::IllegalInstruction { instruction } => {
+                instruction.map_or(0, |instruction| instruction.get() as u64)
+            }
+            Exception::EnvironmentCall { .. } => 0,
+        }
+    }
+}
+
+/// How a [BaseHart] handles exceptions raised during execution.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TrapMode {
+    /// Exceptions are only returned to the host, which is responsible for
+    /// handling them. Execution continues at the next instruction.
+    #[default]
+    Return,
+    /// Exceptions are taken as traps as described by the privileged
+    /// specification, updating `mepc`, `mcause`, `mtval`, and `mstatus` and
+    /// continuing execution at the handler in `mtvec`, or the supervisor-level
+    /// equivalents if the exception is delegated by `medeleg`.
+    ///
+    /// Exceptions are still returned to the host after the trap has been
+    /// taken, so it can observe them. If the trap vector cannot be accessed,
+    /// exceptions are handled as in [TrapMode::Return].
+    Take,
+}
+
+/// How a [BaseHart] handles misaligned loads and stores that cannot be made
+/// as a single access, because they cross a page boundary or are to a region
+/// whose [Attributes] do not support them.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MisalignedMode {
+    /// Address-misaligned exceptions are raised, so software can emulate the
+    /// access.
+    #[default]
+    Trap,
+    /// Misaligned loads and stores are split into two aligned accesses, as
+    /// firmware would emulate them. Faults are reported at the address of the
+    /// first byte of the access within the part that faults, and a store whose
+    /// second part faults does not change memory.
+    ///
+    /// Only accesses to idempotent regions are split; others still raise
+    /// address-misaligned exceptions. `LR`, `SC`, and AMOs are never split.
+    Emulate,
+}
+
+/// How a [BaseHart] updates the elements of a vector register group that
+/// `vtype` marks as agnostic: the tail elements past `vl` when `vta` is set,
+/// and the elements disabled by the mask when `vma` is set.
+///
+/// Mask registers written by vector instructions always have an agnostic tail.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AgnosticMode {
+    /// Agnostic elements keep their previous values, as if they were
+    /// undisturbed.
+    #[default]
+    Undisturbed,
+    /// Agnostic elements are overwritten with all ones, which helps to catch
+    /// software that depends on their values.
+    Ones,
+}
+
+/// The width of the integer registers and addresses of a [BaseHart], which is
+/// given by the `MXL` field of `misa`.
+///
+/// RV32 values are held in the 64-bit registers sign-extended, so most
+/// instructions act the same for both widths.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Xlen {
+    /// 32-bit registers and addresses.
+    Rv32,
+    /// 64-bit registers and addresses.
+    #[default]
+    Rv64,
+}
+
+impl Xlen {
+    /// Gets the XLEN given by the `MXL` field of the given value of `misa`,
+    /// which is RV64 unless it is a 32-bit `misa` with `MXL` = 1.
+    pub const fn from_misa(misa: u64) -> Xlen {
+        if misa >> 30 == 1 {
+            Xlen::Rv32
+        } else {
+            Xlen::Rv64
+        }
+    }
+
+    /// Gets the number of bits in each register.
+    pub const fn bits(self) -> u32 {
+        match self {
+            Xlen::Rv32 => 32,
+            Xlen::Rv64 => 64,
+        }
+    }
+
+    /// Truncates `value` to XLEN bits, zero-extending the result.
+    pub(crate) const fn truncate(self, value: u64) -> u64 {
+        match self {
+            Xlen::Rv32 => value as u32 as u64,
+            Xlen::Rv64 => value,
+        }
+    }
+}
+
+/// The optional extensions implemented by a [BaseHart], each of which can be
+/// enabled or disabled at runtime.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Extensions {
+    /// The Zba extension, which adds instructions that accelerate address
+    /// generation.
+    pub zba: bool,
+    /// The Zbb extension, which adds basic bit-manipulation instructions.
+    pub zbb: bool,
+    /// The Zbc extension, which adds carry-less multiplication.
+    pub zbc: bool,
+    /// The Zbs extension, which adds single-bit instructions.
+    pub zbs: bool,
+    /// The Zbkb extension, which adds the bit-manipulation instructions used
+    /// by cryptography.
+    pub zbkb: bool,
+    /// The Zbkc extension, which adds the carry-less multiplication used by
+    /// cryptography.
+    pub zbkc: bool,
+    /// The Zbkx extension, which adds crossbar permutations.
+    pub zbkx: bool,
+    /// The Zknd extension, which adds AES decryption.
+    pub zknd: bool,
+    /// The Zkne extension, which adds AES encryption.
+    pub zkne: bool,
+    /// The Zknh extension, which adds the SHA-256 and SHA-512 hash functions.
+    pub zknh: bool,
+    /// The Zksed extension, which adds the SM4 block cipher.
+    pub zksed: bool,
+    /// The Zksh extension, which adds the SM3 hash function.
+    pub zksh: bool,
+    /// The Zkr extension, which adds the `seed` CSR to read entropy from
+    /// [BaseHart::entropy].
+    pub zkr: bool,
+    /// The V extension, which adds vector instructions acting on
+    /// [BaseHart::vector].
+    pub v: bool,
+    /// The Zicond extension, which adds the conditional-zero instructions
+    /// `CZERO.EQZ` and `CZERO.NEZ`.
+    pub zicond: bool,
+    /// The Zihintntl extension, which adds hints that the memory accesses of
+    /// the next instruction have no temporal locality, which are passed to
+    /// the bus as [MemoryAccess::locality].
+    pub zihintntl: bool,
+    /// The Zihintpause extension, which adds the `PAUSE` hint (see
+    /// [BaseHart::is_paused]).
+    pub zihintpause: bool,
+    /// The Zimop extension, which adds the may-be-operations `MOP.R.n` and
+    /// `MOP.RR.n`, which write zero to rd until they are redefined.
+    pub zimop: bool,
+    /// The Zcmop extension, which adds the compressed may-be-operations
+    /// `C.MOP.n`, which do nothing until they are redefined.
+    pub zcmop: bool,
+}
+
+impl Default for Extensions {
+    /// Enables all extensions.
+    fn default() -> Extensions {
+        Extensions {
+            zba: true,
+            zbb: true,
+            zbc: true,
+            zbs: true,
+            zbkb: true,
+            zbkc: true,
+            zbkx: true,
+            zknd: true,
+            zkne: true,
+            zknh: true,
+            zksed: true,
+            zksh: true,
+            zkr: true,
+            v: true,
+            zicond: true,
+            zihintntl: true,
+            zihintpause: true,
+            zimop: true,
+            zcmop: true,
+        }
+    }
 }
 
-/// A simple implementation of a processor that implements only machine mode.
+/// A simple implementation of a processor that implements machine mode, and
+/// optionally supervisor mode with virtual memory and user mode.
+///
+/// Interrupts are raised by the bus through [Interrupts], and are taken
+/// between instructions when they are enabled.
+///
+/// Supervisor mode is only available if `csr` implements the supervisor-level
+/// CSRs, as [MachineCsrs] does when the S extension is advertised. Similarly,
+/// user mode is only available if `mstatus.MPP` can hold user mode.
+///
+/// The hart is RV32 or RV64 as given by `misa.MXL` (see [Xlen]), or RV64 if
+/// `csr` does not implement `misa`. If `misa` advertises the E base ISA, the
+/// hart is RV32E or RV64E, and instructions that refer to x16 through x31 are
+/// illegal. Compressed instructions are supported, and instruction addresses
+/// only need to be aligned to two bytes, while `misa` advertises the C
+/// extension (or if `csr` does not implement `misa`).
 pub struct BaseHart<B, C> {
     /// The system bus for reading and writing physical memory addresses.
     pub bus: B,
+    /// The ID of this hart, which identifies its reservations on the bus.
+    pub id: u64,
+    /// The current privilege level.
+    pub privilege: Privilege,
     /// The address of the currently-executing instruction, if one is being executed.
     pub pc: u64,
     /// The address of the next instruction.
     pub next: u64,
     /// The general-purpose registers x0 through x31.
+    ///
+    /// In RV32, registers hold their values sign-extended to 64 bits.
     pub gpr: [u64; 32],
+    /// The floating-point registers f0 through f31.
+    ///
+    /// Single-precision values are NaN-boxed in the lower 32 bits.
+    pub fpr: [u64; 32],
+    /// The floating-point control and status register, containing `frm` in
+    /// bits 7:5 and `fflags` in bits 4:0.
+    pub fcsr: u32,
     /// The state of all control and status registers.
     pub csr: C,
+    /// The counters of cycles, retired instructions, and the events counted
+    /// by the hardware performance-monitoring counters.
+    pub counters: Counters,
+    /// The physical memory protection unit, which holds the state of the
+    /// `pmpcfg` and `pmpaddr` CSRs.
+    pub pmp: Pmp,
+    /// The physical memory attributes of the platform, which each physical
+    /// access is checked against.
+    pub pma: Pma,
+    /// The entropy source that is read through the `seed` CSR.
+    pub entropy: Entropy,
+    /// The vector registers v0 through v31 and the state of the vector CSRs.
+    pub vector: VectorRegisters,
+    /// The optional extensions that are currently enabled.
+    pub extensions: Extensions,
+    /// How exceptions are handled.
+    pub trap_mode: TrapMode,
+    /// How misaligned loads and stores are handled.
+    pub misaligned_mode: MisalignedMode,
+    /// How agnostic elements of vector registers are updated.
+    pub agnostic_mode: AgnosticMode,
+    /// The XLEN given by `misa` before the current instruction.
+    xlen: Xlen,
+    /// Whether `misa` advertised the E base ISA before the current
+    /// instruction, so only x0 through x15 exist.
+    embedded: bool,
+    /// Whether `misa` advertised the C extension before the current
+    /// instruction, so instructions can be compressed.
+    compressed: bool,
+    /// The translation lookaside buffer of the memory management unit.
+    tlb: Tlb,
+    /// The interrupt lines that were raised when they were last checked.
+    lines: u64,
+    /// Whether the hart is stalled by `WFI` until an interrupt is pending.
+    waiting: bool,
+    /// Whether the last instruction executed was `PAUSE`.
+    paused: bool,
+    /// The locality given by an `NTL` hint for the memory accesses of the
+    /// next instruction.
+    hinted_locality: Locality,
+    /// The locality of the memory accesses of the current instruction.
+    locality: Locality,
+    /// The events that have happened while executing the current
+    /// instruction, which are only counted if it retires.
+    events: Events,
     /// The result that will be returned after the current instruction finishes.
     result: Result<(), Exception>,
 }
@@ -48,10 +368,33 @@ impl<B, C> BaseHart<B, C> {
     pub fn new(bus: B, csr: C) -> BaseHart<B, C> {
         BaseHart {
             bus,
+            id: 0,
+            privilege: Privilege::Machine,
             pc: 0,
             next: 0,
             gpr: [0; 32],
+            fpr: [0; 32],
+            fcsr: 0,
             csr,
+            counters: Counters::new(),
+            pmp: Pmp::default(),
+            pma: Pma::default(),
+            entropy: Entropy::default(),
+            vector: VectorRegisters::default(),
+            extensions: Extensions::default(),
+            trap_mode: TrapMode::default(),
+            misaligned_mode: MisalignedMode::default(),
+            agnostic_mode: AgnosticMode::default(),
+            xlen: Xlen::default(),
+            embedded: false,
+            compressed: true,
+            tlb: Tlb::new(),
+            lines: 0,
+            waiting: false,
+            paused: false,
+            hinted_locality: Locality::default(),
+            locality: Locality::default(),
+            events: Events::NONE,
             result: Ok(()),
         }
     }
@@ -61,26 +404,215 @@ impl<B, C> BaseHart<B, C> {
     ///
     /// The address may have any alignment; only PC offsets are checked for
     /// alignment.
+    ///
+    /// Any exception raised by the instruction is returned, after being taken
+    /// as a trap if required by [BaseHart::trap_mode].
+    ///
+    /// Before the instruction is executed, the highest-priority interrupt that
+    /// is pending and enabled is taken as a trap, regardless of
+    /// [BaseHart::trap_mode]. If the hart is waiting for an interrupt, nothing
+    /// is executed until one is pending (see [BaseHart::is_waiting]).
+    ///
+    /// Each call is one cycle, which is counted by [BaseHart::counters] along
+    /// with the instruction if it retires.
     pub fn execute(&mut self) -> Result<(), Exception>
     where
         B: Bus<u64, u64> + Bus<u64, u32> + Bus<u64, u16> + Bus<u64, u8>,
+        B: AtomicBus<u64, u64> + AtomicBus<u64, u32> + Interrupts,
         C: Csr,
     {
-        // Fetch
-        let raw: u32 = match self.bus.load(self.pc) {
-            Ok(raw) => raw,
-            Err(BusError::AccessFault) => {
-                return Err(Exception::InstructionAccessFault {
-                    address: NonZeroU64::new(self.pc),
-                })
+        (self.xlen, self.embedded, self.compressed) =
+            match self.csr.access(CsrAddress::MISA, |misa| misa) {
+                Ok(misa) => (
+                    Xlen::from_misa(misa),
+                    misa & 1 << (b'e' - b'a') != 0,
+                    misa & 1 << (b'c' - b'a') != 0,
+                ),
+                Err(CsrIllegal) => (Xlen::Rv64, false, true),
+            };
+
+        let pending = self.update_interrupts();
+        let mut events = Events::CYCLES;
+
+        if self.waiting {
+            if pending == 0 {
+                self.counters.count(events);
+                return Ok(());
             }
-            Err(BusError::AddressMisaligned) => {
-                return Err(Exception::InstructionAddressMisaligned {
-                    address: NonZeroU64::new(self.pc),
-                })
+
+            self.waiting = false;
+        }
+
+        if let Some(interrupt) = self.enabled_interrupt() {
+            self.take_trap(self.pc, 1 << 63 | interrupt as u64, 0);
+            events |= Events::INTERRUPTS;
+        }
+
+        let pc = self.pc;
+
+        self.events = Events::NONE;
+        let result = self.step();
+
+        // Instructions that raise exceptions do not retire, so none of their
+        // other events are counted
+        events |= match result {
+            Ok(()) => Events::INSTRUCTIONS | self.events,
+            Err(_) => Events::EXCEPTIONS,
+        };
+
+        self.counters.count(events);
+
+        if let (TrapMode::Take, Err(exception)) = (self.trap_mode, &result) {
+            self.take_trap(pc, exception.code(), exception.value());
+        }
+
+        result
+    }
+
+    /// Gets the XLEN that the last instruction was executed with.
+    pub fn xlen(&self) -> Xlen {
+        self.xlen
+    }
+
+    /// Checks whether the last instruction was executed as RV32E or RV64E, in
+    /// which only x0 through x15 exist.
+    pub fn is_embedded(&self) -> bool {
+        self.embedded
+    }
+
+    /// Checks whether the hart is stalled by `WFI`, in which case
+    /// [BaseHart::execute] does nothing until an interrupt is pending.
+    ///
+    /// A run loop can use this to sleep until an interrupt is raised.
+    pub fn is_waiting(&self) -> bool {
+        self.waiting
+    }
+
+    /// Checks whether the last instruction executed was `PAUSE`, which
+    /// software uses to hint that it is spinning while it waits for another
+    /// hart.
+    ///
+    /// A run loop can use this to yield the host thread (such as with
+    /// `std::thread::yield_now`), so that other harts can make progress.
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Updates `mip` with any changes to the interrupt lines raised by the
+    /// bus, returning the interrupts that are both pending and enabled in
+    /// `mie`.
+    ///
+    /// Raising or lowering a line sets or clears its bit of `mip`, so bits
+    /// that software can also write keep the last value written until the line
+    /// changes.
+    fn update_interrupts(&mut self) -> u64
+    where
+        B: Interrupts,
+        C: Csr,
+    {
+        let lines = self.bus.pending(self.id) & (MACHINE_INTERRUPTS | SUPERVISOR_INTERRUPTS);
+        let changed = lines ^ self.lines;
+
+        // Triggered interrupts act as if their lines had just been raised, but
+        // only supervisor-level ones, since software cannot clear the others
+        let triggered = self.bus.triggered(self.id) & SUPERVISOR_INTERRUPTS;
+
+        if changed != 0 || triggered != 0 {
+            self.lines = lines;
+
+            let _ = self.csr.access(CsrAddress::MIP, |mip| {
+                mip & !changed | lines & changed | triggered
+            });
+        }
+
+        let mip = self.csr.access(CsrAddress::MIP, |mip| mip).unwrap_or(0);
+        let mie = self.csr.access(CsrAddress::MIE, |mie| mie).unwrap_or(0);
+
+        mip & mie
+    }
+
+    /// Gets the highest-priority interrupt that is pending and enabled, taking
+    /// into account the current privilege level and the global
+    /// interrupt-enable bits of `mstatus`.
+    ///
+    /// Interrupts delegated by `mideleg` are only enabled below machine mode,
+    /// and interrupts taken into machine mode have priority over them.
+    fn enabled_interrupt(&mut self) -> Option<Interrupt>
+    where
+        C: Csr,
+    {
+        let mip = self.csr.access(CsrAddress::MIP, |mip| mip).unwrap_or(0);
+        let mie = self.csr.access(CsrAddress::MIE, |mie| mie).unwrap_or(0);
+        let mideleg = self.csr.access(CsrAddress::MIDELEG, |m| m).unwrap_or(0);
+        let mstatus = self.csr.access(CsrAddress::MSTATUS, |m| m).unwrap_or(0);
+
+        let pending = mip & mie;
+
+        // Interrupts for a higher privilege level are always enabled, and
+        // those for the current privilege level depend on its enable bit
+        let machine = match self.privilege {
+            Privilege::Machine => mstatus & MSTATUS_MIE != 0,
+            _ => true,
+        };
+
+        let supervisor = match self.privilege {
+            Privilege::Machine => false,
+            Privilege::Supervisor => mstatus & MSTATUS_SIE != 0,
+            Privilege::User => true,
+        };
+
+        let enabled = if machine && pending & !mideleg != 0 {
+            pending & !mideleg
+        } else if supervisor {
+            pending & mideleg
+        } else {
+            0
+        };
+
+        Interrupt::PRIORITY
+            .into_iter()
+            .find(|interrupt| enabled & interrupt.bit() != 0)
+    }
+
+    /// Executes one instruction without taking any exception it raises.
+    fn step(&mut self) -> Result<(), Exception>
+    where
+        B: Bus<u64, u64> + Bus<u64, u32> + Bus<u64, u16> + Bus<u64, u8>,
+        B: AtomicBus<u64, u64> + AtomicBus<u64, u32>,
+        C: Csr,
+    {
+        // Fetch
+        let (raw, compressed) = if self.compressed {
+            // Fetch in 16-bit parcels, since a 32-bit instruction is only
+            // guaranteed to be aligned to two bytes
+            let low: u16 = self.fetch(self.pc)?;
+
+            if low & 0b11 == 0b11 {
+                let high: u16 = self.fetch(self.xlen.truncate(self.pc.wrapping_add(2)))?;
+
+                (low as u32 | (high as u32) << 16, None)
+            } else {
+                let raw = match instruction::compressed::expand(low, self.xlen) {
+                    Some(raw) => raw,
+                    // C.MOP.n is encoded as C.LUI with an odd rd below x16
+                    // and a reserved immediate of zero, and writes nothing
+                    None if self.extensions.zcmop && low & 0xF8FF == 0x6081 => NOP,
+                    // Other reserved encodings expand to zero, which is
+                    // always illegal
+                    None => 0,
+                };
+
+                (raw, Some(low))
             }
+        } else {
+            (self.fetch(self.pc)?, None)
         };
 
+        self.paused = false;
+
+        // An NTL hint only applies to the instruction that follows it
+        self.locality = core::mem::take(&mut self.hinted_locality);
+
         // Decode the part that will be matched on
         let funct3_opcode = raw & 0b1111111 | raw >> 5 & 0b111 << 7;
 
@@ -88,11 +620,214 @@ impl<B, C> BaseHart<B, C> {
         self.gpr[0] = 0;
 
         // Calculate the address of the next instruction.
-        self.next = self.pc.wrapping_add(4);
+        self.next = if compressed.is_some() {
+            self.xlen.truncate(self.pc.wrapping_add(2))
+        } else {
+            self.xlen.truncate(self.pc.wrapping_add(4))
+        };
+
+        let rv32 = self.xlen == Xlen::Rv32;
+        let embedded = self.embedded;
+        let Extensions {
+            zba,
+            zbb,
+            zbc,
+            zbs,
+            zbkb,
+            zbkc,
+            zbkx,
+            zknd,
+            zkne,
+            zknh,
+            zksed,
+            zksh,
+            v,
+            zicond,
+            zihintntl,
+            zihintpause,
+            zimop,
+            ..
+        } = self.extensions;
 
         // Match on the opcode (and funct3) to decode the rest of the
         // instruction and execute it
         let instruction = match funct3_opcode {
+            // Only x0 through x15 exist in RV32E and RV64E
+            _ if embedded && instruction::uses_upper_registers(raw) => instruction::illegal,
+            // RV32 has no LD, LWU, SD, or doubleword AMOs, and no word
+            // instructions, since every instruction acts on words
+            0b011_0000011 | 0b110_0000011 | 0b011_0100011 | 0b011_0101111 | 0b000_0011011
+            | 0b001_0011011 | 0b101_0011011 | 0b000_0111011 | 0b001_0111011 | 0b100_0111011
+            | 0b101_0111011 | 0b110_0111011 | 0b111_0111011
+                if rv32 =>
+            {
+                instruction::illegal
+            }
+            // Shift amounts of 32 or more are reserved in RV32 and for the
+            // word shifts of RV64
+            0b001_0010011 | 0b101_0010011 if rv32 && raw & 1 << 25 != 0 => instruction::illegal,
+            // Zba
+            0b010_0110011 | 0b100_0110011 | 0b110_0110011 if zba && raw >> 25 == 0b0010000 => {
+                instruction::bitmanip::shadd
+            }
+            0b000_0111011 if zba && raw >> 25 == 0b0000100 => instruction::bitmanip::add_uw,
+            0b010_0111011 | 0b100_0111011 | 0b110_0111011 if zba && raw >> 25 == 0b0010000 => {
+                instruction::bitmanip::shadd_uw
+            }
+            0b001_0011011 if zba && raw >> 26 == 0b000010 => instruction::bitmanip::slli_uw,
+            // Zbb (which shares some instructions with Zbkb)
+            0b111_0110011 if (zbb || zbkb) && raw >> 25 == 0b0100000 => instruction::bitmanip::andn,
+            0b110_0110011 if (zbb || zbkb) && raw >> 25 == 0b0100000 => instruction::bitmanip::orn,
+            0b100_0110011 if (zbb || zbkb) && raw >> 25 == 0b0100000 => instruction::bitmanip::xnor,
+            0b001_0010011 if zbb && raw >> 20 == 0x600 => instruction::bitmanip::clz,
+            0b001_0010011 if zbb && raw >> 20 == 0x601 => instruction::bitmanip::ctz,
+            0b001_0010011 if zbb && raw >> 20 == 0x602 => instruction::bitmanip::cpop,
+            0b001_0010011 if zbb && raw >> 20 == 0x604 => instruction::bitmanip::sext_b,
+            0b001_0010011 if zbb && raw >> 20 == 0x605 => instruction::bitmanip::sext_h,
+            0b001_0011011 if zbb && raw >> 20 == 0x600 => instruction::bitmanip::clzw,
+            0b001_0011011 if zbb && raw >> 20 == 0x601 => instruction::bitmanip::ctzw,
+            0b001_0011011 if zbb && raw >> 20 == 0x602 => instruction::bitmanip::cpopw,
+            0b110_0110011 if zbb && raw >> 25 == 0b0000101 => instruction::bitmanip::max,
+            0b111_0110011 if zbb && raw >> 25 == 0b0000101 => instruction::bitmanip::maxu,
+            0b100_0110011 if zbb && raw >> 25 == 0b0000101 => instruction::bitmanip::min,
+            0b101_0110011 if zbb && raw >> 25 == 0b0000101 => instruction::bitmanip::minu,
+            // ZEXT.H is a special case of PACK in RV32 and PACKW in RV64
+            0b100_0110011 if zbb && rv32 && raw >> 20 == 0x080 => instruction::bitmanip::zext_h,
+            0b100_0111011 if zbb && raw >> 20 == 0x080 => instruction::bitmanip::zext_h,
+            0b001_0110011 if (zbb || zbkb) && raw >> 25 == 0b0110000 => instruction::bitmanip::rol,
+            0b101_0110011 if (zbb || zbkb) && raw >> 25 == 0b0110000 => instruction::bitmanip::ror,
+            0b001_0111011 if (zbb || zbkb) && raw >> 25 == 0b0110000 => instruction::bitmanip::rolw,
+            0b101_0111011 if (zbb || zbkb) && raw >> 25 == 0b0110000 => instruction::bitmanip::rorw,
+            0b101_0010011 if (zbb || zbkb) && raw >> 26 == 0b011000 => instruction::bitmanip::rori,
+            0b101_0011011 if (zbb || zbkb) && raw >> 25 == 0b0110000 => {
+                instruction::bitmanip::roriw
+            }
+            0b101_0010011 if zbb && raw >> 20 == 0x287 => instruction::bitmanip::orc_b,
+            0b101_0010011 if (zbb || zbkb) && rv32 && raw >> 20 == 0x698 => {
+                instruction::bitmanip::rev8
+            }
+            0b101_0010011 if (zbb || zbkb) && !rv32 && raw >> 20 == 0x6B8 => {
+                instruction::bitmanip::rev8
+            }
+            // Zbc (of which Zbkc is a subset)
+            0b001_0110011 if (zbc || zbkc) && raw >> 25 == 0b0000101 => {
+                instruction::bitmanip::clmul
+            }
+            0b010_0110011 if zbc && raw >> 25 == 0b0000101 => instruction::bitmanip::clmulr,
+            0b011_0110011 if (zbc || zbkc) && raw >> 25 == 0b0000101 => {
+                instruction::bitmanip::clmulh
+            }
+            // Zbs
+            0b001_0110011 if zbs && raw >> 25 == 0b0100100 => instruction::bitmanip::bclr,
+            0b101_0110011 if zbs && raw >> 25 == 0b0100100 => instruction::bitmanip::bext,
+            0b001_0110011 if zbs && raw >> 25 == 0b0110100 => instruction::bitmanip::binv,
+            0b001_0110011 if zbs && raw >> 25 == 0b0010100 => instruction::bitmanip::bset,
+            0b001_0010011 if zbs && raw >> 26 == 0b010010 => instruction::bitmanip::bclri,
+            0b101_0010011 if zbs && raw >> 26 == 0b010010 => instruction::bitmanip::bexti,
+            0b001_0010011 if zbs && raw >> 26 == 0b011010 => instruction::bitmanip::binvi,
+            0b001_0010011 if zbs && raw >> 26 == 0b001010 => instruction::bitmanip::bseti,
+            // Zbkb
+            0b100_0110011 if zbkb && raw >> 25 == 0b0000100 => instruction::crypto::pack,
+            0b111_0110011 if zbkb && raw >> 25 == 0b0000100 => instruction::crypto::packh,
+            0b100_0111011 if zbkb && raw >> 25 == 0b0000100 => instruction::crypto::packw,
+            0b101_0010011 if zbkb && raw >> 20 == 0x687 => instruction::crypto::brev8,
+            0b001_0010011 if zbkb && rv32 && raw >> 20 == 0x08F => instruction::crypto::zip,
+            0b101_0010011 if zbkb && rv32 && raw >> 20 == 0x08F => instruction::crypto::unzip,
+            // Zbkx
+            0b010_0110011 if zbkx && raw >> 25 == 0b0010100 => instruction::crypto::xperm4,
+            0b100_0110011 if zbkx && raw >> 25 == 0b0010100 => instruction::crypto::xperm8,
+            // Zknd and Zkne, where the AES32 instructions select a byte with
+            // bits 31:30
+            0b000_0110011 if zknd && rv32 && raw >> 25 & 0b11111 == 0b10101 => {
+                instruction::crypto::aes32dsi
+            }
+            0b000_0110011 if zknd && rv32 && raw >> 25 & 0b11111 == 0b10111 => {
+                instruction::crypto::aes32dsmi
+            }
+            0b000_0110011 if zkne && rv32 && raw >> 25 & 0b11111 == 0b10001 => {
+                instruction::crypto::aes32esi
+            }
+            0b000_0110011 if zkne && rv32 && raw >> 25 & 0b11111 == 0b10011 => {
+                instruction::crypto::aes32esmi
+            }
+            0b000_0110011 if zknd && !rv32 && raw >> 25 == 0b0011101 => {
+                instruction::crypto::aes64ds
+            }
+            0b000_0110011 if zknd && !rv32 && raw >> 25 == 0b0011111 => {
+                instruction::crypto::aes64dsm
+            }
+            0b000_0110011 if zkne && !rv32 && raw >> 25 == 0b0011001 => {
+                instruction::crypto::aes64es
+            }
+            0b000_0110011 if zkne && !rv32 && raw >> 25 == 0b0011011 => {
+                instruction::crypto::aes64esm
+            }
+            0b001_0010011 if zknd && !rv32 && raw >> 20 == 0x300 => instruction::crypto::aes64im,
+            0b001_0010011 if (zknd || zkne) && !rv32 && raw >> 24 == 0b00110001 => {
+                instruction::crypto::aes64ks1i
+            }
+            0b000_0110011 if (zknd || zkne) && !rv32 && raw >> 25 == 0b0111111 => {
+                instruction::crypto::aes64ks2
+            }
+            // Zknh
+            0b001_0010011 if zknh && raw >> 20 == 0x100 => instruction::crypto::sha256sum0,
+            0b001_0010011 if zknh && raw >> 20 == 0x101 => instruction::crypto::sha256sum1,
+            0b001_0010011 if zknh && raw >> 20 == 0x102 => instruction::crypto::sha256sig0,
+            0b001_0010011 if zknh && raw >> 20 == 0x103 => instruction::crypto::sha256sig1,
+            0b001_0010011 if zknh && !rv32 && raw >> 20 == 0x104 => instruction::crypto::sha512sum0,
+            0b001_0010011 if zknh && !rv32 && raw >> 20 == 0x105 => instruction::crypto::sha512sum1,
+            0b001_0010011 if zknh && !rv32 && raw >> 20 == 0x106 => instruction::crypto::sha512sig0,
+            0b001_0010011 if zknh && !rv32 && raw >> 20 == 0x107 => instruction::crypto::sha512sig1,
+            0b000_0110011 if zknh && rv32 && raw >> 25 == 0b0101000 => {
+                instruction::crypto::sha512sum0r
+            }
+            0b000_0110011 if zknh && rv32 && raw >> 25 == 0b0101001 => {
+                instruction::crypto::sha512sum1r
+            }
+            0b000_0110011 if zknh && rv32 && raw >> 25 == 0b0101010 => {
+                instruction::crypto::sha512sig0l
+            }
+            0b000_0110011 if zknh && rv32 && raw >> 25 == 0b0101011 => {
+                instruction::crypto::sha512sig1l
+            }
+            0b000_0110011 if zknh && rv32 && raw >> 25 == 0b0101110 => {
+                instruction::crypto::sha512sig0h
+            }
+            0b000_0110011 if zknh && rv32 && raw >> 25 == 0b0101111 => {
+                instruction::crypto::sha512sig1h
+            }
+            // Zksed, where the instructions select a byte with bits 31:30
+            0b000_0110011 if zksed && raw >> 25 & 0b11111 == 0b11000 => instruction::crypto::sm4ed,
+            0b000_0110011 if zksed && raw >> 25 & 0b11111 == 0b11010 => instruction::crypto::sm4ks,
+            // Zksh
+            0b001_0010011 if zksh && raw >> 20 == 0x108 => instruction::crypto::sm3p0,
+            0b001_0010011 if zksh && raw >> 20 == 0x109 => instruction::crypto::sm3p1,
+            // Zicond
+            0b101_0110011 if zicond && raw >> 25 == 0b0000111 => instruction::czero_eqz,
+            0b111_0110011 if zicond && raw >> 25 == 0b0000111 => instruction::czero_nez,
+            // Zihintntl, whose hints are ADDs to x0 from x2 through x5
+            0b000_0110011
+                if zihintntl
+                    && matches!(raw, 0x00200033 | 0x00300033 | 0x00400033 | 0x00500033) =>
+            {
+                instruction::ntl
+            }
+            // Zihintpause, whose hint is a FENCE with only W as a predecessor
+            0b000_0001111 if zihintpause && raw == 0x0100000F => instruction::pause,
+            // Zimop
+            0b100_1110011 if zimop && raw & 0xB3C0707F == 0x81C04073 => instruction::mop,
+            0b100_1110011 if zimop && raw & 0xB200707F == 0x82004073 => instruction::mop,
+            0b001_0011011 | 0b101_0011011 if raw & 1 << 25 != 0 => instruction::illegal,
+            // RV32 shifts, divisions, and remainders act like the word
+            // instructions of RV64
+            0b001_0010011 if rv32 && raw >> 25 == 0 => instruction::slliw,
+            0b101_0010011 if rv32 && raw >> 25 & !0b0100000 == 0 => instruction::srxiw,
+            0b100_0110011 if rv32 && raw >> 25 == 0b0000001 => instruction::divw,
+            0b101_0110011 if rv32 && raw >> 25 == 0b0000001 => instruction::divuw,
+            0b110_0110011 if rv32 && raw >> 25 == 0b0000001 => instruction::remw,
+            0b111_0110011 if rv32 && raw >> 25 == 0b0000001 => instruction::remuw,
+            0b001_0110011 if rv32 && raw >> 25 == 0 => instruction::sllw,
+            0b101_0110011 if rv32 && raw >> 25 & !0b0100000 == 0 => instruction::srxw,
             0b000_0110111 | 0b001_0110111 | 0b010_0110111 | 0b011_0110111 | 0b100_0110111
             | 0b101_0110111 | 0b110_0110111 | 0b111_0110111 => instruction::lui,
             0b000_0010111 | 0b001_0010111 | 0b010_0010111 | 0b011_0010111 | 0b100_0010111
@@ -124,23 +859,69 @@ impl<B, C> BaseHart<B, C> {
             0b100_0010011 => instruction::xori,
             0b110_0010011 => instruction::ori,
             0b111_0010011 => instruction::andi,
-            0b001_0010011 => instruction::slli,
-            0b101_0010011 => instruction::srxi,
-            0b001_0011011 => instruction::slliw,
-            0b101_0011011 => instruction::srxiw,
-            0b000_0110011 => instruction::add_sub,
-            0b000_0111011 => instruction::addw_subw,
-            0b001_0110011 => instruction::sll,
-            0b010_0110011 => instruction::slt,
-            0b011_0110011 => instruction::sltu,
-            0b100_0110011 => instruction::xor,
-            0b101_0110011 => instruction::srx,
-            0b110_0110011 => instruction::or,
-            0b111_0110011 => instruction::and,
-            0b001_0111011 => instruction::sllw,
-            0b101_0111011 => instruction::srxw,
+            0b001_0010011 if raw >> 26 == 0 => instruction::slli,
+            0b101_0010011 if raw >> 26 & !0b010000 == 0 => instruction::srxi,
+            0b001_0011011 if raw >> 25 == 0 => instruction::slliw,
+            0b101_0011011 if raw >> 25 & !0b0100000 == 0 => instruction::srxiw,
+            0b000_0110011 if raw >> 25 == 0b0000001 => instruction::mul,
+            0b001_0110011 if raw >> 25 == 0b0000001 => instruction::mulh,
+            0b010_0110011 if raw >> 25 == 0b0000001 => instruction::mulhsu,
+            0b011_0110011 if raw >> 25 == 0b0000001 => instruction::mulhu,
+            0b100_0110011 if raw >> 25 == 0b0000001 => instruction::div,
+            0b101_0110011 if raw >> 25 == 0b0000001 => instruction::divu,
+            0b110_0110011 if raw >> 25 == 0b0000001 => instruction::rem,
+            0b111_0110011 if raw >> 25 == 0b0000001 => instruction::remu,
+            0b000_0111011 if raw >> 25 == 0b0000001 => instruction::mulw,
+            0b100_0111011 if raw >> 25 == 0b0000001 => instruction::divw,
+            0b101_0111011 if raw >> 25 == 0b0000001 => instruction::divuw,
+            0b110_0111011 if raw >> 25 == 0b0000001 => instruction::remw,
+            0b111_0111011 if raw >> 25 == 0b0000001 => instruction::remuw,
+            0b000_0110011 if raw >> 25 & !0b0100000 == 0 => instruction::add_sub,
+            0b000_0111011 if raw >> 25 & !0b0100000 == 0 => instruction::addw_subw,
+            0b001_0110011 if raw >> 25 == 0 => instruction::sll,
+            0b010_0110011 if raw >> 25 == 0 => instruction::slt,
+            0b011_0110011 if raw >> 25 == 0 => instruction::sltu,
+            0b100_0110011 if raw >> 25 == 0 => instruction::xor,
+            0b101_0110011 if raw >> 25 & !0b0100000 == 0 => instruction::srx,
+            0b110_0110011 if raw >> 25 == 0 => instruction::or,
+            0b111_0110011 if raw >> 25 == 0 => instruction::and,
+            0b001_0111011 if raw >> 25 == 0 => instruction::sllw,
+            0b101_0111011 if raw >> 25 & !0b0100000 == 0 => instruction::srxw,
+            0b010_0101111 => instruction::amo_w,
+            0b011_0101111 => instruction::amo_d,
+            0b010_0000111 => instruction::float::flw,
+            0b011_0000111 => instruction::float::fld,
+            0b010_0100111 => instruction::float::fsw,
+            0b011_0100111 => instruction::float::fsd,
+            0b000_1000011 | 0b001_1000011 | 0b010_1000011 | 0b011_1000011 | 0b100_1000011
+            | 0b101_1000011 | 0b110_1000011 | 0b111_1000011 => instruction::float::fmadd,
+            0b000_1000111 | 0b001_1000111 | 0b010_1000111 | 0b011_1000111 | 0b100_1000111
+            | 0b101_1000111 | 0b110_1000111 | 0b111_1000111 => instruction::float::fmsub,
+            0b000_1001011 | 0b001_1001011 | 0b010_1001011 | 0b011_1001011 | 0b100_1001011
+            | 0b101_1001011 | 0b110_1001011 | 0b111_1001011 => instruction::float::fnmsub,
+            0b000_1001111 | 0b001_1001111 | 0b010_1001111 | 0b011_1001111 | 0b100_1001111
+            | 0b101_1001111 | 0b110_1001111 | 0b111_1001111 => instruction::float::fnmadd,
+            0b000_1010011 | 0b001_1010011 | 0b010_1010011 | 0b011_1010011 | 0b100_1010011
+            | 0b101_1010011 | 0b110_1010011 | 0b111_1010011 => instruction::float::op_fp,
+            0b000_0000111 | 0b101_0000111 | 0b110_0000111 | 0b111_0000111 if v => {
+                instruction::vector::memory::load
+            }
+            0b000_0100111 | 0b101_0100111 | 0b110_0100111 | 0b111_0100111 if v => {
+                instruction::vector::memory::store
+            }
+            0b111_1010111 if v => instruction::vector::vset,
+            0b000_1010111 | 0b001_1010111 | 0b010_1010111 | 0b011_1010111 | 0b100_1010111
+            | 0b101_1010111 | 0b110_1010111
+                if v =>
+            {
+                instruction::vector::op_v
+            }
             0b000_0001111 => instruction::fence,
             0b001_0001111 => instruction::fence_i,
+            0b000_1110011 if raw == 0x30200073 => instruction::mret,
+            0b000_1110011 if raw == 0x10200073 => instruction::sret,
+            0b000_1110011 if raw == 0x10500073 => instruction::wfi,
+            0b000_1110011 if raw & 0xFE007FFF == 0x12000073 => instruction::sfence_vma,
             0b000_1110011 => instruction::ecall_ebreak,
             0b001_1110011 => instruction::csrrw,
             0b010_1110011 => instruction::csrrs,
@@ -148,15 +929,28 @@ impl<B, C> BaseHart<B, C> {
             0b101_1110011 => instruction::csrrwi,
             0b110_1110011 => instruction::csrrsi,
             0b111_1110011 => instruction::csrrci,
-            _ => |hart: &mut BaseHart<B, C>, raw| {
-                hart.raise(Exception::IllegalInstruction {
-                    instruction: NonZeroU32::new(raw),
-                })
-            },
+            _ => instruction::illegal,
         };
 
         instruction(self, raw);
 
+        // RV32 values are kept sign-extended. The only x register that an
+        // instruction can write is rd, and instructions that write a floating-
+        // point or vector register instead leave x[rd] already sign-extended
+        if rv32 {
+            let rd = (raw >> 7 & 0b11111) as usize;
+
+            self.gpr[rd] = self.gpr[rd] as i32 as u64;
+        }
+
+        // Report illegal compressed instructions as they were encoded rather
+        // than as their expansion
+        if let (Some(parcel), Err(Exception::IllegalInstruction { instruction })) =
+            (compressed, &mut self.result)
+        {
+            *instruction = NonZeroU32::new(parcel as u32);
+        }
+
         self.pc = self.next;
 
         let mut result = Ok(());
@@ -164,8 +958,401 @@ impl<B, C> BaseHart<B, C> {
         result
     }
 
+    /// Fetches a parcel of an instruction from the given virtual `address`.
+    fn fetch<T>(&mut self, address: u64) -> Result<T, Exception>
+    where
+        B: Bus<u64, T> + AtomicBus<u64, u64>,
+        C: Csr,
+    {
+        let (physical, privilege) =
+            self.translate(address, size_of::<T>() as u64, Access::Fetch)?;
+
+        let access = MemoryAccess::new(AccessKind::Fetch, privilege);
+
+        self.bus.load_with(physical, access).map_err(|e| match e {
+            BusError::AccessFault => Exception::InstructionAccessFault {
+                address: NonZeroU64::new(address),
+            },
+            BusError::AddressMisaligned => Exception::InstructionAddressMisaligned {
+                address: NonZeroU64::new(address),
+            },
+        })
+    }
+
+    /// Gets the mask of the bits of an instruction address that must be zero
+    /// for it to be aligned.
+    const fn instruction_alignment_mask(&self) -> u64 {
+        if self.compressed {
+            0b1
+        } else {
+            0b11
+        }
+    }
+
+    /// Takes a trap for the instruction at `pc`, with the given `cause` and
+    /// `tval`.
+    ///
+    /// The trap is taken into supervisor mode if it is delegated, or into
+    /// machine mode otherwise. Nothing is done if the trap vector cannot be
+    /// accessed.
+    fn take_trap(&mut self, pc: u64, cause: u64, tval: u64)
+    where
+        C: Csr,
+    {
+        let interrupt = cause >> 63 != 0;
+        let code = cause & !(1 << 63);
+
+        // The interrupt bit is the most significant bit of an XLEN-bit cause
+        let cause = (interrupt as u64) << (self.xlen.bits() - 1) | code;
+
+        let delegation = if interrupt {
+            CsrAddress::MIDELEG
+        } else {
+            CsrAddress::MEDELEG
+        };
+
+        // Traps are never delegated to a lower privilege level
+        let delegated = self.privilege != Privilege::Machine
+            && matches!(self.csr.access(delegation, |d| d), Ok(d) if d >> code & 1 != 0);
+
+        let (tvec, epc, cause_address, tval_address) = if delegated {
+            (
+                CsrAddress::STVEC,
+                CsrAddress::SEPC,
+                CsrAddress::SCAUSE,
+                CsrAddress::STVAL,
+            )
+        } else {
+            (
+                CsrAddress::MTVEC,
+                CsrAddress::MEPC,
+                CsrAddress::MCAUSE,
+                CsrAddress::MTVAL,
+            )
+        };
+
+        let Ok(tvec) = self.csr.access(tvec, |tvec| tvec) else {
+            return;
+        };
+
+        let _ = self.csr.access(epc, |_| pc);
+        let _ = self.csr.access(cause_address, |_| cause);
+        let _ = self.csr.access(tval_address, |_| tval);
+
+        // Save the interrupt-enable bit and disable interrupts, remembering
+        // the privilege level the trap was taken from
+        let privilege = self.privilege as u64;
+
+        let _ = self.csr.access(CsrAddress::MSTATUS, |mstatus| {
+            if delegated {
+                let sie = mstatus >> 1 & 1;
+
+                mstatus & !(MSTATUS_SIE | MSTATUS_SPIE | MSTATUS_SPP) | sie << 5 | privilege << 8
+            } else {
+                let mie = mstatus >> 3 & 1;
+
+                mstatus & !(MSTATUS_MIE | MSTATUS_MPIE | MSTATUS_MPP) | mie << 7 | privilege << 11
+            }
+        });
+
+        self.privilege = if delegated {
+            Privilege::Supervisor
+        } else {
+            Privilege::Machine
+        };
+
+        let base = tvec & !0b11;
+
+        // Only interrupts are vectored; exceptions always go to the base
+        let handler = if tvec & 0b11 == 1 && interrupt {
+            base.wrapping_add(code << 2)
+        } else {
+            base
+        };
+
+        self.pc = handler;
+        self.next = handler;
+
+        // NTL hints do not carry over into trap handlers
+        self.hinted_locality = Locality::default();
+    }
+
+    /// Gets the attributes of a load, store, or AMO of the given kind made by
+    /// the current instruction at the given `privilege` level.
+    fn data_access(&self, kind: AccessKind, privilege: Privilege) -> MemoryAccess {
+        MemoryAccess {
+            locality: self.locality,
+            ..MemoryAccess::new(kind, privilege)
+        }
+    }
+
     /// Sets up state for the given exception to be raised after execution is finished.
     fn raise(&mut self, exception: Exception) {
         self.result = Err(exception);
     }
+
+    /// Accesses the CSR at the given `address` on behalf of a CSR instruction,
+    /// which will write to it if `write` is set.
+    ///
+    /// CSRs holding state that belongs to the hart itself (such as `fcsr`,
+    /// the PMP CSRs, and the counters) are handled here, and all others are
+    /// forwarded to `csr`.
+    ///
+    /// In RV32, only the low 32 bits of each CSR are accessed, and the high
+    /// 32 bits of `mstatus`, `mseccfg`, and the counters are accessed through
+    /// `mstatush`, `mseccfgh`, and CSRs such as `mcycleh` and `cycleh`.
+    fn access_csr(
+        &mut self,
+        address: CsrAddress,
+        write: bool,
+        f: impl FnOnce(u64) -> u64,
+    ) -> Result<u64, CsrIllegal>
+    where
+        C: Csr,
+    {
+        self.csr.check(address, self.privilege, write)?;
+
+        if address == CsrAddress::SEED {
+            return self.access_seed(write);
+        }
+
+        if self.xlen == Xlen::Rv64 {
+            return self.access_csr_bits(address, write, f);
+        }
+
+        let (address, shift) = match address {
+            CsrAddress::MSTATUSH => (CsrAddress::MSTATUS, 32),
+            CsrAddress::MSECCFGH => (CsrAddress::MSECCFG, 32),
+            // The high halves of the counters, such as mcycleh and cycleh, are
+            // 0x80 above their low halves
+            _ if matches!(address.address(), 0xB80..=0xB9F | 0xC80..=0xC9F) => {
+                (CsrAddress::new(address.address() - 0x80).unwrap(), 32)
+            }
+            _ => (address, 0),
+        };
+
+        let mask = 0xFFFF_FFFF << shift;
+
+        let value = self.access_csr_bits(address, write, |value| {
+            value & !mask | f((value & mask) >> shift) << shift & mask
+        })?;
+
+        Ok((value & mask) >> shift)
+    }
+
+    /// Accesses all bits of the CSR at the given `address`, without checking
+    /// whether it can be accessed.
+    fn access_csr_bits(
+        &mut self,
+        address: CsrAddress,
+        write: bool,
+        f: impl FnOnce(u64) -> u64,
+    ) -> Result<u64, CsrIllegal>
+    where
+        C: Csr,
+    {
+        match address.address() {
+            // pmpcfg0 through pmpcfg15
+            index @ 0x3A0..=0x3AF => {
+                return self.pmp.access_cfg(index as usize - 0x3A0, self.xlen, f)
+            }
+            // pmpaddr0 through pmpaddr63
+            index @ 0x3B0..=0x3EF => return self.pmp.access_addr(index as usize - 0x3B0, f),
+            // mcountinhibit, mhpmevent3 through mhpmevent31, and the counters
+            0x320 | 0x323..=0x33F | 0xB00..=0xB1F | 0xC00..=0xC1F => {
+                return self.counters.access_csr(address, write, f)
+            }
+            _ => (),
+        }
+
+        let (shift, mask) = match address {
+            // The machine-level interrupt-pending bits are only changed by
+            // the interrupt lines
+            CsrAddress::MIP => {
+                return self.csr.access(address, |mip| {
+                    f(mip) & !MACHINE_INTERRUPTS | mip & MACHINE_INTERRUPTS
+                })
+            }
+            CsrAddress::VSTART
+            | CsrAddress::VXSAT
+            | CsrAddress::VXRM
+            | CsrAddress::VCSR
+            | CsrAddress::VL
+            | CsrAddress::VTYPE
+            | CsrAddress::VLENB => return self.access_vector_csr(address, f),
+            CsrAddress::FFLAGS => (0, 0b11111),
+            CsrAddress::FRM => (5, 0b111),
+            CsrAddress::FCSR => (0, 0b11111111),
+            _ => return self.csr.access(address, f),
+        };
+
+        if !self.fp_enabled() {
+            return Err(CsrIllegal);
+        }
+
+        let value = self.fcsr >> shift & mask;
+        let new_value = f(value as u64) as u32 & mask;
+
+        if new_value != value {
+            self.fcsr = self.fcsr & !(mask << shift) | new_value << shift;
+            self.fp_dirty();
+        }
+
+        Ok(value as u64)
+    }
+
+    /// Accesses one of the vector CSRs, which only exist when the V extension
+    /// is enabled and can only be accessed while `mstatus.VS` is not off.
+    fn access_vector_csr(
+        &mut self,
+        address: CsrAddress,
+        f: impl FnOnce(u64) -> u64,
+    ) -> Result<u64, CsrIllegal>
+    where
+        C: Csr,
+    {
+        if !self.extensions.v || !self.vector_enabled() {
+            return Err(CsrIllegal);
+        }
+
+        let mut changed = false;
+
+        let value = self.vector.access_csr(address, self.xlen, |value| {
+            let new_value = f(value);
+            changed = new_value != value;
+            new_value
+        })?;
+
+        if changed {
+            self.vector_dirty();
+        }
+
+        Ok(value)
+    }
+
+    /// Polls the entropy source through the `seed` CSR, ignoring the value
+    /// written to it.
+    ///
+    /// `seed` can only be accessed by instructions that write to it, and
+    /// below machine mode only when `mseccfg` grants access to the current
+    /// privilege level. If `csr` does not implement `mseccfg`, only machine
+    /// mode can access it.
+    fn access_seed(&mut self, write: bool) -> Result<u64, CsrIllegal>
+    where
+        C: Csr,
+    {
+        let mseccfg = self
+            .csr
+            .access(CsrAddress::MSECCFG, |mseccfg| mseccfg)
+            .unwrap_or(0);
+
+        let granted = match self.privilege {
+            Privilege::Machine => true,
+            Privilege::Supervisor => mseccfg & MSECCFG_SSEED != 0,
+            Privilege::User => mseccfg & MSECCFG_USEED != 0,
+        };
+
+        if !self.extensions.zkr || !write || !granted {
+            return Err(CsrIllegal);
+        }
+
+        // The entropy source never fails, so it is always ready with 16 bits
+        Ok(SEED_ES16 | self.entropy.poll() as u64)
+    }
+
+    /// Checks whether floating-point instructions are enabled by `mstatus.FS`.
+    ///
+    /// If `csr` does not implement `mstatus`, they are always enabled.
+    fn fp_enabled(&mut self) -> bool
+    where
+        C: Csr,
+    {
+        match self.csr.access(CsrAddress::MSTATUS, |mstatus| mstatus) {
+            Ok(mstatus) => mstatus & MSTATUS_FS != 0,
+            Err(CsrIllegal) => true,
+        }
+    }
+
+    /// Marks the floating-point state as dirty in `mstatus.FS`.
+    fn fp_dirty(&mut self)
+    where
+        C: Csr,
+    {
+        let _ = self
+            .csr
+            .access(CsrAddress::MSTATUS, |mstatus| mstatus | MSTATUS_FS);
+    }
+
+    /// Checks whether vector instructions are enabled by `mstatus.VS`.
+    ///
+    /// If `csr` does not implement `mstatus`, they are always enabled.
+    fn vector_enabled(&mut self) -> bool
+    where
+        C: Csr,
+    {
+        match self.csr.access(CsrAddress::MSTATUS, |mstatus| mstatus) {
+            Ok(mstatus) => mstatus & MSTATUS_VS != 0,
+            Err(CsrIllegal) => true,
+        }
+    }
+
+    /// Marks the vector state as dirty in `mstatus.VS`.
+    fn vector_dirty(&mut self)
+    where
+        C: Csr,
+    {
+        let _ = self
+            .csr
+            .access(CsrAddress::MSTATUS, |mstatus| mstatus | MSTATUS_VS);
+    }
 }
+
+/// `ADDI x0, x0, 0`, the canonical encoding of `NOP`.
+const NOP: u32 = 0x00000013;
+
+/// The machine-level interrupt bits of `mie` and `mip`.
+const MACHINE_INTERRUPTS: u64 = 1 << 3 | 1 << 7 | 1 << 11;
+/// The supervisor-level interrupt bits of `mie` and `mip`.
+const SUPERVISOR_INTERRUPTS: u64 = 1 << 1 | 1 << 5 | 1 << 9;
+
+/// The `SIE` field of `mstatus`.
+const MSTATUS_SIE: u64 = 1 << 1;
+/// The `MIE` field of `mstatus`.
+const MSTATUS_MIE: u64 = 1 << 3;
+/// The `SPIE` field of `mstatus`.
+const MSTATUS_SPIE: u64 = 1 << 5;
+/// The `MPIE` field of `mstatus`.
+const MSTATUS_MPIE: u64 = 1 << 7;
+/// The `SPP` field of `mstatus`.
+const MSTATUS_SPP: u64 = 1 << 8;
+/// The `MPP` field of `mstatus`.
+const MSTATUS_MPP: u64 = 0b11 << 11;
+/// The `VS` field of `mstatus`.
+const MSTATUS_VS: u64 = 0b11 << 9;
+/// The `FS` field of `mstatus`.
+const MSTATUS_FS: u64 = 0b11 << 13;
+/// The `MPRV` field of `mstatus`.
+const MSTATUS_MPRV: u64 = 1 << 17;
+/// The `SUM` field of `mstatus`.
+const MSTATUS_SUM: u64 = 1 << 18;
+/// The `MXR` field of `mstatus`.
+const MSTATUS_MXR: u64 = 1 << 19;
+/// The `TVM` field of `mstatus`.
+const MSTATUS_TVM: u64 = 1 << 20;
+/// The `TW` field of `mstatus`.
+const MSTATUS_TW: u64 = 1 << 21;
+/// The `TSR` field of `mstatus`.
+const MSTATUS_TSR: u64 = 1 << 22;
+/// The `UXL` field of `mstatus`.
+const MSTATUS_UXL: u64 = 0b11 << 32;
+/// The `SD` field of `mstatus`.
+const MSTATUS_SD: u64 = 1 << 63;
+
+/// The `USEED` field of `mseccfg`.
+const MSECCFG_USEED: u64 = 1 << 8;
+/// The `SSEED` field of `mseccfg`.
+const MSECCFG_SSEED: u64 = 1 << 9;
+
+/// The `ES16` state of the `OPST` field of `seed`, in which its low 16 bits
+/// hold entropy.
+const SEED_ES16: u64 = 0b10 << 30;
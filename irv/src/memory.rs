@@ -1,16 +1,29 @@
 use core::{
-    mem::{align_of, size_of},
+    cell::Cell,
+    mem::{size_of, size_of_val},
     ops::{Deref, DerefMut},
     pin::Pin,
 };
 
-use crate::{Bus, BusError};
+use crate::{AtomicBus, Bus, BusError, Interrupts};
+
+/// The size in bytes of each reservation set tracked by a [Memory].
+const RESERVATION_GRANULE: usize = 8;
 
 /// An efficient implementation of main memory.
+///
+/// Misaligned accesses are supported, though harts only make them to regions
+/// whose [Attributes](crate::Attributes) allow it.
+///
+/// Only a single reservation is tracked at a time, so a load-reserved by one
+/// hart invalidates any reservation held by another hart.
 pub struct Memory<T> {
     data: Pin<T>,
     data_ptr: *mut u8,
     data_len: usize,
+    /// The hart holding the current reservation and the address of its
+    /// reservation set, if there is a reservation.
+    reservation: Cell<Option<(u64, usize)>>,
 }
 
 impl_bus! {
@@ -20,6 +33,18 @@ impl_bus! {
     u64 u64,
 }
 
+impl_atomic_bus! {
+    u64 u32,
+    u64 u64,
+}
+
+/// Memory has no devices connected to it, so it never raises interrupts.
+impl<T> Interrupts for Memory<T> {
+    fn pending(&self, _hart: u64) -> u64 {
+        0
+    }
+}
+
 impl<T> Memory<T> {
     /// Converts a slice to be used as memory for a hart.
     pub fn new(data: T) -> Memory<T>
@@ -31,12 +56,13 @@ impl<T> Memory<T> {
         let data_ref = &mut *data;
 
         let data_ptr = data_ref.as_mut_ptr() as *mut u8;
-        let data_len = data_ref.len() * size_of::<u64>();
+        let data_len = size_of_val(data_ref);
 
         Memory {
             data,
             data_ptr,
             data_len,
+            reservation: Cell::new(None),
         }
     }
 
@@ -62,17 +88,26 @@ impl<T> Memory<T> {
             // `self.size()` < `isize::MAX`, since this is a requirement on the
             // length of slices. Finally, we know it lies within the same allocated
             // object and does not wrap around per the condition of the enclosing if statement.
-            let ptr = unsafe { self.data_ptr.add(address) } as *mut V;
-
-            if ptr as usize % align_of::<V>() == 0 {
-                Ok(ptr)
-            } else {
-                Err(BusError::AddressMisaligned)
-            }
+            Ok(unsafe { self.data_ptr.add(address) } as *mut V)
         } else {
             Err(BusError::AccessFault)
         }
     }
+
+    /// Invalidates the current reservation if it covers any byte of a store of
+    /// a `V` to `address`.
+    #[inline]
+    fn invalidate_reservation<V>(&self, address: usize) {
+        if let Some((_, reserved)) = self.reservation.get() {
+            let first = address / RESERVATION_GRANULE * RESERVATION_GRANULE;
+            let last = address.wrapping_add(size_of::<V>() - 1) / RESERVATION_GRANULE
+                * RESERVATION_GRANULE;
+
+            if reserved == first || reserved == last {
+                self.reservation.set(None);
+            }
+        }
+    }
 }
 
 macro_rules! impl_bus {
@@ -80,10 +115,11 @@ macro_rules! impl_bus {
         $(impl<T> Bus<$addr, $val> for Memory<T> {
             fn load(&self, address: $addr) -> Result<$val, BusError> {
                 if address as usize as u64 == address {
-                    let ptr = self.calculate_destination(address as usize)?;
+                    let ptr = self.calculate_destination::<$val>(address as usize)?;
 
-                    // SAFETY: check_address returns a pointer that is guaranteed to be valid.
-                    Ok(unsafe { *ptr })
+                    // SAFETY: calculate_destination returns a pointer that is guaranteed to be
+                    // valid, though it may be misaligned.
+                    Ok(unsafe { ptr.read_unaligned() })
                 } else {
                     Err(BusError::AccessFault)
                 }
@@ -91,10 +127,13 @@ macro_rules! impl_bus {
 
             fn store(&self, address: $addr, value: $val) -> Result<(), BusError> {
                 if address as usize as u64 == address {
-                    let ptr = self.calculate_destination(address as usize)?;
+                    let ptr = self.calculate_destination::<$val>(address as usize)?;
+
+                    self.invalidate_reservation::<$val>(address as usize);
 
-                    // SAFETY: check_address returns a pointer that is guaranteed to be valid.
-                    Ok(unsafe { *ptr = value })
+                    // SAFETY: calculate_destination returns a pointer that is guaranteed to be
+                    // valid, though it may be misaligned.
+                    Ok(unsafe { ptr.write_unaligned(value) })
                 } else {
                     Err(BusError::AccessFault)
                 }
@@ -103,4 +142,58 @@ macro_rules! impl_bus {
     };
 }
 
+macro_rules! impl_atomic_bus {
+    ($($addr:ident $val:ident,)*) => {
+        $(impl<T> AtomicBus<$addr, $val> for Memory<T> {
+            fn load_reserved(&self, hart: u64, address: $addr) -> Result<$val, BusError> {
+                let value = self.load(address)?;
+
+                self.reservation.set(Some((
+                    hart,
+                    address as usize / RESERVATION_GRANULE * RESERVATION_GRANULE,
+                )));
+
+                Ok(value)
+            }
+
+            fn store_conditional(
+                &self,
+                hart: u64,
+                address: $addr,
+                value: $val,
+            ) -> Result<bool, BusError> {
+                let reserved = self.reservation.get()
+                    == Some((
+                        hart,
+                        address as usize / RESERVATION_GRANULE * RESERVATION_GRANULE,
+                    ));
+
+                // The reservation is invalidated whether or not the store succeeds
+                self.reservation.set(None);
+
+                if reserved {
+                    self.store(address, value)?;
+                }
+
+                Ok(reserved)
+            }
+
+            fn fetch_update(
+                &self,
+                address: $addr,
+                f: impl FnOnce($val) -> $val,
+            ) -> Result<$val, BusError> {
+                // Memory is not shared between threads, so a load followed by a
+                // store is atomic.
+                let value = self.load(address)?;
+
+                self.store(address, f(value))?;
+
+                Ok(value)
+            }
+        })*
+    };
+}
+
+use impl_atomic_bus;
 use impl_bus;
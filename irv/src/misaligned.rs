@@ -0,0 +1,251 @@
+//! Emulation of misaligned loads and stores by splitting them into aligned
+//! accesses.
+
+use core::mem::size_of;
+
+use crate::{mmu::Access, AccessKind, AtomicBus, BaseHart, Bus, Csr, Exception, Privilege};
+
+/// A value that can be loaded or stored by a single instruction.
+pub(crate) trait Word: Copy {
+    /// Zero-extends the value to 64 bits.
+    fn to_bits(self) -> u64;
+
+    /// Truncates 64 bits to a value of this type.
+    fn from_bits(bits: u64) -> Self;
+}
+
+macro_rules! impl_word {
+    ($($val:ident)*) => {
+        $(impl Word for $val {
+            fn to_bits(self) -> u64 {
+                self as u64
+            }
+
+            fn from_bits(bits: u64) -> $val {
+                bits as $val
+            }
+        })*
+    };
+}
+
+impl_word! { u8 u16 u32 u64 }
+
+/// One of the two aligned parts of a misaligned access.
+#[derive(Clone, Copy)]
+struct Part {
+    /// The physical address of the part.
+    physical: u64,
+    /// The effective privilege level of the access.
+    privilege: Privilege,
+    /// The virtual address of the first byte of the access within the part,
+    /// which is reported when the part faults.
+    fault: u64,
+}
+
+impl<B, C> BaseHart<B, C> {
+    /// Loads a `T` from the given misaligned virtual `address` by loading the
+    /// two aligned `T`s that contain it.
+    pub(crate) fn load_misaligned<T: Word>(&mut self, address: u64) -> Result<T, Exception>
+    where
+        B: Bus<u64, T> + AtomicBus<u64, u64>,
+        C: Csr,
+    {
+        let parts = self.split::<T>(address, Access::Load)?;
+        let bits = self.load_parts::<T>(parts, Access::Load)?;
+
+        let shift = address % size_of::<T>() as u64 * 8;
+
+        Ok(T::from_bits((bits >> shift) as u64))
+    }
+
+    /// Stores `value` to the given misaligned virtual `address` by replacing
+    /// the bytes it covers in the two aligned `T`s that contain it.
+    ///
+    /// Both parts are loaded before either is stored, and the first part is
+    /// stored again with its previous value if the second part cannot be
+    /// stored, so a part that faults leaves memory unchanged.
+    pub(crate) fn store_misaligned<T: Word>(
+        &mut self,
+        address: u64,
+        value: T,
+    ) -> Result<(), Exception>
+    where
+        B: Bus<u64, T> + AtomicBus<u64, u64>,
+        C: Csr,
+    {
+        let parts = self.split::<T>(address, Access::Store)?;
+        let previous = self.load_parts::<T>(parts, Access::Store)?;
+
+        let size = size_of::<T>() as u32;
+        let shift = (address % size as u64) as u32 * 8;
+        let mask = (u64::MAX >> (64 - size * 8)) as u128;
+        let bits = previous & !(mask << shift) | (value.to_bits() as u128) << shift;
+
+        let [low, high] = parts;
+        let low_access = self.data_access(AccessKind::Store, low.privilege);
+        let high_access = self.data_access(AccessKind::Store, high.privilege);
+
+        self.bus
+            .store_with(low.physical, T::from_bits(bits as u64), low_access)
+            .map_err(|_| Access::Store.access_fault(low.fault))?;
+
+        let high_value = T::from_bits((bits >> (size * 8)) as u64);
+
+        if self
+            .bus
+            .store_with(high.physical, high_value, high_access)
+            .is_err()
+        {
+            // The second part can still be refused by the bus after it was
+            // loaded, so the first part is put back as it was
+            let low_value = T::from_bits(previous as u64);
+            let _ = self.bus.store_with(low.physical, low_value, low_access);
+
+            return Err(Access::Store.access_fault(high.fault));
+        }
+
+        Ok(())
+    }
+
+    /// Translates the two aligned parts of an access of the given kind to a
+    /// `T` at the given misaligned virtual `address`.
+    ///
+    /// Only accesses to idempotent regions are split, since the bytes of each
+    /// part that are outside of the access are also accessed. Other accesses
+    /// raise address-misaligned exceptions.
+    fn split<T>(&mut self, address: u64, access: Access) -> Result<[Part; 2], Exception>
+    where
+        B: AtomicBus<u64, u64>,
+        C: Csr,
+    {
+        let size = size_of::<T>() as u64;
+        let low = address & !(size - 1);
+        let high = low.wrapping_add(size);
+
+        let mut parts = [(low, address), (high, high)].map(|(start, fault)| Part {
+            physical: start,
+            privilege: Privilege::Machine,
+            fault,
+        });
+
+        for part in &mut parts {
+            let (physical, privilege) = self.translate(part.physical, size, access).map_err(
+                |exception| match exception {
+                    Exception::LoadPageFault { .. } | Exception::StoreAmoPageFault { .. } => {
+                        access.page_fault(part.fault)
+                    }
+                    _ => access.access_fault(part.fault),
+                },
+            )?;
+
+            if !self.pma_supports(physical, size, |attributes| attributes.idempotent) {
+                return Err(access.address_misaligned(address));
+            }
+
+            part.physical = physical;
+            part.privilege = privilege;
+        }
+
+        Ok(parts)
+    }
+
+    /// Loads both parts of a misaligned access of the given kind, returning
+    /// the low part in the lower half of the result.
+    fn load_parts<T: Word>(&mut self, parts: [Part; 2], access: Access) -> Result<u128, Exception>
+    where
+        B: Bus<u64, T>,
+    {
+        let mut bits = 0;
+
+        for (index, part) in parts.into_iter().enumerate() {
+            let value: T = self
+                .bus
+                .load_with(
+                    part.physical,
+                    self.data_access(AccessKind::Load, part.privilege),
+                )
+                .map_err(|_| access.access_fault(part.fault))?;
+
+            bits |= (value.to_bits() as u128) << (index * size_of::<T>() * 8);
+        }
+
+        Ok(bits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::num::NonZeroU64;
+
+    use super::*;
+    use crate::mmu::tests::{hart, map, TestHart, BASE, READ, READ_WRITE};
+
+    fn load(hart: &mut TestHart, address: u64) -> u32 {
+        hart.bus.load(address).unwrap()
+    }
+
+    #[test]
+    fn test_page_crossing() {
+        let mut hart = hart();
+
+        // The pages are in the opposite order in physical memory
+        map(&mut hart, 0x1000, BASE + 0x7000, READ_WRITE);
+        map(&mut hart, 0x2000, BASE + 0x5000, READ_WRITE);
+
+        hart.bus
+            .store(BASE + 0x7FF8, 0x4433_2211_0000_0000u64)
+            .unwrap();
+        hart.bus
+            .store(BASE + 0x5000, 0x0000_0000_8877_6655u64)
+            .unwrap();
+
+        let value: u32 = hart.load_misaligned(0x1FFE).unwrap();
+        assert_eq!(value, 0x6655_4433);
+
+        // Bytes of the parts that are outside of the access are unchanged
+        hart.store_misaligned(0x1FFA, 0x0123_4567_89AB_CDEFu64)
+            .unwrap();
+        assert_eq!(load(&mut hart, BASE + 0x7FF8), 0xCDEF_0000);
+        assert_eq!(load(&mut hart, BASE + 0x7FFC), 0x4567_89AB);
+        assert_eq!(load(&mut hart, BASE + 0x5000), 0x8877_0123);
+    }
+
+    #[test]
+    fn test_faults() {
+        let mut hart = hart();
+        map(&mut hart, 0x1000, BASE + 0x7000, READ_WRITE);
+        map(&mut hart, 0x2000, BASE + 0x5000, READ);
+
+        hart.bus.store(BASE + 0x7FFC, 0x4433_2211u32).unwrap();
+
+        // A fault in the second part is reported at its first byte, and
+        // leaves the first part unstored
+        assert!(matches!(
+            hart.store_misaligned(0x1FFE, 0u32),
+            Err(Exception::StoreAmoPageFault { address }) if address == NonZeroU64::new(0x2000)
+        ));
+        assert_eq!(load(&mut hart, BASE + 0x7FFC), 0x4433_2211);
+
+        assert!(matches!(
+            hart.load_misaligned::<u32>(0x2FFE),
+            Err(Exception::LoadPageFault { address }) if address == NonZeroU64::new(0x3000)
+        ));
+
+        // A fault in the first part is reported at the address of the access
+        assert!(matches!(
+            hart.load_misaligned::<u32>(0x3FFE),
+            Err(Exception::LoadPageFault { address }) if address == NonZeroU64::new(0x3FFE)
+        ));
+
+        // The same applies to access faults from the bus
+        let end = BASE + 0x10000;
+        hart.privilege = Privilege::Machine;
+        hart.bus.store(end - 4, 0x4433_2211u32).unwrap();
+
+        assert!(matches!(
+            hart.store_misaligned(end - 2, 0u32),
+            Err(Exception::StoreAmoAccessFault { address }) if address == NonZeroU64::new(end)
+        ));
+        assert_eq!(load(&mut hart, end - 4), 0x4433_2211);
+    }
+}
@@ -0,0 +1,763 @@
+//! Translation of virtual addresses by the memory management unit.
+
+use core::num::NonZeroU64;
+
+use crate::{
+    AccessKind, AtomicBus, Attributes, BaseHart, Csr, CsrAddress, Exception, MemoryAccess,
+    Privilege, Xlen, MSTATUS_MPRV, MSTATUS_MXR, MSTATUS_SUM,
+};
+
+/// The number of entries in a [Tlb].
+const TLB_ENTRIES: usize = 64;
+
+/// The mask of the physical page number in `satp`.
+const SATP_PPN: u64 = (1 << 44) - 1;
+
+/// The mask of the physical page number in `satp` in RV32.
+const SATP32_PPN: u64 = (1 << 22) - 1;
+
+// The fields of page table entries
+const PTE_V: u64 = 1 << 0;
+const PTE_R: u64 = 1 << 1;
+const PTE_W: u64 = 1 << 2;
+const PTE_X: u64 = 1 << 3;
+const PTE_U: u64 = 1 << 4;
+const PTE_A: u64 = 1 << 6;
+const PTE_D: u64 = 1 << 7;
+
+/// A page-based virtual memory scheme, such as Sv39.
+#[derive(Clone, Copy)]
+struct Scheme {
+    /// The number of levels of the page table.
+    levels: u32,
+    /// The number of bits of the virtual page number translated by each
+    /// level.
+    bits: u32,
+    /// The size of each page table entry in bytes.
+    pte_size: u64,
+}
+
+impl Scheme {
+    /// Sv32, the only scheme of RV32.
+    const SV32: Scheme = Scheme {
+        levels: 2,
+        bits: 10,
+        pte_size: 4,
+    };
+
+    /// Sv39, with 39-bit virtual addresses.
+    const SV39: Scheme = Scheme {
+        levels: 3,
+        bits: 9,
+        pte_size: 8,
+    };
+
+    /// Sv48, with 48-bit virtual addresses.
+    const SV48: Scheme = Scheme {
+        levels: 4,
+        ..Scheme::SV39
+    };
+
+    /// Sv57, with 57-bit virtual addresses.
+    const SV57: Scheme = Scheme {
+        levels: 5,
+        ..Scheme::SV39
+    };
+}
+
+/// The kinds of memory access, which are translated separately.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Access {
+    Fetch,
+    Load,
+    Store,
+}
+
+impl Access {
+    /// Gets the page fault raised when translating an access of this kind to
+    /// the given virtual `address` fails.
+    pub(crate) fn page_fault(self, address: u64) -> Exception {
+        let address = NonZeroU64::new(address);
+
+        match self {
+            Access::Fetch => Exception::InstructionPageFault { address },
+            Access::Load => Exception::LoadPageFault { address },
+            Access::Store => Exception::StoreAmoPageFault { address },
+        }
+    }
+
+    /// Gets the access fault raised when an access of this kind to the given
+    /// virtual `address` fails.
+    pub(crate) fn access_fault(self, address: u64) -> Exception {
+        let address = NonZeroU64::new(address);
+
+        match self {
+            Access::Fetch => Exception::InstructionAccessFault { address },
+            Access::Load => Exception::LoadAccessFault { address },
+            Access::Store => Exception::StoreAmoAccessFault { address },
+        }
+    }
+
+    /// Gets the exception raised when an access of this kind to the given
+    /// virtual `address` is misaligned.
+    pub(crate) fn address_misaligned(self, address: u64) -> Exception {
+        let address = NonZeroU64::new(address);
+
+        match self {
+            Access::Fetch => Exception::InstructionAddressMisaligned { address },
+            Access::Load => Exception::LoadAddressMisaligned { address },
+            Access::Store => Exception::StoreAmoAddressMisaligned { address },
+        }
+    }
+}
+
+/// A cached translation of a single 4 KiB virtual page.
+#[derive(Clone, Copy)]
+struct TlbEntry {
+    /// The value of `satp` when the translation was made.
+    satp: u64,
+    /// The virtual page number.
+    vpn: u64,
+    /// The physical page number the virtual page is translated to.
+    ppn: u64,
+    /// The leaf page table entry the translation was made from.
+    pte: u64,
+    /// The number of low bits of the virtual page number that are within the
+    /// page mapped by the leaf page table entry, which are nonzero for
+    /// superpages.
+    superpage_bits: u32,
+}
+
+/// A direct-mapped translation lookaside buffer.
+///
+/// Since entries are tagged with the value of `satp` they were translated
+/// under, they never need to be flushed when `satp` changes, but they must be
+/// flushed when page tables are modified (as by `SFENCE.VMA`).
+pub(crate) struct Tlb {
+    entries: [Option<TlbEntry>; TLB_ENTRIES],
+}
+
+impl Tlb {
+    /// Creates a new, empty TLB.
+    pub(crate) const fn new() -> Tlb {
+        Tlb {
+            entries: [None; TLB_ENTRIES],
+        }
+    }
+
+    /// Flushes the translations of the given virtual `address`, or all
+    /// translations if there is no address.
+    pub(crate) fn flush(&mut self, address: Option<u64>) {
+        for slot in &mut self.entries {
+            let matches = match (address, &slot) {
+                (None, _) => true,
+                (Some(address), Some(entry)) => {
+                    // Superpages cover many virtual pages
+                    let shift = entry.superpage_bits;
+
+                    entry.vpn >> shift == address >> 12 >> shift
+                }
+                (Some(_), None) => false,
+            };
+
+            if matches {
+                *slot = None;
+            }
+        }
+    }
+}
+
+impl<B, C> BaseHart<B, C> {
+    /// Translates the given virtual `address` for an access of the given kind
+    /// to `size` bytes, returning the physical address and the effective
+    /// privilege level of the access.
+    ///
+    /// Both the physical address and any page table accesses are checked by
+    /// the PMP unit and against the PMAs. Misaligned accesses that cross a
+    /// page boundary while translation is active, or that are to regions that
+    /// do not support them, raise address-misaligned exceptions.
+    pub(crate) fn translate(
+        &mut self,
+        address: u64,
+        size: u64,
+        access: Access,
+    ) -> Result<(u64, Privilege), Exception>
+    where
+        B: AtomicBus<u64, u64>,
+        C: Csr,
+    {
+        let mstatus = self.csr.access(CsrAddress::MSTATUS, |m| m).unwrap_or(0);
+
+        // MPRV makes loads and stores act as if they were made at the
+        // privilege level in MPP
+        let privilege = if access != Access::Fetch && mstatus & MSTATUS_MPRV != 0 {
+            Privilege::from_bits(mstatus >> 11 & 0b11).unwrap_or(Privilege::Machine)
+        } else {
+            self.privilege
+        };
+
+        let physical = match self.scheme(privilege) {
+            // Only the first page of an access is translated
+            Some(_) if (address & 0xFFF) + size > 0x1000 => {
+                return Err(access.address_misaligned(address));
+            }
+            Some((scheme, satp)) => {
+                self.translate_page(address, access, privilege, mstatus, scheme, satp)?
+            }
+            None => address,
+        };
+
+        if !self.pmp.check(physical, size, access, privilege) {
+            return Err(access.access_fault(address));
+        }
+
+        let attributes = match self.pma.attributes(physical, size) {
+            Some(attributes) if attributes.permits(access) => attributes,
+            _ => return Err(access.access_fault(address)),
+        };
+
+        if physical & (size - 1) != 0 && !attributes.misaligned {
+            return Err(access.address_misaligned(address));
+        }
+
+        Ok((physical, privilege))
+    }
+
+    /// Gets the scheme that translates accesses made at the given privilege
+    /// level, and the value of `satp` it was selected by, unless they are not
+    /// translated.
+    fn scheme(&mut self, privilege: Privilege) -> Option<(Scheme, u64)>
+    where
+        C: Csr,
+    {
+        if privilege == Privilege::Machine {
+            return None;
+        }
+
+        let satp = self.csr.access(CsrAddress::SATP, |satp| satp).unwrap_or(0);
+
+        let scheme = match (self.xlen, satp >> 60) {
+            (Xlen::Rv32, _) if satp >> 31 == 1 => Scheme::SV32,
+            (Xlen::Rv64, 8) => Scheme::SV39,
+            (Xlen::Rv64, 9) => Scheme::SV48,
+            (Xlen::Rv64, 10) => Scheme::SV57,
+            _ => return None,
+        };
+
+        Some((scheme, satp))
+    }
+
+    /// Translates the given virtual `address` for an access of the given kind
+    /// made at the given privilege level with the given scheme, without
+    /// checking the PMP unit.
+    fn translate_page(
+        &mut self,
+        address: u64,
+        access: Access,
+        privilege: Privilege,
+        mstatus: u64,
+        scheme: Scheme,
+        satp: u64,
+    ) -> Result<u64, Exception>
+    where
+        B: AtomicBus<u64, u64>,
+    {
+        // The upper bits of an RV64 address must be copies of the highest
+        // translated bit, while RV32 addresses are translated in full
+        let shift = 64 - (12 + scheme.bits * scheme.levels);
+
+        if self.xlen == Xlen::Rv64 && ((address << shift) as i64 >> shift) as u64 != address {
+            return Err(access.page_fault(address));
+        }
+
+        let vpn = address >> 12;
+        let slot = vpn as usize % TLB_ENTRIES;
+
+        let entry = match self.tlb.entries[slot] {
+            Some(entry) if entry.satp == satp && entry.vpn == vpn => entry,
+            _ => self.walk(satp, scheme, address, access, privilege, mstatus)?,
+        };
+
+        if !permitted(entry.pte, access, privilege, mstatus) {
+            return Err(access.page_fault(address));
+        }
+
+        // Stores to pages that are not yet dirty must walk the page table
+        // again to set the dirty bit
+        let entry = if access == Access::Store && entry.pte & PTE_D == 0 {
+            self.walk(satp, scheme, address, access, privilege, mstatus)?
+        } else {
+            entry
+        };
+
+        Ok(entry.ppn << 12 | address & 0xFFF)
+    }
+
+    /// Walks the page table given by `satp` to translate the given virtual
+    /// `address`, updating the accessed and dirty bits and caching the
+    /// translation in the TLB.
+    ///
+    /// Sv32 page table entries are accessed through the aligned doublewords
+    /// that contain them, so that only 64-bit accesses are made to the bus.
+    fn walk(
+        &mut self,
+        satp: u64,
+        scheme: Scheme,
+        address: u64,
+        access: Access,
+        privilege: Privilege,
+        mstatus: u64,
+    ) -> Result<TlbEntry, Exception>
+    where
+        B: AtomicBus<u64, u64>,
+    {
+        let root = match scheme.pte_size {
+            4 => satp & SATP32_PPN,
+            _ => satp & SATP_PPN,
+        };
+
+        let pte_mask = u64::MAX >> (64 - scheme.pte_size * 8);
+
+        'walk: loop {
+            let mut table = root << 12;
+
+            for level in (0..scheme.levels).rev() {
+                let index = address >> (12 + scheme.bits * level) & ((1 << scheme.bits) - 1);
+                let pte_address = table.wrapping_add(index * scheme.pte_size);
+
+                let doubleword = pte_address & !0b111;
+                let shift = (pte_address & 0b111) * 8;
+
+                // Page table accesses are made at supervisor level
+                if !self.pmp.check(
+                    pte_address,
+                    scheme.pte_size,
+                    Access::Load,
+                    Privilege::Supervisor,
+                ) || !self.pte_permits(pte_address, scheme.pte_size, Access::Load)
+                {
+                    return Err(access.access_fault(address));
+                }
+
+                let pte = self
+                    .bus
+                    .load_with(doubleword, MemoryAccess::walk(AccessKind::Load))
+                    .map_err(|_| access.access_fault(address))?;
+
+                let pte: u64 = pte >> shift & pte_mask;
+
+                // Invalid entries, writable entries that are not readable,
+                // and entries using reserved bits are faults
+                if pte & PTE_V == 0 || pte & (PTE_R | PTE_W) == PTE_W || pte >> 54 != 0 {
+                    return Err(access.page_fault(address));
+                }
+
+                let ppn = pte >> 10 & SATP_PPN;
+
+                // Entries that are not readable or executable point to the
+                // next level of the page table
+                if pte & (PTE_R | PTE_X) == 0 {
+                    table = ppn << 12;
+                    continue;
+                }
+
+                if !permitted(pte, access, privilege, mstatus) {
+                    return Err(access.page_fault(address));
+                }
+
+                // Superpages must be aligned to their size
+                let superpage_bits = scheme.bits * level;
+                let offset_mask = (1 << superpage_bits) - 1;
+
+                if ppn & offset_mask != 0 {
+                    return Err(access.page_fault(address));
+                }
+
+                let update = if access == Access::Store {
+                    PTE_A | PTE_D
+                } else {
+                    PTE_A
+                };
+
+                // Atomically set the accessed and dirty bits, starting over if
+                // the entry was changed since it was read
+                if pte & update != update {
+                    if !self.pmp.check(
+                        pte_address,
+                        scheme.pte_size,
+                        Access::Store,
+                        Privilege::Supervisor,
+                    ) || !self.pte_permits(pte_address, scheme.pte_size, Access::Store)
+                    {
+                        return Err(access.access_fault(address));
+                    }
+
+                    let update_pte = |current: u64| {
+                        if current >> shift & pte_mask == pte {
+                            current | update << shift
+                        } else {
+                            current
+                        }
+                    };
+
+                    let result = self.bus.fetch_update_with(
+                        doubleword,
+                        update_pte,
+                        MemoryAccess::walk(AccessKind::Amo),
+                    );
+
+                    match result {
+                        Ok(current) if current >> shift & pte_mask == pte => (),
+                        Ok(_) => continue 'walk,
+                        Err(_) => return Err(access.access_fault(address)),
+                    }
+                }
+
+                let entry = TlbEntry {
+                    satp,
+                    vpn: address >> 12,
+                    ppn: ppn | address >> 12 & offset_mask,
+                    pte: pte | update,
+                    superpage_bits,
+                };
+
+                self.tlb.entries[entry.vpn as usize % TLB_ENTRIES] = Some(entry);
+
+                return Ok(entry);
+            }
+
+            // There is no leaf entry
+            return Err(access.page_fault(address));
+        }
+    }
+
+    /// Checks whether the attributes of the `size` bytes at the given physical
+    /// `address` satisfy `f`.
+    pub(crate) fn pma_supports(
+        &self,
+        address: u64,
+        size: u64,
+        f: impl FnOnce(Attributes) -> bool,
+    ) -> bool {
+        self.pma.attributes(address, size).is_some_and(f)
+    }
+
+    /// Checks whether the PMAs permit an access of the given kind to the page
+    /// table entry of the given size at the given physical address.
+    fn pte_permits(&self, pte_address: u64, size: u64, access: Access) -> bool {
+        self.pma_supports(pte_address, size, |attributes| attributes.permits(access))
+    }
+}
+
+/// Checks whether the leaf page table entry `pte` permits an access of the
+/// given kind at the given privilege level.
+fn permitted(pte: u64, access: Access, privilege: Privilege, mstatus: u64) -> bool {
+    let allowed = match access {
+        Access::Fetch => pte & PTE_X != 0,
+        // MXR makes executable pages readable
+        Access::Load => pte & PTE_R != 0 || mstatus & MSTATUS_MXR != 0 && pte & PTE_X != 0,
+        Access::Store => pte & PTE_W != 0,
+    };
+
+    // Supervisor mode can only access user pages when SUM is set, and can
+    // never execute them
+    let user = match privilege {
+        Privilege::User => pte & PTE_U != 0,
+        _ if pte & PTE_U != 0 => access != Access::Fetch && mstatus & MSTATUS_SUM != 0,
+        _ => true,
+    };
+
+    allowed && user
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use alloc::vec;
+
+    use super::*;
+    use crate::{instruction::sfence_vma, Bus, BusDevice, MachineCsrs, Memory, Pma, SystemBus};
+
+    /// Where memory is mapped.
+    pub(crate) const BASE: u64 = 0x8000_0000;
+
+    // The page tables used by [map], which translate the lowest 2 MiB
+    const ROOT: u64 = BASE;
+    const L1: u64 = BASE + 0x1000;
+    const L0: u64 = BASE + 0x2000;
+
+    // The permissions of pages mapped by the tests of other modules
+    pub(crate) const READ: u64 = PTE_R;
+    pub(crate) const READ_WRITE: u64 = PTE_R | PTE_W;
+
+    pub(crate) type TestHart = BaseHart<SystemBus, MachineCsrs>;
+
+    /// Creates a supervisor-mode hart using Sv39, with 64 KiB of memory at
+    /// [BASE] whose first three pages hold the page tables.
+    pub(crate) fn hart() -> TestHart {
+        let memory = Memory::new(vec![0u64; 0x10000 / 8]);
+        let mut bus = SystemBus::new(1);
+        bus.map(BASE, 0x10000, BusDevice(memory)).unwrap();
+
+        let mut hart = BaseHart::new(bus, MachineCsrs::new(0, "imacsu"));
+        hart.privilege = Privilege::Supervisor;
+        hart.csr.satp = 8 << 60 | ROOT >> 12;
+
+        set_pte(&mut hart, ROOT, L1 >> 2 | PTE_V);
+        set_pte(&mut hart, L1, L0 >> 2 | PTE_V);
+
+        hart
+    }
+
+    /// Writes the page table entry at the given physical address.
+    fn set_pte(hart: &mut TestHart, address: u64, pte: u64) {
+        hart.bus.store(address, pte).unwrap();
+    }
+
+    /// Maps the 4 KiB page at the given virtual address, which must be below
+    /// 2 MiB, to the given physical page, with the given permissions.
+    pub(crate) fn map(hart: &mut TestHart, virtual_page: u64, physical: u64, flags: u64) {
+        set_pte(
+            hart,
+            L0 + (virtual_page >> 12) * 8,
+            physical >> 2 | flags | PTE_V,
+        );
+    }
+
+    /// Reads the page table entry that maps the given virtual page.
+    fn pte(hart: &mut TestHart, virtual_page: u64) -> u64 {
+        hart.bus.load(L0 + (virtual_page >> 12) * 8).unwrap()
+    }
+
+    fn translate(hart: &mut TestHart, address: u64, access: Access) -> Result<u64, Exception> {
+        hart.translate(address, 4, access)
+            .map(|(physical, _)| physical)
+    }
+
+    /// Checks whether `result` is a page fault for the given virtual address.
+    fn is_page_fault(result: Result<u64, Exception>, address: u64) -> bool {
+        let expected = NonZeroU64::new(address);
+
+        matches!(
+            result,
+            Err(Exception::InstructionPageFault { address }
+                | Exception::LoadPageFault { address }
+                | Exception::StoreAmoPageFault { address }) if address == expected
+        )
+    }
+
+    #[test]
+    fn test_walk() {
+        let mut hart = hart();
+        map(&mut hart, 0x1000, BASE + 0x5000, PTE_R | PTE_W);
+        map(&mut hart, 0x2000, BASE + 0x6000, PTE_X);
+        map(&mut hart, 0x3000, BASE + 0x7000, PTE_R | PTE_U);
+
+        assert_eq!(
+            translate(&mut hart, 0x1234, Access::Load).unwrap(),
+            BASE + 0x5234
+        );
+        assert_eq!(
+            translate(&mut hart, 0x2FFC, Access::Fetch).unwrap(),
+            BASE + 0x6FFC
+        );
+
+        // Pages can only be accessed in the ways they permit
+        assert!(is_page_fault(
+            translate(&mut hart, 0x2000, Access::Load),
+            0x2000
+        ));
+        assert!(is_page_fault(
+            translate(&mut hart, 0x1000, Access::Fetch),
+            0x1000
+        ));
+
+        // MXR makes executable pages readable
+        hart.csr.mstatus |= MSTATUS_MXR;
+        assert_eq!(
+            translate(&mut hart, 0x2000, Access::Load).unwrap(),
+            BASE + 0x6000
+        );
+
+        // User pages can only be accessed by supervisor mode with SUM
+        assert!(is_page_fault(
+            translate(&mut hart, 0x3000, Access::Load),
+            0x3000
+        ));
+        hart.csr.mstatus |= MSTATUS_SUM;
+        assert_eq!(
+            translate(&mut hart, 0x3000, Access::Load).unwrap(),
+            BASE + 0x7000
+        );
+
+        hart.privilege = Privilege::User;
+        assert!(is_page_fault(
+            translate(&mut hart, 0x1000, Access::Load),
+            0x1000
+        ));
+
+        // Unmapped pages, addresses that are not sign-extended, and writable
+        // pages that are not readable are faults
+        hart.privilege = Privilege::Supervisor;
+        map(&mut hart, 0x4000, BASE + 0x8000, PTE_W);
+
+        for address in [0x5000, 0x4000, 1 << 39 | 0x1000] {
+            assert!(is_page_fault(
+                translate(&mut hart, address, Access::Store),
+                address
+            ));
+        }
+
+        // Machine mode is not translated, unless MPRV is set
+        hart.privilege = Privilege::Machine;
+        assert_eq!(translate(&mut hart, 0x1000, Access::Load).unwrap(), 0x1000);
+
+        hart.csr.mstatus = hart.csr.mstatus & !(0b11 << 11) | MSTATUS_MPRV | 0b01 << 11;
+        assert_eq!(
+            translate(&mut hart, 0x1000, Access::Load).unwrap(),
+            BASE + 0x5000
+        );
+        assert_eq!(translate(&mut hart, 0x1000, Access::Fetch).unwrap(), 0x1000);
+    }
+
+    #[test]
+    fn test_superpages() {
+        let mut hart = hart();
+
+        // A leaf in the root table maps a 1 GiB gigapage
+        set_pte(&mut hart, ROOT + 8 * 2, BASE >> 2 | PTE_R | PTE_V);
+        assert_eq!(
+            translate(&mut hart, 0x8123_4568, Access::Load).unwrap(),
+            BASE + 0x0123_4568
+        );
+
+        // A leaf in the second level maps a 2 MiB megapage
+        set_pte(&mut hart, L1 + 8, (BASE + 0x40_0000) >> 2 | PTE_R | PTE_V);
+        assert_eq!(
+            translate(&mut hart, 0x21_2344, Access::Load).unwrap(),
+            BASE + 0x41_2344
+        );
+
+        // Superpages must be aligned to their size
+        set_pte(&mut hart, L1 + 16, (BASE + 0x1000) >> 2 | PTE_R | PTE_V);
+        assert!(is_page_fault(
+            translate(&mut hart, 0x40_0000, Access::Load),
+            0x40_0000
+        ));
+
+        // Flushing any page of a superpage flushes all of it
+        set_pte(&mut hart, L1 + 8, (BASE + 0x60_0000) >> 2 | PTE_R | PTE_V);
+        hart.tlb.flush(Some(0x3F_F000));
+        assert_eq!(
+            translate(&mut hart, 0x21_2344, Access::Load).unwrap(),
+            BASE + 0x61_2344
+        );
+    }
+
+    #[test]
+    fn test_page_crossing() {
+        let mut hart = hart();
+        map(&mut hart, 0x1000, BASE + 0x5000, PTE_R);
+        map(&mut hart, 0x2000, BASE + 0x6000, PTE_R);
+
+        hart.pma = Pma::new(Attributes {
+            misaligned: true,
+            ..Attributes::MEMORY
+        });
+
+        // Only the first page is translated, so translated accesses cannot
+        // cross into the next one
+        assert!(matches!(
+            hart.translate(0x1FFE, 4, Access::Load),
+            Err(Exception::LoadAddressMisaligned { address }) if address == NonZeroU64::new(0x1FFE)
+        ));
+        assert_eq!(
+            translate(&mut hart, 0x1FFA, Access::Load).unwrap(),
+            BASE + 0x5FFA
+        );
+
+        // Accesses that are not translated can cross pages
+        hart.csr.satp = 0;
+        assert_eq!(
+            translate(&mut hart, BASE + 0x5FFE, Access::Load).unwrap(),
+            BASE + 0x5FFE
+        );
+
+        hart.privilege = Privilege::Machine;
+        assert_eq!(
+            translate(&mut hart, BASE + 0x5FFE, Access::Store).unwrap(),
+            BASE + 0x5FFE
+        );
+
+        // Unless the region does not support misaligned accesses at all
+        hart.pma = Pma::default();
+        assert!(matches!(
+            hart.translate(BASE + 0x5FFE, 4, Access::Store),
+            Err(Exception::StoreAmoAddressMisaligned { .. })
+        ));
+    }
+
+    #[test]
+    fn test_accessed_dirty() {
+        let mut hart = hart();
+        map(&mut hart, 0x1000, BASE + 0x5000, PTE_R | PTE_W);
+
+        // Loads set the accessed bit, and stores also set the dirty bit
+        translate(&mut hart, 0x1000, Access::Load).unwrap();
+        assert_eq!(pte(&mut hart, 0x1000) & (PTE_A | PTE_D), PTE_A);
+
+        // A store after a load walks the table again, even though the
+        // translation is cached
+        translate(&mut hart, 0x1000, Access::Store).unwrap();
+        assert_eq!(pte(&mut hart, 0x1000) & (PTE_A | PTE_D), PTE_A | PTE_D);
+
+        // Faulting accesses do not set either
+        map(&mut hart, 0x2000, BASE + 0x6000, PTE_R);
+        translate(&mut hart, 0x2000, Access::Store).unwrap_err();
+        assert_eq!(pte(&mut hart, 0x2000) & (PTE_A | PTE_D), 0);
+    }
+
+    #[test]
+    fn test_tlb_flush() {
+        let mut hart = hart();
+        map(&mut hart, 0x1000, BASE + 0x5000, PTE_R);
+        map(&mut hart, 0x2000, BASE + 0x6000, PTE_R);
+
+        translate(&mut hart, 0x1000, Access::Load).unwrap();
+        translate(&mut hart, 0x2000, Access::Load).unwrap();
+
+        // Translations are cached until they are flushed
+        map(&mut hart, 0x1000, BASE + 0x7000, PTE_R);
+        map(&mut hart, 0x2000, BASE + 0x8000, PTE_R);
+        assert_eq!(
+            translate(&mut hart, 0x1000, Access::Load).unwrap(),
+            BASE + 0x5000
+        );
+
+        // `sfence.vma x5, x0` flushes a single page
+        hart.gpr[5] = 0x1800;
+        sfence_vma(&mut hart, 0b0001001 << 25 | 5 << 15 | 0b1110011);
+        assert_eq!(
+            translate(&mut hart, 0x1000, Access::Load).unwrap(),
+            BASE + 0x7000
+        );
+        assert_eq!(
+            translate(&mut hart, 0x2000, Access::Load).unwrap(),
+            BASE + 0x6000
+        );
+
+        // `sfence.vma x0, x0` flushes every page
+        sfence_vma(&mut hart, 0b0001001 << 25 | 0b1110011);
+        assert_eq!(
+            translate(&mut hart, 0x2000, Access::Load).unwrap(),
+            BASE + 0x8000
+        );
+
+        // Translations made under a different satp are not used
+        map(&mut hart, 0x1000, BASE + 0x9000, PTE_R);
+        hart.csr.satp |= 1 << 44;
+        assert_eq!(
+            translate(&mut hart, 0x1000, Access::Load).unwrap(),
+            BASE + 0x9000
+        );
+    }
+}
@@ -0,0 +1,263 @@
+//! Physical memory attributes.
+
+use alloc::vec::Vec;
+
+use crate::{mmu::Access, MapError};
+
+/// The classes of AMOs that a region of physical memory can support, each of
+/// which includes the classes before it.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum AmoClass {
+    /// No AMOs are supported.
+    None,
+    /// Only `AMOSWAP` is supported.
+    Swap,
+    /// `AMOSWAP`, `AMOAND`, `AMOOR`, and `AMOXOR` are supported.
+    Logical,
+    /// Every AMO is supported.
+    Arithmetic,
+}
+
+/// The attributes of a region of physical memory.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Attributes {
+    /// Whether the region can be read by loads.
+    pub readable: bool,
+    /// Whether the region can be written by stores.
+    pub writable: bool,
+    /// Whether instructions can be fetched from the region.
+    pub executable: bool,
+    /// The AMOs that the region supports.
+    pub amo: AmoClass,
+    /// Whether the region supports `LR` and `SC`.
+    pub reservable: bool,
+    /// Whether misaligned loads and stores to the region are passed to the
+    /// bus, rather than raising address-misaligned exceptions or being split
+    /// as given by [MisalignedMode](crate::MisalignedMode).
+    pub misaligned: bool,
+    /// Whether accesses to the region have no side effects, so they can be
+    /// repeated or split without changing their result.
+    pub idempotent: bool,
+    /// Whether the region can be cached, which has no effect on the hart
+    /// since caches are not modelled.
+    pub cacheable: bool,
+}
+
+impl Attributes {
+    /// The attributes of main memory that only supports aligned accesses.
+    pub const MEMORY: Attributes = Attributes {
+        readable: true,
+        writable: true,
+        executable: true,
+        amo: AmoClass::Arithmetic,
+        reservable: true,
+        misaligned: false,
+        idempotent: true,
+        cacheable: true,
+    };
+
+    /// The attributes of memory-mapped I/O devices, which cannot be executed
+    /// from and do not support atomic or misaligned accesses.
+    pub const IO: Attributes = Attributes {
+        readable: true,
+        writable: true,
+        executable: false,
+        amo: AmoClass::None,
+        reservable: false,
+        misaligned: false,
+        idempotent: false,
+        cacheable: false,
+    };
+
+    /// The attributes of a vacant region, where every access fails.
+    pub const VACANT: Attributes = Attributes {
+        readable: false,
+        writable: false,
+        executable: false,
+        amo: AmoClass::None,
+        reservable: false,
+        misaligned: false,
+        idempotent: true,
+        cacheable: false,
+    };
+
+    /// Checks whether the region permits an access of the given kind.
+    pub(crate) const fn permits(&self, access: Access) -> bool {
+        match access {
+            Access::Fetch => self.executable,
+            Access::Load => self.readable,
+            Access::Store => self.writable,
+        }
+    }
+}
+
+/// A region of physical memory with the same attributes.
+#[derive(Clone, Debug)]
+struct Region {
+    base: u64,
+    size: u64,
+    attributes: Attributes,
+}
+
+/// The physical memory attributes (PMAs) of a platform, which the hart checks
+/// each physical access against.
+///
+/// Each region given to [Pma::add] has its own attributes, and addresses
+/// outside every region have the default attributes. An access that is only
+/// partly within a region fails.
+#[derive(Clone, Debug)]
+pub struct Pma {
+    /// The regions, sorted by base address.
+    regions: Vec<Region>,
+    default: Attributes,
+}
+
+impl Pma {
+    /// Creates a new table without any regions, in which every address has
+    /// the given `default` attributes.
+    pub const fn new(default: Attributes) -> Pma {
+        Pma {
+            regions: Vec::new(),
+            default,
+        }
+    }
+
+    /// Gives the `size` bytes starting at `base` the given attributes.
+    pub fn add(&mut self, base: u64, size: u64, attributes: Attributes) -> Result<(), MapError> {
+        if size == 0 || base.checked_add(size - 1).is_none() {
+            return Err(MapError::InvalidRange);
+        }
+
+        let index = self.regions.partition_point(|region| region.base < base);
+
+        let overlaps_previous = index > 0 && {
+            let previous = &self.regions[index - 1];
+
+            base - previous.base < previous.size
+        };
+
+        let overlaps_next = self
+            .regions
+            .get(index)
+            .is_some_and(|next| next.base - base < size);
+
+        if overlaps_previous || overlaps_next {
+            return Err(MapError::Overlap);
+        }
+
+        self.regions.insert(
+            index,
+            Region {
+                base,
+                size,
+                attributes,
+            },
+        );
+
+        Ok(())
+    }
+
+    /// Gets the attributes of the `size` bytes starting at `address`, unless
+    /// they are only partly within a region or `size` is zero.
+    pub fn attributes(&self, address: u64, size: u64) -> Option<Attributes> {
+        let last = address.checked_add(size.checked_sub(1)?)?;
+        let index = self.regions.partition_point(|region| region.base <= last);
+
+        // Only the last region starting at or before the end of the access can
+        // contain any of it
+        let region = match index {
+            0 => return Some(self.default),
+            _ => &self.regions[index - 1],
+        };
+
+        if address < region.base {
+            None
+        } else if address - region.base >= region.size {
+            Some(self.default)
+        } else if last - region.base < region.size {
+            Some(region.attributes)
+        } else {
+            None
+        }
+    }
+}
+
+impl Default for Pma {
+    /// Creates a table in which every address is main memory.
+    fn default() -> Pma {
+        Pma::new(Attributes::MEMORY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates a table with I/O at 0x1000 through 0x1FFF, vacant memory just
+    /// after it, and a gap of main memory before vacant memory at 0x4000.
+    fn pma() -> Pma {
+        let mut pma = Pma::default();
+
+        pma.add(0x1000, 0x1000, Attributes::IO).unwrap();
+        pma.add(0x2000, 0x1000, Attributes::VACANT).unwrap();
+        pma.add(0x4000, 0x1000, Attributes::VACANT).unwrap();
+
+        pma
+    }
+
+    #[test]
+    fn test_regions() {
+        let pma = pma();
+
+        assert_eq!(pma.attributes(0, 8), Some(Attributes::MEMORY));
+        assert_eq!(pma.attributes(0x1000, 8), Some(Attributes::IO));
+        assert_eq!(pma.attributes(0x1FF8, 8), Some(Attributes::IO));
+        assert_eq!(pma.attributes(0x2000, 1), Some(Attributes::VACANT));
+        assert_eq!(pma.attributes(0x3000, 8), Some(Attributes::MEMORY));
+        assert_eq!(pma.attributes(0x3FF8, 8), Some(Attributes::MEMORY));
+        assert_eq!(pma.attributes(0x5000, 8), Some(Attributes::MEMORY));
+        assert_eq!(pma.attributes(u64::MAX, 1), Some(Attributes::MEMORY));
+    }
+
+    #[test]
+    fn test_partial_accesses() {
+        let pma = pma();
+
+        // Accesses straddling two adjacent regions
+        assert_eq!(pma.attributes(0x1FFC, 8), None);
+
+        // Accesses only partly covering a region, from either side
+        assert_eq!(pma.attributes(0xFFC, 8), None);
+        assert_eq!(pma.attributes(0x2FFC, 8), None);
+        assert_eq!(pma.attributes(0x3FFC, 8), None);
+        assert_eq!(pma.attributes(0x4FFF, 2), None);
+
+        // Accesses covering a region and more
+        assert_eq!(pma.attributes(0xFF8, 0x2000), None);
+
+        // Accesses that are empty or wrap around the address space
+        assert_eq!(pma.attributes(0, 0), None);
+        assert_eq!(pma.attributes(0x1000, 0), None);
+        assert_eq!(pma.attributes(u64::MAX, 2), None);
+    }
+
+    #[test]
+    fn test_add() {
+        let mut pma = pma();
+
+        assert_eq!(
+            pma.add(0x2800, 0x1000, Attributes::IO),
+            Err(MapError::Overlap)
+        );
+        assert_eq!(
+            pma.add(0x3000, 0x1001, Attributes::IO),
+            Err(MapError::Overlap)
+        );
+        assert_eq!(
+            pma.add(0x3000, 0, Attributes::IO),
+            Err(MapError::InvalidRange)
+        );
+        assert_eq!(pma.add(0x3000, 0x1000, Attributes::IO), Ok(()));
+        assert_eq!(pma.attributes(0x3000, 8), Some(Attributes::IO));
+    }
+}
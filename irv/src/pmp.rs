@@ -0,0 +1,338 @@
+//! Physical memory protection.
+
+use crate::{mmu::Access, CsrIllegal, Privilege, Xlen};
+
+// The fields of each PMP configuration
+const PMP_R: u8 = 1 << 0;
+const PMP_W: u8 = 1 << 1;
+const PMP_X: u8 = 1 << 2;
+const PMP_A: u8 = 0b11 << 3;
+const PMP_L: u8 = 1 << 7;
+
+// The address-matching modes of the A field
+const PMP_OFF: u8 = 0;
+const PMP_TOR: u8 = 1;
+const PMP_NA4: u8 = 2;
+
+/// The mask of the bits of `pmpaddr` registers that are implemented, which
+/// hold bits 55:2 of a physical address.
+const PMPADDR_MASK: u64 = (1 << 54) - 1;
+
+/// The physical memory protection unit of a hart, which holds the state of
+/// the `pmpcfg` and `pmpaddr` CSRs.
+///
+/// All PMP CSRs can always be accessed, but only the given number of
+/// lowest-numbered entries are implemented; the others are read-only zero.
+/// When any entries are implemented, accesses made below machine mode that do
+/// not match any entry fail.
+#[derive(Clone, Debug)]
+pub struct Pmp {
+    /// The number of implemented entries.
+    entries: usize,
+    /// The configuration of each entry.
+    cfg: [u8; 64],
+    /// The address register of each entry.
+    addr: [u64; 64],
+}
+
+impl Pmp {
+    /// Creates a new PMP unit implementing the given number of entries, which
+    /// are all initially off.
+    ///
+    /// # Panics
+    /// Panics if `entries` is greater than 64.
+    pub const fn new(entries: usize) -> Pmp {
+        assert!(entries <= 64, "at most 64 PMP entries can be implemented");
+
+        Pmp {
+            entries,
+            cfg: [0; 64],
+            addr: [0; 64],
+        }
+    }
+
+    /// Gets the number of implemented entries.
+    pub const fn entries(&self) -> usize {
+        self.entries
+    }
+
+    /// Accesses the `pmpcfg` CSR with the given index, which holds the
+    /// configurations of the XLEN / 8 entries starting at `index * 4`.
+    ///
+    /// In RV64, only even-numbered `pmpcfg` CSRs exist.
+    pub(crate) fn access_cfg(
+        &mut self,
+        index: usize,
+        xlen: Xlen,
+        f: impl FnOnce(u64) -> u64,
+    ) -> Result<u64, CsrIllegal> {
+        let count = match xlen {
+            Xlen::Rv32 => 4,
+            Xlen::Rv64 if index & 1 == 0 => 8,
+            Xlen::Rv64 => return Err(CsrIllegal),
+        };
+
+        let first = index * 4;
+
+        let value = (0..count).fold(0, |value, i| {
+            value | (self.cfg[first + i] as u64) << (i * 8)
+        });
+        let new_value = f(value);
+
+        for i in first..(first + count).min(self.entries) {
+            if self.cfg[i] & PMP_L != 0 {
+                continue;
+            }
+
+            let mut cfg =
+                (new_value >> ((i - first) * 8)) as u8 & (PMP_R | PMP_W | PMP_X | PMP_A | PMP_L);
+
+            // Entries that are writable but not readable are reserved
+            if cfg & (PMP_R | PMP_W) == PMP_W {
+                cfg &= !PMP_W;
+            }
+
+            self.cfg[i] = cfg;
+        }
+
+        Ok(value)
+    }
+
+    /// Accesses the `pmpaddr` CSR with the given index.
+    pub(crate) fn access_addr(
+        &mut self,
+        index: usize,
+        f: impl FnOnce(u64) -> u64,
+    ) -> Result<u64, CsrIllegal> {
+        let value = self.addr[index];
+        let new_value = f(value) & PMPADDR_MASK;
+
+        // Locked entries cannot be changed, and neither can the address that
+        // a locked TOR entry uses as its lower bound
+        let locked = self.cfg[index] & PMP_L != 0
+            || index + 1 < self.entries
+                && self.cfg[index + 1] & (PMP_L | PMP_A) == (PMP_L | PMP_TOR << 3);
+
+        if index < self.entries && !locked {
+            self.addr[index] = new_value;
+        }
+
+        Ok(value)
+    }
+
+    /// Checks whether an access of the given kind to the `size` bytes starting
+    /// at the physical `address` is permitted at the given privilege level.
+    pub(crate) fn check(
+        &self,
+        address: u64,
+        size: u64,
+        access: Access,
+        privilege: Privilege,
+    ) -> bool {
+        if self.entries == 0 {
+            return true;
+        }
+
+        let end = address.wrapping_add(size);
+
+        for i in 0..self.entries {
+            let cfg = self.cfg[i];
+
+            let (start, limit) = match (cfg & PMP_A) >> 3 {
+                PMP_OFF => continue,
+                PMP_TOR => {
+                    let start = if i == 0 { 0 } else { self.addr[i - 1] << 2 };
+
+                    (start, self.addr[i] << 2)
+                }
+                PMP_NA4 => (self.addr[i] << 2, (self.addr[i] << 2).wrapping_add(4)),
+                // NAPOT
+                _ => {
+                    // The number of trailing ones gives the size of the region
+                    let ones = self.addr[i].trailing_ones();
+                    let start = (self.addr[i] & !((1 << ones) - 1)) << 2;
+
+                    (start, start.wrapping_add(1 << (ones + 3)))
+                }
+            };
+
+            // The lowest-numbered entry that matches any byte of the access
+            // determines whether it succeeds, and must match every byte
+            if start >= limit || address >= limit || end <= start {
+                continue;
+            }
+
+            if address < start || end > limit {
+                return false;
+            }
+
+            // Entries that are not locked do not apply to machine mode
+            if privilege == Privilege::Machine && cfg & PMP_L == 0 {
+                return true;
+            }
+
+            return match access {
+                Access::Fetch => cfg & PMP_X != 0,
+                Access::Load => cfg & PMP_R != 0,
+                Access::Store => cfg & PMP_W != 0,
+            };
+        }
+
+        privilege == Privilege::Machine
+    }
+}
+
+impl Default for Pmp {
+    /// Creates a PMP unit without any implemented entries.
+    fn default() -> Pmp {
+        Pmp::new(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NAPOT: u8 = 3 << 3;
+    const TOR: u8 = PMP_TOR << 3;
+    const NA4: u8 = PMP_NA4 << 3;
+
+    /// Creates a PMP unit with eight entries, the first of which are
+    /// configured with the given `pmpcfg` and `pmpaddr` values.
+    fn pmp(entries: &[(u8, u64)]) -> Pmp {
+        let mut pmp = Pmp::new(8);
+        let mut cfg = 0;
+
+        for (i, &(entry_cfg, addr)) in entries.iter().enumerate() {
+            write_addr(&mut pmp, i, addr);
+            cfg |= (entry_cfg as u64) << (i * 8);
+        }
+
+        write_cfg(&mut pmp, cfg);
+
+        pmp
+    }
+
+    /// Gets the `pmpaddr` value of a NAPOT region of the given power-of-two
+    /// size.
+    const fn napot(base: u64, size: u64) -> u64 {
+        (base | (size / 2 - 1)) >> 2
+    }
+
+    fn write_cfg(pmp: &mut Pmp, value: u64) {
+        assert!(pmp.access_cfg(0, Xlen::Rv64, |_| value).is_ok());
+    }
+
+    fn read_cfg(pmp: &mut Pmp) -> Option<u64> {
+        pmp.access_cfg(0, Xlen::Rv64, |cfg| cfg).ok()
+    }
+
+    fn write_addr(pmp: &mut Pmp, index: usize, value: u64) {
+        assert!(pmp.access_addr(index, |_| value).is_ok());
+    }
+
+    fn read_addr(pmp: &mut Pmp, index: usize) -> Option<u64> {
+        pmp.access_addr(index, |addr| addr).ok()
+    }
+
+    fn check(pmp: &Pmp, address: u64, size: u64, access: Access) -> bool {
+        pmp.check(address, size, access, Privilege::Supervisor)
+    }
+
+    #[test]
+    fn test_tor() {
+        let pmp = pmp(&[
+            (TOR | PMP_R | PMP_W, 0x1000 >> 2),
+            (TOR | PMP_R, 0x3000 >> 2),
+        ]);
+
+        // The first entry starts at zero, and the others at the address of
+        // the one before
+        assert!(check(&pmp, 0, 8, Access::Store));
+        assert!(check(&pmp, 0xFF8, 8, Access::Store));
+        assert!(check(&pmp, 0x1000, 8, Access::Load));
+        assert!(!check(&pmp, 0x1000, 8, Access::Store));
+        assert!(!check(&pmp, 0x1000, 8, Access::Fetch));
+
+        // Every byte of an access must be within the entry that matches it
+        assert!(!check(&pmp, 0xFFC, 8, Access::Load));
+        assert!(!check(&pmp, 0x2FFC, 8, Access::Load));
+
+        // Accesses that match no entry fail below machine mode
+        assert!(!check(&pmp, 0x3000, 8, Access::Load));
+        assert!(pmp.check(0x3000, 8, Access::Store, Privilege::Machine));
+    }
+
+    #[test]
+    fn test_na4_napot() {
+        let pmp = pmp(&[
+            (NA4 | PMP_R, 0x1000 >> 2),
+            (NAPOT | PMP_R | PMP_X, napot(0x2000, 0x1000)),
+            (NAPOT | PMP_R | PMP_W | PMP_X, napot(0, 0x10000)),
+        ]);
+
+        // NA4 entries cover four bytes
+        assert!(check(&pmp, 0x1000, 4, Access::Load));
+        assert!(!check(&pmp, 0x1000, 8, Access::Load));
+
+        // The lowest-numbered entry that matches an access applies, even when
+        // a later one permits it
+        assert!(!check(&pmp, 0x1000, 4, Access::Store));
+        assert!(check(&pmp, 0x1004, 4, Access::Store));
+
+        assert!(check(&pmp, 0x2000, 8, Access::Fetch));
+        assert!(check(&pmp, 0x2FF8, 8, Access::Load));
+        assert!(!check(&pmp, 0x2FF8, 8, Access::Store));
+        assert!(check(&pmp, 0x3000, 8, Access::Store));
+        assert!(!check(&pmp, 0x1FFC, 8, Access::Load));
+
+        assert!(check(&pmp, 0xFFF8, 8, Access::Store));
+        assert!(!check(&pmp, 0x10000, 8, Access::Load));
+    }
+
+    #[test]
+    fn test_locked() {
+        let mut pmp = pmp(&[
+            (NAPOT | PMP_R | PMP_L, napot(0x2000, 0x1000)),
+            (NAPOT | PMP_R, napot(0x4000, 0x1000)),
+            (TOR | PMP_R | PMP_W | PMP_L, 0x6000 >> 2),
+        ]);
+
+        // Locked entries apply to machine mode too, while others do not
+        assert!(pmp.check(0x2000, 8, Access::Load, Privilege::Machine));
+        assert!(!pmp.check(0x2000, 8, Access::Store, Privilege::Machine));
+        assert!(!pmp.check(0x2000, 8, Access::Fetch, Privilege::Machine));
+        assert!(pmp.check(0x4000, 8, Access::Store, Privilege::Machine));
+        assert!(!pmp.check(0x4000, 8, Access::Store, Privilege::Supervisor));
+
+        // Locked entries cannot be changed, and neither can the lower bound
+        // of a locked TOR entry
+        write_cfg(&mut pmp, 0);
+        let locked = ((TOR | PMP_R | PMP_W | PMP_L) as u64) << 16 | (NAPOT | PMP_R | PMP_L) as u64;
+        assert_eq!(read_cfg(&mut pmp), Some(locked));
+
+        for i in 0..3 {
+            write_addr(&mut pmp, i, 0);
+        }
+
+        assert_eq!(read_addr(&mut pmp, 0), Some(napot(0x2000, 0x1000)));
+        assert_eq!(read_addr(&mut pmp, 1), Some(napot(0x4000, 0x1000)));
+        assert_eq!(read_addr(&mut pmp, 2), Some(0x6000 >> 2));
+    }
+
+    #[test]
+    fn test_reserved() {
+        // Entries that are writable but not readable become inaccessible, and
+        // unimplemented entries are read-only zero
+        let mut pmp = Pmp::new(1);
+        write_cfg(&mut pmp, 0xFF_FF00 | (NAPOT | PMP_W) as u64);
+        write_addr(&mut pmp, 1, 0x1234);
+
+        assert_eq!(read_cfg(&mut pmp), Some(NAPOT as u64));
+        assert_eq!(read_addr(&mut pmp, 1), Some(0));
+
+        // Odd-numbered pmpcfg CSRs only exist in RV32
+        assert!(pmp.access_cfg(1, Xlen::Rv64, |cfg| cfg).is_err());
+        assert!(pmp.access_cfg(1, Xlen::Rv32, |cfg| cfg).is_ok());
+    }
+}
@@ -0,0 +1,1089 @@
+//! A software implementation of IEEE 754 binary floating-point arithmetic.
+//!
+//! Values are passed around as raw bit patterns (in the low bits of a `u64`)
+//! and all arithmetic is done on integers, so results never depend on the
+//! floating-point unit of the host. NaN results are always the canonical NaN,
+//! and tininess is detected after rounding, both as required by RISC-V.
+
+/// The invalid operation exception flag.
+pub const INVALID: u8 = 0b10000;
+/// The divide by zero exception flag.
+pub const DIVIDE_BY_ZERO: u8 = 0b01000;
+/// The overflow exception flag.
+pub const OVERFLOW: u8 = 0b00100;
+/// The underflow exception flag.
+pub const UNDERFLOW: u8 = 0b00010;
+/// The inexact exception flag.
+pub const INEXACT: u8 = 0b00001;
+
+/// A binary interchange format.
+pub trait Format {
+    /// The width of the exponent field.
+    const EXP_BITS: u32;
+    /// The width of the fraction field (the significand without its hidden bit).
+    const FRAC_BITS: u32;
+
+    /// The total width of the format.
+    const BITS: u32 = 1 + Self::EXP_BITS + Self::FRAC_BITS;
+    /// The number of significant bits, including the hidden bit.
+    const PRECISION: u32 = Self::FRAC_BITS + 1;
+    /// The exponent bias.
+    const BIAS: i32 = (1 << (Self::EXP_BITS - 1)) - 1;
+    /// The sign bit.
+    const SIGN: u64 = 1 << (Self::BITS - 1);
+    /// The value of the exponent field of infinities and NaNs.
+    const EXP_MAX: u64 = (1 << Self::EXP_BITS) - 1;
+    /// The mask of the fraction field.
+    const FRAC_MASK: u64 = (1 << Self::FRAC_BITS) - 1;
+    /// The bit of the fraction field that distinguishes quiet NaNs from
+    /// signaling NaNs.
+    const QUIET: u64 = 1 << (Self::FRAC_BITS - 1);
+    /// Positive infinity.
+    const INFINITY: u64 = Self::EXP_MAX << Self::FRAC_BITS;
+    /// The largest finite value.
+    const MAX: u64 = Self::INFINITY - 1;
+    /// The canonical NaN.
+    const CANONICAL_NAN: u64 = Self::INFINITY | Self::QUIET;
+}
+
+/// The binary32 (single-precision) format.
+pub struct F32;
+
+/// The binary64 (double-precision) format.
+pub struct F64;
+
+impl Format for F32 {
+    const EXP_BITS: u32 = 8;
+    const FRAC_BITS: u32 = 23;
+}
+
+impl Format for F64 {
+    const EXP_BITS: u32 = 11;
+    const FRAC_BITS: u32 = 52;
+}
+
+/// The rounding modes defined by RISC-V.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum RoundingMode {
+    /// Round to nearest, ties to even.
+    NearestEven,
+    /// Round towards zero.
+    TowardZero,
+    /// Round down (towards negative infinity).
+    Down,
+    /// Round up (towards positive infinity).
+    Up,
+    /// Round to nearest, ties to max magnitude.
+    NearestMaxMagnitude,
+}
+
+impl RoundingMode {
+    /// Decodes a rounding mode from the encoding used by the `rm` instruction
+    /// field and the `frm` CSR, returning `None` for reserved encodings.
+    pub const fn from_bits(bits: u32) -> Option<RoundingMode> {
+        match bits {
+            0b000 => Some(RoundingMode::NearestEven),
+            0b001 => Some(RoundingMode::TowardZero),
+            0b010 => Some(RoundingMode::Down),
+            0b011 => Some(RoundingMode::Up),
+            0b100 => Some(RoundingMode::NearestMaxMagnitude),
+            _ => None,
+        }
+    }
+}
+
+/// The class of an unpacked value.
+#[derive(Clone, Copy)]
+enum Kind {
+    Zero,
+    /// A finite nonzero value equal to `sig * 2^exp`, where `sig` is normalized
+    /// so that its most significant set bit is bit `PRECISION - 1`.
+    Finite {
+        exp: i32,
+        sig: u64,
+    },
+    Infinity,
+    QuietNan,
+    SignalingNan,
+}
+
+impl Kind {
+    const fn is_nan(self) -> bool {
+        matches!(self, Kind::QuietNan | Kind::SignalingNan)
+    }
+}
+
+/// Splits a value into its sign and its class.
+fn unpack<F: Format>(bits: u64) -> (bool, Kind) {
+    let sign = bits & F::SIGN != 0;
+    let exp = bits >> F::FRAC_BITS & F::EXP_MAX;
+    let frac = bits & F::FRAC_MASK;
+
+    let kind = if exp == F::EXP_MAX {
+        if frac == 0 {
+            Kind::Infinity
+        } else if frac & F::QUIET != 0 {
+            Kind::QuietNan
+        } else {
+            Kind::SignalingNan
+        }
+    } else if exp == 0 {
+        if frac == 0 {
+            Kind::Zero
+        } else {
+            // Subnormal values are normalized so that all finite values can be
+            // treated the same way
+            let shift = frac.leading_zeros() - (63 - F::FRAC_BITS);
+            Kind::Finite {
+                exp: 1 - F::BIAS - F::FRAC_BITS as i32 - shift as i32,
+                sig: frac << shift,
+            }
+        }
+    } else {
+        Kind::Finite {
+            exp: exp as i32 - F::BIAS - F::FRAC_BITS as i32,
+            sig: frac | 1 << F::FRAC_BITS,
+        }
+    };
+
+    (sign, kind)
+}
+
+const fn zero<F: Format>(sign: bool) -> u64 {
+    if sign {
+        F::SIGN
+    } else {
+        0
+    }
+}
+
+const fn infinity<F: Format>(sign: bool) -> u64 {
+    zero::<F>(sign) | F::INFINITY
+}
+
+/// Shifts `sig` right by `shift` bits, rounding the result to an integer
+/// according to `rm`.
+///
+/// Returns the rounded result and whether it is inexact.
+fn shift_right_round(sig: u128, shift: u32, rm: RoundingMode, sign: bool) -> (u128, bool) {
+    if shift == 0 {
+        return (sig, false);
+    }
+
+    let (kept, round, sticky) = match shift {
+        1..=127 => (
+            sig >> shift,
+            sig >> (shift - 1) & 1 != 0,
+            sig & ((1 << (shift - 1)) - 1) != 0,
+        ),
+        128 => (0, sig >> 127 != 0, sig & (u128::MAX >> 1) != 0),
+        _ => (0, false, sig != 0),
+    };
+
+    let increment = match rm {
+        RoundingMode::NearestEven => round && (sticky || kept & 1 != 0),
+        RoundingMode::NearestMaxMagnitude => round,
+        RoundingMode::TowardZero => false,
+        RoundingMode::Down => sign && (round || sticky),
+        RoundingMode::Up => !sign && (round || sticky),
+    };
+
+    (kept + increment as u128, round || sticky)
+}
+
+/// Shifts `sig` right by `shift` bits, setting the least significant bit of the
+/// result if any bits that were shifted out were set.
+const fn shift_right_jam(sig: u128, shift: u32) -> u128 {
+    match shift {
+        0 => sig,
+        1..=127 => sig >> shift | (sig & ((1 << shift) - 1) != 0) as u128,
+        _ => (sig != 0) as u128,
+    }
+}
+
+/// Rounds the nonzero value `sig * 2^exp` to the format `F`.
+///
+/// If the value is not exact, `sig` must have at least `PRECISION + 2`
+/// significant bits with its least significant bit set to mark the
+/// inexactness.
+fn round_pack<F: Format>(sign: bool, exp: i32, sig: u128, rm: RoundingMode, flags: &mut u8) -> u64 {
+    let precision = F::PRECISION as i32;
+    let emin = 1 - F::BIAS;
+
+    // The exponent of the most significant bit of the exact value
+    let msb = exp + (127 - sig.leading_zeros() as i32);
+    // The exponent of the least significant bit of the result
+    let mut lsb = msb.max(emin) - (precision - 1);
+
+    let (mut kept, inexact) = if lsb >= exp {
+        shift_right_round(sig, (lsb - exp) as u32, rm, sign)
+    } else {
+        (sig << (exp - lsb), false)
+    };
+
+    if kept >> precision != 0 {
+        // Rounding carried into a new bit
+        kept >>= 1;
+        lsb += 1;
+    }
+
+    if lsb + precision - 1 > F::BIAS {
+        *flags |= OVERFLOW | INEXACT;
+
+        let to_infinity = match rm {
+            RoundingMode::NearestEven | RoundingMode::NearestMaxMagnitude => true,
+            RoundingMode::TowardZero => false,
+            RoundingMode::Down => sign,
+            RoundingMode::Up => !sign,
+        };
+
+        return zero::<F>(sign) | if to_infinity { F::INFINITY } else { F::MAX };
+    }
+
+    if inexact {
+        *flags |= INEXACT;
+
+        // Tininess is detected after rounding, so a value just below the
+        // smallest normal value is not tiny if rounding it to full precision
+        // with an unbounded exponent would carry it up to the smallest normal.
+        let tiny = msb < emin
+            && !(msb == emin - 1
+                && shift_right_round(sig, (msb - (precision - 1) - exp) as u32, rm, sign).0
+                    >> precision
+                    != 0);
+
+        if tiny {
+            *flags |= UNDERFLOW;
+        }
+    }
+
+    if kept >> (precision - 1) != 0 {
+        let biased = (lsb + precision - 1 + F::BIAS) as u64;
+        zero::<F>(sign) | biased << F::FRAC_BITS | kept as u64 & F::FRAC_MASK
+    } else {
+        zero::<F>(sign) | kept as u64
+    }
+}
+
+/// Computes `a + b`.
+pub fn add<F: Format>(a: u64, b: u64, rm: RoundingMode, flags: &mut u8) -> u64 {
+    let (sa, ka) = unpack::<F>(a);
+    let (sb, kb) = unpack::<F>(b);
+
+    match (ka, kb) {
+        (Kind::SignalingNan, _) | (_, Kind::SignalingNan) => {
+            *flags |= INVALID;
+            F::CANONICAL_NAN
+        }
+        (Kind::QuietNan, _) | (_, Kind::QuietNan) => F::CANONICAL_NAN,
+        (Kind::Infinity, Kind::Infinity) if sa != sb => {
+            *flags |= INVALID;
+            F::CANONICAL_NAN
+        }
+        (Kind::Infinity, _) => infinity::<F>(sa),
+        (_, Kind::Infinity) => infinity::<F>(sb),
+        (Kind::Zero, Kind::Zero) if sa == sb => zero::<F>(sa),
+        (Kind::Zero, Kind::Zero) => zero::<F>(rm == RoundingMode::Down),
+        (Kind::Zero, _) => b,
+        (_, Kind::Zero) => a,
+        (Kind::Finite { exp: ea, sig: ma }, Kind::Finite { exp: eb, sig: mb }) => {
+            // Order the operands by magnitude
+            let ((sx, ex, mx), (sy, ey, my)) = if (ea, ma) >= (eb, mb) {
+                ((sa, ea, ma), (sb, eb, mb))
+            } else {
+                ((sb, eb, mb), (sa, ea, ma))
+            };
+
+            // Leave plenty of room below the larger operand so that the
+            // smaller one can be aligned to it without losing precision
+            const GUARD: u32 = 64;
+
+            let mx = (mx as u128) << GUARD;
+            let my = shift_right_jam((my as u128) << GUARD, (ex - ey) as u32);
+
+            let sig = if sx == sy { mx + my } else { mx - my };
+
+            if sig == 0 {
+                zero::<F>(rm == RoundingMode::Down)
+            } else {
+                round_pack::<F>(sx, ex - GUARD as i32, sig, rm, flags)
+            }
+        }
+    }
+}
+
+/// Computes `a - b`.
+pub fn sub<F: Format>(a: u64, b: u64, rm: RoundingMode, flags: &mut u8) -> u64 {
+    add::<F>(a, b ^ F::SIGN, rm, flags)
+}
+
+/// Computes `a * b`.
+pub fn mul<F: Format>(a: u64, b: u64, rm: RoundingMode, flags: &mut u8) -> u64 {
+    let (sa, ka) = unpack::<F>(a);
+    let (sb, kb) = unpack::<F>(b);
+    let sign = sa != sb;
+
+    match (ka, kb) {
+        (Kind::SignalingNan, _) | (_, Kind::SignalingNan) => {
+            *flags |= INVALID;
+            F::CANONICAL_NAN
+        }
+        (Kind::QuietNan, _) | (_, Kind::QuietNan) => F::CANONICAL_NAN,
+        (Kind::Infinity, Kind::Zero) | (Kind::Zero, Kind::Infinity) => {
+            *flags |= INVALID;
+            F::CANONICAL_NAN
+        }
+        (Kind::Infinity, _) | (_, Kind::Infinity) => infinity::<F>(sign),
+        (Kind::Zero, _) | (_, Kind::Zero) => zero::<F>(sign),
+        (Kind::Finite { exp: ea, sig: ma }, Kind::Finite { exp: eb, sig: mb }) => {
+            round_pack::<F>(sign, ea + eb, ma as u128 * mb as u128, rm, flags)
+        }
+    }
+}
+
+/// Computes `a / b`.
+pub fn div<F: Format>(a: u64, b: u64, rm: RoundingMode, flags: &mut u8) -> u64 {
+    let (sa, ka) = unpack::<F>(a);
+    let (sb, kb) = unpack::<F>(b);
+    let sign = sa != sb;
+
+    match (ka, kb) {
+        (Kind::SignalingNan, _) | (_, Kind::SignalingNan) => {
+            *flags |= INVALID;
+            F::CANONICAL_NAN
+        }
+        (Kind::QuietNan, _) | (_, Kind::QuietNan) => F::CANONICAL_NAN,
+        (Kind::Infinity, Kind::Infinity) | (Kind::Zero, Kind::Zero) => {
+            *flags |= INVALID;
+            F::CANONICAL_NAN
+        }
+        (Kind::Infinity, _) | (Kind::Zero, _) => a & !F::SIGN | zero::<F>(sign),
+        (_, Kind::Infinity) => zero::<F>(sign),
+        (_, Kind::Zero) => {
+            *flags |= DIVIDE_BY_ZERO;
+            infinity::<F>(sign)
+        }
+        (Kind::Finite { exp: ea, sig: ma }, Kind::Finite { exp: eb, sig: mb }) => {
+            // Scale the dividend so that the quotient has at least
+            // `PRECISION + 2` bits
+            let shift = F::PRECISION + 3;
+            let dividend = (ma as u128) << shift;
+            let quotient = dividend / mb as u128;
+            let sticky = quotient * mb as u128 != dividend;
+
+            round_pack::<F>(
+                sign,
+                ea - eb - shift as i32,
+                quotient | sticky as u128,
+                rm,
+                flags,
+            )
+        }
+    }
+}
+
+/// Computes the square root of `a`.
+pub fn sqrt<F: Format>(a: u64, rm: RoundingMode, flags: &mut u8) -> u64 {
+    match unpack::<F>(a) {
+        (_, Kind::SignalingNan) => {
+            *flags |= INVALID;
+            F::CANONICAL_NAN
+        }
+        (_, Kind::QuietNan) => F::CANONICAL_NAN,
+        (_, Kind::Zero) => a,
+        (true, _) => {
+            *flags |= INVALID;
+            F::CANONICAL_NAN
+        }
+        (false, Kind::Infinity) => a,
+        (false, Kind::Finite { exp, sig }) => {
+            // Scale the radicand so that the root has at least `PRECISION + 2`
+            // bits, while keeping the exponent even
+            let shift = F::PRECISION as i32 + 4 + (exp - F::PRECISION as i32) % 2;
+            let radicand = (sig as u128) << shift;
+            let root = isqrt(radicand);
+            let sticky = root * root != radicand;
+
+            round_pack::<F>(false, (exp - shift) / 2, root | sticky as u128, rm, flags)
+        }
+    }
+}
+
+/// Computes the integer square root of `x`, rounded down.
+const fn isqrt(x: u128) -> u128 {
+    let mut remainder = x;
+    let mut root = 0;
+    let mut bit = 1 << 126;
+
+    while bit > x {
+        bit >>= 2;
+    }
+
+    while bit != 0 {
+        if remainder >= root + bit {
+            remainder -= root + bit;
+            root = (root >> 1) + bit;
+        } else {
+            root >>= 1;
+        }
+
+        bit >>= 2;
+    }
+
+    root
+}
+
+/// Computes `a * b + c` with a single rounding.
+pub fn fma<F: Format>(a: u64, b: u64, c: u64, rm: RoundingMode, flags: &mut u8) -> u64 {
+    let (sa, ka) = unpack::<F>(a);
+    let (sb, kb) = unpack::<F>(b);
+    let (sc, kc) = unpack::<F>(c);
+    let sp = sa != sb;
+
+    if matches!(ka, Kind::SignalingNan)
+        || matches!(kb, Kind::SignalingNan)
+        || matches!(kc, Kind::SignalingNan)
+    {
+        *flags |= INVALID;
+    }
+
+    // The invalid multiplication is signaled even if the addend is a quiet NaN
+    if matches!(
+        (ka, kb),
+        (Kind::Infinity, Kind::Zero) | (Kind::Zero, Kind::Infinity)
+    ) {
+        *flags |= INVALID;
+        return F::CANONICAL_NAN;
+    }
+
+    if ka.is_nan() || kb.is_nan() || kc.is_nan() {
+        return F::CANONICAL_NAN;
+    }
+
+    match (ka, kb, kc) {
+        (Kind::Infinity, _, Kind::Infinity) | (_, Kind::Infinity, Kind::Infinity) if sp != sc => {
+            *flags |= INVALID;
+            F::CANONICAL_NAN
+        }
+        (Kind::Infinity, _, _) | (_, Kind::Infinity, _) => infinity::<F>(sp),
+        (_, _, Kind::Infinity) => infinity::<F>(sc),
+        (Kind::Zero, _, Kind::Zero) | (_, Kind::Zero, Kind::Zero) if sp == sc => zero::<F>(sp),
+        (Kind::Zero, _, Kind::Zero) | (_, Kind::Zero, Kind::Zero) => {
+            zero::<F>(rm == RoundingMode::Down)
+        }
+        (Kind::Zero, _, _) | (_, Kind::Zero, _) => c,
+        (Kind::Finite { exp: ea, sig: ma }, Kind::Finite { exp: eb, sig: mb }, Kind::Zero) => {
+            round_pack::<F>(sp, ea + eb, ma as u128 * mb as u128, rm, flags)
+        }
+        (
+            Kind::Finite { exp: ea, sig: ma },
+            Kind::Finite { exp: eb, sig: mb },
+            Kind::Finite { exp: ec, sig: mc },
+        ) => {
+            // Normalize both the exact product and the addend so that their
+            // most significant bits are at bit 125, leaving room for a carry
+            // and plenty of guard bits below
+            let product = ma as u128 * mb as u128;
+            let product_shift = product.leading_zeros() - 2;
+            let (mp, ep) = (product << product_shift, ea + eb - product_shift as i32);
+            let addend_shift = 126 - F::PRECISION;
+            let (mc, ec) = ((mc as u128) << addend_shift, ec - addend_shift as i32);
+
+            let ((sx, ex, mx), (sy, ey, my)) = if (ep, mp) >= (ec, mc) {
+                ((sp, ep, mp), (sc, ec, mc))
+            } else {
+                ((sc, ec, mc), (sp, ep, mp))
+            };
+
+            let my = shift_right_jam(my, (ex - ey) as u32);
+
+            let sig = if sx == sy { mx + my } else { mx - my };
+
+            if sig == 0 {
+                zero::<F>(rm == RoundingMode::Down)
+            } else {
+                round_pack::<F>(sx, ex, sig, rm, flags)
+            }
+        }
+        (Kind::QuietNan | Kind::SignalingNan, _, _)
+        | (_, Kind::QuietNan | Kind::SignalingNan, _)
+        | (_, _, Kind::QuietNan | Kind::SignalingNan) => unreachable!(),
+    }
+}
+
+/// Maps a non-NaN value to an integer with the same ordering, treating both
+/// zeros as equal.
+fn order_key<F: Format>(bits: u64) -> i128 {
+    let magnitude = (bits & !F::SIGN) as i128;
+
+    if bits & F::SIGN != 0 {
+        -magnitude
+    } else {
+        magnitude
+    }
+}
+
+/// Computes the minimum (if `max` is false) or maximum (if `max` is true) of
+/// `a` and `b`, as defined for the `FMIN` and `FMAX` instructions.
+pub fn min_max<F: Format>(a: u64, b: u64, max: bool, flags: &mut u8) -> u64 {
+    let (_, ka) = unpack::<F>(a);
+    let (_, kb) = unpack::<F>(b);
+
+    if matches!(ka, Kind::SignalingNan) || matches!(kb, Kind::SignalingNan) {
+        *flags |= INVALID;
+    }
+
+    match (ka.is_nan(), kb.is_nan()) {
+        (true, true) => F::CANONICAL_NAN,
+        (true, false) => b,
+        (false, true) => a,
+        (false, false) => {
+            let (ka, kb) = (order_key::<F>(a), order_key::<F>(b));
+
+            // When the values are equal, -0 is considered less than +0
+            if (ka < kb || ka == kb && a & F::SIGN != 0) != max {
+                a
+            } else {
+                b
+            }
+        }
+    }
+}
+
+/// Computes `a == b`, signaling invalid only for signaling NaNs.
+pub fn eq<F: Format>(a: u64, b: u64, flags: &mut u8) -> bool {
+    let (_, ka) = unpack::<F>(a);
+    let (_, kb) = unpack::<F>(b);
+
+    if matches!(ka, Kind::SignalingNan) || matches!(kb, Kind::SignalingNan) {
+        *flags |= INVALID;
+    }
+
+    !ka.is_nan() && !kb.is_nan() && order_key::<F>(a) == order_key::<F>(b)
+}
+
+/// Computes `a < b`, signaling invalid for any NaN.
+pub fn lt<F: Format>(a: u64, b: u64, flags: &mut u8) -> bool {
+    let (_, ka) = unpack::<F>(a);
+    let (_, kb) = unpack::<F>(b);
+
+    if ka.is_nan() || kb.is_nan() {
+        *flags |= INVALID;
+        false
+    } else {
+        order_key::<F>(a) < order_key::<F>(b)
+    }
+}
+
+/// Computes `a <= b`, signaling invalid for any NaN.
+pub fn le<F: Format>(a: u64, b: u64, flags: &mut u8) -> bool {
+    let (_, ka) = unpack::<F>(a);
+    let (_, kb) = unpack::<F>(b);
+
+    if ka.is_nan() || kb.is_nan() {
+        *flags |= INVALID;
+        false
+    } else {
+        order_key::<F>(a) <= order_key::<F>(b)
+    }
+}
+
+/// Classifies `a` into the one-hot mask produced by the `FCLASS` instructions.
+pub fn classify<F: Format>(a: u64) -> u64 {
+    let subnormal = a >> F::FRAC_BITS & F::EXP_MAX == 0;
+
+    match unpack::<F>(a) {
+        (true, Kind::Infinity) => 1 << 0,
+        (true, Kind::Finite { .. }) if !subnormal => 1 << 1,
+        (true, Kind::Finite { .. }) => 1 << 2,
+        (true, Kind::Zero) => 1 << 3,
+        (false, Kind::Zero) => 1 << 4,
+        (false, Kind::Finite { .. }) if subnormal => 1 << 5,
+        (false, Kind::Finite { .. }) => 1 << 6,
+        (false, Kind::Infinity) => 1 << 7,
+        (_, Kind::SignalingNan) => 1 << 8,
+        (_, Kind::QuietNan) => 1 << 9,
+    }
+}
+
+/// Converts `a` to an integer of the given width and signedness, saturating
+/// and signaling invalid on values that are out of range.
+///
+/// The result is returned sign-extended to 64 bits from `width` bits.
+pub fn to_int<F: Format>(
+    a: u64,
+    width: u32,
+    signed: bool,
+    rm: RoundingMode,
+    flags: &mut u8,
+) -> u64 {
+    // The magnitudes of the most negative and most positive representable
+    // integers
+    let (min_magnitude, max_magnitude) = if signed {
+        (1u128 << (width - 1), (1u128 << (width - 1)) - 1)
+    } else {
+        (0, (1u128 << width) - 1)
+    };
+    let min = sign_extend((min_magnitude as u64).wrapping_neg(), width);
+    let max = sign_extend(max_magnitude as u64, width);
+
+    let (sign, kind) = unpack::<F>(a);
+
+    let (magnitude, inexact) = match kind {
+        Kind::Zero => return 0,
+        Kind::QuietNan | Kind::SignalingNan => {
+            *flags |= INVALID;
+            return max;
+        }
+        Kind::Infinity => (u128::MAX, false),
+        // Anything at least this large is out of range anyway
+        Kind::Finite { exp, .. } if exp >= 64 => (u128::MAX, false),
+        Kind::Finite { exp, sig } if exp >= 0 => ((sig as u128) << exp, false),
+        Kind::Finite { exp, sig } => shift_right_round(sig as u128, -exp as u32, rm, sign),
+    };
+
+    if sign && magnitude > min_magnitude {
+        *flags |= INVALID;
+        min
+    } else if !sign && magnitude > max_magnitude {
+        *flags |= INVALID;
+        max
+    } else {
+        if inexact {
+            *flags |= INEXACT;
+        }
+
+        let value = if sign {
+            (magnitude as u64).wrapping_neg()
+        } else {
+            magnitude as u64
+        };
+
+        sign_extend(value, width)
+    }
+}
+
+/// Converts the integer in the low `width` bits of `a`, with the given
+/// signedness, to `F`.
+pub fn from_int<F: Format>(
+    a: u64,
+    width: u32,
+    signed: bool,
+    rm: RoundingMode,
+    flags: &mut u8,
+) -> u64 {
+    let (sign, magnitude) = if signed {
+        let a = sign_extend(a, width) as i64;
+        (a < 0, a.unsigned_abs())
+    } else {
+        (false, a & u64::MAX >> (64 - width))
+    };
+
+    if magnitude == 0 {
+        0
+    } else {
+        round_pack::<F>(sign, 0, magnitude as u128, rm, flags)
+    }
+}
+
+/// Converts `a` from the format `F` to the format `T`.
+pub fn convert<F: Format, T: Format>(a: u64, rm: RoundingMode, flags: &mut u8) -> u64 {
+    match unpack::<F>(a) {
+        (_, Kind::SignalingNan) => {
+            *flags |= INVALID;
+            T::CANONICAL_NAN
+        }
+        (_, Kind::QuietNan) => T::CANONICAL_NAN,
+        (sign, Kind::Infinity) => infinity::<T>(sign),
+        (sign, Kind::Zero) => zero::<T>(sign),
+        (sign, Kind::Finite { exp, sig }) => round_pack::<T>(sign, exp, sig as u128, rm, flags),
+    }
+}
+
+/// Converts `a` from the format `F` to the narrower format `T`, rounding to
+/// odd: inexact results are truncated and have their least significant bit
+/// set.
+pub fn convert_round_to_odd<F: Format, T: Format>(a: u64, flags: &mut u8) -> u64 {
+    let mut inexact = 0;
+    let result = convert::<F, T>(a, RoundingMode::TowardZero, &mut inexact);
+
+    *flags |= inexact;
+
+    // Infinities and NaNs are always exact
+    if inexact & INEXACT != 0 {
+        result | 1
+    } else {
+        result
+    }
+}
+
+/// Estimates `1 / a` to 7 bits of precision, as defined for the `VFREC7`
+/// instruction.
+pub fn reciprocal_estimate<F: Format>(a: u64, rm: RoundingMode, flags: &mut u8) -> u64 {
+    let (sign, exp, frac) = match unpack::<F>(a) {
+        (sign, Kind::Zero) => {
+            *flags |= DIVIDE_BY_ZERO;
+            return infinity::<F>(sign);
+        }
+        (sign, Kind::Infinity) => return zero::<F>(sign),
+        (_, Kind::QuietNan) => return F::CANONICAL_NAN,
+        (_, Kind::SignalingNan) => {
+            *flags |= INVALID;
+            return F::CANONICAL_NAN;
+        }
+        (sign, Kind::Finite { exp, sig }) => (sign, normalized_exponent::<F>(exp), sig),
+    };
+
+    let exp = 2 * F::BIAS - 1 - exp;
+
+    // The reciprocals of the smallest subnormal values are too large to be
+    // represented
+    if exp >= F::EXP_MAX as i32 {
+        *flags |= OVERFLOW | INEXACT;
+
+        let to_infinity = match rm {
+            RoundingMode::NearestEven | RoundingMode::NearestMaxMagnitude => true,
+            RoundingMode::TowardZero => false,
+            RoundingMode::Down => sign,
+            RoundingMode::Up => !sign,
+        };
+
+        return zero::<F>(sign) | if to_infinity { F::INFINITY } else { F::MAX };
+    }
+
+    let index = (frac >> (F::FRAC_BITS - 7) & 0x7F) as usize;
+    let frac = (RECIPROCAL_ESTIMATES[index] as u64) << (F::FRAC_BITS - 7);
+
+    if exp > 0 {
+        zero::<F>(sign) | (exp as u64) << F::FRAC_BITS | frac
+    } else {
+        // The result is subnormal when the exponent is 0 or -1
+        zero::<F>(sign) | (frac | 1 << F::FRAC_BITS) >> (1 - exp)
+    }
+}
+
+/// Estimates `1 / sqrt(a)` to 7 bits of precision, as defined for the
+/// `VFRSQRT7` instruction.
+pub fn reciprocal_sqrt_estimate<F: Format>(a: u64, flags: &mut u8) -> u64 {
+    let (exp, frac) = match unpack::<F>(a) {
+        (sign, Kind::Zero) => {
+            *flags |= DIVIDE_BY_ZERO;
+            return infinity::<F>(sign);
+        }
+        (false, Kind::Infinity) => return 0,
+        (_, Kind::QuietNan) => return F::CANONICAL_NAN,
+        (true, _) | (_, Kind::SignalingNan) => {
+            *flags |= INVALID;
+            return F::CANONICAL_NAN;
+        }
+        (false, Kind::Finite { exp, sig }) => (normalized_exponent::<F>(exp), sig),
+    };
+
+    let index = ((exp & 1) as u64) << 6 | frac >> (F::FRAC_BITS - 6) & 0x3F;
+    let frac = (RECIPROCAL_SQRT_ESTIMATES[index as usize] as u64) << (F::FRAC_BITS - 7);
+    let exp = (3 * F::BIAS - 1 - exp) / 2;
+
+    (exp as u64) << F::FRAC_BITS | frac
+}
+
+/// Gets the biased exponent of the finite value `sig * 2^exp` as if it were
+/// normalized, which is zero or negative for subnormal values.
+const fn normalized_exponent<F: Format>(exp: i32) -> i32 {
+    exp + F::FRAC_BITS as i32 + F::BIAS
+}
+
+/// Sign-extends the low `width` bits of `value`.
+const fn sign_extend(value: u64, width: u32) -> u64 {
+    ((value << (64 - width)) as i64 >> (64 - width)) as u64
+}
+
+/// The 7 most significant fraction bits of the reciprocal of each value with
+/// the given 7 most significant fraction bits.
+#[rustfmt::skip]
+const RECIPROCAL_ESTIMATES: [u8; 128] = [
+    127, 125, 123, 121, 119, 117, 116, 114, 112, 110, 109, 107, 105, 104, 102, 100,
+    99, 97, 96, 94, 93, 91, 90, 88, 87, 85, 84, 83, 81, 80, 79, 77,
+    76, 75, 74, 72, 71, 70, 69, 68, 66, 65, 64, 63, 62, 61, 60, 59,
+    58, 57, 56, 55, 54, 53, 52, 51, 50, 49, 48, 47, 46, 45, 44, 43,
+    42, 41, 40, 40, 39, 38, 37, 36, 35, 35, 34, 33, 32, 31, 31, 30,
+    29, 28, 28, 27, 26, 25, 25, 24, 23, 23, 22, 21, 21, 20, 19, 19,
+    18, 17, 17, 16, 15, 15, 14, 14, 13, 12, 12, 11, 11, 10, 9, 9,
+    8, 8, 7, 7, 6, 5, 5, 4, 4, 3, 3, 2, 2, 1, 1, 0,
+];
+
+/// The 7 most significant fraction bits of the reciprocal square root of each
+/// value with the given least significant exponent bit and 6 most significant
+/// fraction bits.
+#[rustfmt::skip]
+const RECIPROCAL_SQRT_ESTIMATES: [u8; 128] = [
+    52, 51, 50, 48, 47, 46, 44, 43, 42, 41, 40, 39, 38, 36, 35, 34,
+    33, 32, 31, 30, 30, 29, 28, 27, 26, 25, 24, 23, 23, 22, 21, 20,
+    19, 19, 18, 17, 16, 16, 15, 14, 14, 13, 12, 12, 11, 10, 10, 9,
+    9, 8, 7, 7, 6, 6, 5, 4, 4, 3, 3, 2, 2, 1, 1, 0,
+    127, 125, 123, 121, 119, 118, 116, 114, 113, 111, 109, 108, 106, 105, 103, 102,
+    100, 99, 97, 96, 95, 93, 92, 91, 90, 88, 87, 86, 85, 84, 83, 82,
+    80, 79, 78, 77, 76, 75, 74, 73, 72, 71, 70, 70, 69, 68, 67, 66,
+    65, 64, 63, 63, 62, 61, 60, 59, 59, 58, 57, 56, 56, 55, 54, 53,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use RoundingMode::*;
+
+    const MODES: [RoundingMode; 5] = [NearestEven, TowardZero, Down, Up, NearestMaxMagnitude];
+
+    const ONE: u64 = 0x3F80_0000;
+    const MINUS_ONE: u64 = 0xBF80_0000;
+    /// 2^-24, which is half of the gap between 1 and the next value.
+    const HALF_ULP: u64 = 0x3380_0000;
+    const SIGNALING_NAN: u64 = 0x7F80_0001;
+    const QUIET_NAN: u64 = 0x7FC1_2345;
+
+    /// Calls `f` with a fresh set of flags, returning its result and the
+    /// flags that it raised.
+    fn flags(f: impl FnOnce(&mut u8) -> u64) -> (u64, u8) {
+        let mut flags = 0;
+        let result = f(&mut flags);
+
+        (result, flags)
+    }
+
+    #[test]
+    fn test_rounding_modes() {
+        // Ties between 1 and the next value, and between the two values below
+        // -1, for each of the rounding modes in order
+        let expected = [
+            (ONE, 0xBF80_0000),
+            (ONE, 0xBF80_0000),
+            (ONE, 0xBF80_0001),
+            (0x3F80_0001, 0xBF80_0000),
+            (0x3F80_0001, 0xBF80_0001),
+        ];
+
+        for (rm, (positive, negative)) in MODES.into_iter().zip(expected) {
+            let sum = flags(|f| add::<F32>(ONE, HALF_ULP, rm, f));
+            assert_eq!(sum, (positive, INEXACT));
+
+            let sum = flags(|f| add::<F32>(MINUS_ONE, HALF_ULP | F32::SIGN, rm, f));
+            assert_eq!(sum, (negative, INEXACT));
+        }
+
+        // A tie whose even neighbour is above it rounds up
+        let sum = flags(|f| add::<F32>(0x3F80_0001, HALF_ULP, NearestEven, f));
+        assert_eq!(sum, (0x3F80_0002, INEXACT));
+
+        // Exact results raise no flags in any mode
+        for rm in MODES {
+            assert_eq!(flags(|f| add::<F32>(ONE, ONE, rm, f)), (0x4000_0000, 0));
+        }
+    }
+
+    #[test]
+    fn test_zero_sign() {
+        // An exact zero sum is negative only when rounding down
+        for rm in MODES {
+            let expected = if rm == Down { F32::SIGN } else { 0 };
+
+            assert_eq!(flags(|f| add::<F32>(ONE, MINUS_ONE, rm, f)), (expected, 0));
+            assert_eq!(flags(|f| sub::<F64>(1, 1, rm, f)), (expected << 32, 0));
+        }
+    }
+
+    #[test]
+    fn test_overflow() {
+        let expected = [
+            F32::INFINITY,
+            F32::MAX,
+            F32::MAX,
+            F32::INFINITY,
+            F32::INFINITY,
+        ];
+
+        for (rm, expected) in MODES.into_iter().zip(expected) {
+            let sum = flags(|f| add::<F32>(F32::MAX, F32::MAX, rm, f));
+            assert_eq!(sum, (expected, OVERFLOW | INEXACT));
+
+            // Rounding down and up are mirrored for negative values
+            let negative = flags(|f| add::<F32>(F32::MAX | F32::SIGN, F32::MAX | F32::SIGN, rm, f));
+            let expected = match rm {
+                Down => F32::INFINITY,
+                Up => F32::MAX,
+                _ => expected,
+            };
+
+            assert_eq!(negative, (expected | F32::SIGN, OVERFLOW | INEXACT));
+        }
+    }
+
+    #[test]
+    fn test_underflow() {
+        // The largest subnormal value times 1 + 2^-23 is just below the
+        // smallest normal value, but rounds up to it before tininess is
+        // detected unless rounding towards zero
+        const LARGEST_SUBNORMAL: u64 = 0x007F_FFFF;
+
+        let product = flags(|f| mul::<F32>(LARGEST_SUBNORMAL, 0x3F80_0001, NearestEven, f));
+        assert_eq!(product, (0x0080_0000, INEXACT));
+
+        let product = flags(|f| mul::<F32>(LARGEST_SUBNORMAL, 0x3F80_0001, TowardZero, f));
+        assert_eq!(product, (LARGEST_SUBNORMAL, UNDERFLOW | INEXACT));
+
+        // Exact subnormal results are not flagged
+        let product = flags(|f| mul::<F32>(LARGEST_SUBNORMAL, ONE, NearestEven, f));
+        assert_eq!(product, (LARGEST_SUBNORMAL, 0));
+    }
+
+    #[test]
+    fn test_fused_multiply_add() {
+        // (1 + 2^-23)^2 - (1 + 2^-22) is exactly 2^-46, which would be lost
+        // if the product were rounded before the addition
+        let result = flags(|f| fma::<F32>(0x3F80_0001, 0x3F80_0001, 0xBF80_0002, NearestEven, f));
+        assert_eq!(result, (0x2880_0000, 0));
+
+        // (1 + 2^-23)^2 is 1 + 2^-22 + 2^-46, which is rounded once
+        let expected = [
+            0x3F80_0002,
+            0x3F80_0002,
+            0x3F80_0002,
+            0x3F80_0003,
+            0x3F80_0002,
+        ];
+
+        for (rm, expected) in MODES.into_iter().zip(expected) {
+            let result = flags(|f| fma::<F32>(0x3F80_0001, 0x3F80_0001, 0, rm, f));
+            assert_eq!(result, (expected, INEXACT));
+        }
+
+        // The invalid multiplication is signaled even with a NaN addend
+        let result = flags(|f| fma::<F32>(F32::INFINITY, 0, QUIET_NAN, NearestEven, f));
+        assert_eq!(result, (F32::CANONICAL_NAN, INVALID));
+    }
+
+    #[test]
+    fn test_canonical_nan() {
+        let rm = NearestEven;
+
+        // Quiet NaNs propagate as the canonical NaN without signaling
+        assert_eq!(
+            flags(|f| add::<F32>(QUIET_NAN, ONE, rm, f)),
+            (F32::CANONICAL_NAN, 0)
+        );
+        assert_eq!(
+            flags(|f| mul::<F32>(ONE, QUIET_NAN | F32::SIGN, rm, f)),
+            (F32::CANONICAL_NAN, 0)
+        );
+
+        // Signaling NaNs and invalid operations signal
+        let invalid = (F32::CANONICAL_NAN, INVALID);
+
+        assert_eq!(flags(|f| add::<F32>(SIGNALING_NAN, ONE, rm, f)), invalid);
+        assert_eq!(
+            flags(|f| sub::<F32>(F32::INFINITY, F32::INFINITY, rm, f)),
+            invalid
+        );
+        assert_eq!(flags(|f| div::<F32>(0, 0, rm, f)), invalid);
+        assert_eq!(flags(|f| sqrt::<F32>(MINUS_ONE, rm, f)), invalid);
+
+        assert_eq!(
+            flags(|f| add::<F64>(0x7FF0_0000_0000_0001, 0, rm, f)),
+            (0x7FF8_0000_0000_0000, INVALID)
+        );
+
+        // Conversions do not preserve the payload either
+        assert_eq!(
+            flags(|f| convert::<F32, F64>(QUIET_NAN, rm, f)),
+            (F64::CANONICAL_NAN, 0)
+        );
+        assert_eq!(
+            flags(|f| convert::<F64, F32>(0xFFF0_0000_0000_0001, rm, f)),
+            (F32::CANONICAL_NAN, INVALID)
+        );
+    }
+
+    #[test]
+    fn test_min_max() {
+        const MINUS_ZERO: u64 = F32::SIGN;
+
+        // -0 is less than +0
+        for (a, b) in [(0, MINUS_ZERO), (MINUS_ZERO, 0)] {
+            assert_eq!(flags(|f| min_max::<F32>(a, b, false, f)), (MINUS_ZERO, 0));
+            assert_eq!(flags(|f| min_max::<F32>(a, b, true, f)), (0, 0));
+        }
+
+        // A NaN operand is ignored, but signals if it is signaling
+        assert_eq!(
+            flags(|f| min_max::<F32>(QUIET_NAN, ONE, false, f)),
+            (ONE, 0)
+        );
+        assert_eq!(flags(|f| min_max::<F32>(ONE, QUIET_NAN, true, f)), (ONE, 0));
+        assert_eq!(
+            flags(|f| min_max::<F32>(SIGNALING_NAN, MINUS_ONE, true, f)),
+            (MINUS_ONE, INVALID)
+        );
+
+        // Two NaNs give the canonical NaN
+        assert_eq!(
+            flags(|f| min_max::<F32>(QUIET_NAN, QUIET_NAN, false, f)),
+            (F32::CANONICAL_NAN, 0)
+        );
+        assert_eq!(
+            flags(|f| min_max::<F32>(QUIET_NAN, SIGNALING_NAN, true, f)),
+            (F32::CANONICAL_NAN, INVALID)
+        );
+    }
+
+    #[test]
+    fn test_to_int() {
+        const MINUS_POINT_3: u64 = 0xBE99_999A;
+        const TWO_POINT_5: u64 = 0x4020_0000;
+
+        // FCVT.WU of -0.3 rounds to zero, which is in range
+        let result = flags(|f| to_int::<F32>(MINUS_POINT_3, 32, false, NearestEven, f));
+        assert_eq!(result, (0, INEXACT));
+
+        // Rounding down gives -1, which is out of range
+        let result = flags(|f| to_int::<F32>(MINUS_POINT_3, 32, false, Down, f));
+        assert_eq!(result, (0, INVALID));
+
+        let expected = [2, 2, 2, 3, 3];
+
+        for (rm, expected) in MODES.into_iter().zip(expected) {
+            let result = flags(|f| to_int::<F32>(TWO_POINT_5, 32, true, rm, f));
+            assert_eq!(result, (expected, INEXACT));
+
+            let result = flags(|f| to_int::<F32>(TWO_POINT_5 | F32::SIGN, 64, true, rm, f));
+            let expected = match rm {
+                Down => 3,
+                Up => 2,
+                _ => expected,
+            };
+
+            assert_eq!(result, (expected.wrapping_neg(), INEXACT));
+        }
+
+        // Out-of-range values and NaNs saturate, with 32-bit results
+        // sign-extended
+        let rm = NearestEven;
+        let cases = [
+            (F32::INFINITY, 32, true, 0x7FFF_FFFF),
+            (F32::INFINITY | F32::SIGN, 32, true, 0xFFFF_FFFF_8000_0000),
+            (F32::INFINITY, 32, false, u64::MAX),
+            (MINUS_ONE, 64, false, 0),
+            (0x4F80_0000, 32, false, u64::MAX),
+            (0x5F00_0000, 64, true, i64::MAX as u64),
+            (QUIET_NAN | F32::SIGN, 32, true, 0x7FFF_FFFF),
+            (SIGNALING_NAN, 64, false, u64::MAX),
+        ];
+
+        for (a, width, signed, expected) in cases {
+            let result = flags(|f| to_int::<F32>(a, width, signed, rm, f));
+            assert_eq!(result, (expected, INVALID), "{a:#x} to {width} bits");
+        }
+
+        // The most negative value is in range
+        let result = flags(|f| to_int::<F32>(0xDF00_0000, 64, true, rm, f));
+        assert_eq!(result, (i64::MIN as u64, 0));
+    }
+}
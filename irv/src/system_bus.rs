@@ -0,0 +1,372 @@
+//! A system bus that decodes physical addresses to the devices mapped into it.
+
+use alloc::{boxed::Box, sync::Arc, vec::Vec};
+use core::{cell::RefCell, mem::size_of};
+
+use crate::{
+    AccessKind, AtomicBus, Bus, BusError, Device, InterruptLines, Interrupts, MemoryAccess,
+};
+
+/// The size in bytes of each reservation set tracked by a [SystemBus].
+const RESERVATION_GRANULE: u64 = 8;
+
+/// An error returned when a device cannot be mapped into a [SystemBus].
+#[derive(Debug, PartialEq, Eq)]
+pub enum MapError {
+    /// The range is empty or extends past the end of the address space.
+    InvalidRange,
+    /// The range overlaps the range of a device that is already mapped.
+    Overlap,
+}
+
+/// A device mapped into a [SystemBus].
+struct Mapping {
+    base: u64,
+    size: u64,
+    device: Box<dyn Device>,
+}
+
+/// A bus that routes each access to the device mapped at its address, such as
+/// memory, ROM, or memory-mapped I/O.
+///
+/// Devices are accessed with offsets relative to the start of their mappings,
+/// and instruction fetches are made with [Device::fetch].
+/// Accesses to addresses where no device is mapped, or that extend past the
+/// end of a mapping, are access faults.
+///
+/// The bus also provides the [InterruptLines] of each hart, and tracks the
+/// reservations of each hart itself, so atomic memory operations work with
+/// every device. Like [Memory](crate::Memory), it is not shared between
+/// threads, so a load followed by a store is atomic.
+pub struct SystemBus {
+    /// The mapped devices, sorted by base address.
+    mappings: Vec<Mapping>,
+    /// The interrupt lines of each hart.
+    lines: Vec<Arc<InterruptLines>>,
+    /// The harts holding reservations and the indices of their reservation
+    /// sets.
+    reservations: RefCell<Vec<(u64, u64)>>,
+}
+
+impl SystemBus {
+    /// Creates a new bus without any devices, with interrupt lines for harts
+    /// 0 through `harts - 1`.
+    pub fn new(harts: usize) -> SystemBus {
+        SystemBus {
+            mappings: Vec::new(),
+            lines: (0..harts)
+                .map(|_| Arc::new(InterruptLines::new()))
+                .collect(),
+            reservations: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Maps the given `device` to the `size` bytes starting at `base`.
+    ///
+    /// A type implementing [Bus] for every access width, such as
+    /// [Memory](crate::Memory), can be mapped with [BusDevice](crate::BusDevice).
+    pub fn map(
+        &mut self,
+        base: u64,
+        size: u64,
+        device: impl Device + 'static,
+    ) -> Result<(), MapError> {
+        if size == 0 || base.checked_add(size - 1).is_none() {
+            return Err(MapError::InvalidRange);
+        }
+
+        // The mappings on either side of the new one must not reach into it
+        let index = self.mappings.partition_point(|mapping| mapping.base < base);
+
+        let overlaps_previous = index > 0 && {
+            let previous = &self.mappings[index - 1];
+
+            base - previous.base < previous.size
+        };
+
+        let overlaps_next = self
+            .mappings
+            .get(index)
+            .is_some_and(|next| next.base - base < size);
+
+        if overlaps_previous || overlaps_next {
+            return Err(MapError::Overlap);
+        }
+
+        self.mappings.insert(
+            index,
+            Mapping {
+                base,
+                size,
+                device: Box::new(device),
+            },
+        );
+
+        Ok(())
+    }
+
+    /// Gets the interrupt lines of the given `hart`, which devices can raise
+    /// interrupts through.
+    pub fn lines(&self, hart: u64) -> Option<&Arc<InterruptLines>> {
+        self.lines.get(hart as usize)
+    }
+
+    /// Finds the device mapped at the given `address`, returning it along
+    /// with the offset of the address within it, if the access of the given
+    /// `size` fits within its mapping.
+    fn decode(&self, address: u64, size: u64) -> Result<(&dyn Device, u64), BusError> {
+        let index = self
+            .mappings
+            .partition_point(|mapping| mapping.base <= address);
+
+        let mapping = match index {
+            0 => return Err(BusError::AccessFault),
+            _ => &self.mappings[index - 1],
+        };
+
+        let offset = address - mapping.base;
+
+        if offset < mapping.size && size - 1 <= mapping.size - 1 - offset {
+            Ok((&*mapping.device, offset))
+        } else {
+            Err(BusError::AccessFault)
+        }
+    }
+
+    /// Invalidates the reservations covering any byte of a store of `size`
+    /// bytes to `address`.
+    fn invalidate_reservations(&self, address: u64, size: u64) {
+        let first = address / RESERVATION_GRANULE;
+        let last = address.wrapping_add(size - 1) / RESERVATION_GRANULE;
+
+        self.reservations
+            .borrow_mut()
+            .retain(|&(_, reserved)| reserved != first && reserved != last);
+    }
+}
+
+impl Interrupts for SystemBus {
+    fn pending(&self, hart: u64) -> u64 {
+        self.lines(hart).map_or(0, |lines| lines.pending())
+    }
+
+    fn triggered(&self, hart: u64) -> u64 {
+        self.lines(hart).map_or(0, |lines| lines.take_triggered())
+    }
+}
+
+macro_rules! impl_bus {
+    ($($val:ident)*) => {
+        $(impl Bus<u64, $val> for SystemBus {
+            fn load(&self, address: u64) -> Result<$val, BusError> {
+                let size = size_of::<$val>() as u64;
+                let (device, offset) = self.decode(address, size)?;
+
+                Ok(device.read(offset, size)? as $val)
+            }
+
+            fn load_with(&self, address: u64, access: MemoryAccess) -> Result<$val, BusError> {
+                let size = size_of::<$val>() as u64;
+                let (device, offset) = self.decode(address, size)?;
+
+                let value = match access.kind {
+                    AccessKind::Fetch => device.fetch(offset, size)?,
+                    _ => device.read(offset, size)?,
+                };
+
+                Ok(value as $val)
+            }
+
+            fn store(&self, address: u64, value: $val) -> Result<(), BusError> {
+                let size = size_of::<$val>() as u64;
+                let (device, offset) = self.decode(address, size)?;
+
+                self.invalidate_reservations(address, size);
+
+                device.write(offset, size, value as u64)
+            }
+        })*
+    };
+}
+
+impl_bus! { u8 u16 u32 u64 }
+
+macro_rules! impl_atomic_bus {
+    ($($val:ident)*) => {
+        $(impl AtomicBus<u64, $val> for SystemBus {
+            fn load_reserved(&self, hart: u64, address: u64) -> Result<$val, BusError> {
+                let value = self.load(address)?;
+                let mut reservations = self.reservations.borrow_mut();
+
+                // Each hart holds at most one reservation
+                reservations.retain(|&(holder, _)| holder != hart);
+                reservations.push((hart, address / RESERVATION_GRANULE));
+
+                Ok(value)
+            }
+
+            fn store_conditional(
+                &self,
+                hart: u64,
+                address: u64,
+                value: $val,
+            ) -> Result<bool, BusError> {
+                let reservation = (hart, address / RESERVATION_GRANULE);
+                let mut reservations = self.reservations.borrow_mut();
+                let reserved = reservations.contains(&reservation);
+
+                // The reservation is invalidated whether or not the store succeeds
+                reservations.retain(|&(holder, _)| holder != hart);
+                drop(reservations);
+
+                if reserved {
+                    self.store(address, value)?;
+                }
+
+                Ok(reserved)
+            }
+
+            fn fetch_update(
+                &self,
+                address: u64,
+                f: impl FnOnce($val) -> $val,
+            ) -> Result<$val, BusError> {
+                let value = self.load(address)?;
+
+                self.store(address, f(value))?;
+
+                Ok(value)
+            }
+        })*
+    };
+}
+
+impl_atomic_bus! { u32 u64 }
+
+/// A read-only view of a device, such as memory used as ROM, on which writes
+/// are access faults.
+#[derive(Debug)]
+pub struct ReadOnly<D>(pub D);
+
+impl<D: Device> Device for ReadOnly<D> {
+    fn read(&self, offset: u64, size: u64) -> Result<u64, BusError> {
+        self.0.read(offset, size)
+    }
+
+    fn write(&self, _offset: u64, _size: u64, _value: u64) -> Result<(), BusError> {
+        Err(BusError::AccessFault)
+    }
+
+    fn fetch(&self, offset: u64, size: u64) -> Result<u64, BusError> {
+        self.0.fetch(offset, size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::*;
+    use crate::{BusDevice, Memory};
+
+    fn memory(size: u64) -> BusDevice<Memory<Vec<u64>>> {
+        BusDevice(Memory::new(vec![0; size as usize / 8]))
+    }
+
+    #[test]
+    fn test_map() {
+        let mut bus = SystemBus::new(1);
+
+        assert_eq!(bus.map(0x1000, 0x1000, memory(0x1000)), Ok(()));
+
+        // Any overlap with an existing mapping is rejected
+        for (base, size) in [(0x1000, 0x1000), (0x1FF8, 8), (0xF00, 0x200), (0, 0x10000)] {
+            assert_eq!(bus.map(base, size, memory(size)), Err(MapError::Overlap));
+        }
+
+        // Adjacent mappings are not overlaps
+        assert_eq!(bus.map(0x2000, 0x1000, memory(0x1000)), Ok(()));
+        assert_eq!(bus.map(0, 0x1000, memory(0x1000)), Ok(()));
+
+        // The range must be nonempty and within the address space, which
+        // includes its last byte
+        assert_eq!(bus.map(0x8000, 0, memory(0)), Err(MapError::InvalidRange));
+        assert_eq!(
+            bus.map(u64::MAX - 7, 16, memory(16)),
+            Err(MapError::InvalidRange)
+        );
+        assert_eq!(bus.map(u64::MAX - 7, 8, memory(8)), Ok(()));
+    }
+
+    #[test]
+    fn test_decode() {
+        let mut bus = SystemBus::new(1);
+
+        bus.map(0x1000, 0x1000, memory(0x1000)).unwrap();
+        bus.map(0x2000, 0x1000, memory(0x1000)).unwrap();
+
+        // Devices are accessed relative to their base address
+        Bus::<u64, u32>::store(&bus, 0x2004, 0x1234_5678).unwrap();
+        assert_eq!(Bus::<u64, u32>::load(&bus, 0x2004).unwrap(), 0x1234_5678);
+
+        let load = |address| Bus::<u64, u32>::load(&bus, address);
+
+        assert!(matches!(load(0xFFC), Err(BusError::AccessFault)));
+        assert!(matches!(load(0x3000), Err(BusError::AccessFault)));
+        assert!(matches!(load(u64::MAX - 3), Err(BusError::AccessFault)));
+
+        // Accesses must fit within a single mapping, even if the next one is
+        // adjacent
+        assert!(matches!(load(0x1FFE), Err(BusError::AccessFault)));
+        assert!(matches!(load(0x2FFE), Err(BusError::AccessFault)));
+        assert!(matches!(
+            Bus::<u64, u64>::store(&bus, 0x2FFC, 0),
+            Err(BusError::AccessFault)
+        ));
+
+        assert!(load(0x1FFC).is_ok());
+        assert!(load(0x2FFC).is_ok());
+    }
+
+    #[test]
+    fn test_reservations() {
+        let mut bus = SystemBus::new(2);
+
+        bus.map(0x1000, 0x1000, memory(0x1000)).unwrap();
+
+        let load_reserved =
+            |hart, address| AtomicBus::<u64, u32>::load_reserved(&bus, hart, address);
+        let store_conditional =
+            |hart, address| AtomicBus::<u64, u32>::store_conditional(&bus, hart, address, 1);
+
+        // A store by another hart anywhere in the reservation set clears it
+        load_reserved(0, 0x1010).unwrap();
+        Bus::<u64, u8>::store(&bus, 0x1017, 0).unwrap();
+        assert!(!store_conditional(0, 0x1010).unwrap());
+
+        // Stores outside of it do not, including those just before it
+        load_reserved(0, 0x1010).unwrap();
+        Bus::<u64, u64>::store(&bus, 0x1008, 0).unwrap();
+        Bus::<u64, u64>::store(&bus, 0x1018, 0).unwrap();
+        assert!(store_conditional(0, 0x1010).unwrap());
+        assert_eq!(Bus::<u64, u32>::load(&bus, 0x1010).unwrap(), 1);
+
+        // A store conditional always clears the reservation
+        assert!(!store_conditional(0, 0x1010).unwrap());
+
+        // Each hart has its own reservation, which is cleared by a store
+        // conditional from another hart
+        load_reserved(0, 0x1020).unwrap();
+        load_reserved(1, 0x1020).unwrap();
+        assert!(store_conditional(1, 0x1020).unwrap());
+        assert!(!store_conditional(0, 0x1020).unwrap());
+
+        // A new reservation replaces the old one
+        load_reserved(0, 0x1030).unwrap();
+        load_reserved(0, 0x1040).unwrap();
+        assert!(!store_conditional(0, 0x1030).unwrap());
+
+        // A failed store conditional does not store
+        assert_eq!(Bus::<u64, u32>::load(&bus, 0x1030).unwrap(), 0);
+    }
+}
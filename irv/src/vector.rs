@@ -0,0 +1,174 @@
+//! The state of the V extension.
+
+use alloc::{vec, vec::Vec};
+
+use crate::{CsrAddress, CsrIllegal, Xlen};
+
+/// The vector register file of a hart, along with the state of the vector
+/// CSRs.
+///
+/// VLEN (the number of bits in each register) is chosen when the register file
+/// is created, and ELEN (the widest supported element) is always 64 bits.
+/// Registers are held in little-endian order, so element `i` of a register
+/// group with `n`-byte elements starts at byte `i * n` of its first register,
+/// continuing into the following registers.
+///
+/// At reset, `vtype.vill` is set and `vl` is zero, so software must execute a
+/// `vset{i}vl{i}` instruction before any instruction that depends on `vtype`.
+#[derive(Clone, Debug)]
+pub struct VectorRegisters {
+    /// The number of bytes in each register (VLEN / 8).
+    vlenb: usize,
+    /// The contents of v0 through v31, one after another.
+    data: Vec<u8>,
+    /// The fields of `vtype` other than `vill`.
+    pub(crate) vtype: u64,
+    /// Whether `vtype` holds an unsupported configuration.
+    pub(crate) vill: bool,
+    /// The vector length.
+    pub(crate) vl: u64,
+    /// The index of the first element to be executed by the next vector
+    /// instruction.
+    pub(crate) vstart: u64,
+    /// The fixed-point rounding mode.
+    pub(crate) vxrm: u64,
+    /// The fixed-point accrued saturation flag.
+    pub(crate) vxsat: bool,
+}
+
+impl VectorRegisters {
+    /// Creates a new register file with the given VLEN in bits, with every
+    /// register set to zero.
+    ///
+    /// # Panics
+    /// Panics if `vlen` is not a power of two between 64 and 65536.
+    pub fn new(vlen: usize) -> VectorRegisters {
+        assert!(
+            vlen.is_power_of_two() && (64..=65536).contains(&vlen),
+            "VLEN must be a power of two between 64 and 65536"
+        );
+
+        VectorRegisters {
+            vlenb: vlen / 8,
+            data: vec![0; vlen / 8 * 32],
+            vtype: 0,
+            vill: true,
+            vl: 0,
+            vstart: 0,
+            vxrm: 0,
+            vxsat: false,
+        }
+    }
+
+    /// Gets the number of bits in each register.
+    pub const fn vlen(&self) -> usize {
+        self.vlenb * 8
+    }
+
+    /// Gets the current vector length.
+    pub const fn vl(&self) -> u64 {
+        self.vl
+    }
+
+    /// Gets the contents of the register with the given index.
+    ///
+    /// # Panics
+    /// Panics if `index` is not less than 32.
+    pub fn register(&self, index: usize) -> &[u8] {
+        assert!(index < 32, "there are only 32 vector registers");
+
+        &self.data[index * self.vlenb..(index + 1) * self.vlenb]
+    }
+
+    /// Gets the contents of the register with the given index mutably.
+    ///
+    /// # Panics
+    /// Panics if `index` is not less than 32.
+    pub fn register_mut(&mut self, index: usize) -> &mut [u8] {
+        assert!(index < 32, "there are only 32 vector registers");
+
+        &mut self.data[index * self.vlenb..(index + 1) * self.vlenb]
+    }
+
+    /// Gets the number of bytes in each register.
+    pub(crate) const fn vlenb(&self) -> usize {
+        self.vlenb
+    }
+
+    /// Gets element `index` of the register group starting at `register`,
+    /// whose elements are `width` bytes wide, zero-extended.
+    pub(crate) fn element(&self, register: usize, index: usize, width: usize) -> u64 {
+        let start = register * self.vlenb + index * width;
+        let mut bytes = [0; 8];
+
+        bytes[..width].copy_from_slice(&self.data[start..start + width]);
+        u64::from_le_bytes(bytes)
+    }
+
+    /// Sets element `index` of the register group starting at `register`,
+    /// whose elements are `width` bytes wide, to the low bytes of `value`.
+    pub(crate) fn set_element(&mut self, register: usize, index: usize, width: usize, value: u64) {
+        let start = register * self.vlenb + index * width;
+
+        self.data[start..start + width].copy_from_slice(&value.to_le_bytes()[..width]);
+    }
+
+    /// Gets bit `index` of the mask held in `register`.
+    pub(crate) fn mask(&self, register: usize, index: usize) -> bool {
+        self.data[register * self.vlenb + index / 8] >> (index % 8) & 1 != 0
+    }
+
+    /// Sets bit `index` of the mask held in `register`.
+    pub(crate) fn set_mask(&mut self, register: usize, index: usize, value: bool) {
+        let byte = &mut self.data[register * self.vlenb + index / 8];
+
+        *byte = *byte & !(1 << (index % 8)) | (value as u8) << (index % 8);
+    }
+
+    /// Accesses one of the vector CSRs, where `vl`, `vtype`, and `vlenb` are
+    /// read-only.
+    pub(crate) fn access_csr(
+        &mut self,
+        address: CsrAddress,
+        xlen: Xlen,
+        f: impl FnOnce(u64) -> u64,
+    ) -> Result<u64, CsrIllegal> {
+        match address {
+            CsrAddress::VSTART => {
+                let value = self.vstart;
+                // Only enough bits to hold the largest element index exist
+                self.vstart = f(value) & (self.vlen() as u64 - 1);
+                Ok(value)
+            }
+            CsrAddress::VXSAT => {
+                let value = self.vxsat as u64;
+                self.vxsat = f(value) & 1 != 0;
+                Ok(value)
+            }
+            CsrAddress::VXRM => {
+                let value = self.vxrm;
+                self.vxrm = f(value) & 0b11;
+                Ok(value)
+            }
+            CsrAddress::VCSR => {
+                let value = self.vxrm << 1 | self.vxsat as u64;
+                let new_value = f(value);
+                self.vxrm = new_value >> 1 & 0b11;
+                self.vxsat = new_value & 1 != 0;
+                Ok(value)
+            }
+            CsrAddress::VL => Ok(self.vl),
+            CsrAddress::VTYPE => Ok((self.vill as u64) << (xlen.bits() - 1) | self.vtype),
+            CsrAddress::VLENB => Ok(self.vlenb as u64),
+            _ => Err(CsrIllegal),
+        }
+    }
+}
+
+impl Default for VectorRegisters {
+    /// Creates a register file with a VLEN of 128 bits, the minimum required
+    /// by the V extension.
+    fn default() -> VectorRegisters {
+        VectorRegisters::new(128)
+    }
+}
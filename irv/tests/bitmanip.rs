@@ -0,0 +1,198 @@
+//! Checks the instructions of the Zba, Zbb, Zbc, and Zbs extensions where
+//! they differ between RV32 and RV64, share encodings with the base shifts,
+//! or are disabled.
+
+mod common;
+
+use common::{is_illegal, Hart};
+
+// Each instruction reads rs1 from x11 and rs2 from x12, and writes rd to x10
+const RD: u32 = 10 << 7;
+const RS1: u32 = 11 << 15;
+const RS2: u32 = 12 << 20;
+
+const fn op(funct7: u32, funct3: u32) -> u32 {
+    funct7 << 25 | RS2 | RS1 | funct3 << 12 | RD | 0b0110011
+}
+
+const fn op_imm(imm: u32, funct3: u32) -> u32 {
+    imm << 20 | RS1 | funct3 << 12 | RD | 0b0010011
+}
+
+const fn op_imm_32(imm: u32, funct3: u32) -> u32 {
+    imm << 20 | RS1 | funct3 << 12 | RD | 0b0011011
+}
+
+const SH2ADD: u32 = op(0b0010000, 0b100);
+const CLZ: u32 = op_imm(0x600, 0b001);
+const CTZ: u32 = op_imm(0x601, 0b001);
+const CPOP: u32 = op_imm(0x602, 0b001);
+const CLZW: u32 = op_imm_32(0x600, 0b001);
+const CTZW: u32 = op_imm_32(0x601, 0b001);
+const ORC_B: u32 = op_imm(0x287, 0b101);
+const REV8_32: u32 = op_imm(0x698, 0b101);
+const REV8_64: u32 = op_imm(0x6B8, 0b101);
+const ANDN: u32 = op(0b0100000, 0b111);
+const MAX: u32 = op(0b0000101, 0b110);
+const CLMUL: u32 = op(0b0000101, 0b001);
+const CLMULR: u32 = op(0b0000101, 0b010);
+const CLMULH: u32 = op(0b0000101, 0b011);
+const BSET: u32 = op(0b0010100, 0b001);
+
+const fn slli(shamt: u32) -> u32 {
+    op_imm(shamt, 0b001)
+}
+
+const fn srai(shamt: u32) -> u32 {
+    op_imm(0x400 | shamt, 0b101)
+}
+
+const fn rori(shamt: u32) -> u32 {
+    op_imm(0x600 | shamt, 0b101)
+}
+
+const fn bseti(shamt: u32) -> u32 {
+    op_imm(0x280 | shamt, 0b001)
+}
+
+const fn slliw(shamt: u32) -> u32 {
+    op_imm_32(shamt, 0b001)
+}
+
+const fn slli_uw(shamt: u32) -> u32 {
+    op_imm_32(0x080 | shamt, 0b001)
+}
+
+const fn roriw(shamt: u32) -> u32 {
+    op_imm_32(0x600 | shamt, 0b101)
+}
+
+#[test]
+fn test_bit_counts() {
+    let mut hart = Hart::new("imacsu");
+
+    // Zero has XLEN leading and trailing zeros
+    assert_eq!(hart.compute(CLZ, 0, 0), 64);
+    assert_eq!(hart.compute(CTZ, 0, 0), 64);
+    assert_eq!(hart.compute(CLZ, 1 << 40, 0), 23);
+    assert_eq!(hart.compute(CTZ, 1 << 40, 0), 40);
+    assert_eq!(hart.compute(CPOP, u64::MAX, 0), 64);
+
+    // The word instructions only count the low 32 bits
+    assert_eq!(hart.compute(CLZW, 0xFFFF_FFFF_0000_0000, 0), 32);
+    assert_eq!(hart.compute(CTZW, 0xFFFF_FFFF_0000_0000, 0), 32);
+
+    let mut hart = Hart::new("rv32imacsu");
+
+    assert_eq!(hart.compute_32(CLZ, 0, 0), 32);
+    assert_eq!(hart.compute_32(CTZ, 0, 0), 32);
+    assert_eq!(hart.compute_32(CLZ, 1 << 31, 0), 0);
+    assert_eq!(hart.compute_32(CPOP, u32::MAX, 0), 32);
+}
+
+#[test]
+fn test_bytes() {
+    let mut hart = Hart::new("imacsu");
+
+    assert_eq!(
+        hart.compute(REV8_64, 0x0123_4567_89AB_CDEF, 0),
+        0xEFCD_AB89_6745_2301
+    );
+    assert_eq!(
+        hart.compute(ORC_B, 0x0100_2000_0003_0000, 0),
+        0xFF00_FF00_00FF_0000
+    );
+
+    // Each XLEN has its own encoding of REV8
+    assert!(is_illegal(hart.evaluate(REV8_32, 0, 0)));
+
+    let mut hart = Hart::new("rv32imacsu");
+
+    assert_eq!(hart.compute_32(REV8_32, 0x0123_4580, 0), 0x8045_2301);
+    assert!(is_illegal(hart.evaluate(REV8_64, 0, 0)));
+}
+
+#[test]
+fn test_carryless() {
+    let mut hart = Hart::new("imacsu");
+
+    let a = 0x8000_0000_0000_0001;
+    assert_eq!(hart.compute(CLMUL, a, a), 1);
+    assert_eq!(hart.compute(CLMULH, a, a), 0x4000_0000_0000_0000);
+    assert_eq!(hart.compute(CLMULR, a, a), 0x8000_0000_0000_0000);
+
+    // The product of two 32-bit values is split at bit 32 in RV32
+    let mut hart = Hart::new("rv32imacsu");
+
+    let a = 0x8000_0001;
+    assert_eq!(hart.compute_32(CLMUL, a, a), 1);
+    assert_eq!(hart.compute_32(CLMULH, a, a), 0x4000_0000);
+    assert_eq!(hart.compute_32(CLMULR, a, a), 0x8000_0000);
+    assert_eq!(
+        hart.compute_32(CLMULH, 0xFFFF_FFFF, 0xFFFF_FFFF),
+        0x5555_5555
+    );
+}
+
+#[test]
+fn test_shift_encodings() {
+    let mut hart = Hart::new("imacsu");
+    let value = 0x8000_0000_0000_0081;
+
+    // RORI is next to SRAI, and both have a 6-bit shift amount
+    assert_eq!(hart.compute(rori(40), value, 0), 0x8180_0000);
+    assert_eq!(hart.compute(srai(40), value, 0), 0xFFFF_FFFF_FF80_0000);
+    assert_eq!(hart.compute(slli(40), value, 0), 0x8100_0000_0000);
+    assert_eq!(hart.compute(bseti(40), value, 0), value | 1 << 40);
+
+    // SLLI.UW takes up the encodings of SLLIW with a funct7 of 0b0000100 or
+    // 0b0000101, while SLLIW and RORIW cannot use a shift amount of 32
+    let value = 0xFFFF_FFFF_8000_0001;
+    assert_eq!(hart.compute(slli_uw(40), value, 0), 0x0100_0000_0000);
+    assert_eq!(hart.compute(slli_uw(4), value, 0), 0x8_0000_0010);
+    assert_eq!(hart.compute(slliw(4), value, 0), 0x10);
+    assert!(is_illegal(hart.evaluate(slliw(32), 0, 0)));
+    assert!(is_illegal(hart.evaluate(roriw(32), 0, 0)));
+    assert_eq!(hart.compute(roriw(4), value, 0), 0x1800_0000);
+
+    // RV32 has a 5-bit shift amount
+    let mut hart = Hart::new("rv32imacsu");
+
+    assert_eq!(hart.compute_32(rori(4), 0x8000_0001, 0), 0x1800_0000);
+
+    for instruction in [rori(32), srai(32), slli(32), bseti(32), slli_uw(4)] {
+        assert!(is_illegal(hart.evaluate(instruction, 0, 0)));
+    }
+}
+
+#[test]
+fn test_disabled() {
+    let mut hart = Hart::new("imacsu");
+    let extensions = &mut hart.0.extensions;
+
+    extensions.zba = false;
+    extensions.zbb = false;
+    extensions.zbc = false;
+    extensions.zbs = false;
+
+    // Instructions shared with Zbkb and Zbkc are still enabled by them
+    assert_eq!(hart.compute(ANDN, 0b1100, 0b1010), 0b0100);
+    assert_eq!(hart.compute(rori(4), 0x10, 0), 1);
+    assert_eq!(hart.compute(CLMULH, 1 << 63, 0b110), 0b11);
+
+    for instruction in [SH2ADD, slli_uw(4), CLZ, ORC_B, MAX, CLMULR, BSET, bseti(4)] {
+        assert!(is_illegal(hart.evaluate(instruction, 0, 0)));
+    }
+
+    let extensions = &mut hart.0.extensions;
+
+    extensions.zbkb = false;
+    extensions.zbkc = false;
+
+    for instruction in [ANDN, rori(4), REV8_64, CLMUL, CLMULH] {
+        assert!(is_illegal(hart.evaluate(instruction, 0, 0)));
+    }
+
+    // The base shifts are not affected
+    assert_eq!(hart.compute(srai(4), 0x80, 0), 0x8);
+}
@@ -0,0 +1,115 @@
+//! A hart that runs short programs from memory, shared by the tests.
+
+// Each test only uses some of these helpers
+#![allow(dead_code)]
+
+use irv::{
+    AtomicBus, BaseHart, Bus, BusDevice, Exception, Interrupts, MachineCsrs, Memory, SystemBus,
+};
+
+pub const TEST_BUS_BASE: u64 = 0x80000000;
+pub const TEST_BUS_SIZE: u64 = 0x2000;
+/// Where data is placed, after the instructions being run.
+pub const DATA: u64 = TEST_BUS_BASE + 0x1000;
+
+/// Creates a bus with memory mapped at [TEST_BUS_BASE].
+pub fn test_bus() -> SystemBus {
+    let memory = Memory::new(vec![0u64; TEST_BUS_SIZE as usize / 8]);
+    let mut bus = SystemBus::new(1);
+
+    bus.map(TEST_BUS_BASE, memory.size() as u64, BusDevice(memory))
+        .expect("Failed to map memory");
+
+    bus
+}
+
+/// A hart that executes short programs placed at [TEST_BUS_BASE].
+pub struct Hart<B = SystemBus>(pub BaseHart<B, MachineCsrs>);
+
+impl Hart {
+    pub fn new(isa: &str) -> Hart {
+        Hart::with_bus(test_bus(), isa)
+    }
+}
+
+impl<B> Hart<B>
+where
+    B: Bus<u64, u64> + Bus<u64, u32> + Bus<u64, u16> + Bus<u64, u8>,
+    B: AtomicBus<u64, u64> + AtomicBus<u64, u32> + Interrupts,
+{
+    /// Creates a hart on the given bus, which must have memory mapped at
+    /// [TEST_BUS_BASE].
+    pub fn with_bus(bus: B, isa: &str) -> Hart<B> {
+        Hart(BaseHart::new(bus, MachineCsrs::new(0, isa)))
+    }
+
+    /// Runs the given program, made of 16-bit parcels, until `count`
+    /// instructions have been executed, returning the first exception raised.
+    pub fn run_parcels(&mut self, parcels: &[u16], count: usize) -> Result<(), Exception> {
+        let hart = &mut self.0;
+
+        for (i, &parcel) in parcels.iter().enumerate() {
+            hart.bus
+                .store(TEST_BUS_BASE + i as u64 * 2, parcel)
+                .expect("Failed to store instruction");
+        }
+
+        hart.pc = TEST_BUS_BASE;
+        hart.next = TEST_BUS_BASE;
+
+        for _ in 0..count {
+            hart.execute()?;
+        }
+
+        Ok(())
+    }
+
+    /// Runs the given instructions in order, returning the first exception
+    /// raised.
+    pub fn run(&mut self, program: &[u32]) -> Result<(), Exception> {
+        let parcels: Vec<u16> = program
+            .iter()
+            .flat_map(|&instruction| [instruction as u16, (instruction >> 16) as u16])
+            .collect();
+
+        self.run_parcels(&parcels, program.len())
+    }
+
+    /// Runs the given instructions in order, panicking if any of them raise
+    /// an exception.
+    pub fn execute(&mut self, program: &[u32]) {
+        if let Err(e) = self.run(program) {
+            panic!("Unexpected exception {e:?}");
+        }
+    }
+
+    /// Runs the given instruction with x11 and x12 as its sources, returning
+    /// the value it writes to x10.
+    pub fn evaluate(&mut self, instruction: u32, rs1: u64, rs2: u64) -> Result<u64, Exception> {
+        self.0.gpr[10] = 0xDEAD_BEEF;
+        self.0.gpr[11] = rs1;
+        self.0.gpr[12] = rs2;
+
+        self.run(&[instruction]).map(|()| self.0.gpr[10])
+    }
+
+    /// Runs the given instruction, returning the value that it writes to x10.
+    pub fn compute(&mut self, instruction: u32, rs1: u64, rs2: u64) -> u64 {
+        self.evaluate(instruction, rs1, rs2)
+            .unwrap_or_else(|e| panic!("Unexpected exception {e:?} from {instruction:#010x}"))
+    }
+
+    /// Runs the given instruction on 32-bit values, checking that its result
+    /// is sign-extended.
+    pub fn compute_32(&mut self, instruction: u32, rs1: u32, rs2: u32) -> u32 {
+        let result = self.compute(instruction, rs1 as i32 as u64, rs2 as i32 as u64);
+
+        assert_eq!(result, result as i32 as u64, "result is not sign-extended");
+
+        result as u32
+    }
+}
+
+pub fn is_illegal<T>(result: Result<T, Exception>) -> bool {
+    matches!(result, Err(Exception::IllegalInstruction { .. }))
+}
@@ -0,0 +1,107 @@
+//! Checks how compressed instructions are decoded, and how the C extension
+//! relaxes the alignment of instruction addresses.
+
+mod common;
+
+use std::num::NonZeroU64;
+
+use common::{is_illegal, Hart, TEST_BUS_BASE, TEST_BUS_SIZE};
+use irv::{Bus, Exception};
+
+/// `c.nop`
+const C_NOP: u16 = 0x0001;
+/// `jal x0, 6`
+const JAL_6: u32 = 3 << 21 | 0b1101111;
+/// `beq x0, x0, 6`
+const BEQ_6: u32 = 3 << 8 | 0b1100011;
+/// `bne x0, x0, 6`
+const BNE_6: u32 = 3 << 8 | 0b001 << 12 | 0b1100011;
+/// `jalr x0, 2(x11)`
+const JALR_2: u32 = 2 << 20 | 11 << 15 | 0b1100111;
+/// `jalr x0, 1(x11)`
+const JALR_1: u32 = 1 << 20 | 11 << 15 | 0b1100111;
+/// `addi x10, x0, 0x123`
+const ADDI: u32 = 0x123 << 20 | 10 << 7 | 0b0010011;
+
+fn is_misaligned(result: Result<(), Exception>, target: u64) -> bool {
+    matches!(
+        result,
+        Err(Exception::InstructionAddressMisaligned { address })
+            if address == NonZeroU64::new(target)
+    )
+}
+
+#[test]
+fn test_misa() {
+    // Whether instructions can be compressed is given by misa
+    let mut hart = Hart::new("rv32esu");
+
+    assert!(is_illegal(hart.run_parcels(&[C_NOP, C_NOP], 1)));
+    assert!(is_misaligned(hart.run(&[JAL_6]), TEST_BUS_BASE + 6));
+
+    let mut hart = Hart::new("rv32ecsu");
+
+    hart.run_parcels(&[C_NOP], 1).unwrap();
+    assert_eq!(hart.0.pc, TEST_BUS_BASE + 2);
+
+    hart.execute(&[JAL_6]);
+    assert_eq!(hart.0.pc, TEST_BUS_BASE + 6);
+}
+
+#[test]
+fn test_straddling() {
+    let mut hart = Hart::new("imacsu");
+
+    // 32-bit instructions only need to be aligned to two bytes
+    let parcels = [C_NOP, ADDI as u16, (ADDI >> 16) as u16, C_NOP];
+    hart.run_parcels(&parcels, 3).unwrap();
+
+    assert_eq!(hart.0.gpr[10], 0x123);
+    assert_eq!(hart.0.pc, TEST_BUS_BASE + 8);
+
+    // The second half of an instruction can fault on its own
+    let end = TEST_BUS_BASE + TEST_BUS_SIZE;
+    hart.0.bus.store(end - 2, ADDI as u16).unwrap();
+    hart.0.pc = end - 2;
+
+    assert!(matches!(
+        hart.0.execute(),
+        Err(Exception::InstructionAccessFault { address }) if address == NonZeroU64::new(end)
+    ));
+}
+
+#[test]
+fn test_alignment() {
+    let mut hart = Hart::new("imacsu");
+    hart.0.gpr[11] = TEST_BUS_BASE + 4;
+
+    // With the C extension, targets only need to be aligned to two bytes
+    hart.execute(&[BEQ_6]);
+    assert_eq!(hart.0.pc, TEST_BUS_BASE + 6);
+
+    hart.execute(&[JALR_2]);
+    assert_eq!(hart.0.pc, TEST_BUS_BASE + 6);
+
+    // JALR always clears the least significant bit of its target
+    hart.execute(&[JALR_1]);
+    assert_eq!(hart.0.pc, TEST_BUS_BASE + 4);
+
+    // Without it, they must be aligned to four bytes
+    let mut hart = Hart::new("imasu");
+    hart.0.gpr[11] = TEST_BUS_BASE + 4;
+
+    assert!(is_misaligned(hart.run(&[BEQ_6]), TEST_BUS_BASE + 6));
+    assert!(is_misaligned(hart.run(&[JALR_2]), TEST_BUS_BASE + 6));
+
+    // Branches that are not taken do not check their targets
+    hart.execute(&[BNE_6]);
+    assert_eq!(hart.0.pc, TEST_BUS_BASE + 4);
+
+    hart.execute(&[JALR_1]);
+    assert_eq!(hart.0.pc, TEST_BUS_BASE + 4);
+
+    // No instructions are compressed, even those with a valid expansion
+    hart.0.gpr[10] = 0;
+    assert!(is_illegal(hart.run_parcels(&[0x4505, 0], 1)));
+    assert_eq!(hart.0.gpr[10], 0);
+}
@@ -0,0 +1,279 @@
+//! Checks the counters of the Zicntr and Zihpm extensions, and the CSRs that
+//! control them.
+
+mod common;
+
+use common::{is_illegal, Hart, DATA};
+use irv::{Events, Exception, Privilege};
+
+const SCOUNTEREN: u32 = 0x106;
+const MCOUNTEREN: u32 = 0x306;
+const MCOUNTINHIBIT: u32 = 0x320;
+const MHPMEVENT3: u32 = 0x323;
+const MCYCLE: u32 = 0xB00;
+const MINSTRET: u32 = 0xB02;
+const MHPMCOUNTER3: u32 = 0xB03;
+const MCYCLEH: u32 = 0xB80;
+const CYCLE: u32 = 0xC00;
+const TIME: u32 = 0xC01;
+const INSTRET: u32 = 0xC02;
+const HPMCOUNTER3: u32 = 0xC03;
+const CYCLEH: u32 = 0xC80;
+
+const NOP: u32 = 0x00000013;
+const ECALL: u32 = 0x00000073;
+const WFI: u32 = 0x10500073;
+/// `lw x5, 0(x10)`
+const LW: u32 = 10 << 15 | 0b010 << 12 | 5 << 7 | 0b0000011;
+/// `sw x5, 4(x10)`
+const SW: u32 = 5 << 20 | 10 << 15 | 0b010 << 12 | 4 << 7 | 0b0100011;
+/// `beq x0, x0, 4`, which is always taken
+const BEQ: u32 = 0b0010 << 8 | 0b1100011;
+/// `bne x0, x0, 4`, which is never taken
+const BNE: u32 = BEQ | 0b001 << 12;
+
+/// `csrrs rd, csr, x0`
+const fn csrr(rd: u32, csr: u32) -> u32 {
+    csr << 20 | 0b010 << 12 | rd << 7 | 0b1110011
+}
+
+/// `csrrw x0, csr, rs1`
+const fn csrw(csr: u32, rs1: u32) -> u32 {
+    csr << 20 | rs1 << 15 | 0b001 << 12 | 0b1110011
+}
+
+impl Hart {
+    /// Reads the given CSR with an instruction, at the current privilege
+    /// level.
+    fn read(&mut self, csr: u32) -> Result<u64, Exception> {
+        self.run(&[csrr(30, csr)]).map(|()| self.0.gpr[30])
+    }
+
+    /// Writes the given CSR with an instruction, at the current privilege
+    /// level.
+    fn write(&mut self, csr: u32, value: u64) -> Result<(), Exception> {
+        self.0.gpr[31] = value;
+        self.run(&[csrw(csr, 31)])
+    }
+}
+
+#[test]
+fn test_machine_counters() {
+    let mut hart = Hart::new("imsu");
+
+    hart.execute(&[NOP, NOP, NOP]);
+
+    assert_eq!(hart.0.counters.mcycle, 3);
+    assert_eq!(hart.0.counters.minstret, 3);
+
+    // Counters are read before the reading instruction is counted
+    assert_eq!(hart.read(MCYCLE).unwrap(), 3);
+    assert_eq!(hart.read(MINSTRET).unwrap(), 4);
+    assert_eq!(hart.read(CYCLE).unwrap(), 5);
+    assert_eq!(hart.read(INSTRET).unwrap(), 6);
+
+    // Instructions that raise exceptions take a cycle but do not retire
+    assert!(hart.run(&[ECALL]).is_err());
+    assert_eq!(hart.0.counters.mcycle, 8);
+    assert_eq!(hart.0.counters.minstret, 7);
+
+    // A written counter is not incremented by the writing instruction
+    hart.write(MCYCLE, 100).unwrap();
+    hart.write(MINSTRET, 200).unwrap();
+    assert_eq!(hart.0.counters.mcycle, 101);
+    assert_eq!(hart.0.counters.minstret, 200);
+    assert_eq!(hart.read(MINSTRET).unwrap(), 200);
+
+    // The unprivileged counters are read-only, and there is no counter at
+    // 0xB01
+    assert!(is_illegal(hart.write(CYCLE, 0)));
+    assert!(is_illegal(hart.read(0xB01)));
+
+    // The time counter is kept by the host
+    hart.0.counters.time = 1234;
+    assert_eq!(hart.read(TIME).unwrap(), 1234);
+
+    // Only RV32 has the high halves
+    assert!(is_illegal(hart.read(MCYCLEH)));
+}
+
+#[test]
+fn test_waiting() {
+    let mut hart = Hart::new("imsu");
+
+    hart.execute(&[WFI]);
+    assert!(hart.0.is_waiting());
+
+    // Cycles pass while waiting, but no instructions retire
+    hart.0.execute().unwrap();
+    hart.0.execute().unwrap();
+
+    assert!(hart.0.is_waiting());
+    assert_eq!(hart.0.counters.mcycle, 3);
+    assert_eq!(hart.0.counters.minstret, 1);
+}
+
+#[test]
+fn test_inhibit() {
+    let mut hart = Hart::new("imsu");
+
+    // TM cannot be set, since time is not a counter of the hart
+    hart.write(MCOUNTINHIBIT, 0b111).unwrap();
+    assert_eq!(hart.read(MCOUNTINHIBIT).unwrap(), 0b101);
+
+    let (mcycle, minstret) = (hart.0.counters.mcycle, hart.0.counters.minstret);
+    hart.execute(&[NOP, NOP]);
+
+    assert_eq!(hart.0.counters.mcycle, mcycle);
+    assert_eq!(hart.0.counters.minstret, minstret);
+
+    hart.write(MCOUNTINHIBIT, 0b001).unwrap();
+    hart.execute(&[NOP, NOP]);
+
+    assert_eq!(hart.0.counters.mcycle, mcycle);
+    assert_eq!(hart.0.counters.minstret, minstret + 3);
+}
+
+#[test]
+fn test_counter_enables() {
+    let mut hart = Hart::new("imsu");
+
+    hart.0.privilege = Privilege::Supervisor;
+
+    // Supervisor mode cannot access the machine-level CSRs, and needs
+    // mcounteren to read the unprivileged counters
+    assert!(is_illegal(hart.read(MCYCLE)));
+    assert!(is_illegal(hart.read(MCOUNTEREN)));
+    assert!(is_illegal(hart.read(CYCLE)));
+    assert!(is_illegal(hart.read(TIME)));
+
+    hart.0.csr.mcounteren = 0b011;
+
+    assert!(hart.read(CYCLE).is_ok());
+    assert!(hart.read(TIME).is_ok());
+    assert!(is_illegal(hart.read(INSTRET)));
+
+    // User mode also needs scounteren when supervisor mode exists
+    hart.0.privilege = Privilege::User;
+
+    assert!(is_illegal(hart.read(CYCLE)));
+
+    hart.0.csr.scounteren = 0b110;
+
+    assert!(is_illegal(hart.read(CYCLE)));
+    assert!(hart.read(TIME).is_ok());
+    assert!(is_illegal(hart.read(INSTRET)));
+
+    // Only the 32 enable bits can be written
+    hart.0.privilege = Privilege::Machine;
+
+    hart.write(MCOUNTEREN, u64::MAX).unwrap();
+    assert_eq!(hart.read(MCOUNTEREN).unwrap(), 0xFFFF_FFFF);
+    hart.write(SCOUNTEREN, u64::MAX).unwrap();
+    assert_eq!(hart.read(SCOUNTEREN).unwrap(), 0xFFFF_FFFF);
+
+    hart.0.privilege = Privilege::User;
+
+    assert!(hart.read(HPMCOUNTER3).is_ok());
+    assert!(hart.read(0xC1F).is_ok());
+
+    // Without supervisor mode, mcounteren alone controls user mode, and
+    // scounteren does not exist
+    let mut hart = Hart::new("imu");
+
+    hart.0.privilege = Privilege::User;
+    assert!(is_illegal(hart.read(CYCLE)));
+
+    hart.0.csr.mcounteren = 0b001;
+    assert!(hart.read(CYCLE).is_ok());
+
+    hart.0.privilege = Privilege::Machine;
+    assert!(is_illegal(hart.read(SCOUNTEREN)));
+}
+
+#[test]
+fn test_rv32_high_halves() {
+    let mut hart = Hart::new("rv32imsu");
+
+    hart.0.counters.mcycle = 0x1_FFFF_FFFF;
+
+    assert_eq!(hart.read(MCYCLEH).unwrap(), 0x1);
+    assert_eq!(hart.read(CYCLEH).unwrap(), 0x2);
+    assert_eq!(hart.read(CYCLE).unwrap(), 0x1);
+
+    // Writing one half leaves the other unchanged
+    hart.write(MCYCLEH, 0x5).unwrap();
+    assert_eq!(hart.0.counters.mcycle, 0x5_0000_0002);
+
+    hart.write(MCYCLE, 0xFFFF_FFFF).unwrap();
+    assert_eq!(hart.0.counters.mcycle, 0x5_FFFF_FFFF);
+
+    // Incrementing the low half carries into the high half
+    hart.execute(&[NOP]);
+    assert_eq!(hart.read(MCYCLEH).unwrap(), 0x6);
+}
+
+#[test]
+fn test_hpm_counters() {
+    let mut hart = Hart::new("imsu");
+
+    hart.0.counters.define_event(1, Events::LOADS);
+    hart.0.counters.define_event(2, Events::STORES);
+    hart.0.counters.define_event(3, Events::BRANCHES);
+    hart.0.counters.define_event(4, Events::BRANCHES_TAKEN);
+    hart.0.counters.define_event(5, Events::TRAPS);
+    hart.0
+        .counters
+        .define_event(6, Events::LOADS | Events::STORES);
+
+    // Each counter is programmed with the selector that has its number, and
+    // mhpmcounter31 with a selector that is not defined
+    for n in 3..=8 {
+        hart.write(MHPMEVENT3 + n - 3, n as u64 - 2).unwrap();
+    }
+
+    hart.write(MHPMEVENT3 + 28, 99).unwrap();
+    assert_eq!(hart.read(MHPMEVENT3 + 28).unwrap(), 99);
+    assert_eq!(hart.0.counters.hpm_event(31), 99);
+
+    hart.0.gpr[10] = DATA;
+
+    let result = hart.run(&[LW, SW, LW, BEQ, BNE, ECALL]);
+
+    assert!(matches!(result, Err(Exception::EnvironmentCall { .. })));
+
+    let counters = &hart.0.counters;
+
+    assert_eq!(counters.hpm_counter(3), 2);
+    assert_eq!(counters.hpm_counter(4), 1);
+    assert_eq!(counters.hpm_counter(5), 2);
+    assert_eq!(counters.hpm_counter(6), 1);
+    assert_eq!(counters.hpm_counter(7), 1);
+    assert_eq!(counters.hpm_counter(8), 3);
+    assert_eq!(counters.hpm_counter(9), 0);
+    assert_eq!(counters.hpm_counter(31), 0);
+
+    // Loads that fault do not retire, so they are only counted as traps
+    hart.0.gpr[10] = 0;
+    assert!(hart.run(&[LW]).is_err());
+
+    assert_eq!(hart.0.counters.hpm_counter(3), 2);
+    assert_eq!(hart.0.counters.hpm_counter(7), 2);
+
+    // Redefining a selector changes what the counters using it count
+    hart.0.counters.define_event(99, Events::INSTRUCTIONS);
+    hart.execute(&[NOP, NOP]);
+
+    assert_eq!(hart.0.counters.hpm_counter(31), 2);
+
+    // Counters can be written, read through their unprivileged shadows, and
+    // inhibited
+    hart.write(MHPMCOUNTER3, 40).unwrap();
+    hart.write(MCOUNTINHIBIT, 1 << 3).unwrap();
+    hart.0.gpr[10] = DATA;
+    hart.execute(&[LW, LW]);
+
+    assert_eq!(hart.read(MHPMCOUNTER3).unwrap(), 40);
+    assert_eq!(hart.read(HPMCOUNTER3).unwrap(), 40);
+    assert_eq!(hart.read(HPMCOUNTER3 + 28).unwrap(), 8);
+}
@@ -0,0 +1,842 @@
+//! Runs the instructions of the scalar cryptography extensions on the
+//! official test vectors of the algorithms that they accelerate.
+
+mod common;
+
+use common::{is_illegal, Hart};
+use irv::{Entropy, Privilege};
+
+// Each instruction reads rs1 from x11 and rs2 from x12, and writes rd to x10
+const RD: u32 = 10 << 7;
+const RS1: u32 = 11 << 15;
+const RS2: u32 = 12 << 20;
+
+const fn op(funct7: u32, funct3: u32) -> u32 {
+    funct7 << 25 | RS2 | RS1 | funct3 << 12 | RD | 0b0110011
+}
+
+const fn op_32(funct7: u32, funct3: u32) -> u32 {
+    funct7 << 25 | RS2 | RS1 | funct3 << 12 | RD | 0b0111011
+}
+
+const fn op_imm(imm: u32, funct3: u32) -> u32 {
+    imm << 20 | RS1 | funct3 << 12 | RD | 0b0010011
+}
+
+/// Encodes an instruction that selects the byte `bs` of rs2.
+const fn op_bs(funct5: u32, bs: u32) -> u32 {
+    op(bs << 5 | funct5, 0b000)
+}
+
+const PACK: u32 = op(0b0000100, 0b100);
+const PACKH: u32 = op(0b0000100, 0b111);
+const PACKW: u32 = op_32(0b0000100, 0b100);
+const BREV8: u32 = op_imm(0x687, 0b101);
+const ZIP: u32 = op_imm(0x08F, 0b001);
+const UNZIP: u32 = op_imm(0x08F, 0b101);
+const CLMUL: u32 = op(0b0000101, 0b001);
+const CLMULH: u32 = op(0b0000101, 0b011);
+const XPERM4: u32 = op(0b0010100, 0b010);
+const XPERM8: u32 = op(0b0010100, 0b100);
+
+const AES32ESI: u32 = 0b10001;
+const AES32ESMI: u32 = 0b10011;
+const AES32DSI: u32 = 0b10101;
+const AES32DSMI: u32 = 0b10111;
+const AES64ES: u32 = op(0b0011001, 0b000);
+const AES64ESM: u32 = op(0b0011011, 0b000);
+const AES64DS: u32 = op(0b0011101, 0b000);
+const AES64DSM: u32 = op(0b0011111, 0b000);
+const AES64IM: u32 = op_imm(0x300, 0b001);
+const AES64KS2: u32 = op(0b0111111, 0b000);
+
+const fn aes64ks1i(rnum: u32) -> u32 {
+    op_imm(0x310 | rnum, 0b001)
+}
+
+const SHA256SUM0: u32 = op_imm(0x100, 0b001);
+const SHA256SUM1: u32 = op_imm(0x101, 0b001);
+const SHA256SIG0: u32 = op_imm(0x102, 0b001);
+const SHA256SIG1: u32 = op_imm(0x103, 0b001);
+const SHA512SUM0: u32 = op_imm(0x104, 0b001);
+const SHA512SUM1: u32 = op_imm(0x105, 0b001);
+const SHA512SIG0: u32 = op_imm(0x106, 0b001);
+const SHA512SIG1: u32 = op_imm(0x107, 0b001);
+const SHA512SUM0R: u32 = op(0b0101000, 0b000);
+const SHA512SUM1R: u32 = op(0b0101001, 0b000);
+const SHA512SIG0L: u32 = op(0b0101010, 0b000);
+const SHA512SIG1L: u32 = op(0b0101011, 0b000);
+const SHA512SIG0H: u32 = op(0b0101110, 0b000);
+const SHA512SIG1H: u32 = op(0b0101111, 0b000);
+
+const SM4ED: u32 = 0b11000;
+const SM4KS: u32 = 0b11010;
+const SM3P0: u32 = op_imm(0x108, 0b001);
+const SM3P1: u32 = op_imm(0x109, 0b001);
+
+/// `csrrw x10, seed, x11`
+const CSRRW_SEED: u32 = 0x015 << 20 | RS1 | 0b001 << 12 | RD | 0b1110011;
+/// `csrrs x10, seed, x0`
+const CSRRS_SEED: u32 = 0x015 << 20 | 0b010 << 12 | RD | 0b1110011;
+
+impl Hart {
+    /// Accumulates the transformation of each byte of `value` into `acc`,
+    /// using an instruction that selects a byte of rs2 by its bs field.
+    fn each_byte(&mut self, funct5: u32, acc: u32, value: u32) -> u32 {
+        (0..4).fold(acc, |acc, bs| {
+            self.compute_32(op_bs(funct5, bs), acc, value)
+        })
+    }
+}
+
+fn hex(digits: &str) -> Vec<u8> {
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+fn words_64(bytes: &[u8]) -> Vec<u64> {
+    bytes
+        .chunks(8)
+        .map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap()))
+        .collect()
+}
+
+fn words_32(bytes: &[u8]) -> Vec<u32> {
+    bytes
+        .chunks(4)
+        .map(|chunk| u32::from_le_bytes(chunk.try_into().unwrap()))
+        .collect()
+}
+
+/// Pads `message` to a whole number of blocks of the given size for SHA-2
+/// and SM3, ending with its length in bits.
+fn pad(message: &[u8], block: usize) -> Vec<u8> {
+    let length = block / 8;
+    let mut padded = message.to_vec();
+
+    padded.push(0x80);
+
+    while padded.len() % block != block - length {
+        padded.push(0);
+    }
+
+    padded.extend_from_slice(&(message.len() as u128 * 8).to_be_bytes()[16 - length..]);
+    padded
+}
+
+// Zbkb, Zbkc, and Zbkx
+
+#[test]
+fn test_bit_manipulation() {
+    let mut hart = Hart::new("imacsu");
+
+    assert_eq!(
+        hart.compute(PACK, 0xAAAA_AAAA_0123_4567, 0x89AB_CDEF),
+        0x89AB_CDEF_0123_4567
+    );
+    assert_eq!(hart.compute(PACKH, 0x1234, 0x5678), 0x7834);
+    assert_eq!(hart.compute(PACKW, 0x1234, 0x8765), 0xFFFF_FFFF_8765_1234);
+    assert_eq!(
+        hart.compute(BREV8, 0x0102_0408_1020_4080, 0),
+        0x8040_2010_0804_0201
+    );
+    assert_eq!(hart.compute(CLMUL, 0b1011, 0b0110), 0b111010);
+    assert_eq!(hart.compute(CLMULH, 1 << 63, 0b110), 0b11);
+    assert_eq!(
+        hart.compute(XPERM8, 0x0706_0504_0302_0100, 0x00FF_0203_0405_0607),
+        0x0000_0203_0405_0607
+    );
+    assert_eq!(
+        hart.compute(XPERM4, 0xFEDC_BA98_7654_3210, 0x0123_4567_89AB_CDEF),
+        0x0123_4567_89AB_CDEF
+    );
+
+    // ZIP and UNZIP only exist in RV32
+    assert!(is_illegal(hart.evaluate(ZIP, 0, 0)));
+
+    let mut hart = Hart::new("rv32imacsu");
+
+    assert_eq!(hart.compute_32(ZIP, 0xFFFF_0000, 0), 0xAAAA_AAAA);
+    assert_eq!(hart.compute_32(UNZIP, 0xAAAA_AAAA, 0), 0xFFFF_0000);
+    assert_eq!(hart.compute_32(PACK, 0x1234_5678, 0x9ABC_DEF0), 0xDEF0_5678);
+    assert_eq!(
+        hart.compute_32(XPERM8, 0x0302_0100, 0x0004_0203),
+        0x0000_0203
+    );
+    assert_eq!(
+        hart.compute_32(XPERM4, 0x7654_3210, 0x0123_4568),
+        0x0123_4560
+    );
+}
+
+// Zknd and Zkne
+
+/// FIPS 197, Appendix C.1 (AES-128)
+const AES128_KEY: &str = "000102030405060708090a0b0c0d0e0f";
+const AES128_CIPHERTEXT: &str = "69c4e0d86a7b0430d8cdb78070b4c55a";
+/// FIPS 197, Appendix C.3 (AES-256)
+const AES256_KEY: &str = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+const AES256_CIPHERTEXT: &str = "8ea2b7ca516745bfeafc49904b496089";
+const AES_PLAINTEXT: &str = "00112233445566778899aabbccddeeff";
+
+/// Expands an AES-128 key into its round keys with the RV64 instructions.
+fn aes128_key_schedule_64(hart: &mut Hart, key: &[u8]) -> Vec<u64> {
+    let mut keys = words_64(key);
+
+    for round in 0..10 {
+        let n = keys.len();
+        let temp = hart.compute(aes64ks1i(round), keys[n - 1], 0);
+        let low = hart.compute(AES64KS2, temp, keys[n - 2]);
+        let high = hart.compute(AES64KS2, low, keys[n - 1]);
+
+        keys.extend([low, high]);
+    }
+
+    keys
+}
+
+/// Expands an AES-256 key into its round keys with the RV64 instructions,
+/// where a round number of 10 substitutes the words of odd rounds without
+/// rotating them.
+fn aes256_key_schedule_64(hart: &mut Hart, key: &[u8]) -> Vec<u64> {
+    let mut keys = words_64(key);
+
+    for round in 0..7 {
+        let n = keys.len();
+        let temp = hart.compute(aes64ks1i(round), keys[n - 1], 0);
+        let first = hart.compute(AES64KS2, temp, keys[n - 4]);
+        let second = hart.compute(AES64KS2, first, keys[n - 3]);
+
+        keys.extend([first, second]);
+
+        if round < 6 {
+            let temp = hart.compute(aes64ks1i(0xA), second, 0);
+            let third = hart.compute(AES64KS2, temp, keys[n - 2]);
+            let fourth = hart.compute(AES64KS2, third, keys[n - 1]);
+
+            keys.extend([third, fourth]);
+        }
+    }
+
+    keys
+}
+
+fn aes_encrypt_64(hart: &mut Hart, keys: &[u64], plaintext: &[u8]) -> Vec<u8> {
+    let rounds = keys.len() / 2 - 1;
+    let state = words_64(plaintext);
+    let mut state = [state[0] ^ keys[0], state[1] ^ keys[1]];
+
+    for round in 1..=rounds {
+        // The last round has no MixColumns
+        let instruction = if round == rounds { AES64ES } else { AES64ESM };
+
+        state = [
+            hart.compute(instruction, state[0], state[1]) ^ keys[round * 2],
+            hart.compute(instruction, state[1], state[0]) ^ keys[round * 2 + 1],
+        ];
+    }
+
+    state.iter().flat_map(|word| word.to_le_bytes()).collect()
+}
+
+fn aes_decrypt_64(hart: &mut Hart, keys: &[u64], ciphertext: &[u8]) -> Vec<u8> {
+    let rounds = keys.len() / 2 - 1;
+    let state = words_64(ciphertext);
+    let mut state = [state[0] ^ keys[rounds * 2], state[1] ^ keys[rounds * 2 + 1]];
+
+    for round in (0..rounds).rev() {
+        let (instruction, keys) = if round == 0 {
+            (AES64DS, [keys[0], keys[1]])
+        } else {
+            // InvMixColumns is applied to the round key instead of after
+            // adding it, which is equivalent since it is linear
+            (
+                AES64DSM,
+                [
+                    hart.compute(AES64IM, keys[round * 2], 0),
+                    hart.compute(AES64IM, keys[round * 2 + 1], 0),
+                ],
+            )
+        };
+
+        state = [
+            hart.compute(instruction, state[0], state[1]) ^ keys[0],
+            hart.compute(instruction, state[1], state[0]) ^ keys[1],
+        ];
+    }
+
+    state.iter().flat_map(|word| word.to_le_bytes()).collect()
+}
+
+#[test]
+fn test_aes_64() {
+    let mut hart = Hart::new("imacsu");
+
+    let keys = aes128_key_schedule_64(&mut hart, &hex(AES128_KEY));
+    let ciphertext = aes_encrypt_64(&mut hart, &keys, &hex(AES_PLAINTEXT));
+
+    assert_eq!(ciphertext, hex(AES128_CIPHERTEXT));
+    assert_eq!(
+        aes_decrypt_64(&mut hart, &keys, &ciphertext),
+        hex(AES_PLAINTEXT)
+    );
+
+    let keys = aes256_key_schedule_64(&mut hart, &hex(AES256_KEY));
+    let ciphertext = aes_encrypt_64(&mut hart, &keys, &hex(AES_PLAINTEXT));
+
+    assert_eq!(ciphertext, hex(AES256_CIPHERTEXT));
+    assert_eq!(
+        aes_decrypt_64(&mut hart, &keys, &ciphertext),
+        hex(AES_PLAINTEXT)
+    );
+
+    // Round numbers above 10 are reserved
+    assert!(is_illegal(hart.evaluate(aes64ks1i(0xB), 0, 0)));
+}
+
+/// Expands an AES-128 key into its round keys with the RV32 instructions.
+fn aes128_key_schedule_32(hart: &mut Hart, key: &[u8]) -> Vec<u32> {
+    const ROUND_CONSTANTS: [u32; 10] = [0x01, 0x02, 0x04, 0x08, 0x10, 0x20, 0x40, 0x80, 0x1B, 0x36];
+
+    let mut keys = words_32(key);
+
+    for round_constant in ROUND_CONSTANTS {
+        let n = keys.len();
+        let mut temp = hart.each_byte(AES32ESI, round_constant, keys[n - 1].rotate_right(8));
+
+        for i in 0..4 {
+            temp ^= keys[n - 4 + i];
+            keys.push(temp);
+        }
+    }
+
+    keys
+}
+
+fn aes_encrypt_32(hart: &mut Hart, keys: &[u32], plaintext: &[u8]) -> Vec<u8> {
+    let mut state: Vec<u32> = words_32(plaintext)
+        .iter()
+        .zip(keys)
+        .map(|(word, key)| word ^ key)
+        .collect();
+
+    for (round, keys) in keys.chunks(4).enumerate().skip(1) {
+        let instruction = if round == 10 { AES32ESI } else { AES32ESMI };
+
+        // Row j of column i of the result comes from column i + j
+        state = (0..4)
+            .map(|i| {
+                (0..4).fold(keys[i], |acc, j| {
+                    hart.compute_32(op_bs(instruction, j as u32), acc, state[(i + j) % 4])
+                })
+            })
+            .collect();
+    }
+
+    state.iter().flat_map(|word| word.to_le_bytes()).collect()
+}
+
+fn aes_decrypt_32(hart: &mut Hart, keys: &[u32], ciphertext: &[u8]) -> Vec<u8> {
+    let mut state: Vec<u32> = words_32(ciphertext)
+        .iter()
+        .zip(&keys[40..])
+        .map(|(word, key)| word ^ key)
+        .collect();
+
+    for round in (0..10).rev() {
+        let round_keys = &keys[round * 4..round * 4 + 4];
+
+        let (instruction, keys): (_, Vec<u32>) = if round == 0 {
+            (AES32DSI, round_keys.to_vec())
+        } else {
+            // InvMixColumns of each word of the round key, since AES32DSMI
+            // applies it after undoing the substitution of AES32ESI
+            let keys = round_keys
+                .iter()
+                .map(|&key| {
+                    let substituted = hart.each_byte(AES32ESI, 0, key);
+
+                    hart.each_byte(AES32DSMI, 0, substituted)
+                })
+                .collect();
+
+            (AES32DSMI, keys)
+        };
+
+        // Row j of column i of the result comes from column i - j
+        state = (0..4)
+            .map(|i| {
+                (0..4).fold(keys[i], |acc, j| {
+                    hart.compute_32(op_bs(instruction, j as u32), acc, state[(i + 4 - j) % 4])
+                })
+            })
+            .collect();
+    }
+
+    state.iter().flat_map(|word| word.to_le_bytes()).collect()
+}
+
+#[test]
+fn test_aes_32() {
+    let mut hart = Hart::new("rv32imacsu");
+
+    let keys = aes128_key_schedule_32(&mut hart, &hex(AES128_KEY));
+    let ciphertext = aes_encrypt_32(&mut hart, &keys, &hex(AES_PLAINTEXT));
+
+    assert_eq!(ciphertext, hex(AES128_CIPHERTEXT));
+    assert_eq!(
+        aes_decrypt_32(&mut hart, &keys, &ciphertext),
+        hex(AES_PLAINTEXT)
+    );
+
+    // The RV64 instructions do not exist in RV32
+    assert!(is_illegal(hart.evaluate(AES64ESM, 0, 0)));
+}
+
+// Zknh
+
+/// The functions of SHA-2 that the Zknh instructions compute.
+#[derive(Clone, Copy)]
+enum Sha {
+    Sum0,
+    Sum1,
+    Sig0,
+    Sig1,
+}
+
+#[rustfmt::skip]
+const SHA256_K: [u32; 64] = [
+    0x428A2F98, 0x71374491, 0xB5C0FBCF, 0xE9B5DBA5, 0x3956C25B, 0x59F111F1, 0x923F82A4, 0xAB1C5ED5,
+    0xD807AA98, 0x12835B01, 0x243185BE, 0x550C7DC3, 0x72BE5D74, 0x80DEB1FE, 0x9BDC06A7, 0xC19BF174,
+    0xE49B69C1, 0xEFBE4786, 0x0FC19DC6, 0x240CA1CC, 0x2DE92C6F, 0x4A7484AA, 0x5CB0A9DC, 0x76F988DA,
+    0x983E5152, 0xA831C66D, 0xB00327C8, 0xBF597FC7, 0xC6E00BF3, 0xD5A79147, 0x06CA6351, 0x14292967,
+    0x27B70A85, 0x2E1B2138, 0x4D2C6DFC, 0x53380D13, 0x650A7354, 0x766A0ABB, 0x81C2C92E, 0x92722C85,
+    0xA2BFE8A1, 0xA81A664B, 0xC24B8B70, 0xC76C51A3, 0xD192E819, 0xD6990624, 0xF40E3585, 0x106AA070,
+    0x19A4C116, 0x1E376C08, 0x2748774C, 0x34B0BCB5, 0x391C0CB3, 0x4ED8AA4A, 0x5B9CCA4F, 0x682E6FF3,
+    0x748F82EE, 0x78A5636F, 0x84C87814, 0x8CC70208, 0x90BEFFFA, 0xA4506CEB, 0xBEF9A3F7, 0xC67178F2,
+];
+
+#[rustfmt::skip]
+const SHA256_H: [u32; 8] = [
+    0x6A09E667, 0xBB67AE85, 0x3C6EF372, 0xA54FF53A, 0x510E527F, 0x9B05688C, 0x1F83D9AB, 0x5BE0CD19,
+];
+
+#[rustfmt::skip]
+const SHA512_K: [u64; 80] = [
+    0x428A2F98D728AE22, 0x7137449123EF65CD, 0xB5C0FBCFEC4D3B2F, 0xE9B5DBA58189DBBC,
+    0x3956C25BF348B538, 0x59F111F1B605D019, 0x923F82A4AF194F9B, 0xAB1C5ED5DA6D8118,
+    0xD807AA98A3030242, 0x12835B0145706FBE, 0x243185BE4EE4B28C, 0x550C7DC3D5FFB4E2,
+    0x72BE5D74F27B896F, 0x80DEB1FE3B1696B1, 0x9BDC06A725C71235, 0xC19BF174CF692694,
+    0xE49B69C19EF14AD2, 0xEFBE4786384F25E3, 0x0FC19DC68B8CD5B5, 0x240CA1CC77AC9C65,
+    0x2DE92C6F592B0275, 0x4A7484AA6EA6E483, 0x5CB0A9DCBD41FBD4, 0x76F988DA831153B5,
+    0x983E5152EE66DFAB, 0xA831C66D2DB43210, 0xB00327C898FB213F, 0xBF597FC7BEEF0EE4,
+    0xC6E00BF33DA88FC2, 0xD5A79147930AA725, 0x06CA6351E003826F, 0x142929670A0E6E70,
+    0x27B70A8546D22FFC, 0x2E1B21385C26C926, 0x4D2C6DFC5AC42AED, 0x53380D139D95B3DF,
+    0x650A73548BAF63DE, 0x766A0ABB3C77B2A8, 0x81C2C92E47EDAEE6, 0x92722C851482353B,
+    0xA2BFE8A14CF10364, 0xA81A664BBC423001, 0xC24B8B70D0F89791, 0xC76C51A30654BE30,
+    0xD192E819D6EF5218, 0xD69906245565A910, 0xF40E35855771202A, 0x106AA07032BBD1B8,
+    0x19A4C116B8D2D0C8, 0x1E376C085141AB53, 0x2748774CDF8EEB99, 0x34B0BCB5E19B48A8,
+    0x391C0CB3C5C95A63, 0x4ED8AA4AE3418ACB, 0x5B9CCA4F7763E373, 0x682E6FF3D6B2B8A3,
+    0x748F82EE5DEFB2FC, 0x78A5636F43172F60, 0x84C87814A1F0AB72, 0x8CC702081A6439EC,
+    0x90BEFFFA23631E28, 0xA4506CEBDE82BDE9, 0xBEF9A3F7B2C67915, 0xC67178F2E372532B,
+    0xCA273ECEEA26619C, 0xD186B8C721C0C207, 0xEADA7DD6CDE0EB1E, 0xF57D4F7FEE6ED178,
+    0x06F067AA72176FBA, 0x0A637DC5A2C898A6, 0x113F9804BEF90DAE, 0x1B710B35131C471B,
+    0x28DB77F523047D84, 0x32CAAB7B40C72493, 0x3C9EBE0A15C9BEBC, 0x431D67C49C100D4C,
+    0x4CC5D4BECB3E42B6, 0x597F299CFC657E2A, 0x5FCB6FAB3AD6FAEC, 0x6C44198C4A475817,
+];
+
+#[rustfmt::skip]
+const SHA512_H: [u64; 8] = [
+    0x6A09E667F3BCC908, 0xBB67AE8584CAA73B, 0x3C6EF372FE94F82B, 0xA54FF53A5F1D36F1,
+    0x510E527FADE682D1, 0x9B05688C2B3E6C1F, 0x1F83D9ABFB41BD6B, 0x5BE0CD19137E2179,
+];
+
+/// Computes the SHA-256 digest of `message`, using `f` for the functions
+/// computed by Zknh.
+fn sha256(message: &[u8], mut f: impl FnMut(Sha, u32) -> u32) -> Vec<u8> {
+    let mut hash = SHA256_H;
+
+    for block in pad(message, 64).chunks(64) {
+        let mut w = [0; 64];
+
+        for (i, word) in block.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes(word.try_into().unwrap());
+        }
+
+        for i in 16..64 {
+            w[i] = f(Sha::Sig1, w[i - 2])
+                .wrapping_add(w[i - 7])
+                .wrapping_add(f(Sha::Sig0, w[i - 15]))
+                .wrapping_add(w[i - 16]);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f_, mut g, mut h] = hash;
+
+        for i in 0..64 {
+            let t1 = h
+                .wrapping_add(f(Sha::Sum1, e))
+                .wrapping_add(e & f_ ^ !e & g)
+                .wrapping_add(SHA256_K[i])
+                .wrapping_add(w[i]);
+            let t2 = f(Sha::Sum0, a).wrapping_add(a & b ^ a & c ^ b & c);
+
+            (h, g, f_, e) = (g, f_, e, d.wrapping_add(t1));
+            (d, c, b, a) = (c, b, a, t1.wrapping_add(t2));
+        }
+
+        for (hash, value) in hash.iter_mut().zip([a, b, c, d, e, f_, g, h]) {
+            *hash = hash.wrapping_add(value);
+        }
+    }
+
+    hash.iter().flat_map(|word| word.to_be_bytes()).collect()
+}
+
+/// Computes the SHA-512 digest of `message`, using `f` for the functions
+/// computed by Zknh.
+fn sha512(message: &[u8], mut f: impl FnMut(Sha, u64) -> u64) -> Vec<u8> {
+    let mut hash = SHA512_H;
+
+    for block in pad(message, 128).chunks(128) {
+        let mut w = [0; 80];
+
+        for (i, word) in block.chunks(8).enumerate() {
+            w[i] = u64::from_be_bytes(word.try_into().unwrap());
+        }
+
+        for i in 16..80 {
+            w[i] = f(Sha::Sig1, w[i - 2])
+                .wrapping_add(w[i - 7])
+                .wrapping_add(f(Sha::Sig0, w[i - 15]))
+                .wrapping_add(w[i - 16]);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f_, mut g, mut h] = hash;
+
+        for i in 0..80 {
+            let t1 = h
+                .wrapping_add(f(Sha::Sum1, e))
+                .wrapping_add(e & f_ ^ !e & g)
+                .wrapping_add(SHA512_K[i])
+                .wrapping_add(w[i]);
+            let t2 = f(Sha::Sum0, a).wrapping_add(a & b ^ a & c ^ b & c);
+
+            (h, g, f_, e) = (g, f_, e, d.wrapping_add(t1));
+            (d, c, b, a) = (c, b, a, t1.wrapping_add(t2));
+        }
+
+        for (hash, value) in hash.iter_mut().zip([a, b, c, d, e, f_, g, h]) {
+            *hash = hash.wrapping_add(value);
+        }
+    }
+
+    hash.iter().flat_map(|word| word.to_be_bytes()).collect()
+}
+
+/// FIPS 180-2, Appendices B.1 and B.2
+const SHA256_VECTORS: [(&str, &str); 2] = [
+    (
+        "abc",
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+    ),
+    (
+        "abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq",
+        "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1",
+    ),
+];
+
+/// FIPS 180-2, Appendices C.1 and C.2
+const SHA512_VECTORS: [(&str, &str); 2] = [
+    (
+        "abc",
+        "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a\
+         2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f",
+    ),
+    (
+        "abcdefghbcdefghicdefghijdefghijkefghijklfghijklmghijklmnhijklmno\
+         ijklmnopjklmnopqklmnopqrlmnopqrsmnopqrstnopqrstu",
+        "8e959b75dae313da8cf4f72814fc143f8f7779c6eb9f7fa17299aeadb6889018\
+         501d289e4900f7e4331b99dec4b5433ac7d329eeb6dd26545e96e55b874be909",
+    ),
+];
+
+#[test]
+fn test_sha256() {
+    for isa in ["imacsu", "rv32imacsu"] {
+        let mut hart = Hart::new(isa);
+
+        for (message, digest) in SHA256_VECTORS {
+            let result = sha256(message.as_bytes(), |function, x| {
+                let instruction = match function {
+                    Sha::Sum0 => SHA256SUM0,
+                    Sha::Sum1 => SHA256SUM1,
+                    Sha::Sig0 => SHA256SIG0,
+                    Sha::Sig1 => SHA256SIG1,
+                };
+
+                hart.compute_32(instruction, x, 0)
+            });
+
+            assert_eq!(result, hex(digest), "SHA-256 of {message:?} on {isa}");
+        }
+    }
+}
+
+#[test]
+fn test_sha512_64() {
+    let mut hart = Hart::new("imacsu");
+
+    for (message, digest) in SHA512_VECTORS {
+        let result = sha512(message.as_bytes(), |function, x| {
+            let instruction = match function {
+                Sha::Sum0 => SHA512SUM0,
+                Sha::Sum1 => SHA512SUM1,
+                Sha::Sig0 => SHA512SIG0,
+                Sha::Sig1 => SHA512SIG1,
+            };
+
+            hart.compute(instruction, x, 0)
+        });
+
+        assert_eq!(result, hex(digest), "SHA-512 of {message:?}");
+    }
+
+    // The RV32 instructions do not exist in RV64
+    assert!(is_illegal(hart.evaluate(SHA512SUM0R, 0, 0)));
+}
+
+#[test]
+fn test_sha512_32() {
+    let mut hart = Hart::new("rv32imacsu");
+
+    for (message, digest) in SHA512_VECTORS {
+        let result = sha512(message.as_bytes(), |function, x| {
+            let (high, low) = ((x >> 32) as u32, x as u32);
+
+            // Each instruction computes one half of the result, given that
+            // half of the input in rs1 and the other half in rs2
+            let (high, low) = match function {
+                Sha::Sum0 => (
+                    hart.compute_32(SHA512SUM0R, high, low),
+                    hart.compute_32(SHA512SUM0R, low, high),
+                ),
+                Sha::Sum1 => (
+                    hart.compute_32(SHA512SUM1R, high, low),
+                    hart.compute_32(SHA512SUM1R, low, high),
+                ),
+                Sha::Sig0 => (
+                    hart.compute_32(SHA512SIG0H, high, low),
+                    hart.compute_32(SHA512SIG0L, low, high),
+                ),
+                Sha::Sig1 => (
+                    hart.compute_32(SHA512SIG1H, high, low),
+                    hart.compute_32(SHA512SIG1L, low, high),
+                ),
+            };
+
+            (high as u64) << 32 | low as u64
+        });
+
+        assert_eq!(result, hex(digest), "SHA-512 of {message:?}");
+    }
+}
+
+// Zksed and Zksh
+
+/// GB/T 32907-2016, Appendix A.1
+const SM4_KEY: &str = "0123456789abcdeffedcba9876543210";
+const SM4_PLAINTEXT: &str = "0123456789abcdeffedcba9876543210";
+const SM4_CIPHERTEXT: &str = "681edf34d206965e86b3e94f536e4246";
+
+/// Expands an SM4 key into its round keys.
+fn sm4_key_schedule(hart: &mut Hart, key: &[u8]) -> Vec<u32> {
+    const FK: [u32; 4] = [0xA3B1BAC6, 0x56AA3350, 0x677D9197, 0xB27022DC];
+
+    let mut k: Vec<u32> = key
+        .chunks(4)
+        .zip(FK)
+        .map(|(word, fk)| u32::from_be_bytes(word.try_into().unwrap()) ^ fk)
+        .collect();
+
+    for i in 0..32 {
+        // Byte j of CK[i] is (4i + j) * 7 mod 256
+        let ck = u32::from_be_bytes([0, 1, 2, 3].map(|j| ((4 * i + j) * 7) as u8));
+        let next = hart.each_byte(SM4KS, k[i], k[i + 1] ^ k[i + 2] ^ k[i + 3] ^ ck);
+
+        k.push(next);
+    }
+
+    k.split_off(4)
+}
+
+fn sm4_crypt(hart: &mut Hart, keys: impl Iterator<Item = u32>, input: &[u8]) -> Vec<u8> {
+    let mut x: Vec<u32> = input
+        .chunks(4)
+        .map(|word| u32::from_be_bytes(word.try_into().unwrap()))
+        .collect();
+
+    for (i, key) in keys.enumerate() {
+        let next = hart.each_byte(SM4ED, x[i], x[i + 1] ^ x[i + 2] ^ x[i + 3] ^ key);
+
+        x.push(next);
+    }
+
+    x.iter()
+        .rev()
+        .take(4)
+        .flat_map(|word| word.to_be_bytes())
+        .collect()
+}
+
+#[test]
+fn test_sm4() {
+    for isa in ["imacsu", "rv32imacsu"] {
+        let mut hart = Hart::new(isa);
+
+        let keys = sm4_key_schedule(&mut hart, &hex(SM4_KEY));
+        let ciphertext = sm4_crypt(&mut hart, keys.iter().copied(), &hex(SM4_PLAINTEXT));
+
+        assert_eq!(ciphertext, hex(SM4_CIPHERTEXT), "SM4 on {isa}");
+
+        // Decryption uses the round keys in reverse order
+        let plaintext = sm4_crypt(&mut hart, keys.iter().rev().copied(), &ciphertext);
+
+        assert_eq!(plaintext, hex(SM4_PLAINTEXT), "SM4 on {isa}");
+    }
+}
+
+/// Computes the SM3 digest of `message`.
+fn sm3(hart: &mut Hart, message: &[u8]) -> Vec<u8> {
+    let mut hash: [u32; 8] = [
+        0x7380166F, 0x4914B2B9, 0x172442D7, 0xDA8A0600, 0xA96F30BC, 0x163138AA, 0xE38DEE4D,
+        0xB0FB0E4E,
+    ];
+
+    for block in pad(message, 64).chunks(64) {
+        let mut w = [0; 68];
+
+        for (i, word) in block.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes(word.try_into().unwrap());
+        }
+
+        for j in 16..68 {
+            let x = w[j - 16] ^ w[j - 9] ^ w[j - 3].rotate_left(15);
+
+            w[j] = hart.compute_32(SM3P1, x, 0) ^ w[j - 13].rotate_left(7) ^ w[j - 6];
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = hash;
+
+        for j in 0..64 {
+            let (t, ff, gg) = if j < 16 {
+                (0x79CC4519u32, a ^ b ^ c, e ^ f ^ g)
+            } else {
+                (0x7A879D8A, a & b | a & c | b & c, e & f | !e & g)
+            };
+
+            let ss1 = a
+                .rotate_left(12)
+                .wrapping_add(e)
+                .wrapping_add(t.rotate_left(j as u32 % 32))
+                .rotate_left(7);
+            let ss2 = ss1 ^ a.rotate_left(12);
+            let tt1 = ff
+                .wrapping_add(d)
+                .wrapping_add(ss2)
+                .wrapping_add(w[j] ^ w[j + 4]);
+            let tt2 = gg.wrapping_add(h).wrapping_add(ss1).wrapping_add(w[j]);
+
+            (d, c, b, a) = (c, b.rotate_left(9), a, tt1);
+            (h, g, f, e) = (g, f.rotate_left(19), e, hart.compute_32(SM3P0, tt2, 0));
+        }
+
+        for (hash, value) in hash.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *hash ^= value;
+        }
+    }
+
+    hash.iter().flat_map(|word| word.to_be_bytes()).collect()
+}
+
+/// GB/T 32905-2016, Appendices A.1 and A.2
+const SM3_VECTORS: [(&str, &str); 2] = [
+    (
+        "abc",
+        "66c7f0f462eeedd9d1f2d46bdc10e4e24167c4875cf2f7a2297da02b8f4ba8e0",
+    ),
+    (
+        "abcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcd",
+        "debe9ff92275b8a138604889c18e5a4d6fdb70e5387e5765293dcba39c0c5732",
+    ),
+];
+
+#[test]
+fn test_sm3() {
+    for isa in ["imacsu", "rv32imacsu"] {
+        let mut hart = Hart::new(isa);
+
+        for (message, digest) in SM3_VECTORS {
+            let result = sm3(&mut hart, message.as_bytes());
+
+            assert_eq!(result, hex(digest), "SM3 of {message:?} on {isa}");
+        }
+    }
+}
+
+#[test]
+fn test_disabled_extensions() {
+    let mut hart = Hart::new("imacsu");
+
+    hart.0.extensions.zknh = false;
+    hart.0.extensions.zkne = false;
+    hart.0.extensions.zksh = false;
+
+    for instruction in [SHA256SIG0, SHA512SUM1, AES64ESM, SM3P0] {
+        assert!(is_illegal(hart.evaluate(instruction, 0, 0)));
+    }
+
+    // AES64KS1I and AES64KS2 are in both Zknd and Zkne
+    hart.compute(aes64ks1i(0), 0, 0);
+    hart.compute(AES64KS2, 0, 0);
+    hart.compute(AES64DSM, 0, 0);
+}
+
+// Zkr
+
+#[test]
+fn test_seed() {
+    let mut first = Hart::new("imacsu");
+    let mut second = Hart::new("imacsu");
+
+    first.0.entropy = Entropy::new(42);
+    second.0.entropy = Entropy::new(42);
+
+    for _ in 0..16 {
+        let seed = first.compute(CSRRW_SEED, 0, 0);
+
+        // OPST is always ES16, leaving 16 bits of entropy
+        assert_eq!(seed >> 16, 0b10 << 14);
+        assert_eq!(second.compute(CSRRW_SEED, 0, 0), seed);
+    }
+
+    // seed cannot be read without writing to it
+    assert!(is_illegal(first.evaluate(CSRRS_SEED, 0, 0)));
+
+    // Below machine mode, mseccfg must grant access to seed
+    first.0.privilege = Privilege::Supervisor;
+    assert!(is_illegal(first.evaluate(CSRRW_SEED, 0, 0)));
+
+    first.0.csr.mseccfg = 1 << 9;
+    first.compute(CSRRW_SEED, 0, 0);
+
+    first.0.privilege = Privilege::User;
+    assert!(is_illegal(first.evaluate(CSRRW_SEED, 0, 0)));
+
+    first.0.csr.mseccfg = 1 << 8;
+    first.compute(CSRRW_SEED, 0, 0);
+
+    first.0.extensions.zkr = false;
+    assert!(is_illegal(first.evaluate(CSRRW_SEED, 0, 0)));
+}
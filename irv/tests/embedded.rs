@@ -0,0 +1,138 @@
+//! Checks that RV32E and RV64E harts reject instructions that refer to x16
+//! through x31 in any of their register fields.
+
+mod common;
+
+use common::{is_illegal, Hart, DATA};
+
+const fn op(rd: u32, rs1: u32, rs2: u32) -> u32 {
+    rs2 << 20 | rs1 << 15 | rd << 7 | 0b0110011
+}
+
+const fn op_32(rd: u32, rs1: u32, rs2: u32) -> u32 {
+    rs2 << 20 | rs1 << 15 | rd << 7 | 0b0111011
+}
+
+const fn op_imm(rd: u32, rs1: u32) -> u32 {
+    1 << 20 | rs1 << 15 | rd << 7 | 0b0010011
+}
+
+const fn load(rd: u32, rs1: u32) -> u32 {
+    rs1 << 15 | 0b010 << 12 | rd << 7 | 0b0000011
+}
+
+const fn store(rs1: u32, rs2: u32) -> u32 {
+    rs2 << 20 | rs1 << 15 | 0b010 << 12 | 0b0100011
+}
+
+const fn branch(rs1: u32, rs2: u32) -> u32 {
+    rs2 << 20 | rs1 << 15 | 0b1100011
+}
+
+const fn jalr(rd: u32, rs1: u32) -> u32 {
+    rs1 << 15 | rd << 7 | 0b1100111
+}
+
+/// `csrrs rd, mscratch, rs1`
+const fn csrrs(rd: u32, rs1: u32) -> u32 {
+    0x340 << 20 | rs1 << 15 | 0b010 << 12 | rd << 7 | 0b1110011
+}
+
+/// Instructions that refer to the given register in each of their fields.
+const INSTRUCTIONS: [fn(u32) -> u32; 17] = [
+    |r| op(r, 1, 2),
+    |r| op(1, r, 2),
+    |r| op(1, 2, r),
+    |r| op_imm(r, 1),
+    |r| op_imm(1, r),
+    |r| load(r, 1),
+    |r| load(1, r),
+    |r| store(r, 1),
+    |r| store(1, r),
+    |r| branch(r, 1),
+    |r| branch(1, r),
+    |r| jalr(r, 1),
+    |r| jalr(1, r),
+    // LUI and JAL
+    |r| 1 << 12 | r << 7 | 0b0110111,
+    |r| r << 7 | 0b1101111,
+    |r| csrrs(r, 1),
+    |r| csrrs(1, r),
+];
+
+/// Instructions that only exist in RV64E.
+const INSTRUCTIONS_64: [fn(u32) -> u32; 3] =
+    [|r| op_32(r, 1, 2), |r| op_32(1, r, 2), |r| op_32(1, 2, r)];
+
+/// Compressed instructions that refer to the given register, which are
+/// C.ADDI, C.MV, C.ADD, C.LWSP, C.SWSP, and C.JR.
+const COMPRESSED: [fn(u32) -> u16; 7] = [
+    |r| (r << 7 | 1 << 2 | 0b01) as u16,
+    |r| (0b1000 << 12 | 10 << 7 | r << 2 | 0b10) as u16,
+    |r| (0b1000 << 12 | r << 7 | 10 << 2 | 0b10) as u16,
+    |r| (0b1001 << 12 | r << 7 | 10 << 2 | 0b10) as u16,
+    |r| (0b010 << 13 | r << 7 | 0b10) as u16,
+    |r| (0b110 << 13 | r << 2 | 0b10) as u16,
+    |r| (0b1000 << 12 | r << 7 | 0b10) as u16,
+];
+
+impl Hart {
+    /// Creates a hart where every register that can be used as an address
+    /// points to data.
+    fn embedded(isa: &str) -> Hart {
+        let mut hart = Hart::new(isa);
+        hart.0.gpr = [DATA; 32];
+        hart.0.gpr[0] = 0;
+
+        hart
+    }
+}
+
+fn check(isa: &str, instructions: &[fn(u32) -> u32]) {
+    for register in [1, 15, 16, 31] {
+        for instruction in instructions.iter().map(|f| f(register)) {
+            let mut hart = Hart::embedded(isa);
+
+            assert_eq!(
+                is_illegal(hart.run(&[instruction])),
+                register >= 16,
+                "{instruction:#010x} in {isa}"
+            );
+        }
+
+        for parcel in COMPRESSED.iter().map(|f| f(register)) {
+            let mut hart = Hart::embedded(isa);
+
+            assert_eq!(
+                is_illegal(hart.run_parcels(&[parcel], 1)),
+                register >= 16,
+                "{parcel:#06x} in {isa}"
+            );
+        }
+    }
+}
+
+#[test]
+fn test_rv32e() {
+    check("rv32emcsu", &INSTRUCTIONS);
+}
+
+#[test]
+fn test_rv64e() {
+    check("emcsu", &INSTRUCTIONS);
+    check("emcsu", &INSTRUCTIONS_64);
+}
+
+#[test]
+fn test_upper_registers() {
+    // The same instructions are legal with all 32 registers
+    let mut hart = Hart::embedded("imcsu");
+
+    for instruction in INSTRUCTIONS.iter().chain(&INSTRUCTIONS_64) {
+        assert!(!is_illegal(hart.run(&[instruction(31)])));
+    }
+
+    for parcel in COMPRESSED {
+        assert!(!is_illegal(hart.run_parcels(&[parcel(31)], 1)));
+    }
+}
@@ -0,0 +1,215 @@
+//! Checks the NaN-boxing, NaN results, and exception flags of the F and D
+//! extensions at the level of the instructions.
+
+mod common;
+
+use common::{is_illegal, Hart, DATA};
+use irv::Bus;
+
+const MSTATUS_FS: u64 = 0b11 << 13;
+
+const FFLAGS: u32 = 0x001;
+const FRM: u32 = 0x002;
+
+const NV: u64 = 0b10000;
+const NX: u64 = 0b00001;
+
+const RNE: u32 = 0b000;
+const RDN: u32 = 0b010;
+const DYN: u32 = 0b111;
+
+const ONE: u64 = 0x3F80_0000;
+const MINUS_POINT_3: u64 = 0xBE99_999A;
+const SIGNALING_NAN: u64 = 0x7F80_0001;
+/// The canonical single-precision NaN, NaN-boxed.
+const BOXED_NAN: u64 = 0xFFFF_FFFF_7FC0_0000;
+
+/// Encodes an OP-FP instruction that writes f3 or x10 from f1 and f2.
+const fn op_fp(funct7: u32, rs2: u32, rm: u32) -> u32 {
+    funct7 << 25 | rs2 << 20 | 1 << 15 | rm << 12 | 3 << 7 | 0b1010011
+}
+
+/// Encodes an OP-FP instruction that moves or converts between f1 and x10 or
+/// x11.
+const fn op_fp_int(funct7: u32, rs2: u32, rm: u32, rd: u32, rs1: u32) -> u32 {
+    funct7 << 25 | rs2 << 20 | rs1 << 15 | rm << 12 | rd << 7 | 0b1010011
+}
+
+const FADD_S: u32 = op_fp(0b0000000, 2, DYN);
+const FADD_D: u32 = op_fp(0b0000001, 2, DYN);
+const FSGNJN_S: u32 = op_fp(0b0010000, 2, 0b001);
+const FMIN_S: u32 = op_fp(0b0010100, 2, 0b000);
+const FMAX_S: u32 = op_fp(0b0010100, 2, 0b001);
+/// `fmv.x.w x10, f1`
+const FMV_X_W: u32 = op_fp_int(0b1110000, 0, 0b000, 10, 1);
+/// `fmv.w.x f1, x11`
+const FMV_W_X: u32 = op_fp_int(0b1111000, 0, 0b000, 1, 11);
+
+/// `fcvt.wu.s x10, f1, rm`
+const fn fcvt_wu_s(rm: u32) -> u32 {
+    op_fp_int(0b1100000, 1, rm, 10, 1)
+}
+
+/// `fcvt.w.s x10, f1, rm`
+const fn fcvt_w_s(rm: u32) -> u32 {
+    op_fp_int(0b1100000, 0, rm, 10, 1)
+}
+
+/// `flw f1, 0(x11)`
+const FLW: u32 = 11 << 15 | 0b010 << 12 | 1 << 7 | 0b0000111;
+/// `fsw f1, 8(x11)`
+const FSW: u32 = 1 << 20 | 11 << 15 | 0b010 << 12 | 8 << 7 | 0b0100111;
+
+/// `csrrwi x30, csr, uimm`
+const fn csrrwi(csr: u32, uimm: u32) -> u32 {
+    csr << 20 | uimm << 15 | 0b101 << 12 | 30 << 7 | 0b1110011
+}
+
+impl Hart {
+    /// Creates a hart with the F and D extensions, whose floating-point unit
+    /// is on.
+    fn float() -> Hart {
+        let mut hart = Hart::new("imafdcsu");
+        hart.0.csr.mstatus |= MSTATUS_FS;
+
+        hart
+    }
+
+    /// Runs the given instruction on f1 and f2, returning f3.
+    fn compute_fp(&mut self, instruction: u32, a: u64, b: u64) -> u64 {
+        self.0.fpr[1] = a;
+        self.0.fpr[2] = b;
+        self.execute(&[instruction]);
+        self.0.fpr[3]
+    }
+
+    /// Runs the given instruction on f1, returning x10.
+    fn compute_int(&mut self, instruction: u32, a: u64) -> u64 {
+        self.0.fpr[1] = a;
+        self.execute(&[instruction]);
+        self.0.gpr[10]
+    }
+
+    /// Reads and clears `fflags` with an instruction.
+    fn take_flags(&mut self) -> u64 {
+        self.execute(&[csrrwi(FFLAGS, 0)]);
+        self.0.gpr[30]
+    }
+}
+
+/// NaN-boxes a single-precision value.
+const fn boxed(value: u64) -> u64 {
+    value | 0xFFFF_FFFF_0000_0000
+}
+
+#[test]
+fn test_nan_boxing() {
+    let mut hart = Hart::float();
+
+    // Single-precision values are NaN-boxed when written
+    hart.0.gpr[11] = 0x1234_5678 << 32 | ONE;
+    hart.execute(&[FMV_W_X]);
+    assert_eq!(hart.0.fpr[1], boxed(ONE));
+
+    hart.0.bus.store(DATA, 0x4000_0000u32).unwrap();
+    hart.0.gpr[11] = DATA;
+    hart.execute(&[FLW]);
+    assert_eq!(hart.0.fpr[1], boxed(0x4000_0000));
+
+    // Values that are not properly NaN-boxed are read as the canonical NaN,
+    // which does not signal
+    assert_eq!(hart.compute_fp(FADD_S, ONE, boxed(ONE)), BOXED_NAN);
+    assert_eq!(
+        hart.compute_fp(FADD_S, 0xFFFF_FFFE_3F80_0000, boxed(ONE)),
+        BOXED_NAN
+    );
+
+    // The canonical NaN is positive, so this negates the result
+    assert_eq!(
+        hart.compute_fp(FSGNJN_S, boxed(ONE), ONE),
+        boxed(0xBF80_0000)
+    );
+    assert_eq!(hart.take_flags(), 0);
+
+    // Moves and stores use the raw register contents
+    assert_eq!(
+        hart.compute_int(FMV_X_W, 0x1234_5678_BF80_0000),
+        0xFFFF_FFFF_BF80_0000
+    );
+
+    hart.0.fpr[1] = ONE;
+    hart.execute(&[FSW]);
+    let stored: u32 = hart.0.bus.load(DATA + 8).unwrap();
+    assert_eq!(stored, ONE as u32);
+
+    // Double-precision values use the whole register
+    let two = 0x4000_0000_0000_0000;
+    assert_eq!(hart.compute_fp(FADD_D, two, two), 0x4010_0000_0000_0000);
+}
+
+#[test]
+fn test_canonical_nan() {
+    let mut hart = Hart::float();
+
+    assert_eq!(
+        hart.compute_fp(FADD_S, boxed(SIGNALING_NAN), boxed(ONE)),
+        BOXED_NAN
+    );
+    assert_eq!(hart.take_flags(), NV);
+
+    // The payload and sign of a quiet NaN are not propagated
+    assert_eq!(
+        hart.compute_fp(FADD_S, boxed(0xFFC1_2345), boxed(ONE)),
+        BOXED_NAN
+    );
+    assert_eq!(hart.take_flags(), 0);
+
+    let nan = 0xFFF8_0000_0000_1234;
+    assert_eq!(hart.compute_fp(FADD_D, nan, 0), 0x7FF8_0000_0000_0000);
+}
+
+#[test]
+fn test_min_max() {
+    let mut hart = Hart::float();
+    let minus_zero = boxed(0x8000_0000);
+
+    assert_eq!(hart.compute_fp(FMIN_S, boxed(0), minus_zero), minus_zero);
+    assert_eq!(hart.compute_fp(FMAX_S, minus_zero, boxed(0)), boxed(0));
+    assert_eq!(hart.take_flags(), 0);
+
+    assert_eq!(
+        hart.compute_fp(FMAX_S, boxed(SIGNALING_NAN), boxed(ONE)),
+        boxed(ONE)
+    );
+    assert_eq!(hart.take_flags(), NV);
+}
+
+#[test]
+fn test_convert_to_int() {
+    let mut hart = Hart::float();
+
+    // -0.3 rounds to zero, so converting it to an unsigned integer is only
+    // inexact
+    assert_eq!(hart.compute_int(fcvt_wu_s(RNE), boxed(MINUS_POINT_3)), 0);
+    assert_eq!(hart.take_flags(), NX);
+
+    // Rounding down with the dynamic rounding mode gives -1 instead
+    hart.execute(&[csrrwi(FRM, RDN)]);
+    assert_eq!(hart.compute_int(fcvt_wu_s(DYN), boxed(MINUS_POINT_3)), 0);
+    assert_eq!(hart.take_flags(), NV);
+
+    // Out-of-range values and NaNs saturate, sign-extended from 32 bits
+    assert_eq!(
+        hart.compute_int(fcvt_w_s(RNE), boxed(0x4F80_0000)),
+        0x7FFF_FFFF
+    );
+    assert_eq!(hart.compute_int(fcvt_wu_s(RNE), BOXED_NAN), u64::MAX);
+    assert_eq!(hart.take_flags(), NV);
+
+    // Reserved rounding modes are illegal, both in the instruction and in frm
+    hart.0.fpr[1] = boxed(ONE);
+    assert!(is_illegal(hart.run(&[fcvt_w_s(0b101)])));
+
+    hart.execute(&[csrrwi(FRM, 0b101)]);
+    assert!(is_illegal(hart.run(&[fcvt_w_s(DYN)])));
+}
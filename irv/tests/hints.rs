@@ -0,0 +1,282 @@
+//! Checks the instructions of Zicond, and the hints and may-be-operations of
+//! Zihintntl, Zihintpause, Zimop, and Zcmop.
+
+mod common;
+
+use std::cell::RefCell;
+
+use common::{is_illegal, test_bus, Hart, DATA, TEST_BUS_BASE};
+use irv::{
+    AccessKind, AtomicBus, Bus, BusError, Exception, Interrupts, Locality, MemoryAccess, SystemBus,
+};
+
+const NOP: u32 = 0x00000013;
+const PAUSE: u32 = 0x0100000F;
+/// `lw x5, 0(x10)`
+const LW: u32 = 10 << 15 | 0b010 << 12 | 5 << 7 | 0b0000011;
+
+/// `czero.eqz x10, x11, x12`
+const CZERO_EQZ: u32 = 0b0000111 << 25 | 12 << 20 | 11 << 15 | 0b101 << 12 | 10 << 7 | 0b0110011;
+/// `czero.nez x10, x11, x12`
+const CZERO_NEZ: u32 = CZERO_EQZ | 0b010 << 12;
+
+/// `add x0, x0, rs2`, which is an `NTL` hint when rs2 is x2 through x5
+const fn ntl(rs2: u32) -> u32 {
+    rs2 << 20 | 0b0110011
+}
+
+/// `mop.r.n x10, x11`
+const fn mop_r(n: u32) -> u32 {
+    1 << 31
+        | (n >> 4 & 1) << 30
+        | (n >> 2 & 0b11) << 26
+        | 0b0111 << 22
+        | (n & 0b11) << 20
+        | 11 << 15
+        | 0b100 << 12
+        | 10 << 7
+        | 0b1110011
+}
+
+/// `mop.rr.n x10, x11, rs2`
+const fn mop_rr(n: u32, rs2: u32) -> u32 {
+    1 << 31
+        | (n >> 2 & 1) << 30
+        | (n & 0b11) << 26
+        | 1 << 25
+        | rs2 << 20
+        | 11 << 15
+        | 0b100 << 12
+        | 10 << 7
+        | 0b1110011
+}
+
+/// `c.mop.n`, for odd `n` below 16
+const fn c_mop(n: u16) -> u16 {
+    0b011 << 13 | n << 7 | 0b01
+}
+
+/// `c.add x0, rs2`, which is a `C.NTL` hint when rs2 is x2 through x5
+const fn c_ntl(rs2: u16) -> u16 {
+    0b1001 << 12 | rs2 << 2 | 0b10
+}
+
+/// A bus that records the attributes of each load and store made through it.
+struct RecordingBus {
+    bus: SystemBus,
+    accesses: RefCell<Vec<MemoryAccess>>,
+}
+
+macro_rules! impl_recording_bus {
+    ($($val:ident)*) => {
+        $(impl Bus<u64, $val> for RecordingBus {
+            fn load(&self, address: u64) -> Result<$val, BusError> {
+                self.bus.load(address)
+            }
+
+            fn store(&self, address: u64, value: $val) -> Result<(), BusError> {
+                self.bus.store(address, value)
+            }
+
+            fn load_with(&self, address: u64, access: MemoryAccess) -> Result<$val, BusError> {
+                if access.kind != AccessKind::Fetch {
+                    self.accesses.borrow_mut().push(access);
+                }
+
+                self.bus.load_with(address, access)
+            }
+
+            fn store_with(
+                &self,
+                address: u64,
+                value: $val,
+                access: MemoryAccess,
+            ) -> Result<(), BusError> {
+                self.accesses.borrow_mut().push(access);
+                self.bus.store_with(address, value, access)
+            }
+        })*
+    };
+}
+
+impl_recording_bus!(u8 u16 u32 u64);
+
+macro_rules! impl_recording_atomic_bus {
+    ($($val:ident)*) => {
+        $(impl AtomicBus<u64, $val> for RecordingBus {
+            fn load_reserved(&self, hart: u64, address: u64) -> Result<$val, BusError> {
+                self.bus.load_reserved(hart, address)
+            }
+
+            fn store_conditional(
+                &self,
+                hart: u64,
+                address: u64,
+                value: $val,
+            ) -> Result<bool, BusError> {
+                self.bus.store_conditional(hart, address, value)
+            }
+
+            fn fetch_update(
+                &self,
+                address: u64,
+                f: impl FnOnce($val) -> $val,
+            ) -> Result<$val, BusError> {
+                self.bus.fetch_update(address, f)
+            }
+        })*
+    };
+}
+
+impl_recording_atomic_bus!(u32 u64);
+
+impl Interrupts for RecordingBus {
+    fn pending(&self, hart: u64) -> u64 {
+        self.bus.pending(hart)
+    }
+}
+
+impl Hart<RecordingBus> {
+    fn recording(isa: &str) -> Hart<RecordingBus> {
+        let bus = RecordingBus {
+            bus: test_bus(),
+            accesses: RefCell::new(Vec::new()),
+        };
+
+        Hart::with_bus(bus, isa)
+    }
+
+    /// Takes the localities of the loads and stores made so far.
+    fn localities(&mut self) -> Vec<Locality> {
+        self.0
+            .bus
+            .accesses
+            .take()
+            .into_iter()
+            .map(|access| access.locality)
+            .collect()
+    }
+}
+
+#[test]
+fn test_conditional_zero() {
+    let mut hart = Hart::new("imcsu");
+
+    assert_eq!(hart.evaluate(CZERO_EQZ, 1234, 0).unwrap(), 0);
+    assert_eq!(hart.evaluate(CZERO_EQZ, 1234, 1 << 63).unwrap(), 1234);
+    assert_eq!(hart.evaluate(CZERO_NEZ, 1234, 0).unwrap(), 1234);
+    assert_eq!(hart.evaluate(CZERO_NEZ, 1234, 1 << 63).unwrap(), 0);
+
+    // The condition is all XLEN bits of rs2, which RV32 holds sign-extended
+    let mut hart = Hart::new("rv32imcsu");
+
+    assert_eq!(
+        hart.evaluate(CZERO_EQZ, -5i64 as u64, 1 << 31).unwrap(),
+        -5i64 as u64
+    );
+
+    hart.0.extensions.zicond = false;
+
+    assert!(is_illegal(hart.evaluate(CZERO_EQZ, 1, 1)));
+    assert!(is_illegal(hart.evaluate(CZERO_NEZ, 1, 1)));
+}
+
+#[test]
+fn test_pause() {
+    let mut hart = Hart::new("imcsu");
+
+    hart.run(&[PAUSE]).unwrap();
+    assert!(hart.0.is_paused());
+
+    // The hint only applies to the instruction that gave it
+    hart.run(&[PAUSE, NOP]).unwrap();
+    assert!(!hart.0.is_paused());
+
+    // Without Zihintpause, PAUSE is an ordinary FENCE
+    hart.0.extensions.zihintpause = false;
+
+    hart.run(&[PAUSE]).unwrap();
+    assert!(!hart.0.is_paused());
+}
+
+#[test]
+fn test_non_temporal_hints() {
+    let mut hart = Hart::recording("imcsu");
+
+    hart.0.gpr[10] = DATA;
+
+    // Each hint only applies to the accesses of the next instruction
+    hart.run(&[ntl(2), LW, LW, ntl(3), LW, ntl(4), LW, ntl(5), LW])
+        .unwrap();
+
+    assert_eq!(
+        hart.localities(),
+        [
+            Locality::InnermostPrivate,
+            Locality::Temporal,
+            Locality::AllPrivate,
+            Locality::InnermostShared,
+            Locality::All,
+        ]
+    );
+
+    // The compressed hints are C.ADDs, and other ADDs to x0 are not hints
+    let [low, high] = [LW as u16, (LW >> 16) as u16];
+
+    hart.run_parcels(&[c_ntl(5), low, high], 2).unwrap();
+    hart.run(&[ntl(6), LW]).unwrap();
+
+    assert_eq!(hart.localities(), [Locality::All, Locality::Temporal]);
+
+    // Without Zihintntl, the hints are ordinary ADDs to x0
+    hart.0.extensions.zihintntl = false;
+
+    hart.run(&[ntl(2), LW]).unwrap();
+    assert_eq!(hart.localities(), [Locality::Temporal]);
+}
+
+#[test]
+fn test_may_be_operations() {
+    let mut hart = Hart::new("imcsu");
+
+    for n in 0..32 {
+        assert_eq!(hart.evaluate(mop_r(n), 1234, 0).unwrap(), 0, "MOP.R.{n}");
+    }
+
+    for n in 0..8 {
+        assert_eq!(
+            hart.evaluate(mop_rr(n, 12), 1234, 5678).unwrap(),
+            0,
+            "MOP.RR.{n}"
+        );
+    }
+
+    // C.MOP.n does not write any register
+    for n in (1..16).step_by(2) {
+        hart.0.gpr[n as usize] = 1234;
+        hart.run_parcels(&[c_mop(n)], 1).unwrap();
+
+        assert_eq!(hart.0.gpr[n as usize], 1234, "C.MOP.{n}");
+        assert_eq!(hart.0.pc, TEST_BUS_BASE + 2);
+    }
+
+    // Registers above x15 do not exist in RV64E
+    let mut hart = Hart::new("ecsu");
+
+    assert!(hart.evaluate(mop_rr(0, 12), 0, 0).is_ok());
+    assert!(is_illegal(hart.evaluate(mop_rr(0, 16), 0, 0)));
+
+    hart.0.extensions.zimop = false;
+    hart.0.extensions.zcmop = false;
+
+    assert!(is_illegal(hart.evaluate(mop_r(0), 0, 0)));
+    assert!(is_illegal(hart.evaluate(mop_rr(0, 12), 0, 0)));
+
+    // C.MOP.n is reported as the reserved C.LUI encoding it occupies
+    match hart.run_parcels(&[c_mop(1)], 1) {
+        Err(Exception::IllegalInstruction { instruction }) => {
+            assert_eq!(instruction.map_or(0, |i| i.get()), c_mop(1) as u32);
+        }
+        result => panic!("Expected an illegal instruction, got {result:?}"),
+    }
+}
@@ -0,0 +1,306 @@
+//! Checks which interrupts raised on the lines of a hart are taken, and where
+//! they are taken to.
+
+mod common;
+
+use common::{is_illegal, Hart, DATA, TEST_BUS_BASE, TEST_BUS_SIZE};
+use irv::{Bus, Interrupt, Privilege};
+
+const NOP: u32 = 0x00000013;
+const WFI: u32 = 0x10500073;
+
+/// The trap handler for machine mode.
+const MTVEC: u64 = DATA;
+/// The trap handler for supervisor mode.
+const STVEC: u64 = DATA + 0x400;
+
+const MSTATUS_SIE: u64 = 1 << 1;
+const MSTATUS_MIE: u64 = 1 << 3;
+const MSTATUS_MPP: u64 = 0b11 << 11;
+const MSTATUS_TW: u64 = 1 << 21;
+
+/// All interrupts, in the order of their codes.
+const ALL: u64 = 1 << 1 | 1 << 3 | 1 << 5 | 1 << 7 | 1 << 9 | 1 << 11;
+
+impl Hart {
+    /// Creates a hart with NOPs for its program and trap handlers.
+    fn interruptible(isa: &str) -> Hart {
+        let mut hart = Hart::new(isa);
+
+        for offset in (0..TEST_BUS_SIZE).step_by(4) {
+            hart.0.bus.store(TEST_BUS_BASE + offset, NOP).unwrap();
+        }
+
+        hart.0.csr.mtvec = MTVEC;
+        hart.0.csr.stvec = STVEC;
+        hart.0.csr.mstatus &= !MSTATUS_MPP;
+
+        hart
+    }
+
+    fn raise(&self, interrupt: Interrupt) {
+        self.0.bus.lines(0).unwrap().raise(interrupt);
+    }
+
+    fn lower(&self, interrupt: Interrupt) {
+        self.0.bus.lines(0).unwrap().lower(interrupt);
+    }
+
+    /// Executes a NOP at the given privilege level, returning the level and
+    /// cause of the interrupt taken before it, if any.
+    ///
+    /// `mstatus` is left as it was before the interrupt was taken.
+    fn interrupt(&mut self, privilege: Privilege) -> Option<(Privilege, u64)> {
+        let mstatus = self.0.csr.mstatus;
+
+        self.0.privilege = privilege;
+        self.0.pc = TEST_BUS_BASE;
+        self.0.next = TEST_BUS_BASE;
+        self.0.execute().unwrap();
+
+        self.0.csr.mstatus = mstatus;
+
+        match self.0.pc {
+            pc if pc == TEST_BUS_BASE + 4 => None,
+            pc if pc == MTVEC + 4 => Some((self.0.privilege, self.0.csr.mcause)),
+            pc if pc == STVEC + 4 => Some((self.0.privilege, self.0.csr.scause)),
+            pc => panic!("Unexpected PC {pc:#x}"),
+        }
+    }
+}
+
+fn machine(interrupt: Interrupt) -> Option<(Privilege, u64)> {
+    Some((Privilege::Machine, 1 << 63 | interrupt as u64))
+}
+
+fn supervisor(interrupt: Interrupt) -> Option<(Privilege, u64)> {
+    Some((Privilege::Supervisor, 1 << 63 | interrupt as u64))
+}
+
+#[test]
+fn test_masking() {
+    let mut hart = Hart::interruptible("imacsu");
+    hart.0.csr.mstatus |= MSTATUS_MIE;
+
+    hart.raise(Interrupt::MachineTimer);
+    assert_eq!(hart.interrupt(Privilege::Machine), None);
+    assert_eq!(hart.0.csr.mip, Interrupt::MachineTimer.bit());
+
+    hart.0.csr.mie = ALL & !Interrupt::MachineTimer.bit();
+    assert_eq!(hart.interrupt(Privilege::Machine), None);
+
+    hart.0.csr.mie = Interrupt::MachineTimer.bit();
+    assert_eq!(
+        hart.interrupt(Privilege::Machine),
+        machine(Interrupt::MachineTimer)
+    );
+
+    // Lowering the line clears the pending bit
+    hart.lower(Interrupt::MachineTimer);
+    assert_eq!(hart.interrupt(Privilege::Machine), None);
+    assert_eq!(hart.0.csr.mip, 0);
+}
+
+#[test]
+fn test_global_enables() {
+    let mut hart = Hart::interruptible("imacsu");
+    hart.0.csr.mie = ALL;
+    hart.0.csr.mideleg = Interrupt::SupervisorTimer.bit();
+
+    // Machine-level interrupts are enabled by MIE in machine mode, and always
+    // enabled below it
+    hart.raise(Interrupt::MachineTimer);
+    assert_eq!(hart.interrupt(Privilege::Machine), None);
+    assert_eq!(
+        hart.interrupt(Privilege::Supervisor),
+        machine(Interrupt::MachineTimer)
+    );
+    assert_eq!(
+        hart.interrupt(Privilege::User),
+        machine(Interrupt::MachineTimer)
+    );
+
+    hart.0.csr.mstatus |= MSTATUS_MIE;
+    assert_eq!(
+        hart.interrupt(Privilege::Machine),
+        machine(Interrupt::MachineTimer)
+    );
+
+    hart.lower(Interrupt::MachineTimer);
+
+    // Delegated interrupts are enabled by SIE in supervisor mode, always
+    // enabled in user mode, and never taken in machine mode
+    hart.raise(Interrupt::SupervisorTimer);
+    assert_eq!(hart.interrupt(Privilege::Machine), None);
+    assert_eq!(hart.interrupt(Privilege::Supervisor), None);
+    assert_eq!(
+        hart.interrupt(Privilege::User),
+        supervisor(Interrupt::SupervisorTimer)
+    );
+
+    hart.0.csr.mstatus |= MSTATUS_SIE;
+    assert_eq!(hart.interrupt(Privilege::Machine), None);
+    assert_eq!(
+        hart.interrupt(Privilege::Supervisor),
+        supervisor(Interrupt::SupervisorTimer)
+    );
+}
+
+#[test]
+fn test_delegation() {
+    let mut hart = Hart::interruptible("imacsu");
+    hart.0.csr.mie = ALL;
+    hart.0.csr.mstatus |= MSTATUS_SIE;
+
+    // Supervisor-level interrupts are taken into machine mode unless they are
+    // delegated
+    hart.raise(Interrupt::SupervisorExternal);
+    assert_eq!(
+        hart.interrupt(Privilege::Supervisor),
+        machine(Interrupt::SupervisorExternal)
+    );
+
+    hart.0.csr.mideleg = Interrupt::SupervisorExternal.bit();
+    assert_eq!(
+        hart.interrupt(Privilege::Supervisor),
+        supervisor(Interrupt::SupervisorExternal)
+    );
+    assert_eq!(
+        hart.0.csr.mcause,
+        1 << 63 | Interrupt::SupervisorExternal as u64
+    );
+
+    // Interrupts taken into machine mode win over delegated ones, even if
+    // they would otherwise have a lower priority
+    hart.raise(Interrupt::SupervisorTimer);
+    assert_eq!(
+        hart.interrupt(Privilege::Supervisor),
+        machine(Interrupt::SupervisorTimer)
+    );
+
+    hart.lower(Interrupt::SupervisorTimer);
+    assert_eq!(
+        hart.interrupt(Privilege::Supervisor),
+        supervisor(Interrupt::SupervisorExternal)
+    );
+}
+
+#[test]
+fn test_priority() {
+    let mut hart = Hart::interruptible("imacsu");
+    hart.0.csr.mie = ALL;
+    hart.0.csr.mstatus |= MSTATUS_MIE;
+
+    let order = [
+        Interrupt::MachineExternal,
+        Interrupt::MachineSoftware,
+        Interrupt::MachineTimer,
+        Interrupt::SupervisorExternal,
+        Interrupt::SupervisorSoftware,
+        Interrupt::SupervisorTimer,
+    ];
+    assert_eq!(Interrupt::PRIORITY, order);
+
+    for interrupt in order {
+        hart.raise(interrupt);
+    }
+
+    for interrupt in order {
+        assert_eq!(hart.interrupt(Privilege::Machine), machine(interrupt));
+        hart.lower(interrupt);
+    }
+
+    assert_eq!(hart.interrupt(Privilege::Machine), None);
+}
+
+#[test]
+fn test_cause() {
+    // The interrupt bit is the most significant bit of mcause
+    let mut hart = Hart::interruptible("rv32imacsu");
+    hart.0.csr.mie = ALL;
+    hart.0.csr.mstatus |= MSTATUS_MIE;
+
+    hart.raise(Interrupt::MachineSoftware);
+    assert_eq!(
+        hart.interrupt(Privilege::Machine),
+        Some((Privilege::Machine, 1 << 31 | 3))
+    );
+    assert_eq!(hart.0.csr.mepc, TEST_BUS_BASE);
+    assert_eq!(hart.0.csr.mtval, 0);
+}
+
+#[test]
+fn test_wfi() {
+    let mut hart = Hart::interruptible("imacsu");
+
+    hart.execute(&[WFI]);
+    assert!(hart.0.is_waiting());
+
+    // Nothing is executed while the hart is waiting, and interrupts that are
+    // not enabled in mie do not wake it
+    hart.raise(Interrupt::MachineTimer);
+    hart.0.execute().unwrap();
+    assert!(hart.0.is_waiting());
+    assert_eq!(hart.0.pc, TEST_BUS_BASE + 4);
+
+    // Pending interrupts wake the hart even if they are not globally enabled,
+    // without being taken
+    hart.0.csr.mie = Interrupt::MachineTimer.bit();
+    hart.0.execute().unwrap();
+    assert!(!hart.0.is_waiting());
+    assert_eq!(hart.0.pc, TEST_BUS_BASE + 8);
+
+    // Otherwise, they are taken once the hart wakes
+    hart.lower(Interrupt::MachineTimer);
+    hart.0.csr.mstatus |= MSTATUS_MIE;
+
+    hart.execute(&[WFI]);
+    assert!(hart.0.is_waiting());
+
+    hart.raise(Interrupt::MachineTimer);
+    hart.0.execute().unwrap();
+    assert!(!hart.0.is_waiting());
+    assert_eq!(hart.0.pc, MTVEC + 4);
+    assert_eq!(hart.0.csr.mepc, TEST_BUS_BASE + 4);
+}
+
+/// Checks whether WFI waits at the given privilege level and with the given
+/// value of `mstatus`, rather than raising an illegal-instruction exception.
+fn waits(hart: &mut Hart, privilege: Privilege, mstatus: u64) -> bool {
+    hart.0.privilege = privilege;
+    hart.0.csr.mstatus = mstatus;
+    let result = hart.run(&[WFI]);
+    let waiting = hart.0.is_waiting();
+
+    // Wake the hart for the next check
+    hart.raise(Interrupt::MachineSoftware);
+    hart.0.csr.mie = Interrupt::MachineSoftware.bit();
+    hart.0.execute().unwrap();
+    hart.lower(Interrupt::MachineSoftware);
+    hart.0.csr.mie = 0;
+
+    match result {
+        Ok(()) => waiting,
+        result => {
+            assert!(is_illegal(result));
+            false
+        }
+    }
+}
+
+#[test]
+fn test_wfi_trapped() {
+    let mut hart = Hart::interruptible("imacsu");
+
+    // TW traps WFI below machine mode
+    assert!(waits(&mut hart, Privilege::Machine, MSTATUS_TW));
+    assert!(waits(&mut hart, Privilege::Supervisor, 0));
+    assert!(!waits(&mut hart, Privilege::Supervisor, MSTATUS_TW));
+
+    // User mode cannot wait when there is a supervisor mode
+    assert!(!waits(&mut hart, Privilege::User, 0));
+
+    let mut hart = Hart::interruptible("imacu");
+    assert!(waits(&mut hart, Privilege::User, 0));
+    assert!(!waits(&mut hart, Privilege::User, MSTATUS_TW));
+}
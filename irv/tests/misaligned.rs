@@ -0,0 +1,115 @@
+//! Checks which misaligned loads and stores are split when they are emulated,
+//! and that a split store that faults has no effect.
+
+mod common;
+
+use std::num::NonZeroU64;
+
+use common::{Hart, DATA, TEST_BUS_BASE, TEST_BUS_SIZE};
+use irv::{Attributes, Bus, BusDevice, Exception, Memory, MisalignedMode, ReadOnly, SystemBus};
+
+/// `lw x10, 0(x11)`
+const LW: u32 = 11 << 15 | 0b010 << 12 | 10 << 7 | 0b0000011;
+/// `sw x12, 0(x11)`
+const SW: u32 = 12 << 20 | 11 << 15 | 0b010 << 12 | 0b0100011;
+
+/// The base of read-only memory mapped just after the test memory.
+const ROM: u64 = TEST_BUS_BASE + TEST_BUS_SIZE;
+
+fn memory(size: u64) -> BusDevice<Memory<Vec<u64>>> {
+    BusDevice(Memory::new(vec![0; size as usize / 8]))
+}
+
+impl Hart {
+    /// Creates a hart that emulates misaligned accesses, with read-only
+    /// memory mapped after the test memory.
+    fn emulating() -> Hart {
+        let mut bus = SystemBus::new(1);
+
+        bus.map(TEST_BUS_BASE, TEST_BUS_SIZE, memory(TEST_BUS_SIZE))
+            .expect("Failed to map memory");
+        bus.map(ROM, 0x1000, ReadOnly(memory(0x1000)))
+            .expect("Failed to map ROM");
+
+        let mut hart = Hart::with_bus(bus, "imacsu");
+        hart.0.misaligned_mode = MisalignedMode::Emulate;
+
+        hart
+    }
+
+    fn store_word(&mut self, address: u64, value: u64) -> Result<(), Exception> {
+        self.0.gpr[11] = address;
+        self.0.gpr[12] = value;
+        self.run(&[SW])
+    }
+
+    fn load_word(&mut self, address: u64) -> Result<u64, Exception> {
+        self.0.gpr[11] = address;
+        self.run(&[LW]).map(|()| self.0.gpr[10])
+    }
+}
+
+#[test]
+fn test_split() {
+    let mut hart = Hart::emulating();
+
+    hart.store_word(DATA - 2, 0x4433_2211).unwrap();
+    assert_eq!(hart.load_word(DATA - 2).unwrap(), 0x4433_2211);
+
+    let high: u32 = hart.0.bus.load(DATA).unwrap();
+    assert_eq!(high, 0x4433);
+
+    // Without emulation, the main memory PMA does not allow misaligned
+    // accesses
+    hart.0.misaligned_mode = MisalignedMode::Trap;
+    assert!(matches!(
+        hart.load_word(DATA + 1),
+        Err(Exception::LoadAddressMisaligned { address }) if address == NonZeroU64::new(DATA + 1)
+    ));
+}
+
+#[test]
+fn test_misaligned_regions() {
+    let mut hart = Hart::emulating();
+
+    // Regions that support misaligned accesses get them as a single access,
+    // even if they could not be split
+    let attributes = Attributes {
+        misaligned: true,
+        idempotent: false,
+        ..Attributes::MEMORY
+    };
+    hart.0.pma.add(DATA, 0x800, attributes).unwrap();
+
+    hart.store_word(DATA + 1, 0x4433_2211).unwrap();
+    assert_eq!(hart.load_word(DATA + 1).unwrap(), 0x4433_2211);
+
+    // Other regions that cannot be split still raise exceptions
+    let attributes = Attributes {
+        idempotent: false,
+        ..Attributes::MEMORY
+    };
+    hart.0.pma.add(DATA + 0x800, 0x800, attributes).unwrap();
+
+    assert!(matches!(
+        hart.store_word(DATA + 0x801, 0),
+        Err(Exception::StoreAmoAddressMisaligned { .. })
+    ));
+}
+
+#[test]
+fn test_read_only() {
+    let mut hart = Hart::emulating();
+
+    hart.0.bus.store(ROM - 4, 0x4433_2211u32).unwrap();
+
+    // The second part can be loaded, but not stored, so the first part is
+    // left as it was
+    assert!(matches!(
+        hart.store_word(ROM - 2, 0),
+        Err(Exception::StoreAmoAccessFault { address }) if address == NonZeroU64::new(ROM)
+    ));
+
+    let low: u32 = hart.0.bus.load(ROM - 4).unwrap();
+    assert_eq!(low, 0x4433_2211);
+}
@@ -0,0 +1,140 @@
+//! Checks the multiplication and division instructions of the M extension,
+//! including division by zero and overflow, the word instructions of RV64,
+//! and RV32.
+
+mod common;
+
+use common::Hart;
+
+// Each instruction reads rs1 from x11 and rs2 from x12, and writes rd to x10
+const RD: u32 = 10 << 7;
+const RS1: u32 = 11 << 15;
+const RS2: u32 = 12 << 20;
+
+const fn op(funct3: u32) -> u32 {
+    1 << 25 | RS2 | RS1 | funct3 << 12 | RD | 0b0110011
+}
+
+const fn op_32(funct3: u32) -> u32 {
+    1 << 25 | RS2 | RS1 | funct3 << 12 | RD | 0b0111011
+}
+
+const MUL: u32 = op(0b000);
+const MULH: u32 = op(0b001);
+const MULHSU: u32 = op(0b010);
+const MULHU: u32 = op(0b011);
+const DIV: u32 = op(0b100);
+const DIVU: u32 = op(0b101);
+const REM: u32 = op(0b110);
+const REMU: u32 = op(0b111);
+const MULW: u32 = op_32(0b000);
+const DIVW: u32 = op_32(0b100);
+const DIVUW: u32 = op_32(0b101);
+const REMW: u32 = op_32(0b110);
+const REMUW: u32 = op_32(0b111);
+
+const MIN: u64 = i64::MIN as u64;
+const NEG_1: u64 = -1i64 as u64;
+const NEG_7: u64 = -7i64 as u64;
+
+#[test]
+fn test_multiply() {
+    let mut hart = Hart::new("imacsu");
+
+    assert_eq!(hart.compute(MUL, 3, NEG_7), -21i64 as u64);
+    assert_eq!(hart.compute(MUL, MIN, 2), 0);
+
+    // The upper halves of 128-bit products
+    assert_eq!(hart.compute(MULH, MIN, MIN), 1 << 62);
+    assert_eq!(hart.compute(MULH, NEG_1, NEG_1), 0);
+    assert_eq!(hart.compute(MULH, NEG_1, 1), NEG_1);
+    assert_eq!(hart.compute(MULHU, u64::MAX, u64::MAX), u64::MAX - 1);
+    assert_eq!(hart.compute(MULHU, NEG_1, 1), 0);
+    assert_eq!(hart.compute(MULHSU, NEG_1, u64::MAX), NEG_1);
+    assert_eq!(hart.compute(MULHSU, MIN, 2), NEG_1);
+}
+
+#[test]
+fn test_divide() {
+    let mut hart = Hart::new("imacsu");
+
+    // Division rounds towards zero, and remainders take the sign of the
+    // dividend
+    assert_eq!(hart.compute(DIV, NEG_7, 2), -3i64 as u64);
+    assert_eq!(hart.compute(REM, NEG_7, 2), NEG_1);
+    assert_eq!(hart.compute(REM, 7, -2i64 as u64), 1);
+    assert_eq!(hart.compute(DIVU, NEG_7, 2), u64::MAX / 2 - 3);
+    assert_eq!(hart.compute(REMU, NEG_7, 2), 1);
+
+    // Division by zero sets every bit of the quotient, and leaves the
+    // dividend as the remainder
+    assert_eq!(hart.compute(DIV, NEG_7, 0), u64::MAX);
+    assert_eq!(hart.compute(DIVU, NEG_7, 0), u64::MAX);
+    assert_eq!(hart.compute(REM, NEG_7, 0), NEG_7);
+    assert_eq!(hart.compute(REMU, NEG_7, 0), NEG_7);
+
+    // Overflow leaves the dividend as the quotient, with no remainder
+    assert_eq!(hart.compute(DIV, MIN, NEG_1), MIN);
+    assert_eq!(hart.compute(REM, MIN, NEG_1), 0);
+}
+
+#[test]
+fn test_words() {
+    let mut hart = Hart::new("imacsu");
+
+    // Only the low 32 bits of each operand are used, and results are
+    // sign-extended
+    assert_eq!(hart.compute(MULW, 0x1_0000_0003, 0x2_0000_0005), 15);
+    assert_eq!(hart.compute_32(MULW, 0x7FFF_FFFF, 2), 0xFFFF_FFFE);
+    assert_eq!(hart.compute_32(MULW, 0x1_0000, 0x1_0000), 0);
+
+    assert_eq!(hart.compute(DIVW, 0x1_FFFF_FFF9, 2), -3i64 as u64);
+    assert_eq!(hart.compute_32(REMW, 0xFFFF_FFF9, 2), 0xFFFF_FFFF);
+    assert_eq!(hart.compute_32(DIVUW, 0xFFFF_FFFF, 2), 0x7FFF_FFFF);
+    assert_eq!(hart.compute_32(REMUW, 0x8000_0001, 0x8000_0000), 1);
+
+    // Division by zero
+    assert_eq!(hart.compute_32(DIVW, 5, 0), 0xFFFF_FFFF);
+    assert_eq!(hart.compute_32(DIVUW, 5, 0), 0xFFFF_FFFF);
+    assert_eq!(hart.compute(DIVUW, 5, 0x1_0000_0000), u64::MAX);
+    assert_eq!(hart.compute_32(REMW, 0x8000_0005, 0), 0x8000_0005);
+    assert_eq!(hart.compute_32(REMUW, 0x8000_0005, 0), 0x8000_0005);
+
+    // Overflow
+    assert_eq!(hart.compute_32(DIVW, 0x8000_0000, 0xFFFF_FFFF), 0x8000_0000);
+    assert_eq!(hart.compute_32(REMW, 0x8000_0000, 0xFFFF_FFFF), 0);
+}
+
+#[test]
+fn test_rv32() {
+    // RV32 acts on 32-bit values, which have different upper halves,
+    // overflow, and unsigned values
+    let mut hart = Hart::new("rv32imacsu");
+
+    assert_eq!(hart.compute_32(MUL, 0x1_0000, 0x1_0000), 0);
+    assert_eq!(hart.compute_32(MULH, 0x8000_0000, 0x8000_0000), 0x4000_0000);
+    assert_eq!(hart.compute_32(MULH, 0xFFFF_FFFF, 1), 0xFFFF_FFFF);
+    assert_eq!(
+        hart.compute_32(MULHU, 0xFFFF_FFFF, 0xFFFF_FFFF),
+        0xFFFF_FFFE
+    );
+    assert_eq!(
+        hart.compute_32(MULHSU, 0xFFFF_FFFF, 0xFFFF_FFFF),
+        0xFFFF_FFFF
+    );
+    assert_eq!(hart.compute_32(MULHSU, 1, 0xFFFF_FFFF), 0);
+
+    assert_eq!(hart.compute_32(DIV, 0xFFFF_FFF9, 2), 0xFFFF_FFFD);
+    assert_eq!(hart.compute_32(DIVU, 0xFFFF_FFFF, 2), 0x7FFF_FFFF);
+    assert_eq!(hart.compute_32(REMU, 0x8000_0000, 3), 2);
+
+    // Division by zero
+    assert_eq!(hart.compute_32(DIV, 5, 0), 0xFFFF_FFFF);
+    assert_eq!(hart.compute_32(DIVU, 0x8000_0000, 0), 0xFFFF_FFFF);
+    assert_eq!(hart.compute_32(REM, 0x8000_0005, 0), 0x8000_0005);
+    assert_eq!(hart.compute_32(REMU, 0x8000_0005, 0), 0x8000_0005);
+
+    // Overflow
+    assert_eq!(hart.compute_32(DIV, 0x8000_0000, 0xFFFF_FFFF), 0x8000_0000);
+    assert_eq!(hart.compute_32(REM, 0x8000_0000, 0xFFFF_FFFF), 0);
+}
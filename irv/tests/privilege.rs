@@ -0,0 +1,114 @@
+//! Checks the exceptions raised by `ECALL` at each privilege level, and which
+//! CSRs each privilege level can access.
+
+mod common;
+
+use common::{is_illegal, Hart, TEST_BUS_BASE};
+use irv::{Exception, Privilege, TrapMode};
+
+const ECALL: u32 = 0x00000073;
+
+const MSTATUS: u32 = 0x300;
+const MSCRATCH: u32 = 0x340;
+const SSTATUS: u32 = 0x100;
+const SSCRATCH: u32 = 0x140;
+const MHARTID: u32 = 0xF14;
+
+/// `csrrs x10, csr, x0`, which only reads the CSR.
+const fn csrr(csr: u32) -> u32 {
+    csr << 20 | 0b010 << 12 | 10 << 7 | 0b1110011
+}
+
+/// `csrrw x10, csr, x11`
+const fn csrw(csr: u32) -> u32 {
+    csr << 20 | 11 << 15 | 0b001 << 12 | 10 << 7 | 0b1110011
+}
+
+impl Hart {
+    fn run_at(&mut self, privilege: Privilege, instruction: u32) -> Result<(), Exception> {
+        self.0.privilege = privilege;
+        self.run(&[instruction])
+    }
+
+    fn can_read(&mut self, privilege: Privilege, csr: u32) -> bool {
+        !is_illegal(self.run_at(privilege, csrr(csr)))
+    }
+
+    fn can_write(&mut self, privilege: Privilege, csr: u32) -> bool {
+        !is_illegal(self.run_at(privilege, csrw(csr)))
+    }
+}
+
+#[test]
+fn test_ecall() {
+    let mut hart = Hart::new("imacsu");
+
+    for (privilege, code) in [
+        (Privilege::User, 8),
+        (Privilege::Supervisor, 9),
+        (Privilege::Machine, 11),
+    ] {
+        let exception = hart.run_at(privilege, ECALL).unwrap_err();
+
+        assert!(matches!(
+            exception,
+            Exception::EnvironmentCall { privilege: p } if p == privilege
+        ));
+        assert_eq!(exception.code(), code);
+        assert_eq!(exception.value(), 0);
+    }
+
+    // Each is taken with its own cause
+    hart.0.trap_mode = TrapMode::Take;
+
+    for (privilege, code) in [
+        (Privilege::User, 8),
+        (Privilege::Supervisor, 9),
+        (Privilege::Machine, 11),
+    ] {
+        assert!(hart.run_at(privilege, ECALL).is_err());
+        assert_eq!(hart.0.privilege, Privilege::Machine);
+        assert_eq!(hart.0.csr.mcause, code);
+        assert_eq!(hart.0.csr.mepc, TEST_BUS_BASE);
+    }
+}
+
+#[test]
+fn test_csr_privilege() {
+    let mut hart = Hart::new("imacsu");
+
+    // Machine-level CSRs can only be accessed in machine mode
+    for csr in [MSTATUS, MSCRATCH, MHARTID] {
+        assert!(hart.can_read(Privilege::Machine, csr));
+        assert!(!hart.can_read(Privilege::Supervisor, csr));
+        assert!(!hart.can_read(Privilege::User, csr));
+    }
+
+    // Supervisor-level CSRs can also be accessed in supervisor mode
+    for csr in [SSTATUS, SSCRATCH] {
+        assert!(hart.can_write(Privilege::Machine, csr));
+        assert!(hart.can_write(Privilege::Supervisor, csr));
+        assert!(!hart.can_write(Privilege::User, csr));
+    }
+
+    // Read-only CSRs cannot be written even in machine mode
+    assert!(!hart.can_write(Privilege::Machine, MHARTID));
+}
+
+#[test]
+fn test_illegal_access() {
+    let mut hart = Hart::new("imacsu");
+    hart.0.csr.mscratch = 5;
+    hart.0.gpr[11] = 6;
+
+    // Illegal accesses write neither the CSR nor rd
+    hart.0.gpr[10] = 7;
+
+    assert!(is_illegal(hart.run_at(Privilege::User, csrw(MSCRATCH))));
+    assert_eq!(hart.0.csr.mscratch, 5);
+    assert_eq!(hart.0.gpr[10], 7);
+
+    hart.run_at(Privilege::Machine, csrw(MSCRATCH)).unwrap();
+    assert_eq!(hart.0.csr.mscratch, 6);
+    assert_eq!(hart.0.gpr[10], 5);
+}
@@ -1,29 +1,28 @@
 use std::{fs, io, path::Path};
 
-use irv::{BaseHart, Bus, Exception, Memory};
+use irv::{BaseHart, BusDevice, Exception, MachineCsrs, Memory, Pmp, SystemBus, TrapMode};
 
 const TEST_BUS_BASE: u64 = 0x80000000;
-const MAX_INSTRET: usize = 1000;
+const MAX_INSTRET: u64 = 1000;
 
-struct TestBus(Memory<Vec<u64>>);
-
-macro_rules! impl_test_bus {
-    ($($ty:ident)*) => {
-        $(
-            impl Bus<u64, $ty> for TestBus {
-                fn load(&self, address: u64) -> Result<$ty, irv::BusError> {
-                    self.0.load(address.wrapping_sub(TEST_BUS_BASE))
-                }
-
-                fn store(&self, address: u64, value: $ty) -> Result<(), irv::BusError> {
-                    self.0.store(address.wrapping_sub(TEST_BUS_BASE), value)
-                }
-            }
-        )*
-    };
-}
-
-impl_test_bus! { u8 u16 u32 u64 }
+/// The prefixes of the names of the test files that should be run, and the
+/// ISA of the hart that each is run on.
+const TEST_PREFIXES: &[(&str, &str)] = &[
+    ("rv64ui-irv-", "imafdcsu"),
+    ("rv64um-irv-", "imafdcsu"),
+    ("rv64ua-irv-", "imafdcsu"),
+    ("rv64uf-irv-", "imafdcsu"),
+    ("rv64ud-irv-", "imafdcsu"),
+    ("rv64uc-irv-", "imafdcsu"),
+    ("rv64mi-irv-", "imafdcsu"),
+    ("rv64si-irv-", "imafdcsu"),
+    ("rv64uzba-irv-", "imafdcsu"),
+    ("rv64uzbb-irv-", "imafdcsu"),
+    ("rv64uzbc-irv-", "imafdcsu"),
+    ("rv64uzbs-irv-", "imafdcsu"),
+    ("rv32ui-irv-", "rv32imafdcsu"),
+    ("rv32ue-irv-", "rv32esu"),
+];
 
 #[test]
 fn test_riscv_tests() -> Result<(), io::Error> {
@@ -32,9 +31,14 @@ fn test_riscv_tests() -> Result<(), io::Error> {
         let entry = entry?;
 
         if let Ok(file_name) = entry.file_name().into_string() {
-            if file_name.starts_with("rv64ui-irv-") && !file_name.ends_with(".dump") {
+            let isa = TEST_PREFIXES
+                .iter()
+                .find(|(prefix, _)| file_name.starts_with(prefix))
+                .map(|&(_, isa)| isa);
+
+            if let Some(isa) = isa.filter(|_| !file_name.ends_with(".dump")) {
                 println!("Testing {file_name}...");
-                test_riscv_test(fs::read(entry.path())?)
+                test_riscv_test(fs::read(entry.path())?, isa)
             }
         }
     }
@@ -42,25 +46,43 @@ fn test_riscv_tests() -> Result<(), io::Error> {
     Ok(())
 }
 
-fn test_riscv_test(elf_data: Vec<u8>) {
-    let bus = TestBus(Memory::new(vec![0u64; 8000]));
+fn test_riscv_test(elf_data: Vec<u8>, isa: &str) {
+    let memory = Memory::new(vec![0u64; 8000]);
+    let mut bus = SystemBus::new(1);
+
+    bus.map(TEST_BUS_BASE, memory.size() as u64, BusDevice(memory))
+        .expect("Failed to map memory");
 
     irv_loader::load(&bus, &elf_data).expect("Failed to parse or load ELF");
 
-    let mut hart = BaseHart::new(bus, ());
+    let mut hart = BaseHart::new(bus, MachineCsrs::new(0, isa));
+
+    // Start with the floating-point unit enabled, as firmware would
+    hart.csr.mstatus |= 0b01 << 13;
+
+    hart.pmp = Pmp::new(16);
+    hart.trap_mode = TrapMode::Take;
 
     hart.pc = TEST_BUS_BASE;
     hart.next = TEST_BUS_BASE;
 
-    for instret in 0.. {
+    // The ILP32E and LP64E ABIs pass the system call number in t0, since
+    // there is no a7
+    let syscall = if hart.csr.has_extension(b'e') { 5 } else { 17 };
+
+    loop {
+        let instret = hart.counters.minstret;
+
         if instret > MAX_INSTRET {
             panic!("Test took too many ({instret}) instructions! Is it in an infinite loop, or does MAX_INSTRET need to be increased?")
         }
 
         match hart.execute() {
             Ok(()) => (),
-            Err(Exception::EnvironmentCall) => {
-                if hart.gpr[17] != 93 || hart.gpr[10] != 0 {
+            // Tests report their result with an exit system call; other
+            // exceptions are handled by the trap handler of the test
+            Err(Exception::EnvironmentCall { .. }) if hart.gpr[syscall] == 93 => {
+                if hart.gpr[10] != 0 {
                     // fail
                     println!("Test reports as failing from within RISC-V");
                     dbg!(hart.pc, hart.gpr);
@@ -70,7 +92,10 @@ fn test_riscv_test(elf_data: Vec<u8>) {
                     break;
                 }
             }
-            Err(e) => panic!("Unexpected exception thrown during execution: {e:#?}"),
+            Err(e) if hart.csr.mtvec == 0 => {
+                panic!("Unexpected exception thrown during execution: {e:#?}")
+            }
+            Err(_) => (),
         }
     }
 }
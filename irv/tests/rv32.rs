@@ -0,0 +1,168 @@
+//! Checks that RV32 harts reject the instructions that only exist in RV64,
+//! and act on 32-bit values.
+
+mod common;
+
+use common::{is_illegal, Hart, DATA};
+use irv::Bus;
+
+// Each instruction reads rs1 from x11 and rs2 from x12, and writes rd to x10
+const RD: u32 = 10 << 7;
+const RS1: u32 = 11 << 15;
+const RS2: u32 = 12 << 20;
+
+const fn op(funct7: u32, funct3: u32) -> u32 {
+    funct7 << 25 | RS2 | RS1 | funct3 << 12 | RD | 0b0110011
+}
+
+const fn op_32(funct7: u32, funct3: u32) -> u32 {
+    funct7 << 25 | RS2 | RS1 | funct3 << 12 | RD | 0b0111011
+}
+
+const fn op_imm(imm: u32, funct3: u32) -> u32 {
+    imm << 20 | RS1 | funct3 << 12 | RD | 0b0010011
+}
+
+const fn op_imm_32(imm: u32, funct3: u32) -> u32 {
+    imm << 20 | RS1 | funct3 << 12 | RD | 0b0011011
+}
+
+const fn load(funct3: u32) -> u32 {
+    RS1 | funct3 << 12 | RD | 0b0000011
+}
+
+const fn amo(funct5: u32, funct3: u32) -> u32 {
+    funct5 << 27 | RS2 | RS1 | funct3 << 12 | RD | 0b0101111
+}
+
+const ADD: u32 = op(0, 0b000);
+const SUB: u32 = op(0b0100000, 0b000);
+const SLT: u32 = op(0, 0b010);
+const SLTU: u32 = op(0, 0b011);
+const SRL: u32 = op(0, 0b101);
+const SRA: u32 = op(0b0100000, 0b101);
+const MUL: u32 = op(1, 0b000);
+const MULH: u32 = op(1, 0b001);
+const MULHU: u32 = op(1, 0b011);
+const ADDI_NEG: u32 = op_imm(0xFFF, 0b000);
+const LW: u32 = load(0b010);
+/// `lui x10, 0x80000`
+const LUI: u32 = 0x80000 << 12 | RD | 0b0110111;
+/// `auipc x10, 0x80000`
+const AUIPC: u32 = 0x80000 << 12 | RD | 0b0010111;
+
+/// The instructions that only exist in RV64.
+const RV64_ONLY: [u32; 20] = [
+    // LD, LWU, and SD
+    load(0b011),
+    load(0b110),
+    RS2 | RS1 | 0b011 << 12 | 0b0100011,
+    // ADDIW, SLLIW, SRLIW, and SRAIW
+    op_imm_32(0, 0b000),
+    op_imm_32(1, 0b001),
+    op_imm_32(1, 0b101),
+    op_imm_32(0x400 | 1, 0b101),
+    // ADDW, SUBW, SLLW, SRLW, and SRAW
+    op_32(0, 0b000),
+    op_32(0b0100000, 0b000),
+    op_32(0, 0b001),
+    op_32(0, 0b101),
+    op_32(0b0100000, 0b101),
+    // MULW, DIVW, DIVUW, REMW, and REMUW
+    op_32(1, 0b000),
+    op_32(1, 0b100),
+    op_32(1, 0b101),
+    op_32(1, 0b110),
+    op_32(1, 0b111),
+    // LR.D, SC.D, and AMOADD.D
+    amo(0b00010, 0b011) & !RS2,
+    amo(0b00011, 0b011),
+    amo(0b00000, 0b011),
+];
+
+#[test]
+fn test_rv64_only() {
+    let mut rv32 = Hart::new("rv32imacsu");
+    let mut rv64 = Hart::new("imacsu");
+
+    for instruction in RV64_ONLY {
+        assert!(
+            is_illegal(rv32.evaluate(instruction, DATA, 1)),
+            "{instruction:#010x} is legal in RV32"
+        );
+        assert!(
+            !is_illegal(rv64.evaluate(instruction, DATA, 1)),
+            "{instruction:#010x} is illegal in RV64"
+        );
+    }
+}
+
+#[test]
+fn test_shift_amounts() {
+    let mut rv32 = Hart::new("rv32imacsu");
+    let mut rv64 = Hart::new("imacsu");
+
+    // Shift amounts of 32 or more are reserved in RV32
+    for instruction in [
+        op_imm(32, 0b001),
+        op_imm(32, 0b101),
+        op_imm(0x400 | 32, 0b101),
+    ] {
+        assert!(is_illegal(rv32.evaluate(instruction, 1, 0)));
+        assert!(!is_illegal(rv64.evaluate(instruction, 1, 0)));
+    }
+
+    assert_eq!(rv32.compute_32(op_imm(31, 0b001), 1, 0), 0x8000_0000);
+    assert_eq!(rv32.compute_32(op_imm(31, 0b101), 0x8000_0000, 0), 1);
+    assert_eq!(
+        rv32.compute_32(op_imm(0x400 | 31, 0b101), 0x8000_0000, 0),
+        !0
+    );
+
+    // Register shift amounts only use their low five bits
+    assert_eq!(rv32.compute_32(op(0, 0b001), 1, 33), 2);
+}
+
+#[test]
+fn test_wraparound() {
+    let mut hart = Hart::new("rv32imacsu");
+
+    assert_eq!(hart.compute_32(ADD, 0x7FFF_FFFF, 1), 0x8000_0000);
+    assert_eq!(hart.compute_32(ADD, 0xFFFF_FFFF, 1), 0);
+    assert_eq!(hart.compute_32(SUB, 0, 1), 0xFFFF_FFFF);
+    assert_eq!(hart.compute_32(ADDI_NEG, 0, 0), 0xFFFF_FFFF);
+    assert_eq!(hart.compute_32(MUL, 0x10000, 0x10000), 0);
+    assert_eq!(hart.compute_32(MULH, 0x8000_0000, 0x8000_0000), 0x4000_0000);
+    assert_eq!(
+        hart.compute_32(MULHU, 0xFFFF_FFFF, 0xFFFF_FFFF),
+        0xFFFF_FFFE
+    );
+
+    // Values are compared and shifted as 32-bit values
+    assert_eq!(hart.compute_32(SLT, 0x8000_0000, 1), 1);
+    assert_eq!(hart.compute_32(SLTU, 0x8000_0000, 1), 0);
+    assert_eq!(hart.compute_32(SLTU, 1, 0x8000_0000), 1);
+    assert_eq!(hart.compute_32(SRL, 0x8000_0000, 1), 0x4000_0000);
+    assert_eq!(hart.compute_32(SRA, 0x8000_0000, 1), 0xC000_0000);
+}
+
+#[test]
+fn test_sign_extension() {
+    let mut hart = Hart::new("rv32imacsu");
+
+    // Results are kept sign-extended
+    hart.execute(&[LUI]);
+    assert_eq!(hart.0.gpr[10], 0xFFFF_FFFF_8000_0000);
+
+    // Adding 0x8000_0000 to the PC of the test program wraps around to zero
+    hart.execute(&[AUIPC]);
+    assert_eq!(hart.0.gpr[10], 0);
+
+    // Addresses only use the low 32 bits of sign-extended registers
+    hart.0.bus.store(DATA, 0x8765_4321u32).unwrap();
+
+    assert_eq!(
+        hart.compute(LW, DATA as i32 as u64, 0),
+        0xFFFF_FFFF_8765_4321
+    );
+}
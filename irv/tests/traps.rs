@@ -0,0 +1,201 @@
+//! Checks how traps are taken with [TrapMode::Take], and how `MRET` returns
+//! from them.
+
+mod common;
+
+use common::{is_illegal, test_bus, Hart, DATA, TEST_BUS_BASE, TEST_BUS_SIZE};
+use irv::{BaseHart, Bus, Exception, Interrupt, Privilege, TrapMode};
+
+const NOP: u32 = 0x00000013;
+const MRET: u32 = 0x30200073;
+const ILLEGAL: u32 = 0xFFFFFFFF;
+/// `lw x10, 0(x11)`
+const LW: u32 = 11 << 15 | 0b010 << 12 | 10 << 7 | 0b0000011;
+
+/// The trap handler for machine mode.
+const MTVEC: u64 = DATA;
+/// The trap handler for supervisor mode.
+const STVEC: u64 = DATA + 0x400;
+
+const MSTATUS_SIE: u64 = 1 << 1;
+const MSTATUS_MIE: u64 = 1 << 3;
+const MSTATUS_SPIE: u64 = 1 << 5;
+const MSTATUS_MPIE: u64 = 1 << 7;
+const MSTATUS_SPP: u64 = 1 << 8;
+const MSTATUS_MPP: u64 = 0b11 << 11;
+const MSTATUS_MPRV: u64 = 1 << 17;
+
+impl Hart {
+    /// Creates a hart that takes traps, with NOPs for its trap handlers.
+    fn trapping(isa: &str) -> Hart {
+        let mut hart = Hart::new(isa);
+
+        for offset in (0x1000..TEST_BUS_SIZE).step_by(4) {
+            hart.0.bus.store(TEST_BUS_BASE + offset, NOP).unwrap();
+        }
+
+        hart.0.trap_mode = TrapMode::Take;
+        hart.0.csr.mtvec = MTVEC;
+        hart.0.csr.stvec = STVEC;
+        hart.0.csr.mstatus &= !MSTATUS_MPP;
+
+        hart
+    }
+}
+
+#[test]
+fn test_exception() {
+    let mut hart = Hart::trapping("imacsu");
+    hart.0.privilege = Privilege::Supervisor;
+    hart.0.csr.mstatus |= MSTATUS_MIE;
+
+    assert!(is_illegal(hart.run(&[NOP, ILLEGAL])));
+
+    assert_eq!(hart.0.privilege, Privilege::Machine);
+    assert_eq!(hart.0.pc, MTVEC);
+    assert_eq!(hart.0.csr.mepc, TEST_BUS_BASE + 4);
+    assert_eq!(hart.0.csr.mcause, 2);
+    assert_eq!(hart.0.csr.mtval, ILLEGAL as u64);
+
+    // The previous interrupt-enable bit and privilege level are saved
+    let mstatus = hart.0.csr.mstatus;
+    assert_eq!(mstatus & (MSTATUS_MIE | MSTATUS_MPIE), MSTATUS_MPIE);
+    assert_eq!(mstatus & MSTATUS_MPP, (Privilege::Supervisor as u64) << 11);
+
+    // mtval holds the faulting address of memory accesses
+    hart.0.gpr[11] = 0x1000;
+
+    assert!(matches!(
+        hart.run(&[LW]),
+        Err(Exception::LoadAccessFault { .. })
+    ));
+    assert_eq!(hart.0.csr.mepc, TEST_BUS_BASE);
+    assert_eq!(hart.0.csr.mcause, 5);
+    assert_eq!(hart.0.csr.mtval, 0x1000);
+
+    // The interrupts that were enabled were disabled by the first trap
+    let mstatus = hart.0.csr.mstatus;
+    assert_eq!(mstatus & (MSTATUS_MIE | MSTATUS_MPIE), 0);
+    assert_eq!(mstatus & MSTATUS_MPP, MSTATUS_MPP);
+}
+
+#[test]
+fn test_delegation() {
+    let mut hart = Hart::trapping("imacsu");
+    hart.0.privilege = Privilege::User;
+    hart.0.csr.medeleg = 1 << 2;
+    hart.0.csr.mstatus |= MSTATUS_SIE;
+
+    assert!(is_illegal(hart.run(&[ILLEGAL])));
+
+    assert_eq!(hart.0.privilege, Privilege::Supervisor);
+    assert_eq!(hart.0.pc, STVEC);
+    assert_eq!(hart.0.csr.sepc, TEST_BUS_BASE);
+    assert_eq!(hart.0.csr.scause, 2);
+    assert_eq!(hart.0.csr.stval, ILLEGAL as u64);
+    assert_eq!(hart.0.csr.mcause, 0);
+
+    let mstatus = hart.0.csr.mstatus;
+    assert_eq!(mstatus & (MSTATUS_SIE | MSTATUS_SPIE), MSTATUS_SPIE);
+    assert_eq!(mstatus & MSTATUS_SPP, 0);
+
+    // Traps are never delegated away from machine mode
+    hart.0.privilege = Privilege::Machine;
+
+    assert!(is_illegal(hart.run(&[ILLEGAL])));
+    assert_eq!(hart.0.privilege, Privilege::Machine);
+    assert_eq!(hart.0.pc, MTVEC);
+    assert_eq!(hart.0.csr.mcause, 2);
+}
+
+#[test]
+fn test_vectored() {
+    let mut hart = Hart::trapping("imacsu");
+    hart.0.csr.mtvec = MTVEC | 1;
+
+    // Exceptions always go to the base address
+    assert!(is_illegal(hart.run(&[ILLEGAL])));
+    assert_eq!(hart.0.pc, MTVEC);
+
+    // Interrupts go to the entry for their code
+    hart.0.csr.mstatus |= MSTATUS_MIE;
+    hart.0.csr.mie = Interrupt::MachineTimer.bit();
+    hart.0.bus.lines(0).unwrap().raise(Interrupt::MachineTimer);
+
+    hart.execute(&[NOP]);
+
+    assert_eq!(hart.0.csr.mepc, TEST_BUS_BASE);
+    assert_eq!(hart.0.csr.mcause, 1 << 63 | 7);
+    assert_eq!(hart.0.pc, MTVEC + 7 * 4 + 4);
+}
+
+#[test]
+fn test_mret() {
+    let mut hart = Hart::trapping("imacsu");
+    hart.0.csr.mepc = DATA + 0x100;
+    hart.0.csr.mstatus |= MSTATUS_MPIE | 1 << 11 | MSTATUS_MPRV;
+
+    hart.execute(&[MRET]);
+
+    // The interrupt-enable bit and privilege level are restored, and MPP is
+    // left with the least-privileged level
+    assert_eq!(hart.0.privilege, Privilege::Supervisor);
+    assert_eq!(hart.0.pc, DATA + 0x100);
+
+    let mstatus = hart.0.csr.mstatus;
+    assert_eq!(
+        mstatus & (MSTATUS_MIE | MSTATUS_MPIE),
+        MSTATUS_MIE | MSTATUS_MPIE
+    );
+    assert_eq!(mstatus & MSTATUS_MPP, 0);
+    assert_eq!(mstatus & MSTATUS_MPRV, 0);
+
+    // MRET is only legal in machine mode
+    assert!(is_illegal(hart.run(&[MRET])));
+    assert_eq!(hart.0.privilege, Privilege::Machine);
+    assert_eq!(hart.0.csr.mcause, 2);
+
+    // Without user mode, MPP is left with supervisor mode
+    let mut hart = Hart::trapping("imacs");
+    hart.0.csr.mepc = DATA;
+    hart.0.csr.mstatus |= MSTATUS_MPP;
+
+    hart.execute(&[MRET]);
+
+    assert_eq!(hart.0.privilege, Privilege::Machine);
+    assert_eq!(hart.0.csr.mstatus & MSTATUS_MPP, 1 << 11);
+}
+
+#[test]
+fn test_round_trip() {
+    let mut hart = Hart::trapping("imasu");
+    hart.0.privilege = Privilege::User;
+
+    assert!(is_illegal(hart.run(&[NOP, ILLEGAL])));
+    assert_eq!(hart.0.privilege, Privilege::Machine);
+
+    // The handler skips the instruction, which is aligned to four bytes
+    // without the C extension
+    hart.0.csr.mepc += 6;
+    assert_eq!(hart.0.csr.mepc, TEST_BUS_BASE + 10);
+
+    hart.0.pc = DATA + 0x100;
+    hart.0.bus.store(DATA + 0x100, MRET).unwrap();
+    hart.0.execute().unwrap();
+
+    assert_eq!(hart.0.privilege, Privilege::User);
+    assert_eq!(hart.0.pc, TEST_BUS_BASE + 8);
+}
+
+#[test]
+fn test_inaccessible_vector() {
+    // Without CSRs, exceptions are returned as if they were not taken
+    let mut hart = BaseHart::new(test_bus(), ());
+    hart.trap_mode = TrapMode::Take;
+    hart.pc = TEST_BUS_BASE;
+    hart.bus.store(TEST_BUS_BASE, ILLEGAL).unwrap();
+
+    assert!(is_illegal(hart.execute()));
+    assert_eq!(hart.privilege, Privilege::Machine);
+    assert_eq!(hart.pc, TEST_BUS_BASE + 4);
+}
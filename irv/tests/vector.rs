@@ -0,0 +1,708 @@
+//! Runs the instructions of the V extension on a hart with 128-bit vector
+//! registers.
+
+mod common;
+
+use common::{is_illegal, Hart, DATA, TEST_BUS_BASE, TEST_BUS_SIZE};
+use irv::{AgnosticMode, Bus, Exception};
+
+const MSTATUS_VS: u64 = 0b11 << 9;
+const MSTATUS_FS: u64 = 0b11 << 13;
+
+const E8: u32 = 0b000 << 3;
+const E16: u32 = 0b001 << 3;
+const E32: u32 = 0b010 << 3;
+const E64: u32 = 0b011 << 3;
+const M1: u32 = 0b000;
+const M2: u32 = 0b001;
+const M8: u32 = 0b011;
+const MF2: u32 = 0b111;
+const MF8: u32 = 0b101;
+const TA: u32 = 1 << 6;
+const MA: u32 = 1 << 7;
+
+const OPIVV: u32 = 0b000;
+const OPFVV: u32 = 0b001;
+const OPMVV: u32 = 0b010;
+const OPIVI: u32 = 0b011;
+const OPIVX: u32 = 0b100;
+const OPFVF: u32 = 0b101;
+const OPMVX: u32 = 0b110;
+
+const VSTART: u32 = 0x008;
+const VXSAT: u32 = 0x009;
+const VXRM: u32 = 0x00A;
+const VCSR: u32 = 0x00F;
+const VL: u32 = 0xC20;
+const VTYPE: u32 = 0xC21;
+const VLENB: u32 = 0xC22;
+
+/// `vsetvli rd, rs1, vtype`
+const fn vsetvli(rd: u32, rs1: u32, vtype: u32) -> u32 {
+    vtype << 20 | rs1 << 15 | 0b111 << 12 | rd << 7 | 0b1010111
+}
+
+/// `vsetivli rd, uimm, vtype`
+const fn vsetivli(rd: u32, uimm: u32, vtype: u32) -> u32 {
+    0b11 << 30 | vtype << 20 | uimm << 15 | 0b111 << 12 | rd << 7 | 0b1010111
+}
+
+/// `vsetvl rd, rs1, rs2`
+const fn vsetvl(rd: u32, rs1: u32, rs2: u32) -> u32 {
+    0b1000000 << 25 | rs2 << 20 | rs1 << 15 | 0b111 << 12 | rd << 7 | 0b1010111
+}
+
+/// Encodes an unmasked OP-V instruction, where `rs1` is vs1, rs1, or an
+/// immediate depending on `funct3`.
+const fn op_v(funct6: u32, funct3: u32, vd: u32, vs2: u32, rs1: u32) -> u32 {
+    funct6 << 26 | 1 << 25 | vs2 << 20 | rs1 << 15 | funct3 << 12 | vd << 7 | 0b1010111
+}
+
+/// Clears the `vm` field of an instruction, so it is masked by v0.
+const fn masked(instruction: u32) -> u32 {
+    instruction & !(1 << 25)
+}
+
+/// Encodes an unmasked vector load, where `rs2` is rs2, vs2, or the `lumop`
+/// field depending on `mop`.
+const fn load(nf: u32, mop: u32, rs2: u32, rs1: u32, width: u32, vd: u32) -> u32 {
+    (nf - 1) << 29 | mop << 26 | 1 << 25 | rs2 << 20 | rs1 << 15 | width << 12 | vd << 7 | 0b0000111
+}
+
+/// Encodes an unmasked vector store, where `rs2` is rs2, vs2, or the `sumop`
+/// field depending on `mop`.
+const fn store(nf: u32, mop: u32, rs2: u32, rs1: u32, width: u32, vs3: u32) -> u32 {
+    load(nf, mop, rs2, rs1, width, vs3) & !0b1111111 | 0b0100111
+}
+
+const WIDTH_8: u32 = 0b000;
+const WIDTH_16: u32 = 0b101;
+const WIDTH_32: u32 = 0b110;
+
+/// `csrrs rd, csr, x0`
+const fn csrr(rd: u32, csr: u32) -> u32 {
+    csr << 20 | 0b010 << 12 | rd << 7 | 0b1110011
+}
+
+/// `csrrwi x0, csr, uimm`
+const fn csrwi(csr: u32, uimm: u32) -> u32 {
+    csr << 20 | uimm << 15 | 0b101 << 12 | 0b1110011
+}
+
+impl Hart {
+    /// Creates a hart with the V extension, whose vector and floating-point
+    /// units are on.
+    fn vector() -> Hart {
+        let mut hart = Hart::new("imafdcsuv");
+        hart.0.csr.mstatus |= MSTATUS_VS | MSTATUS_FS;
+
+        hart
+    }
+
+    /// Sets `vtype` and `vl` with `VSETVLI`, returning the new `vl`.
+    fn configure(&mut self, vtype: u32, avl: u64) -> u64 {
+        self.0.gpr[31] = avl;
+        self.execute(&[vsetvli(30, 31, vtype)]);
+        self.0.gpr[30]
+    }
+
+    /// Reads the given CSR with an instruction.
+    fn csr(&mut self, csr: u32) -> u64 {
+        self.execute(&[csrr(30, csr)]);
+        self.0.gpr[30]
+    }
+
+    /// Sets elements of `width` bytes of the register group starting at
+    /// `register`.
+    fn set_elements(&mut self, register: usize, width: usize, values: &[u64]) {
+        let vlenb = self.0.vector.vlen() / 8;
+
+        for (i, value) in values.iter().enumerate() {
+            let offset = i * width;
+            let bytes = self.0.vector.register_mut(register + offset / vlenb);
+
+            bytes[offset % vlenb..][..width].copy_from_slice(&value.to_le_bytes()[..width]);
+        }
+    }
+
+    /// Gets the first `count` elements of `width` bytes of the register group
+    /// starting at `register`.
+    fn elements(&self, register: usize, width: usize, count: usize) -> Vec<u64> {
+        let vlenb = self.0.vector.vlen() / 8;
+
+        (0..count)
+            .map(|i| {
+                let offset = i * width;
+                let bytes = self.0.vector.register(register + offset / vlenb);
+                let mut value = [0; 8];
+
+                value[..width].copy_from_slice(&bytes[offset % vlenb..][..width]);
+                u64::from_le_bytes(value)
+            })
+            .collect()
+    }
+
+    fn write_memory(&mut self, address: u64, bytes: &[u8]) {
+        for (i, &byte) in bytes.iter().enumerate() {
+            self.0
+                .bus
+                .store(address + i as u64, byte)
+                .expect("Failed to store data");
+        }
+    }
+
+    fn read_memory(&mut self, address: u64, len: usize) -> Vec<u8> {
+        (0..len as u64)
+            .map(|i| self.0.bus.load(address + i).expect("Failed to load data"))
+            .collect()
+    }
+}
+
+fn f32s(values: &[f32]) -> Vec<u64> {
+    values.iter().map(|value| value.to_bits() as u64).collect()
+}
+
+fn f64s(values: &[f64]) -> Vec<u64> {
+    values.iter().map(|value| value.to_bits()).collect()
+}
+
+#[test]
+fn test_configuration() {
+    let mut hart = Hart::vector();
+
+    assert_eq!(hart.csr(VLENB), 16);
+    assert_eq!(hart.csr(VTYPE), 1 << 63, "vill is set at reset");
+
+    assert_eq!(hart.configure(E32 | M1, 100), 4);
+    assert_eq!(hart.configure(E8 | M8, 1000), 128);
+    assert_eq!(hart.configure(E8 | MF2, 3), 3);
+
+    // rs1 = x0 selects VLMAX, unless rd is also x0, which keeps vl
+    hart.execute(&[vsetvli(10, 0, E16 | M2)]);
+    assert_eq!(hart.0.gpr[10], 16);
+    hart.execute(&[vsetvli(0, 0, E32 | M2 | TA)]);
+    assert_eq!(hart.csr(VL), 8);
+    assert_eq!(hart.csr(VTYPE), (E32 | M2 | TA) as u64);
+
+    hart.execute(&[vsetivli(10, 5, E64 | M1)]);
+    assert_eq!(hart.0.gpr[10], 2);
+
+    hart.0.gpr[11] = 7;
+    hart.0.gpr[12] = (E16 | M1 | TA | MA) as u64;
+    hart.execute(&[vsetvl(10, 11, 12)]);
+    assert_eq!(hart.0.gpr[10], 7);
+    assert_eq!(hart.csr(VTYPE), 0xC8);
+
+    // Unsupported configurations set vill, which makes most instructions
+    // illegal
+    assert_eq!(hart.configure(E64 | MF8, 4), 0);
+    assert_eq!(hart.csr(VTYPE), 1 << 63);
+    assert!(is_illegal(hart.run(&[op_v(0b000000, OPIVV, 1, 2, 3)])));
+
+    assert_eq!(hart.configure(1 << 8 | E8 | M1, 4), 0);
+    assert_eq!(hart.csr(VTYPE), 1 << 63);
+
+    // Vector instructions and CSRs are disabled while mstatus.VS is off
+    hart.0.csr.mstatus &= !MSTATUS_VS;
+    assert!(is_illegal(hart.run(&[vsetvli(10, 0, E8 | M1)])));
+    assert!(is_illegal(hart.run(&[csrr(10, VL)])));
+}
+
+#[test]
+fn test_csrs() {
+    let mut hart = Hart::vector();
+
+    hart.0.csr.mstatus = hart.0.csr.mstatus & !MSTATUS_VS | 1 << 9;
+
+    hart.execute(&[csrwi(VXRM, 0b11), csrwi(VXSAT, 1)]);
+    assert_eq!(hart.csr(VCSR), 0b111);
+    assert_eq!(hart.0.csr.mstatus & MSTATUS_VS, MSTATUS_VS);
+    assert_eq!(hart.0.csr.mstatus >> 63, 1, "SD is set");
+
+    hart.execute(&[csrwi(VCSR, 0b11100)]);
+    assert_eq!(hart.csr(VXRM), 0b10);
+    assert_eq!(hart.csr(VXSAT), 0);
+
+    hart.execute(&[csrwi(VSTART, 0b11111)]);
+    assert_eq!(hart.csr(VSTART), 0b11111 & 127);
+
+    // vl, vtype, and vlenb are read-only
+    assert!(is_illegal(hart.run(&[csrwi(VL, 1)])));
+    assert!(is_illegal(hart.run(&[csrwi(VLENB, 1)])));
+}
+
+#[test]
+fn test_integer_arithmetic() {
+    let mut hart = Hart::vector();
+
+    hart.configure(E32 | M1, 4);
+    hart.set_elements(2, 4, &[1, 2, 3, 0xFFFF_FFFF]);
+    hart.set_elements(3, 4, &[10, 20, 30, 1]);
+    hart.set_elements(16, 1, &[0x80, 0x7F, 0xFF, 0x01]);
+    hart.0.gpr[11] = 100;
+
+    hart.execute(&[
+        // vadd.vv v1, v2, v3
+        op_v(0b000000, OPIVV, 1, 2, 3),
+        // vadd.vi v4, v2, -1
+        op_v(0b000000, OPIVI, 4, 2, 0b11111),
+        // vrsub.vx v5, v2, x11
+        op_v(0b000011, OPIVX, 5, 2, 11),
+        // vmul.vv v6, v2, v3
+        op_v(0b100101, OPMVV, 6, 2, 3),
+        // vdivu.vx v7, v3, x0
+        op_v(0b100000, OPMVX, 7, 3, 0),
+        // vsra.vi v9, v2, 1
+        op_v(0b101001, OPIVI, 9, 2, 1),
+        // vwaddu.vv v10, v2, v3
+        op_v(0b110000, OPMVV, 10, 2, 3),
+        // vnsrl.wi v12, v10, 1
+        op_v(0b101100, OPIVI, 12, 10, 1),
+        // vmulhu.vv v13, v2, v2
+        op_v(0b100100, OPMVV, 13, 2, 2),
+        // vsext.vf4 v17, v16
+        op_v(0b010010, OPMVV, 17, 16, 0b00101),
+    ]);
+
+    assert_eq!(hart.elements(1, 4, 4), [11, 22, 33, 0]);
+    assert_eq!(hart.elements(4, 4, 4), [0, 1, 2, 0xFFFF_FFFE]);
+    assert_eq!(hart.elements(5, 4, 4), [99, 98, 97, 101]);
+    assert_eq!(hart.elements(6, 4, 4), [10, 40, 90, 0xFFFF_FFFF]);
+    assert_eq!(hart.elements(7, 4, 4), [0xFFFF_FFFF; 4]);
+    assert_eq!(hart.elements(9, 4, 4), [0, 1, 1, 0xFFFF_FFFF]);
+    assert_eq!(hart.elements(10, 8, 4), [11, 22, 33, 0x1_0000_0000]);
+    assert_eq!(hart.elements(12, 4, 4), [5, 11, 16, 0x8000_0000]);
+    assert_eq!(hart.elements(13, 4, 4), [0, 0, 0, 0xFFFF_FFFE]);
+    assert_eq!(hart.elements(17, 4, 4), [0xFFFF_FF80, 0x7F, 0xFFFF_FFFF, 1]);
+
+    hart.0.gpr[12] = 3;
+    hart.execute(&[
+        // vmslt.vx v20, v2, x12
+        op_v(0b011011, OPIVX, 20, 2, 12),
+        // vmadc.vv v21, v2, v3
+        op_v(0b010001, OPIVV, 21, 2, 3),
+    ]);
+
+    assert_eq!(hart.elements(20, 1, 1), [0b1011]);
+    assert_eq!(hart.elements(21, 1, 1), [0b1000]);
+
+    // Register groups must be aligned to their size
+    hart.configure(E32 | M2, 8);
+    assert!(is_illegal(hart.run(&[op_v(0b000000, OPIVV, 1, 2, 4)])));
+    assert!(is_illegal(hart.run(&[op_v(0b110000, OPMVV, 2, 4, 6)])));
+}
+
+#[test]
+fn test_fixed_point() {
+    let mut hart = Hart::vector();
+
+    hart.configure(E8 | M1, 4);
+    hart.set_elements(2, 1, &[200, 100, 0x80, 0x7F]);
+    hart.set_elements(3, 1, &[100, 100, 0xFF, 1]);
+    hart.set_elements(5, 1, &[0x80, 0x40]);
+    hart.set_elements(8, 2, &[0x1234, 0x00FF, 0x0180, 3]);
+
+    hart.execute(&[
+        // vsaddu.vv v1, v2, v3
+        op_v(0b100000, OPIVV, 1, 2, 3),
+        // vsadd.vv v4, v2, v3
+        op_v(0b100001, OPIVV, 4, 2, 3),
+    ]);
+
+    assert_eq!(hart.elements(1, 1, 4), [255, 200, 255, 128]);
+    assert_eq!(hart.elements(4, 1, 4), [44, 127, 0x80, 127]);
+    assert_eq!(hart.csr(VXSAT), 1);
+
+    hart.execute(&[
+        csrwi(VXSAT, 0),
+        // vaaddu.vv v6, v2, v3 rounding to nearest, ties up
+        csrwi(VXRM, 0b00),
+        op_v(0b001000, OPMVV, 6, 2, 3),
+        // vaaddu.vv v7, v2, v3 rounding down
+        csrwi(VXRM, 0b10),
+        op_v(0b001000, OPMVV, 7, 2, 3),
+    ]);
+
+    assert_eq!(hart.elements(6, 1, 4), [150, 100, 192, 64]);
+    assert_eq!(hart.elements(7, 1, 4), [150, 100, 191, 64]);
+    assert_eq!(hart.csr(VXSAT), 0);
+
+    hart.execute(&[
+        csrwi(VXRM, 0b00),
+        // vsmul.vv v10, v5, v5
+        op_v(0b100111, OPIVV, 10, 5, 5),
+    ]);
+
+    assert_eq!(hart.elements(10, 1, 4), [0x7F, 0x20, 0, 0]);
+    assert_eq!(hart.csr(VXSAT), 1);
+
+    hart.execute(&[
+        csrwi(VXSAT, 0),
+        // vnclipu.wi v11, v8, 1
+        op_v(0b101110, OPIVI, 11, 8, 1),
+    ]);
+
+    assert_eq!(hart.elements(11, 1, 4), [255, 128, 0xC0, 2]);
+    assert_eq!(hart.csr(VXSAT), 1);
+}
+
+#[test]
+fn test_agnostic_elements() {
+    let mut hart = Hart::vector();
+    // vadd.vi v1, v2, 5, v0.t
+    let vadd = masked(op_v(0b000000, OPIVI, 1, 2, 5));
+
+    hart.set_elements(0, 1, &[0b101]);
+    hart.set_elements(2, 4, &[10, 20, 30, 40]);
+
+    // Agnostic elements are left undisturbed by default
+    hart.configure(E32 | M1 | TA | MA, 3);
+    hart.set_elements(1, 4, &[1; 4]);
+    hart.execute(&[vadd]);
+    assert_eq!(hart.elements(1, 4, 4), [15, 1, 35, 1]);
+
+    hart.0.agnostic_mode = AgnosticMode::Ones;
+    hart.set_elements(1, 4, &[1; 4]);
+    hart.execute(&[vadd]);
+    assert_eq!(hart.elements(1, 4, 4), [15, 0xFFFF_FFFF, 35, 0xFFFF_FFFF]);
+
+    // Undisturbed elements are never overwritten
+    hart.configure(E32 | M1, 3);
+    hart.set_elements(1, 4, &[1; 4]);
+    hart.execute(&[vadd]);
+    assert_eq!(hart.elements(1, 4, 4), [15, 1, 35, 1]);
+
+    hart.configure(E32 | M1 | TA, 3);
+    hart.set_elements(1, 4, &[1; 4]);
+    hart.execute(&[vadd]);
+    assert_eq!(hart.elements(1, 4, 4), [15, 1, 35, 0xFFFF_FFFF]);
+
+    // The tail of a mask is always agnostic
+    hart.0.gpr[11] = 20;
+    hart.execute(&[op_v(0b011000, OPIVX, 3, 2, 11)]);
+    assert_eq!(hart.elements(3, 8, 2), [!0b101, u64::MAX]);
+
+    // A masked instruction cannot overwrite its mask
+    assert!(is_illegal(
+        hart.run(&[masked(op_v(0b000000, OPIVI, 0, 2, 5))])
+    ));
+}
+
+#[test]
+fn test_loads_and_stores() {
+    let mut hart = Hart::vector();
+    let data: Vec<u8> = (0..32).collect();
+
+    hart.write_memory(DATA, &data);
+    hart.0.gpr[11] = DATA;
+    hart.0.gpr[12] = DATA + 0x100;
+
+    hart.configure(E8 | M1, 16);
+    hart.execute(&[load(1, 0b00, 0b00000, 11, WIDTH_8, 1)]);
+    assert_eq!(hart.0.vector.register(1), &data[..16]);
+
+    hart.configure(E32 | M1, 4);
+    hart.execute(&[
+        load(1, 0b00, 0b00000, 11, WIDTH_32, 2),
+        store(1, 0b00, 0b00000, 12, WIDTH_32, 2),
+    ]);
+    assert_eq!(
+        hart.elements(2, 4, 4),
+        [0x03020100, 0x07060504, 0x0B0A0908, 0x0F0E0D0C]
+    );
+    assert_eq!(hart.read_memory(DATA + 0x100, 16), &data[..16]);
+
+    // Strided, with positive and negative strides
+    hart.configure(E16 | M1, 4);
+    hart.0.gpr[13] = 4;
+    hart.execute(&[load(1, 0b10, 13, 11, WIDTH_16, 3)]);
+    assert_eq!(hart.elements(3, 2, 4), [0x0100, 0x0504, 0x0908, 0x0D0C]);
+
+    hart.0.gpr[13] = -2i64 as u64;
+    hart.0.gpr[14] = DATA + 6;
+    hart.execute(&[load(1, 0b10, 13, 14, WIDTH_16, 3)]);
+    assert_eq!(hart.elements(3, 2, 4), [0x0706, 0x0504, 0x0302, 0x0100]);
+
+    // Indexed with 8-bit byte offsets and 32-bit data
+    hart.configure(E32 | M1, 4);
+    hart.set_elements(5, 1, &[12, 0, 4, 8]);
+    hart.execute(&[load(1, 0b01, 5, 11, WIDTH_8, 4)]);
+    assert_eq!(
+        hart.elements(4, 4, 4),
+        [0x0F0E0D0C, 0x03020100, 0x07060504, 0x0B0A0908]
+    );
+
+    // Segments of two fields
+    hart.configure(E16 | M1, 4);
+    hart.0.gpr[12] = DATA + 0x140;
+    hart.execute(&[
+        load(2, 0b00, 0b00000, 11, WIDTH_16, 6),
+        store(2, 0b00, 0b00000, 12, WIDTH_16, 6),
+    ]);
+    assert_eq!(hart.elements(6, 2, 4), [0x0100, 0x0504, 0x0908, 0x0D0C]);
+    assert_eq!(hart.elements(7, 2, 4), [0x0302, 0x0706, 0x0B0A, 0x0F0E]);
+    assert_eq!(hart.read_memory(DATA + 0x140, 16), &data[..16]);
+
+    // Whole registers, which ignore vl
+    hart.configure(E8 | M1, 1);
+    hart.0.gpr[12] = DATA + 0x200;
+    hart.execute(&[
+        load(2, 0b00, 0b01000, 11, WIDTH_32, 8),
+        store(1, 0b00, 0b01000, 12, WIDTH_8, 9),
+    ]);
+    assert_eq!(hart.0.vector.register(8), &data[..16]);
+    assert_eq!(hart.0.vector.register(9), &data[16..]);
+    assert_eq!(hart.read_memory(DATA + 0x200, 16), &data[16..]);
+    assert!(is_illegal(
+        hart.run(&[load(2, 0b00, 0b01000, 11, WIDTH_32, 9)])
+    ));
+
+    // Masks, which have ceil(vl / 8) bytes
+    hart.configure(E8 | M2, 20);
+    hart.set_elements(10, 1, &[0xAA; 16]);
+    hart.execute(&[load(1, 0b00, 0b01011, 11, WIDTH_8, 10)]);
+    assert_eq!(hart.elements(10, 1, 4), [0, 1, 2, 0xAA]);
+
+    // Segments cannot extend past v31
+    hart.configure(E32 | M2, 8);
+    assert!(is_illegal(
+        hart.run(&[load(2, 0b00, 0b00000, 11, WIDTH_32, 30)])
+    ));
+}
+
+#[test]
+fn test_faults() {
+    let mut hart = Hart::vector();
+    let end = TEST_BUS_BASE + TEST_BUS_SIZE;
+
+    hart.write_memory(end - 8, &[1, 0, 0, 0, 2, 0, 0, 0]);
+    hart.0.gpr[11] = end - 8;
+    hart.configure(E32 | M1, 4);
+
+    // A fault after element 0 of a fault-only-first load reduces vl
+    hart.execute(&[load(1, 0b00, 0b10000, 11, WIDTH_32, 1)]);
+    assert_eq!(hart.csr(VL), 2);
+    assert_eq!(hart.elements(1, 4, 2), [1, 2]);
+
+    // A fault at element 0 traps as usual
+    hart.0.gpr[12] = end;
+    assert!(matches!(
+        hart.run(&[load(1, 0b00, 0b10000, 12, WIDTH_32, 1)]),
+        Err(Exception::LoadAccessFault { .. })
+    ));
+
+    // Other loads keep the elements before the fault and set vstart to
+    // its index
+    hart.configure(E32 | M1, 4);
+    hart.set_elements(2, 4, &[9; 4]);
+    assert!(matches!(
+        hart.run(&[load(1, 0b00, 0b00000, 11, WIDTH_32, 2)]),
+        Err(Exception::LoadAccessFault { .. })
+    ));
+    assert_eq!(hart.elements(2, 4, 4), [1, 2, 9, 9]);
+    assert_eq!(hart.csr(VSTART), 2);
+
+    // Vector instructions start at vstart, then reset it
+    hart.execute(&[op_v(0b000000, OPIVV, 3, 2, 2)]);
+    assert_eq!(hart.elements(3, 4, 4), [0, 0, 18, 18]);
+    assert_eq!(hart.csr(VSTART), 0);
+
+    assert!(matches!(
+        hart.run(&[store(1, 0b00, 0b00000, 11, WIDTH_32, 2)]),
+        Err(Exception::StoreAmoAccessFault { .. })
+    ));
+    assert_eq!(hart.csr(VSTART), 2);
+}
+
+#[test]
+fn test_floating_point() {
+    let mut hart = Hart::vector();
+
+    hart.configure(E32 | M1, 4);
+    hart.set_elements(2, 4, &f32s(&[1.0, 2.0, -3.0, 4.0]));
+    hart.set_elements(3, 4, &f32s(&[1.0; 4]));
+    hart.set_elements(7, 4, &f32s(&[4.0, 9.0, 0.25, 16.0]));
+    hart.set_elements(9, 4, &f32s(&[10.0]));
+    hart.0.fpr[10] = 0xFFFF_FFFF_0000_0000 | 0.5f32.to_bits() as u64;
+
+    hart.execute(&[
+        // vfadd.vf v1, v2, f10
+        op_v(0b000000, OPFVF, 1, 2, 10),
+        // vfmacc.vv v3, v2, v2
+        op_v(0b101100, OPFVV, 3, 2, 2),
+        // vmflt.vf v4, v2, f10
+        op_v(0b011011, OPFVF, 4, 2, 10),
+        // vfcvt.x.f.v v5, v2
+        op_v(0b010010, OPFVV, 5, 2, 0b00001),
+        // vfsqrt.v v6, v7
+        op_v(0b010011, OPFVV, 6, 7, 0b00000),
+        // vfredosum.vs v8, v2, v9
+        op_v(0b000011, OPFVV, 8, 2, 9),
+        // vfwcvt.f.f.v v10, v2
+        op_v(0b010010, OPFVV, 10, 2, 0b01100),
+        // vfncvt.f.f.w v12, v10
+        op_v(0b010010, OPFVV, 12, 10, 0b10100),
+        // vfwadd.vv v14, v2, v2
+        op_v(0b110000, OPFVV, 14, 2, 2),
+        // vfmv.f.s f11, v1
+        op_v(0b010000, OPFVV, 11, 1, 0b00000),
+    ]);
+
+    assert_eq!(hart.elements(1, 4, 4), f32s(&[1.5, 2.5, -2.5, 4.5]));
+    assert_eq!(hart.elements(3, 4, 4), f32s(&[2.0, 5.0, 10.0, 17.0]));
+    assert_eq!(hart.elements(4, 1, 1), [0b0100]);
+    assert_eq!(hart.elements(5, 4, 4), [1, 2, 0xFFFF_FFFD, 4]);
+    assert_eq!(hart.elements(6, 4, 4), f32s(&[2.0, 3.0, 0.5, 4.0]));
+    assert_eq!(hart.elements(8, 4, 1), f32s(&[14.0]));
+    assert_eq!(hart.elements(10, 8, 4), f64s(&[1.0, 2.0, -3.0, 4.0]));
+    assert_eq!(hart.elements(12, 4, 4), f32s(&[1.0, 2.0, -3.0, 4.0]));
+    assert_eq!(hart.elements(14, 8, 4), f64s(&[2.0, 4.0, -6.0, 8.0]));
+    assert_eq!(
+        hart.0.fpr[11],
+        0xFFFF_FFFF_0000_0000 | 1.5f32.to_bits() as u64
+    );
+    assert_eq!(hart.0.fcsr, 0);
+
+    // Exceptions accrue into fflags
+    hart.set_elements(7, 4, &f32s(&[2.0, -1.0, 1.0, 1.0]));
+    hart.execute(&[op_v(0b010011, OPFVV, 6, 7, 0b00000)]);
+    assert_eq!(hart.elements(6, 4, 2)[1], 0x7FC0_0000);
+    assert_eq!(hart.0.fcsr, 0b10001);
+
+    // There is no half-precision arithmetic
+    hart.configure(E16 | M1, 8);
+    assert!(is_illegal(hart.run(&[op_v(0b000000, OPFVV, 1, 2, 3)])));
+
+    // Floating-point instructions are disabled while mstatus.FS is off
+    hart.configure(E32 | M1, 4);
+    hart.0.csr.mstatus &= !MSTATUS_FS;
+    assert!(is_illegal(hart.run(&[op_v(0b000000, OPFVV, 1, 2, 3)])));
+}
+
+#[test]
+fn test_mask_instructions() {
+    let mut hart = Hart::vector();
+
+    hart.configure(E8 | M1, 10);
+    hart.set_elements(2, 1, &[0b0110_1000, 0]);
+    hart.set_elements(3, 1, &[0b1111_0000, 0b11]);
+
+    hart.execute(&[
+        // vmand.mm v1, v2, v3
+        op_v(0b011001, OPMVV, 1, 2, 3),
+        // vcpop.m x10, v2
+        op_v(0b010000, OPMVV, 10, 2, 0b10000),
+        // vfirst.m x11, v2
+        op_v(0b010000, OPMVV, 11, 2, 0b10001),
+        // vfirst.m x12, v4
+        op_v(0b010000, OPMVV, 12, 4, 0b10001),
+        // vmsbf.m v5, v2
+        op_v(0b010100, OPMVV, 5, 2, 0b00001),
+        // vmsif.m v6, v2
+        op_v(0b010100, OPMVV, 6, 2, 0b00011),
+        // vmsof.m v7, v2
+        op_v(0b010100, OPMVV, 7, 2, 0b00010),
+        // viota.m v8, v2
+        op_v(0b010100, OPMVV, 8, 2, 0b10000),
+        // vid.v v9
+        op_v(0b010100, OPMVV, 9, 0, 0b10001),
+    ]);
+
+    assert_eq!(hart.elements(1, 1, 2), [0b0110_0000, 0]);
+    assert_eq!(hart.0.gpr[10], 3);
+    assert_eq!(hart.0.gpr[11], 3);
+    assert_eq!(hart.0.gpr[12], u64::MAX);
+    assert_eq!(hart.elements(5, 1, 2), [0b0000_0111, 0]);
+    assert_eq!(hart.elements(6, 1, 2), [0b0000_1111, 0]);
+    assert_eq!(hart.elements(7, 1, 2), [0b0000_1000, 0]);
+    assert_eq!(hart.elements(8, 1, 10), [0, 0, 0, 0, 1, 1, 2, 3, 3, 3]);
+    assert_eq!(hart.elements(9, 1, 10), [0, 1, 2, 3, 4, 5, 6, 7, 8, 9]);
+
+    // vmsbf.m cannot overwrite its source
+    assert!(is_illegal(
+        hart.run(&[op_v(0b010100, OPMVV, 2, 2, 0b00001)])
+    ));
+}
+
+#[test]
+fn test_permutations() {
+    let mut hart = Hart::vector();
+
+    hart.configure(E32 | M1, 4);
+    hart.set_elements(1, 4, &[9; 4]);
+    hart.set_elements(2, 4, &[1, 2, 3, 4]);
+    hart.set_elements(3, 4, &[5, 6, 7, 8]);
+    hart.set_elements(7, 4, &[3, 0, 9, 1]);
+    hart.set_elements(10, 1, &[0b1010]);
+    hart.set_elements(11, 4, &[0x8000_0000]);
+    hart.0.gpr[11] = 7;
+
+    hart.execute(&[
+        // vslideup.vi v1, v2, 1
+        op_v(0b001110, OPIVI, 1, 2, 1),
+        // vslidedown.vi v4, v2, 2
+        op_v(0b001111, OPIVI, 4, 2, 2),
+        // vslide1down.vx v5, v2, x11
+        op_v(0b001111, OPMVX, 5, 2, 11),
+        // vslide1up.vx v6, v2, x11
+        op_v(0b001110, OPMVX, 6, 2, 11),
+        // vrgather.vv v8, v2, v7
+        op_v(0b001100, OPIVV, 8, 2, 7),
+        // vrgather.vi v9, v2, 2
+        op_v(0b001100, OPIVI, 9, 2, 2),
+        // vcompress.vm v12, v2, v10
+        op_v(0b010111, OPMVV, 12, 2, 10),
+        // vmv.x.s x10, v11
+        op_v(0b010000, OPMVV, 10, 11, 0b00000),
+        // vmv.s.x v13, x11
+        op_v(0b010000, OPMVX, 13, 0, 11),
+        // vmv2r.v v14, v2
+        op_v(0b100111, OPIVI, 14, 2, 1),
+    ]);
+
+    assert_eq!(hart.elements(1, 4, 4), [9, 1, 2, 3]);
+    assert_eq!(hart.elements(4, 4, 4), [3, 4, 0, 0]);
+    assert_eq!(hart.elements(5, 4, 4), [2, 3, 4, 7]);
+    assert_eq!(hart.elements(6, 4, 4), [7, 1, 2, 3]);
+    assert_eq!(hart.elements(8, 4, 4), [4, 1, 0, 2]);
+    assert_eq!(hart.elements(9, 4, 4), [3; 4]);
+    assert_eq!(hart.elements(12, 4, 4), [2, 4, 0, 0]);
+    assert_eq!(hart.0.gpr[10], 0xFFFF_FFFF_8000_0000);
+    assert_eq!(hart.elements(13, 4, 4), [7, 0, 0, 0]);
+    assert_eq!(hart.elements(14, 4, 8), [1, 2, 3, 4, 5, 6, 7, 8]);
+
+    // The destination of a slide up cannot overlap its source, and whole
+    // register moves must be aligned
+    assert!(is_illegal(hart.run(&[op_v(0b001110, OPIVI, 2, 2, 1)])));
+    assert!(is_illegal(hart.run(&[op_v(0b100111, OPIVI, 13, 2, 1)])));
+}
+
+#[test]
+fn test_reductions() {
+    let mut hart = Hart::vector();
+
+    hart.configure(E16 | M1, 4);
+    hart.set_elements(0, 1, &[0b0101]);
+    hart.set_elements(2, 2, &[1, 2, 0xFFFF, 4]);
+    hart.set_elements(3, 2, &[10]);
+    hart.set_elements(5, 4, &[100]);
+
+    hart.execute(&[
+        // vredsum.vs v1, v2, v3
+        op_v(0b000000, OPMVV, 1, 2, 3),
+        // vredmaxu.vs v4, v2, v3
+        op_v(0b000110, OPMVV, 4, 2, 3),
+        // vredmax.vs v6, v2, v3
+        op_v(0b000111, OPMVV, 6, 2, 3),
+        // vwredsum.vs v7, v2, v5
+        op_v(0b110001, OPIVV, 7, 2, 5),
+        // vredsum.vs v8, v2, v3, v0.t
+        masked(op_v(0b000000, OPMVV, 8, 2, 3)),
+    ]);
+
+    assert_eq!(hart.elements(1, 2, 1), [16]);
+    assert_eq!(hart.elements(4, 2, 1), [0xFFFF]);
+    assert_eq!(hart.elements(6, 2, 1), [10]);
+    assert_eq!(hart.elements(7, 4, 1), [106]);
+    assert_eq!(hart.elements(8, 2, 1), [10]);
+}